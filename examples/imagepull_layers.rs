@@ -4,9 +4,9 @@
 // information comes in.
 
 use futures::StreamExt;
-use shiplift::{Docker, PullOptions};
+use shiplift::{image::ProgressPhase, Docker, PullOptions};
 use std::{
-    collections::HashMap,
+    collections::HashSet,
     env,
     io::{self, Write},
 };
@@ -21,28 +21,27 @@ async fn main() {
 
     let mut stream = docker
         .images()
-        .pull(&PullOptions::builder().image(&img).build());
+        .pull_events(&PullOptions::builder().image(&img).build());
 
-    let mut layers = HashMap::new();
-    let mut layer_count: u32 = 0;
+    let mut layers = HashSet::new();
     let mut total_bytes: u64 = 0;
-    while let Some(pull_result) = stream.next().await {
-        match pull_result {
-            Ok(output) => {
+    while let Some(event) = stream.next().await {
+        match event {
+            Ok(event) => {
                 print!(".");
-                //println!("{:?}", output);
-                if let Some((layer_id, layer_bytes)) = output.image_layer_bytes() {
-                    // We have layer information.
-                    match layers.get(&layer_id) {
-                        Some(&_bytes) => (),
-                        None => {
-                            // This is a new layer.
-                            layer_count += 1;
-                            total_bytes += layer_bytes;
-                            layers.insert(layer_id.clone(), layer_bytes);
-                            println!("\n{} image layer {} ({}) compressed bytes: {} ({:.3} MB total so far)",
-                                        img, layer_count, &layer_id, layer_bytes, total_bytes as f64 / (1024.0 * 1024.0));
-                        }
+                if let (ProgressPhase::Downloading, Some(layer_id), Some(layer_bytes)) =
+                    (&event.phase, event.layer_id, event.total)
+                {
+                    if layers.insert(layer_id.clone()) {
+                        total_bytes += layer_bytes;
+                        println!(
+                            "\n{} image layer {} ({}) compressed bytes: {} ({:.3} MB total so far)",
+                            img,
+                            layers.len(),
+                            layer_id,
+                            layer_bytes,
+                            total_bytes as f64 / (1024.0 * 1024.0)
+                        );
                     }
                 }
             }
@@ -55,7 +54,7 @@ async fn main() {
     }
     println!(
         "\n{} layers totalling {:.3} MB",
-        layer_count,
+        layers.len(),
         total_bytes as f64 / (1024.0 * 1024.0)
     );
 }
@@ -1,11 +1,12 @@
-use shiplift::Docker;
+use shiplift::{Docker, SearchOptions};
 
 #[tokio::main]
 async fn main() {
     let docker = Docker::new();
     println!("remote docker images in stock");
 
-    match docker.images().search("rust").await {
+    let opts = SearchOptions::builder("rust").limit(10).build();
+    match docker.images().search_with(&opts).await {
         Ok(results) => {
             for result in results {
                 println!("{} - {}", result.name, result.description);
@@ -21,7 +21,8 @@ async fn main() {
 
     let image = Image::new(&docker, img);
 
-    if let Err(e) = image.tag(&tag_opts).await {
-        eprintln!("Error: {}", e)
+    match image.tag(&tag_opts).await {
+        Ok(reference) => println!("Tagged {}", reference),
+        Err(e) => eprintln!("Error: {}", e),
     }
 }
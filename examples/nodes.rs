@@ -0,0 +1,15 @@
+use shiplift::{Docker, NodeListOptions};
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let docker = Docker::new();
+    match docker.nodes().list(&NodeListOptions::default()).await {
+        Ok(nodes) => {
+            for n in nodes {
+                println!("node -> {:#?}", n)
+            }
+        }
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
@@ -0,0 +1,34 @@
+// cargo run --example plugininstall vieux/sshfs
+
+use futures::StreamExt;
+use shiplift::{Docker, PluginInstallOptions};
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let docker = Docker::new();
+    let remote = env::args()
+        .nth(1)
+        .expect("You need to specify a plugin name");
+
+    let mut stream =
+        docker
+            .plugins()
+            .install(&remote, &PluginInstallOptions::default(), |privileges| {
+                for privilege in privileges {
+                    println!(
+                        "granting {} ({}): {:?}",
+                        privilege.name, privilege.description, privilege.value
+                    );
+                }
+                true
+            });
+
+    while let Some(install_result) = stream.next().await {
+        match install_result {
+            Ok(output) => println!("{:?}", output),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use shiplift::{Docker, PluginListOptions};
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let docker = Docker::new();
+    match docker.plugins().list(&PluginListOptions::default()).await {
+        Ok(plugins) => {
+            for p in plugins {
+                println!("plugin -> {:#?}", p)
+            }
+        }
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
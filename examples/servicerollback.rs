@@ -0,0 +1,15 @@
+use shiplift::Docker;
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let id = env::args()
+        .nth(1)
+        .expect("You need to specify a service name");
+
+    match docker.services().get(&id).rollback().await {
+        Ok(info) => println!("{:?}", info),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
@@ -0,0 +1,20 @@
+use shiplift::{Docker, TaskFilter, TaskListOptions};
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let docker = Docker::new();
+    let mut builder = TaskListOptions::builder();
+    if let Some(service) = env::args().nth(1) {
+        builder = builder.filter(vec![TaskFilter::Service(service)]);
+    }
+    match docker.tasks().list(&builder.build()).await {
+        Ok(tasks) => {
+            for t in tasks {
+                println!("task -> {:#?}", t)
+            }
+        }
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
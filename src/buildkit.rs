@@ -0,0 +1,1035 @@
+//! Progress of builds by BuildKit, see [Images::build_with_buildkit](crate::Images::build_with_buildkit).
+//!
+//! BuildKit reports the progress of a build as the vertexes of its build graph, the steps that
+//! run or are cached, with their status and logs. The daemon passes on these reports encoded as
+//! protobuf messages, which are decoded into a [SolveStatus].
+//!
+//! Secrets and SSH agents are served to a build by a [Session], a gRPC server the daemon calls
+//! back over a connection to `/session`, see
+//! [Images::build_with_session](crate::Images::build_with_session).
+
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_util::{
+    future::{self, TryFutureExt},
+    stream::{self, BoxStream, Stream, StreamExt},
+};
+use hyper::{
+    body::HttpBody,
+    header::{self, HeaderValue},
+    server::conn::Http,
+    service::service_fn,
+    Body, HeaderMap, Method, Request, Response,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    task::JoinHandle,
+};
+
+use crate::{
+    errors::{self, StreamProgress},
+    id::ImageId,
+    image::{fault_message, ErrorDetail},
+    Error,
+};
+
+/// A response chunk from the daemon when building an image with BuildKit
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BuildKitChunk {
+    /// Progress of the build
+    Status(SolveStatus),
+    /// The ID of the built image, reported at the end of a successful build
+    ImageId(ImageId),
+    /// Output of the daemon that is not about a vertex
+    Stream(String),
+}
+
+/// A message of a BuildKit build, as the daemon streams it
+#[derive(Deserialize)]
+pub(crate) struct RawChunk {
+    id: Option<String>,
+    aux: Option<Value>,
+    stream: Option<String>,
+    error: Option<String>,
+    #[serde(rename = "errorDetail")]
+    error_detail: Option<ErrorDetail>,
+}
+
+impl BuildKitChunk {
+    /// The chunk for a message of the daemon, or `None` for messages that carry no information,
+    /// like those of unknown kinds
+    ///
+    /// A message with an error fails with [Error::StreamFault], the error of the build.
+    pub(crate) fn from_raw(raw: RawChunk) -> Option<errors::Result<Self>> {
+        if let Some(error) = raw.error {
+            let error_detail = raw
+                .error_detail
+                .unwrap_or_else(|| ErrorDetail::new(error.clone()));
+            return Some(Err(Error::StreamFault {
+                message: fault_message(error, error_detail),
+                method: Method::POST,
+                path: "/build".to_owned(),
+            }));
+        }
+        match (raw.id.as_deref(), raw.aux) {
+            (Some("moby.buildkit.trace"), Some(Value::String(aux))) => Some(
+                base64::decode(aux)
+                    .map_err(|_| DecodeError("the trace is not base64 encoded"))
+                    .and_then(|bytes| SolveStatus::decode(&bytes))
+                    .map(BuildKitChunk::Status)
+                    .map_err(|e| Error::InvalidResponse(e.to_string())),
+            ),
+            (Some("moby.image.id"), Some(aux)) => aux["ID"]
+                .as_str()
+                .map(|id| Ok(BuildKitChunk::ImageId(ImageId::from(id)))),
+            _ => raw.stream.map(|stream| Ok(BuildKitChunk::Stream(stream))),
+        }
+    }
+
+    /// Updates `progress` with the vertexes this chunk reports started
+    pub(crate) fn record_progress(
+        progress: &mut StreamProgress,
+        chunk: &BuildKitChunk,
+    ) {
+        if let BuildKitChunk::Status(status) = chunk {
+            for vertex in status
+                .vertexes
+                .iter()
+                .filter(|vertex| vertex.started.is_some())
+            {
+                progress.record_step(&vertex.name);
+            }
+        }
+    }
+}
+
+/// A report on the progress of a build, which updates the vertexes it names
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SolveStatus {
+    pub vertexes: Vec<Vertex>,
+    pub statuses: Vec<VertexStatus>,
+    pub logs: Vec<VertexLog>,
+    pub warnings: Vec<VertexWarning>,
+}
+
+/// A step of a build, like a `RUN` instruction or the download of the base image
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vertex {
+    pub digest: String,
+    /// Digests of the vertexes this one depends on
+    pub inputs: Vec<String>,
+    /// Description of the step, like `[2/3] RUN make`
+    pub name: String,
+    /// Whether the result of the step was taken from the cache
+    pub cached: bool,
+    pub started: Option<SystemTime>,
+    pub completed: Option<SystemTime>,
+    /// Why the step failed
+    pub error: Option<String>,
+}
+
+/// Progress of a task of a vertex, like the download of a layer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexStatus {
+    pub id: String,
+    /// Digest of the vertex of the task
+    pub vertex: String,
+    pub name: String,
+    pub current: i64,
+    /// The amount the task is complete at, or 0 if it is not known
+    pub total: i64,
+    pub timestamp: Option<SystemTime>,
+    pub started: Option<SystemTime>,
+    pub completed: Option<SystemTime>,
+}
+
+/// Output of a vertex, like the output of a `RUN` instruction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexLog {
+    /// Digest of the vertex that wrote the output
+    pub vertex: String,
+    pub timestamp: Option<SystemTime>,
+    /// 1 for standard output, 2 for standard error
+    pub stream: i64,
+    pub msg: Vec<u8>,
+}
+
+/// A warning about a vertex, like a deprecated Dockerfile instruction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexWarning {
+    pub vertex: String,
+    pub level: i64,
+    pub short: String,
+}
+
+/// A BuildKit trace that could not be decoded
+#[derive(Debug)]
+pub(crate) struct DecodeError(&'static str);
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "invalid BuildKit trace: {}", self.0)
+    }
+}
+
+impl SolveStatus {
+    /// Decodes the protobuf encoding of the `StatusResponse` message of BuildKit
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut status = SolveStatus::default();
+        for field in Fields(bytes) {
+            match field? {
+                (1, Wire::Bytes(bytes)) => status.vertexes.push(Vertex::decode(bytes)?),
+                (2, Wire::Bytes(bytes)) => status.statuses.push(VertexStatus::decode(bytes)?),
+                (3, Wire::Bytes(bytes)) => status.logs.push(VertexLog::decode(bytes)?),
+                (4, Wire::Bytes(bytes)) => status.warnings.push(VertexWarning::decode(bytes)?),
+                _ => (),
+            }
+        }
+        Ok(status)
+    }
+}
+
+impl Vertex {
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut vertex = Vertex::default();
+        for field in Fields(bytes) {
+            match field? {
+                (1, Wire::Bytes(bytes)) => vertex.digest = string(bytes),
+                (2, Wire::Bytes(bytes)) => vertex.inputs.push(string(bytes)),
+                (3, Wire::Bytes(bytes)) => vertex.name = string(bytes),
+                (4, Wire::Varint(cached)) => vertex.cached = cached != 0,
+                (5, Wire::Bytes(bytes)) => vertex.started = Some(timestamp(bytes)?),
+                (6, Wire::Bytes(bytes)) => vertex.completed = Some(timestamp(bytes)?),
+                (7, Wire::Bytes(bytes)) => vertex.error = Some(string(bytes)),
+                _ => (),
+            }
+        }
+        Ok(vertex)
+    }
+}
+
+impl VertexStatus {
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut status = VertexStatus::default();
+        for field in Fields(bytes) {
+            match field? {
+                (1, Wire::Bytes(bytes)) => status.id = string(bytes),
+                (2, Wire::Bytes(bytes)) => status.vertex = string(bytes),
+                (3, Wire::Bytes(bytes)) => status.name = string(bytes),
+                (4, Wire::Varint(current)) => status.current = current as i64,
+                (5, Wire::Varint(total)) => status.total = total as i64,
+                (6, Wire::Bytes(bytes)) => status.timestamp = Some(timestamp(bytes)?),
+                (7, Wire::Bytes(bytes)) => status.started = Some(timestamp(bytes)?),
+                (8, Wire::Bytes(bytes)) => status.completed = Some(timestamp(bytes)?),
+                _ => (),
+            }
+        }
+        Ok(status)
+    }
+}
+
+impl VertexLog {
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut log = VertexLog::default();
+        for field in Fields(bytes) {
+            match field? {
+                (1, Wire::Bytes(bytes)) => log.vertex = string(bytes),
+                (2, Wire::Bytes(bytes)) => log.timestamp = Some(timestamp(bytes)?),
+                (3, Wire::Varint(stream)) => log.stream = stream as i64,
+                (4, Wire::Bytes(bytes)) => log.msg = bytes.to_vec(),
+                _ => (),
+            }
+        }
+        Ok(log)
+    }
+}
+
+impl VertexWarning {
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut warning = VertexWarning::default();
+        for field in Fields(bytes) {
+            match field? {
+                (1, Wire::Bytes(bytes)) => warning.vertex = string(bytes),
+                (2, Wire::Varint(level)) => warning.level = level as i64,
+                (3, Wire::Bytes(bytes)) => warning.short = string(bytes),
+                _ => (),
+            }
+        }
+        Ok(warning)
+    }
+}
+
+fn string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Decodes a `google.protobuf.Timestamp`
+fn timestamp(bytes: &[u8]) -> Result<SystemTime, DecodeError> {
+    let (mut seconds, mut nanos) = (0, 0);
+    for field in Fields(bytes) {
+        match field? {
+            (1, Wire::Varint(value)) => seconds = value as i64,
+            (2, Wire::Varint(value)) => nanos = value as u32,
+            _ => (),
+        }
+    }
+    let since_epoch = Duration::new(seconds.unsigned_abs(), nanos);
+    Ok(if seconds < 0 {
+        UNIX_EPOCH - since_epoch
+    } else {
+        UNIX_EPOCH + since_epoch
+    })
+}
+
+/// The value of a protobuf field, by its wire type
+enum Wire<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the fields of a protobuf message as their number and value
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .0
+                .split_first()
+                .ok_or(DecodeError("truncated varint"))?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError("varint too long"))
+    }
+
+    fn take(
+        &mut self,
+        len: usize,
+    ) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < len {
+            return Err(DecodeError("truncated field"));
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> Result<(u64, Wire<'a>), DecodeError> {
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Wire::Varint(self.varint()?),
+            1 => self.take(8).map(|_| Wire::Fixed)?,
+            2 => {
+                let len = self.varint()? as usize;
+                Wire::Bytes(self.take(len)?)
+            }
+            5 => self.take(4).map(|_| Wire::Fixed)?,
+            _ => return Err(DecodeError("unsupported wire type")),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Wire<'a>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let field = self.field();
+        if field.is_err() {
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+/// Appends the protobuf encoding of a varint
+fn varint(
+    out: &mut Vec<u8>,
+    mut value: u64,
+) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends a field of a length-delimited value, like bytes or a message
+fn bytes_field(
+    out: &mut Vec<u8>,
+    number: u64,
+    bytes: &[u8],
+) {
+    varint(out, number << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Appends a field of a varint value
+fn varint_field(
+    out: &mut Vec<u8>,
+    number: u64,
+    value: u64,
+) {
+    varint(out, number << 3);
+    varint(out, value);
+}
+
+/// Paths of the gRPC methods a [Session] serves to the daemon
+const HEALTH_CHECK: &str = "/grpc.health.v1.Health/Check";
+const GET_SECRET: &str = "/moby.buildkit.secrets.v1.Secrets/GetSecret";
+const CHECK_AGENT: &str = "/moby.sshforward.v1.SSH/CheckAgent";
+const FORWARD_AGENT: &str = "/moby.sshforward.v1.SSH/ForwardAgent";
+
+/// The SSH agent of `--mount=type=ssh` mounts that don't name one
+const DEFAULT_SSH_ID: &str = "default";
+
+/// gRPC status codes, see `google.golang.org/grpc/codes`
+const OK: u32 = 0;
+const INVALID_ARGUMENT: u32 = 3;
+const NOT_FOUND: u32 = 5;
+const INTERNAL: u32 = 13;
+const UNAVAILABLE: u32 = 14;
+const UNIMPLEMENTED: u32 = 12;
+
+/// A BuildKit session, which serves secrets and SSH agents to the steps of a build that mount
+/// them, see [Images::build_with_session](crate::Images::build_with_session)
+///
+/// The session is a gRPC server the daemon calls over the connection of `/session`. A secret is
+/// mounted with `RUN --mount=type=secret,id=<id>`, an SSH agent with
+/// `RUN --mount=type=ssh[,id=<id>]`.
+pub struct Session {
+    id: String,
+    secrets: HashMap<String, Vec<u8>>,
+    #[cfg(unix)]
+    ssh_agents: HashMap<String, PathBuf>,
+}
+
+impl Session {
+    /// A new session without secrets and SSH agents, with a unique ID
+    pub fn new() -> Self {
+        static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Session {
+            id: format!(
+                "shiplift-{:x}-{:x}-{:x}",
+                std::process::id(),
+                since_epoch.as_nanos(),
+                SESSIONS.fetch_add(1, Ordering::Relaxed)
+            ),
+            secrets: HashMap::new(),
+            #[cfg(unix)]
+            ssh_agents: HashMap::new(),
+        }
+    }
+
+    /// The ID the build names the session by, see [BuildOptionsBuilder::session](crate::BuildOptionsBuilder::session)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Serves `data` as the secret `id`
+    pub fn secret<I, D>(
+        mut self,
+        id: I,
+        data: D,
+    ) -> Self
+    where
+        I: Into<String>,
+        D: Into<Vec<u8>>,
+    {
+        self.secrets.insert(id.into(), data.into());
+        self
+    }
+
+    /// Forwards the SSH agent listening on the unix socket at `socket` as the agent `id`, or as
+    /// the agent of mounts that don't name one if `id` is `default`
+    #[cfg(unix)]
+    pub fn ssh_agent<I, P>(
+        mut self,
+        id: I,
+        socket: P,
+    ) -> Self
+    where
+        I: Into<String>,
+        P: Into<PathBuf>,
+    {
+        self.ssh_agents.insert(id.into(), socket.into());
+        self
+    }
+
+    /// The headers of the request to `/session`, which announce the ID and the methods of the
+    /// session to the daemon
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("X-Docker-Expose-Session-Uuid", self.id.clone()),
+            ("X-Docker-Expose-Session-Name", "shiplift".to_owned()),
+            ("X-Docker-Expose-Session-Sharedkey", self.id.clone()),
+        ];
+        let mut methods = vec![HEALTH_CHECK, GET_SECRET];
+        if cfg!(unix) {
+            methods.extend([CHECK_AGENT, FORWARD_AGENT]);
+        }
+        headers.extend(
+            methods
+                .into_iter()
+                .map(|method| ("X-Docker-Expose-Session-Grpc-Method", method.to_owned())),
+        );
+        headers
+    }
+
+    /// Serves the session over `connection`, the connection of `/session` after the daemon
+    /// switched it to HTTP/2, until the daemon closes it
+    pub(crate) async fn serve<C>(
+        self,
+        connection: C,
+    ) -> Result<(), hyper::Error>
+    where
+        C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let session = Arc::new(self);
+        Http::new()
+            .http2_only(true)
+            .serve_connection(
+                connection,
+                service_fn(move |request| {
+                    let session = session.clone();
+                    async move { Ok::<_, Infallible>(session.call(request).await) }
+                }),
+            )
+            .await
+    }
+
+    async fn call(
+        &self,
+        request: Request<Body>,
+    ) -> Response<GrpcBody> {
+        let body = match request.uri().path() {
+            HEALTH_CHECK => {
+                // SERVING
+                let mut response = Vec::new();
+                varint_field(&mut response, 1, 1);
+                GrpcBody::unary(Ok(response))
+            }
+            GET_SECRET => GrpcBody::unary(requested_id(request.into_body()).await.and_then(|id| {
+                match self.secrets.get(&id) {
+                    Some(data) => {
+                        let mut response = Vec::new();
+                        bytes_field(&mut response, 1, data);
+                        Ok(response)
+                    }
+                    None => Err(Status::new(NOT_FOUND, format!("secret {} not found", id))),
+                }
+            })),
+            #[cfg(unix)]
+            CHECK_AGENT => GrpcBody::unary(
+                requested_id(request.into_body())
+                    .await
+                    .and_then(|id| self.ssh_agent_socket(&id).map(|_| Vec::new())),
+            ),
+            #[cfg(unix)]
+            FORWARD_AGENT => {
+                let id = request
+                    .headers()
+                    .get("buildkit.ssh.id")
+                    .and_then(|id| id.to_str().ok())
+                    .unwrap_or_default()
+                    .to_owned();
+                match self.ssh_agent_socket(&id) {
+                    Ok(socket) => {
+                        GrpcBody::streaming(forward_agent(socket.to_owned(), request.into_body()))
+                    }
+                    Err(status) => GrpcBody::unary(Err(status)),
+                }
+            }
+            method => GrpcBody::unary(Err(Status::new(
+                UNIMPLEMENTED,
+                format!("unknown method {}", method),
+            ))),
+        };
+        let mut response = Response::new(body);
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/grpc"),
+        );
+        response
+    }
+
+    #[cfg(unix)]
+    fn ssh_agent_socket(
+        &self,
+        id: &str,
+    ) -> Result<&PathBuf, Status> {
+        let id = if id.is_empty() { DEFAULT_SSH_ID } else { id };
+        self.ssh_agents
+            .get(id)
+            .ok_or_else(|| Status::new(NOT_FOUND, format!("SSH agent {} not found", id)))
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        // the values of secrets stay out of logs
+        let mut session = f.debug_struct("Session");
+        session
+            .field("id", &self.id)
+            .field("secrets", &self.secrets.keys().collect::<Vec<_>>());
+        #[cfg(unix)]
+        session.field("ssh_agents", &self.ssh_agents);
+        session.finish()
+    }
+}
+
+/// The status a gRPC call ended with
+#[derive(Debug)]
+struct Status {
+    code: u32,
+    message: String,
+}
+
+impl Status {
+    fn new(
+        code: u32,
+        message: impl Into<String>,
+    ) -> Self {
+        Status {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn trailers(&self) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from(self.code));
+        if let Ok(message) = HeaderValue::from_str(&self.message) {
+            trailers.insert("grpc-message", message);
+        }
+        trailers
+    }
+}
+
+/// The ID of the secret or SSH agent a unary request asks for, which all these requests have as
+/// their first field
+async fn requested_id(body: Body) -> Result<String, Status> {
+    let mut messages = Messages::new(body);
+    let message = messages
+        .next()
+        .await?
+        .ok_or_else(|| Status::new(INVALID_ARGUMENT, "missing request"))?;
+    let mut id = String::new();
+    for field in Fields(&message) {
+        if let (1, Wire::Bytes(bytes)) =
+            field.map_err(|e| Status::new(INVALID_ARGUMENT, e.to_string()))?
+        {
+            id = string(bytes);
+        }
+    }
+    Ok(id)
+}
+
+/// Bridges the `BytesMessage`s of a `ForwardAgent` call to the SSH agent listening on `socket`,
+/// with the messages the agent answers with as the response
+#[cfg(unix)]
+fn forward_agent(
+    socket: PathBuf,
+    body: Body,
+) -> impl Stream<Item = Result<Vec<u8>, Status>> + Send {
+    use tokio::{io::AsyncReadExt, net::UnixStream};
+
+    async move {
+        let agent = UnixStream::connect(&socket).await.map_err(|e| {
+            Status::new(
+                UNAVAILABLE,
+                format!("connecting to SSH agent {}: {}", socket.display(), e),
+            )
+        })?;
+        let (reader, mut writer) = agent.into_split();
+        // the requests of the daemon go to the agent while its answers are read
+        let requests = AbortOnDrop(tokio::spawn(async move {
+            let mut messages = Messages::new(body);
+            while let Ok(Some(message)) = messages.next().await {
+                for field in Fields(&message) {
+                    if let Ok((1, Wire::Bytes(data))) = field {
+                        if writer.write_all(data).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            let _ = writer.shutdown().await;
+        }));
+        Ok(stream::unfold(
+            (reader, vec![0; 16 * 1024], requests),
+            |(mut reader, mut buf, requests)| async move {
+                let answer = match reader.read(&mut buf).await {
+                    Ok(0) => return None,
+                    Ok(n) => {
+                        let mut message = Vec::new();
+                        bytes_field(&mut message, 1, &buf[..n]);
+                        Ok(message)
+                    }
+                    Err(e) => Err(Status::new(INTERNAL, e.to_string())),
+                };
+                Some((answer, (reader, buf, requests)))
+            },
+        ))
+    }
+    .try_flatten_stream()
+}
+
+/// Aborts a spawned task when dropped, to tie it to the lifetime of what owns the guard
+pub(crate) struct AbortOnDrop(pub(crate) JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Reads the length-prefixed messages of a gRPC request
+struct Messages {
+    body: Body,
+    buf: BytesMut,
+}
+
+impl Messages {
+    fn new(body: Body) -> Self {
+        Messages {
+            body,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// The next message, or `None` at the end of the request
+    async fn next(&mut self) -> Result<Option<Bytes>, Status> {
+        loop {
+            if self.buf.len() >= 5 {
+                if self.buf[0] != 0 {
+                    return Err(Status::new(UNIMPLEMENTED, "compressed messages"));
+                }
+                let len = BigEndian::read_u32(&self.buf[1..5]) as usize;
+                if self.buf.len() >= 5 + len {
+                    self.buf.advance(5);
+                    return Ok(Some(self.buf.split_to(len).freeze()));
+                }
+            }
+            match self.body.data().await {
+                Some(Ok(data)) => self.buf.extend_from_slice(&data),
+                Some(Err(e)) => return Err(Status::new(INTERNAL, e.to_string())),
+                None if self.buf.is_empty() => return Ok(None),
+                None => return Err(Status::new(INVALID_ARGUMENT, "truncated message")),
+            }
+        }
+    }
+}
+
+/// The response to a gRPC call, its messages followed by the status of the call as trailers
+struct GrpcBody {
+    messages: Option<BoxStream<'static, Result<Vec<u8>, Status>>>,
+    status: Status,
+}
+
+impl GrpcBody {
+    fn unary(message: Result<Vec<u8>, Status>) -> Self {
+        GrpcBody::streaming(stream::once(future::ready(message)))
+    }
+
+    fn streaming<S>(messages: S) -> Self
+    where
+        S: Stream<Item = Result<Vec<u8>, Status>> + Send + 'static,
+    {
+        GrpcBody {
+            messages: Some(messages.boxed()),
+            status: Status::new(OK, ""),
+        }
+    }
+}
+
+impl HttpBody for GrpcBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Infallible>>> {
+        let messages = match self.messages.as_mut() {
+            Some(messages) => messages,
+            None => return Poll::Ready(None),
+        };
+        match futures_util::ready!(messages.poll_next_unpin(cx)) {
+            Some(Ok(message)) => {
+                let mut frame = BytesMut::with_capacity(5 + message.len());
+                frame.put_u8(0);
+                frame.put_u32(message.len() as u32);
+                frame.extend_from_slice(&message);
+                Poll::Ready(Some(Ok(frame.freeze())))
+            }
+            Some(Err(status)) => {
+                self.status = status;
+                self.messages = None;
+                Poll::Ready(None)
+            }
+            None => {
+                self.messages = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Infallible>> {
+        Poll::Ready(Ok(Some(self.status.trailers())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_solve_status() {
+        let mut started = Vec::new();
+        varint_field(&mut started, 1, 1_625_140_000);
+        varint_field(&mut started, 2, 500);
+
+        let mut vertex = Vec::new();
+        bytes_field(&mut vertex, 1, b"sha256:v1");
+        bytes_field(&mut vertex, 2, b"sha256:v0");
+        bytes_field(&mut vertex, 3, b"[2/3] RUN make");
+        varint_field(&mut vertex, 4, 1);
+        bytes_field(&mut vertex, 5, &started);
+        // an unknown fixed64 field is skipped
+        varint(&mut vertex, 9 << 3 | 1);
+        vertex.extend_from_slice(&[0; 8]);
+
+        let mut status = Vec::new();
+        bytes_field(&mut status, 2, b"sha256:v1");
+        varint_field(&mut status, 4, 300);
+        varint_field(&mut status, 5, 1000);
+
+        let mut log = Vec::new();
+        bytes_field(&mut log, 1, b"sha256:v1");
+        varint_field(&mut log, 3, 2);
+        bytes_field(&mut log, 4, b"warning: unused\n");
+
+        let mut response = Vec::new();
+        bytes_field(&mut response, 1, &vertex);
+        bytes_field(&mut response, 2, &status);
+        bytes_field(&mut response, 3, &log);
+
+        let raw: RawChunk = serde_json::from_value(serde_json::json!({
+            "id": "moby.buildkit.trace",
+            "aux": base64::encode(&response),
+        }))
+        .unwrap();
+        let status = match BuildKitChunk::from_raw(raw) {
+            Some(Ok(BuildKitChunk::Status(status))) => status,
+            chunk => panic!("unexpected chunk {:?}", chunk),
+        };
+        assert_eq!(
+            status.vertexes,
+            [Vertex {
+                digest: "sha256:v1".to_owned(),
+                inputs: vec!["sha256:v0".to_owned()],
+                name: "[2/3] RUN make".to_owned(),
+                cached: true,
+                started: Some(UNIX_EPOCH + Duration::new(1_625_140_000, 500)),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(status.statuses[0].current, 300);
+        assert_eq!(status.statuses[0].total, 1000);
+        assert_eq!(status.logs[0].stream, 2);
+        assert_eq!(status.logs[0].msg, b"warning: unused\n");
+
+        assert!(SolveStatus::decode(&response[..response.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn chunks_of_messages() {
+        let chunk = |json| BuildKitChunk::from_raw(serde_json::from_value(json).unwrap());
+        match chunk(serde_json::json!({ "id": "moby.image.id", "aux": { "ID": "sha256:1a2b" } })) {
+            Some(Ok(BuildKitChunk::ImageId(id))) => assert_eq!(id, "sha256:1a2b"),
+            chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        match chunk(serde_json::json!({
+            "error": "failed to solve",
+            "errorDetail": { "message": "failed to solve: exit code 1" },
+        })) {
+            Some(Err(Error::StreamFault { message, .. })) => {
+                assert_eq!(message, "failed to solve: exit code 1")
+            }
+            chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        assert!(chunk(serde_json::json!({ "id": "moby.unknown" })).is_none());
+    }
+
+    /// Calls `method` of `session` over an in-memory HTTP/2 connection, with the messages sent
+    /// on `requests`, and returns the messages and the status of the response
+    async fn call(
+        session: Session,
+        method: &str,
+        headers: &[(&str, &str)],
+        requests: Body,
+    ) -> (Vec<Vec<u8>>, HeaderMap) {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(session.serve(server));
+        let (mut sender, connection) = hyper::client::conn::Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(client)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let mut request = Request::post(format!("http://session{}", method))
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .header(header::TE, "trailers");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = sender
+            .send_request(request.body(requests).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/grpc");
+
+        let mut body = response.into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        let trailers = body.trailers().await.unwrap().unwrap();
+        let mut messages = Vec::new();
+        let mut data = &data[..];
+        while !data.is_empty() {
+            let len = BigEndian::read_u32(&data[1..5]) as usize;
+            messages.push(data[5..5 + len].to_vec());
+            data = &data[5 + len..];
+        }
+        (messages, trailers)
+    }
+
+    fn request(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    #[tokio::test]
+    async fn serve_secrets() {
+        let session = || Session::new().secret("token", "s3cr3t");
+
+        let (messages, status) = call(session(), HEALTH_CHECK, &[], Body::from(request(&[]))).await;
+        assert_eq!(messages, [[1 << 3, 1]]);
+        assert_eq!(status["grpc-status"], "0");
+
+        let mut id = Vec::new();
+        bytes_field(&mut id, 1, b"token");
+        let (messages, status) = call(session(), GET_SECRET, &[], Body::from(request(&id))).await;
+        let mut secret = Vec::new();
+        bytes_field(&mut secret, 1, b"s3cr3t");
+        assert_eq!(messages, [secret]);
+        assert_eq!(status["grpc-status"], "0");
+
+        let mut id = Vec::new();
+        bytes_field(&mut id, 1, b"missing");
+        let (messages, status) = call(session(), GET_SECRET, &[], Body::from(request(&id))).await;
+        assert!(messages.is_empty());
+        assert_eq!(status["grpc-status"], "5");
+        assert_eq!(status["grpc-message"], "secret missing not found");
+
+        let (_, status) = call(
+            session(),
+            "/moby.filesync.v1.FileSync/DiffCopy",
+            &[],
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(status["grpc-status"], "12");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn forward_ssh_agents() {
+        use tokio::{io::AsyncReadExt, net::UnixListener};
+
+        let socket = std::env::temp_dir().join(format!("shiplift-agent-{}", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        // an agent that answers a request with its reverse
+        tokio::spawn(async move {
+            let (mut agent, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4];
+            agent.read_exact(&mut buf).await.unwrap();
+            buf.reverse();
+            agent.write_all(&buf).await.unwrap();
+        });
+        let session = || Session::new().ssh_agent("default", &socket);
+
+        let (_, status) = call(session(), CHECK_AGENT, &[], Body::from(request(&[]))).await;
+        assert_eq!(status["grpc-status"], "0");
+        let mut id = Vec::new();
+        bytes_field(&mut id, 1, b"deploy");
+        let (_, status) = call(session(), CHECK_AGENT, &[], Body::from(request(&id))).await;
+        assert_eq!(status["grpc-status"], "5");
+
+        let mut message = Vec::new();
+        bytes_field(&mut message, 1, b"ping");
+        let (messages, status) = call(
+            session(),
+            FORWARD_AGENT,
+            &[("buildkit.ssh.id", "default")],
+            Body::from(request(&message)),
+        )
+        .await;
+        std::fs::remove_file(&socket).unwrap();
+        let mut answer = Vec::new();
+        bytes_field(&mut answer, 1, b"gnip");
+        assert_eq!(messages, [answer]);
+        assert_eq!(status["grpc-status"], "0");
+    }
+}
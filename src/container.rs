@@ -2,25 +2,38 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Container>
 
-use std::{collections::HashMap, hash::Hash, io, iter::Peekable, path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io,
+    iter::Peekable,
+    net::SocketAddr,
+    path::Path,
+    pin::Pin,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use futures_util::{
+    future::Either,
     io::{AsyncRead, AsyncWrite},
-    stream::Stream,
-    TryStreamExt,
+    stream::{Stream, StreamExt},
+    TryFutureExt, TryStreamExt,
 };
-use hyper::Body;
+use hyper::{Body, Method};
 use mime::Mime;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use url::form_urlencoded;
 
 use crate::{
-    docker::Docker,
-    errors::{Error, Result},
+    docker::{Docker, Event, EventFilter, EventFilterType, EventsOptions},
+    duration,
+    errors::{self, Error, Result},
     exec::{Exec, ExecContainerOptions},
-    image::ContainerConfig,
+    id::{ContainerId, ExecId, ImageId},
+    image::{ContainerConfig, ImageConfig, Images},
     network::NetworkSettings,
+    query::{self, Filters},
     transport::Payload,
     tty::{self, Multiplexer as TtyMultiPlexer},
 };
@@ -29,6 +42,8 @@ use crate::{
 use crate::datetime::datetime_from_unix_timestamp;
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
 
 /// Interface for accessing and manipulating a docker container
 ///
@@ -45,11 +60,11 @@ impl<'docker> Container<'docker> {
         id: S,
     ) -> Self
     where
-        S: Into<String>,
+        S: Into<ContainerId>,
     {
         Container {
             docker,
-            id: id.into(),
+            id: id.into().into_string(),
         }
     }
 
@@ -75,7 +90,7 @@ impl<'docker> Container<'docker> {
         psargs: Option<&str>,
     ) -> Result<Top> {
         let mut path = vec![format!("/containers/{}/top", self.id)];
-        if let Some(ref args) = psargs {
+        if let Some(args) = psargs {
             let encoded = form_urlencoded::Serializer::new(String::new())
                 .append_pair("ps_args", args)
                 .finish();
@@ -86,19 +101,36 @@ impl<'docker> Container<'docker> {
 
     /// Returns a stream of logs emitted but the container instance
     ///
+    /// The daemon multiplexes the output of the container, which is split into
+    /// [StdOut](tty::TtyChunk::StdOut) and [StdErr](tty::TtyChunk::StdErr) chunks. The output
+    /// of a container with a TTY is not multiplexed and returned as
+    /// [StdOut](tty::TtyChunk::StdOut) chunks, like [attach](Container::attach) does. The
+    /// stream fails with [Error::InvalidResponse] if multiplexed output is malformed.
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerLogs)
     pub fn logs(
         &self,
         opts: &LogsOptions,
     ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
-        let mut path = vec![format!("/containers/{}/logs", self.id)];
-        if let Some(query) = opts.serialize() {
-            path.push(query)
-        }
-
-        let stream = Box::pin(self.docker.stream_get(path.join("?")));
-
-        Box::pin(tty::decode(stream))
+        let query = opts.serialize();
+        let docker = self.docker;
+        let container = Container::new(docker, self.id.clone());
+        Box::pin(
+            async move {
+                let mut path = vec![format!("/containers/{}/logs", container.id)];
+                if let Some(query) = query? {
+                    path.push(query)
+                }
+                let tty = container.inspect().await?.config.tty;
+                let stream = Box::pin(docker.stream_get(path.join("?")));
+                Ok(if tty {
+                    Either::Left(tty::raw(stream))
+                } else {
+                    Either::Right(tty::decode(stream))
+                })
+            }
+            .try_flatten_stream(),
+        )
     }
 
     /// Attaches a multiplexed TCP stream to the container that can be used to read Stdout, Stderr and write Stdin.
@@ -119,14 +151,23 @@ impl<'docker> Container<'docker> {
     /// The [Multiplexer](crate::tty::Multiplexer) implements Stream for returning Stdout and
     /// Stderr chunks. It also implements `[AsyncWrite]` for writing to Stdin.
     ///
+    /// The container is inspected first to tell whether it has a TTY. A TTY merges stdout and
+    /// stderr, so all output of such a container is returned as
+    /// [StdOut](crate::tty::TtyChunk::StdOut) chunks.
+    ///
     /// The multiplexer can be split into its read and write halves with the
     /// [split](crate::tty::Multiplexer::split) method
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerAttach)
     pub async fn attach(&self) -> Result<TtyMultiPlexer<'docker>> {
+        let tty = self.inspect().await?.config.tty;
         let tcp_stream = self.attach_raw().await?;
 
-        Ok(TtyMultiPlexer::new(tcp_stream))
+        Ok(if tty {
+            TtyMultiPlexer::raw(tcp_stream)
+        } else {
+            TtyMultiPlexer::new(tcp_stream)
+        })
     }
 
     /// Returns a set of changes made to the container instance
@@ -142,20 +183,27 @@ impl<'docker> Container<'docker> {
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerExport)
     pub fn export(&self) -> impl Stream<Item = Result<Vec<u8>>> + 'docker {
-        self.docker
-            .stream_get(format!("/containers/{}/export", self.id))
-            .map_ok(|c| c.to_vec())
+        errors::track_progress(
+            self.docker
+                .stream_get(format!("/containers/{}/export", self.id))
+                .map_ok(|c| c.to_vec()),
+            |progress, chunk| progress.record_bytes(chunk.len()),
+        )
     }
 
     /// Returns a stream of stats specific to this container instance
     ///
+    /// The daemon sends a sample about once a second until the stream is dropped or the
+    /// container is removed. Use [stats_once](Container::stats_once) for a single sample.
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStats)
     pub fn stats(&self) -> impl Stream<Item = Result<Stats>> + Unpin + 'docker {
         let codec = futures_codec::LinesCodec {};
 
+        let path = format!("/containers/{}/stats?stream=true", self.id);
         let reader = Box::pin(
             self.docker
-                .stream_get(format!("/containers/{}/stats", self.id))
+                .stream_get(path.clone())
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
         )
         .into_async_read();
@@ -163,12 +211,25 @@ impl<'docker> Container<'docker> {
         Box::pin(
             futures_codec::FramedRead::new(reader, codec)
                 .map_err(Error::IO)
-                .and_then(|s: String| async move {
-                    serde_json::from_str(&s).map_err(Error::SerdeJsonError)
+                .and_then(move |s: String| {
+                    let result = serde_json::from_str(&s)
+                        .map_err(|e| Error::decode(Method::GET, path.as_str(), &s, e));
+                    async move { result }
                 }),
         )
     }
 
+    /// Returns a single sample of the stats of this container instance
+    ///
+    /// The daemon samples the container twice before responding, so this takes about a second.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStats)
+    pub async fn stats_once(&self) -> Result<Stats> {
+        self.docker
+            .get_json(&format!("/containers/{}/stats?stream=false", self.id))
+            .await
+    }
+
     /// Start the container instance
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStart)
@@ -189,7 +250,7 @@ impl<'docker> Container<'docker> {
         let mut path = vec![format!("/containers/{}/stop", self.id)];
         if let Some(w) = wait {
             let encoded = form_urlencoded::Serializer::new(String::new())
-                .append_pair("t", &w.as_secs().to_string())
+                .append_pair("t", &duration::whole_secs(w).to_string())
                 .finish();
 
             path.push(encoded)
@@ -208,7 +269,7 @@ impl<'docker> Container<'docker> {
         let mut path = vec![format!("/containers/{}/restart", self.id)];
         if let Some(w) = wait {
             let encoded = form_urlencoded::Serializer::new(String::new())
-                .append_pair("t", &w.as_secs().to_string())
+                .append_pair("t", &duration::whole_secs(w).to_string())
                 .finish();
             path.push(encoded)
         }
@@ -226,7 +287,7 @@ impl<'docker> Container<'docker> {
         let mut path = vec![format!("/containers/{}/kill", self.id)];
         if let Some(sig) = signal {
             let encoded = form_urlencoded::Serializer::new(String::new())
-                .append_pair("signal", &sig.to_owned())
+                .append_pair("signal", sig)
                 .finish();
             path.push(encoded)
         }
@@ -253,6 +314,38 @@ impl<'docker> Container<'docker> {
         Ok(())
     }
 
+    /// Creates an image from the changes made to the container instance, returning its ID
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageCommit)
+    pub async fn commit(
+        &self,
+        opts: &CommitOptions,
+    ) -> Result<ImageId> {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("container", &self.id)
+            .finish();
+        let mut path = format!("/commit?{}", query);
+        if let Some(params) = opts.serialize()? {
+            path.push('&');
+            path.push_str(&params);
+        }
+        let config = match opts.config {
+            Some(ref config) => serde_json::to_string(config)?,
+            None => "{}".to_owned(),
+        };
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Committed {
+            id: ImageId,
+        }
+        let committed: Committed = self
+            .docker
+            .post_json(&path, Some((config, mime::APPLICATION_JSON)))
+            .await?;
+        Ok(committed.id)
+    }
+
     /// Pause the container instance
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerPause)
@@ -282,6 +375,158 @@ impl<'docker> Container<'docker> {
             .await
     }
 
+    /// Returns a stream of the states the container goes through
+    ///
+    /// The stream starts with the current state of the container, including its health,
+    /// followed by each change of its state the daemon reports in its events, and ends once the
+    /// container is removed. Events are requested from the time of the daemon just before the
+    /// initial inspect, so that no change in between is missed.
+    pub fn watch(&self) -> impl Stream<Item = Result<ContainerState>> + Unpin + 'docker {
+        let container = Container::new(self.docker, &self.id);
+        Box::pin(
+            async move {
+                let (secs, nanos) = container.docker.system_time().await?;
+                let details = container.inspect().await?;
+                let opts = EventsOptions::builder()
+                    .filter(vec![
+                        EventFilter::Type(EventFilterType::Container),
+                        EventFilter::Container(details.id.to_string()),
+                    ])
+                    .since_nanos(secs, nanos)
+                    .build();
+                let events = container.docker.events(&opts);
+                let initial = ContainerState::from(&details.state);
+
+                Ok(futures_util::stream::unfold(
+                    Some((events, initial, true)),
+                    |watch| async move {
+                        let (mut events, current, first) = watch?;
+                        if first {
+                            return Some((Ok(current.clone()), Some((events, current, false))));
+                        }
+                        if current == ContainerState::Removed {
+                            return None;
+                        }
+                        loop {
+                            match events.next().await? {
+                                Ok(event) => match ContainerState::after(&event, &current) {
+                                    Some(next) if next != current => {
+                                        return Some((
+                                            Ok(next.clone()),
+                                            Some((events, next, false)),
+                                        ))
+                                    }
+                                    _ => (),
+                                },
+                                Err(e) => return Some((Err(e), None)),
+                            }
+                        }
+                    },
+                ))
+            }
+            .try_flatten_stream(),
+        )
+    }
+
+    /// Waits until the TCP port `container_port` of the container accepts connections, and
+    /// returns the address it was reached at
+    ///
+    /// The port is reached at the host port it is published on. Ports that are not published
+    /// are reached at the IP address of the container on one of its networks, preferring
+    /// user-defined networks, which only works if the daemon runs on this host. Fails with an
+    /// [io::ErrorKind::TimedOut] error if no connection succeeds within `timeout`.
+    pub async fn wait_for_port(
+        &self,
+        container_port: u16,
+        timeout: Duration,
+    ) -> Result<SocketAddr> {
+        let deadline = Instant::now() + timeout;
+        let remote_host = self.docker.remote_host();
+        loop {
+            let details = self.inspect().await?;
+            let failure = match port_address(
+                &details.network_settings,
+                container_port,
+                remote_host.as_deref(),
+            ) {
+                Some((host, port)) => {
+                    let attempt = tokio::time::timeout(
+                        deadline.saturating_duration_since(Instant::now()),
+                        tokio::net::TcpStream::connect((host.as_str(), port)),
+                    )
+                    .await;
+                    match attempt {
+                        Ok(Ok(stream)) => return Ok(stream.peer_addr()?),
+                        Ok(Err(e)) => format!("{}:{}: {}", host, port, e),
+                        Err(_) => format!("{}:{}: timed out", host, port),
+                    }
+                }
+                None => "the port is not published".to_owned(),
+            };
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::IO(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "port {} of container {} did not accept connections in time, last \
+                         attempt: {}",
+                        container_port, self.id, failure
+                    ),
+                )));
+            }
+            tokio::time::sleep(PORT_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    /// Waits until the healthcheck of the container reports it healthy
+    ///
+    /// The container is inspected until its health is `healthy`. Fails with
+    /// [Error::NotHealthy] if the container has no healthcheck or stops, and with an
+    /// [io::ErrorKind::TimedOut] error carrying the output of the last healthcheck if it is
+    /// not healthy within `timeout`.
+    pub async fn wait_healthy(
+        &self,
+        timeout: Duration,
+    ) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let state = self.inspect().await?.state;
+            let not_healthy = |reason: &str| Error::NotHealthy {
+                container: self.id.to_string(),
+                reason: reason.to_owned(),
+            };
+            let health = match state.health {
+                Some(health) if health.status != HealthStatus::None => health,
+                _ => return Err(not_healthy("it has no healthcheck")),
+            };
+            if health.status == HealthStatus::Healthy {
+                return Ok(());
+            }
+            if !state.running && !state.restarting {
+                return Err(not_healthy(&format!("it is {}", state.status)));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                let output = health
+                    .log
+                    .last()
+                    .map(|check| check.output.trim())
+                    .unwrap_or_default();
+                return Err(Error::IO(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "container {} did not become healthy in time, it is {:?}, last \
+                         healthcheck output: {}",
+                        self.id, health.status, output
+                    ),
+                )));
+            }
+            tokio::time::sleep(HEALTH_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
     /// Delete the container instance
     ///
     /// Use remove instead to use the force/v options.
@@ -302,7 +547,7 @@ impl<'docker> Container<'docker> {
         opts: RmContainerOptions,
     ) -> Result<()> {
         let mut path = vec![format!("/containers/{}", self.id)];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query)
         }
         self.docker.delete(&path.join("?")).await?;
@@ -319,6 +564,70 @@ impl<'docker> Container<'docker> {
         Exec::create_and_start(self.docker, &self.id, opts)
     }
 
+    /// Streams the contents of the file at `path` inside the container, for applications that
+    /// log to a file instead of stdout
+    ///
+    /// The file is read by running `tail` in the container, which its image has to provide.
+    /// With `follow`, lines are streamed as they are appended, also across rotations of the
+    /// file, and `tail` is run again if it dies while the container is running. Lines appended
+    /// in between are skipped then. Errors of `tail`, like a missing file, are streamed as
+    /// [TtyChunk::StdErr](tty::TtyChunk::StdErr).
+    pub fn tail_file(
+        &self,
+        path: &str,
+        follow: bool,
+    ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
+        let tail = TailFile {
+            container: Container::new(self.docker, &self.id),
+            path: path.to_owned(),
+            follow,
+            exec: None,
+            output: None,
+            started: false,
+        };
+        Box::pin(futures_util::stream::unfold(
+            Some(tail),
+            |tail| async move {
+                let mut tail = tail?;
+                loop {
+                    if let Some(output) = &mut tail.output {
+                        if let Some(chunk) = output.next().await {
+                            return Some((chunk, Some(tail)));
+                        }
+                        tail.output = None;
+                        match tail.should_retail().await {
+                            Ok(true) => tokio::time::sleep(RETAIL_INTERVAL).await,
+                            Ok(false) => return None,
+                            Err(e) => return Some((Err(e), None)),
+                        }
+                    }
+
+                    // after the first run, only what is appended from then on is tailed
+                    let lines = if tail.started { "0" } else { "+1" };
+                    let mut cmd = vec!["tail", "-n", lines];
+                    if tail.follow {
+                        cmd.push("-F");
+                    }
+                    cmd.extend(["--", tail.path.as_str()]);
+                    let opts = ExecContainerOptions::builder()
+                        .cmd(cmd)
+                        .attach_stdout(true)
+                        .attach_stderr(true)
+                        .build();
+                    let exec = match Exec::create(tail.container.docker, &tail.container.id, &opts)
+                        .await
+                    {
+                        Ok(exec) => exec,
+                        Err(e) => return Some((Err(e), None)),
+                    };
+                    tail.output = Some(Box::pin(exec.start()));
+                    tail.exec = Some(exec);
+                    tail.started = true;
+                }
+            },
+        ))
+    }
+
     /// Copy a file/folder from the container.  The resulting stream is a tarball of the extracted
     /// files.
     ///
@@ -398,6 +707,97 @@ impl<'docker> Container<'docker> {
     }
 }
 
+/// Time between two connection attempts of [Container::wait_for_port]
+const PORT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time between two inspections of [Container::wait_healthy]
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time to wait before running `tail` again for [Container::tail_file]
+const RETAIL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The state of [Container::tail_file]
+struct TailFile<'docker> {
+    container: Container<'docker>,
+    path: String,
+    follow: bool,
+    /// The last `tail` that was run
+    exec: Option<Exec<'docker>>,
+    output: Option<Pin<Box<dyn Stream<Item = Result<tty::TtyChunk>> + Send + 'docker>>>,
+    started: bool,
+}
+
+impl TailFile<'_> {
+    /// Whether to run `tail` again after it exited
+    async fn should_retail(&self) -> Result<bool> {
+        if !self.follow {
+            return Ok(false);
+        }
+        if let Some(exec) = &self.exec {
+            // the image has no tail, which running again won't change
+            if let Some(code @ (126 | 127)) = exec.inspect().await?.exit_code {
+                return Err(Error::IO(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "tail could not be run in container {}, it exited with {}",
+                        self.container.id, code
+                    ),
+                )));
+            }
+        }
+        match self.container.inspect().await {
+            Ok(details) => Ok(details.state.running),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The host and port at which the TCP port `container_port` of a container can be reached, see
+/// [Container::wait_for_port]
+fn port_address(
+    settings: &NetworkSettings,
+    container_port: u16,
+    remote_host: Option<&str>,
+) -> Option<(String, u16)> {
+    let published = settings
+        .ports
+        .as_ref()
+        .and_then(|ports| ports.get(&format!("{}/tcp", container_port)))
+        .and_then(Option::as_ref)
+        .and_then(|bindings| {
+            bindings.iter().find_map(|binding| {
+                let port = binding.get("HostPort")?.parse().ok()?;
+                let ip = binding.get("HostIp").map(String::as_str).unwrap_or("");
+                Some((ip, port))
+            })
+        });
+    if let Some((ip, port)) = published {
+        let host = match remote_host {
+            Some(host) => host,
+            None if ip.is_empty() || ip == "0.0.0.0" || ip == "::" => "127.0.0.1",
+            None => ip,
+        };
+        return Some((host.to_owned(), port));
+    }
+    if remote_host.is_some() {
+        return None;
+    }
+
+    let mut networks: Vec<_> = settings
+        .networks
+        .iter()
+        .filter(|(_, network)| !network.ip_address.is_empty())
+        .collect();
+    // user-defined networks first, then by name to not depend on the order of the map
+    networks.sort_by_key(|(name, _)| (name.as_str() == "bridge", name.as_str()));
+    networks
+        .first()
+        .map(|(_, network)| network.ip_address.as_str())
+        .or(Some(settings.ip_address.as_str()).filter(|ip| !ip.is_empty()))
+        .map(|ip| (ip.to_owned(), container_port))
+}
+
 /// Interface for docker containers
 ///
 /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#tag/Containers)
@@ -419,7 +819,7 @@ impl<'docker> Containers<'docker> {
         opts: &ContainerListOptions,
     ) -> Result<Vec<ContainerInfo>> {
         let mut path = vec!["/containers/json".to_owned()];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query)
         }
         self.docker
@@ -433,7 +833,7 @@ impl<'docker> Containers<'docker> {
         name: S,
     ) -> Container<'docker>
     where
-        S: Into<String>,
+        S: Into<ContainerId>,
     {
         Container::new(self.docker, name)
     }
@@ -443,10 +843,19 @@ impl<'docker> Containers<'docker> {
         &self,
         opts: &ContainerOptions,
     ) -> Result<ContainerCreateInfo> {
-        let body: Body = opts.serialize()?.into();
+        self.create_json(opts.name.as_deref(), &opts.to_json()?)
+            .await
+    }
+
+    async fn create_json(
+        &self,
+        name: Option<&str>,
+        config: &Value,
+    ) -> Result<ContainerCreateInfo> {
+        let body: Body = serde_json::to_string(config)?.into();
         let mut path = vec!["/containers/create".to_owned()];
 
-        if let Some(ref name) = opts.name {
+        if let Some(name) = name {
             path.push(
                 form_urlencoded::Serializer::new(String::new())
                     .append_pair("name", name)
@@ -458,12 +867,320 @@ impl<'docker> Containers<'docker> {
             .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
             .await
     }
+
+    /// Makes sure a container named `name` is running with the options `opts`
+    ///
+    /// A missing container is created and started. An existing container is started if it is
+    /// not running, or replaced by a new one as `policy` requires. To tell whether the options
+    /// changed, the container is labeled with a hash of them, so that containers created by
+    /// other means count as changed. If another container of the name is created concurrently,
+    /// that one is used instead.
+    pub async fn ensure(
+        &self,
+        name: &str,
+        opts: &ContainerOptions,
+        policy: RecreatePolicy,
+    ) -> Result<EnsuredContainer> {
+        let mut config = opts.to_json()?;
+        let hash = config_hash(&config);
+        match config["Labels"].as_object_mut() {
+            Some(labels) => {
+                labels.insert(CONFIG_HASH_LABEL.to_owned(), json!(hash));
+            }
+            None => config["Labels"] = json!({ CONFIG_HASH_LABEL: hash }),
+        }
+
+        let container = self.get(name);
+        let mut retried = false;
+        loop {
+            let outcome = match container.inspect().await {
+                Ok(details) => {
+                    if !self
+                        .needs_recreate(&details, &config, &hash, policy)
+                        .await?
+                    {
+                        if !details.state.running {
+                            start_if_stopped(&container).await?;
+                        }
+                        return Ok(EnsuredContainer {
+                            id: details.id,
+                            outcome: EnsureOutcome::Reused,
+                        });
+                    }
+                    container
+                        .remove(RmContainerOptions::builder().force(true).build())
+                        .await?;
+                    EnsureOutcome::Replaced
+                }
+                Err(e) if e.is_not_found() => EnsureOutcome::Created,
+                Err(e) => return Err(e),
+            };
+
+            match self.create_json(Some(name), &config).await {
+                Ok(info) => {
+                    start_if_stopped(&self.get(&info.id)).await?;
+                    return Ok(EnsuredContainer {
+                        id: info.id,
+                        outcome,
+                    });
+                }
+                Err(e) if e.is_conflict() && !retried => retried = true,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Removes the stopped containers in one of `states` that match `selector`, typically labels,
+    /// and were created more than `age` ago, and reports what was removed
+    ///
+    /// Without `states`, created and exited containers are removed. The age is measured by the
+    /// clock of this host. With `volumes`, the anonymous volumes of the containers are removed
+    /// with them. Containers that are removed concurrently are left out of the report.
+    pub async fn remove_older_than(
+        &self,
+        age: Duration,
+        selector: Vec<ContainerFilter>,
+        states: &[StoppedState],
+        volumes: bool,
+    ) -> Result<RemovalReport> {
+        let states = if states.is_empty() {
+            &[StoppedState::Created, StoppedState::Exited][..]
+        } else {
+            states
+        };
+        let filters = selector
+            .into_iter()
+            .chain(
+                states
+                    .iter()
+                    .map(|state| ContainerFilter::Status(state.as_str().to_owned())),
+            )
+            .collect();
+        let opts = ContainerListOptions::builder()
+            .all()
+            .filter(filters)
+            .build();
+        let cutoff = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_sub(age)
+            .as_secs();
+
+        let mut report = RemovalReport::default();
+        for container in self.list(&opts).await? {
+            if created_secs(&container) >= cutoff as i64 {
+                continue;
+            }
+            let opts = RmContainerOptions::builder().volumes(volumes).build();
+            match self.get(&container.id).remove(opts).await {
+                Ok(()) => report.removed.push(container.id),
+                Err(e) if e.is_not_found() => (),
+                Err(e) => report.failed.push((container.id, e)),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Stops the running containers matching `filters`, `concurrency` at a time, and streams
+    /// the outcome for each of them as it is done
+    ///
+    /// Each container is sent its stop signal and killed by the daemon if it did not stop within
+    /// `grace`. If the daemon did not report the container stopped shortly after that, it is
+    /// killed explicitly and reported as [StopOutcome::Killed]. The stream fails if the
+    /// containers cannot be listed.
+    pub fn stop_all(
+        &self,
+        filters: Vec<ContainerFilter>,
+        grace: Duration,
+        concurrency: usize,
+    ) -> impl Stream<Item = Result<StopReport>> + Unpin + 'docker {
+        let docker = self.docker;
+        let opts = ContainerListOptions::builder().filter(filters).build();
+        Box::pin(
+            async move {
+                let containers = Containers::new(docker).list(&opts).await?;
+                Ok(futures_util::stream::iter(containers)
+                    .map(move |container| async move {
+                        let outcome =
+                            stop_or_kill(&docker.containers().get(&container.id), grace).await;
+                        Ok(StopReport {
+                            id: container.id,
+                            outcome,
+                        })
+                    })
+                    .buffer_unordered(concurrency.max(1)))
+            }
+            .try_flatten_stream(),
+        )
+    }
+
+    async fn needs_recreate(
+        &self,
+        details: &ContainerDetails,
+        config: &Value,
+        hash: &str,
+        policy: RecreatePolicy,
+    ) -> Result<bool> {
+        match policy {
+            RecreatePolicy::Never => Ok(false),
+            RecreatePolicy::Always => Ok(true),
+            RecreatePolicy::IfChanged => {
+                let labels = details.config.labels.as_ref();
+                if labels
+                    .and_then(|labels| labels.get(CONFIG_HASH_LABEL))
+                    .map(String::as_str)
+                    != Some(hash)
+                {
+                    return Ok(true);
+                }
+                // the same reference may have been pulled again since
+                let image = config["Image"].as_str().unwrap_or_default();
+                let image = Images::new(self.docker).get(image).inspect().await?;
+                Ok(image.id != details.image)
+            }
+        }
+    }
+}
+
+/// Label under which [Containers::ensure] records the hash of the options of a container
+const CONFIG_HASH_LABEL: &str = "shiplift.config-hash";
+
+/// The FNV-1a hash of the create options of a container, which is stable across versions of
+/// Rust unlike the hasher of std
+fn config_hash(config: &Value) -> String {
+    let hash = config
+        .to_string()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Starts `container`, which another client may have started already
+async fn start_if_stopped(container: &Container<'_>) -> Result<()> {
+    match container.start().await {
+        Err(e) if e.is_not_modified() => Ok(()),
+        result => result,
+    }
+}
+
+/// How long after the grace period [Containers::stop_all] waits for the daemon to report a
+/// container stopped before killing it
+const STOP_KILL_MARGIN: Duration = Duration::from_secs(5);
+
+/// Stops `container` within `grace`, killing it if the daemon does not stop it in time
+async fn stop_or_kill(
+    container: &Container<'_>,
+    grace: Duration,
+) -> StopOutcome {
+    match tokio::time::timeout(grace + STOP_KILL_MARGIN, container.stop(Some(grace))).await {
+        Ok(Ok(())) => StopOutcome::Stopped,
+        Ok(Err(e)) if e.is_not_modified() || e.is_not_found() => StopOutcome::AlreadyStopped,
+        Ok(Err(e)) => StopOutcome::Failed(e),
+        Err(_) => match container.kill(None).await {
+            Ok(()) => StopOutcome::Killed,
+            Err(e) if e.is_conflict() || e.is_not_found() => StopOutcome::AlreadyStopped,
+            Err(e) => StopOutcome::Failed(e),
+        },
+    }
+}
+
+/// What [Containers::stop_all] did to a container
+#[derive(Debug)]
+pub struct StopReport {
+    pub id: ContainerId,
+    pub outcome: StopOutcome,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StopOutcome {
+    /// The container stopped, by itself or killed by the daemon after the grace period
+    Stopped,
+    /// The daemon did not stop the container in time, so it was killed
+    Killed,
+    /// The container had stopped or was removed before it was stopped
+    AlreadyStopped,
+    Failed(Error),
+}
+
+/// The creation time of `container` in seconds since the Unix epoch
+fn created_secs(container: &ContainerInfo) -> i64 {
+    #[cfg(feature = "chrono")]
+    let created = container.created.timestamp();
+    #[cfg(feature = "time")]
+    let created = container.created.unix_timestamp();
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    let created = container.created as i64;
+    created
+}
+
+/// A state of a container that is not running, see [Containers::remove_older_than]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoppedState {
+    /// Created, but never started
+    Created,
+    Exited,
+    /// Failed to be removed, typically because a resource of it was busy
+    Dead,
+}
+
+impl StoppedState {
+    fn as_str(self) -> &'static str {
+        match self {
+            StoppedState::Created => "created",
+            StoppedState::Exited => "exited",
+            StoppedState::Dead => "dead",
+        }
+    }
+}
+
+/// What [Containers::remove_older_than] removed
+#[derive(Debug, Default)]
+pub struct RemovalReport {
+    pub removed: Vec<ContainerId>,
+    /// Containers that could not be removed, like containers that were started again
+    pub failed: Vec<(ContainerId, Error)>,
+}
+
+/// When [Containers::ensure] replaces an existing container
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecreatePolicy {
+    /// Replace the container if it was created with other options, or its image reference now
+    /// resolves to another image
+    #[default]
+    IfChanged,
+    /// Never replace an existing container
+    Never,
+    /// Always replace an existing container
+    Always,
+}
+
+/// What [Containers::ensure] did to get the container running
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnsureOutcome {
+    Created,
+    Reused,
+    Replaced,
+}
+
+/// A container made sure to run by [Containers::ensure]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnsuredContainer {
+    pub id: ContainerId,
+    pub outcome: EnsureOutcome,
 }
 
 /// Options for filtering container list results
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct ContainerListOptions {
-    params: HashMap<&'static str, String>,
+    all: Option<bool>,
+    since: Option<String>,
+    before: Option<String>,
+    size: Option<bool>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl ContainerListOptions {
@@ -473,16 +1190,8 @@ impl ContainerListOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
@@ -495,66 +1204,62 @@ pub enum ContainerFilter {
     Name(String),
 }
 
+impl query::Filter for ContainerFilter {
+    fn query(self) -> (&'static str, String) {
+        match self {
+            ContainerFilter::ExitCode(c) => ("exited", c.to_string()),
+            ContainerFilter::Status(s) => ("status", s),
+            ContainerFilter::LabelName(n) => ("label", n),
+            ContainerFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
+            ContainerFilter::Name(n) => ("name", n),
+        }
+    }
+}
+
 /// Builder interface for `ContainerListOptions`
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ContainerListOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: ContainerListOptions,
 }
 
 impl ContainerListOptionsBuilder {
     pub fn filter(
-        &mut self,
+        mut self,
         filters: Vec<ContainerFilter>,
-    ) -> &mut Self {
-        let mut param: HashMap<&str, Vec<String>> = HashMap::new();
-        for f in filters {
-            let (key, value) = match f {
-                ContainerFilter::ExitCode(c) => ("exited", c.to_string()),
-                ContainerFilter::Status(s) => ("status", s),
-                ContainerFilter::LabelName(n) => ("label", n),
-                ContainerFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
-                ContainerFilter::Name(n) => ("name", n.to_string()),
-            };
-
-            param.entry(key).or_insert_with(Vec::new).push(value);
-        }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
+    ) -> Self {
+        self.options.filters = Filters::typed(filters);
         self
     }
 
-    pub fn all(&mut self) -> &mut Self {
-        self.params.insert("all", "true".to_owned());
+    pub fn all(mut self) -> Self {
+        self.options.all = Some(true);
         self
     }
 
     pub fn since(
-        &mut self,
+        mut self,
         since: &str,
-    ) -> &mut Self {
-        self.params.insert("since", since.to_owned());
+    ) -> Self {
+        self.options.since = Some(since.to_owned());
         self
     }
 
     pub fn before(
-        &mut self,
+        mut self,
         before: &str,
-    ) -> &mut Self {
-        self.params.insert("before", before.to_owned());
+    ) -> Self {
+        self.options.before = Some(before.to_owned());
         self
     }
 
-    pub fn sized(&mut self) -> &mut Self {
-        self.params.insert("size", "true".to_owned());
+    pub fn sized(mut self) -> Self {
+        self.options.size = Some(true);
         self
     }
 
-    pub fn build(&self) -> ContainerListOptions {
-        ContainerListOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> ContainerListOptions {
+        self.options
     }
 }
 
@@ -571,25 +1276,31 @@ fn insert<'a, I, V>(
     key_path: &mut Peekable<I>,
     value: &V,
     parent_node: &mut Value,
-) where
+) -> Result<()>
+where
     V: Serialize,
     I: Iterator<Item = &'a str>,
 {
-    let local_key = key_path.next().unwrap();
+    let local_key = match key_path.next() {
+        Some(local_key) => local_key,
+        None => return Ok(()),
+    };
+    let parent = parent_node.as_object_mut().ok_or_else(|| {
+        <serde_json::Error as serde::ser::Error>::custom(format!(
+            "cannot set {} on a value that is not an object",
+            local_key
+        ))
+    })?;
 
     if key_path.peek().is_some() {
-        let node = parent_node
-            .as_object_mut()
-            .unwrap()
+        let node = parent
             .entry(local_key.to_string())
             .or_insert(Value::Object(Map::new()));
 
-        insert(key_path, value, node);
+        insert(key_path, value, node)
     } else {
-        parent_node
-            .as_object_mut()
-            .unwrap()
-            .insert(local_key.to_string(), serde_json::to_value(value).unwrap());
+        parent.insert(local_key.to_string(), serde_json::to_value(value)?);
+        Ok(())
     }
 }
 
@@ -601,36 +1312,39 @@ impl ContainerOptions {
 
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.to_json()).map_err(Error::from)
+        serde_json::to_string(&self.to_json()?).map_err(Error::from)
     }
 
-    fn to_json(&self) -> Value {
+    fn to_json(&self) -> Result<Value> {
         let mut body_members = Map::new();
         // The HostConfig element gets initialized to an empty object,
         // for backward compatibility.
         body_members.insert("HostConfig".to_string(), Value::Object(Map::new()));
         let mut body = Value::Object(body_members);
-        self.parse_from(&self.params, &mut body);
-        body
+        self.parse_from(&self.params, &mut body)?;
+        Ok(body)
     }
 
     pub fn parse_from<'a, K, V>(
         &self,
         params: &'a HashMap<K, V>,
         body: &mut Value,
-    ) where
+    ) -> Result<()>
+    where
         &'a HashMap<K, V>: IntoIterator,
         K: ToString + Eq + Hash,
         V: Serialize,
     {
         for (k, v) in params.iter() {
             let key_string = k.to_string();
-            insert(&mut key_string.split('.').peekable(), v, body)
+            insert(&mut key_string.split('.').peekable(), v, body)?;
         }
+        Ok(())
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ContainerOptionsBuilder {
     name: Option<String>,
     params: HashMap<&'static str, Value>,
@@ -645,44 +1359,44 @@ impl ContainerOptionsBuilder {
     }
 
     pub fn name(
-        &mut self,
+        mut self,
         name: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.name = Some(name.to_owned());
         self
     }
 
     /// Specify the working dir (corresponds to the `-w` docker cli argument)
     pub fn working_dir(
-        &mut self,
+        mut self,
         working_dir: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("WorkingDir", json!(working_dir));
         self
     }
 
     /// Specify any bind mounts, taking the form of `/some/host/path:/some/container/path`
     pub fn volumes(
-        &mut self,
+        mut self,
         volumes: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.Binds", json!(volumes));
         self
     }
 
     /// enable all exposed ports on the container to be mapped to random, available, ports on the host
-    pub fn publish_all_ports(&mut self) -> &mut Self {
+    pub fn publish_all_ports(mut self) -> Self {
         self.params
             .insert("HostConfig.PublishAllPorts", json!(true));
         self
     }
 
     pub fn expose(
-        &mut self,
+        mut self,
         srcport: u32,
         protocol: &str,
         hostport: u32,
-    ) -> &mut Self {
+    ) -> Self {
         let mut exposedport: HashMap<String, String> = HashMap::new();
         exposedport.insert("HostPort".to_string(), hostport.to_string());
 
@@ -721,10 +1435,10 @@ impl ContainerOptionsBuilder {
 
     /// Publish a port in the container without assigning a port on the host
     pub fn publish(
-        &mut self,
+        mut self,
         srcport: u32,
         protocol: &str,
-    ) -> &mut Self {
+    ) -> Self {
         /* The idea here is to go thought the 'old' port binds
          * and to apply them to the local 'exposedport_bindings' variable,
          * add the bind we want and replace the 'old' value */
@@ -754,26 +1468,26 @@ impl ContainerOptionsBuilder {
     }
 
     pub fn links(
-        &mut self,
+        mut self,
         links: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.Links", json!(links));
         self
     }
 
     pub fn memory(
-        &mut self,
+        mut self,
         memory: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.Memory", json!(memory));
         self
     }
 
     /// Total memory limit (memory + swap) in bytes. Set to -1 (default) to enable unlimited swap.
     pub fn memory_swap(
-        &mut self,
+        mut self,
         memory_swap: i64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params
             .insert("HostConfig.MemorySwap", json!(memory_swap));
         self
@@ -784,9 +1498,9 @@ impl ContainerOptionsBuilder {
     /// For example, setting `nano_cpus` to `500_000_000` results in the container being allocated
     /// 50% of a single CPU, while `2_000_000_000` results in the container being allocated 2 CPUs.
     pub fn nano_cpus(
-        &mut self,
+        mut self,
         nano_cpus: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.NanoCpus", json!(nano_cpus));
         self
     }
@@ -795,36 +1509,36 @@ impl ContainerOptionsBuilder {
     ///
     /// See [`nano_cpus`](#method.nano_cpus).
     pub fn cpus(
-        &mut self,
+        self,
         cpus: f64,
-    ) -> &mut Self {
+    ) -> Self {
         self.nano_cpus((1_000_000_000.0 * cpus) as u64)
     }
 
     /// Sets an integer value representing the container's relative CPU weight versus other
     /// containers.
     pub fn cpu_shares(
-        &mut self,
+        mut self,
         cpu_shares: u32,
-    ) -> &mut Self {
+    ) -> Self {
         self.params
             .insert("HostConfig.CpuShares", json!(cpu_shares));
         self
     }
 
     pub fn labels(
-        &mut self,
+        mut self,
         labels: &HashMap<&str, &str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Labels", json!(labels));
         self
     }
 
     /// Whether to attach to `stdin`.
     pub fn attach_stdin(
-        &mut self,
+        mut self,
         attach: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("AttachStdin", json!(attach));
         self.params.insert("OpenStdin", json!(attach));
         self
@@ -832,59 +1546,59 @@ impl ContainerOptionsBuilder {
 
     /// Whether to attach to `stdout`.
     pub fn attach_stdout(
-        &mut self,
+        mut self,
         attach: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("AttachStdout", json!(attach));
         self
     }
 
     /// Whether to attach to `stderr`.
     pub fn attach_stderr(
-        &mut self,
+        mut self,
         attach: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("AttachStderr", json!(attach));
         self
     }
 
     /// Whether standard streams should be attached to a TTY.
     pub fn tty(
-        &mut self,
+        mut self,
         tty: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Tty", json!(tty));
         self
     }
 
     pub fn extra_hosts(
-        &mut self,
+        mut self,
         hosts: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.ExtraHosts", json!(hosts));
         self
     }
 
     pub fn volumes_from(
-        &mut self,
+        mut self,
         volumes: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.VolumesFrom", json!(volumes));
         self
     }
 
     pub fn network_mode(
-        &mut self,
+        mut self,
         network: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.NetworkMode", json!(network));
         self
     }
 
     pub fn env<E, S>(
-        &mut self,
+        mut self,
         envs: E,
-    ) -> &mut Self
+    ) -> Self
     where
         S: AsRef<str> + Serialize,
         E: AsRef<[S]> + Serialize,
@@ -894,51 +1608,151 @@ impl ContainerOptionsBuilder {
     }
 
     pub fn cmd(
-        &mut self,
+        mut self,
         cmds: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Cmd", json!(cmds));
         self
     }
 
     pub fn entrypoint(
-        &mut self,
+        mut self,
         entrypoint: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Entrypoint", json!(entrypoint));
         self
     }
 
     pub fn capabilities(
-        &mut self,
+        mut self,
         capabilities: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.CapAdd", json!(capabilities));
         self
     }
 
+    /// Kernel capabilities to drop from the container, like `NET_RAW`, or `ALL`
+    pub fn cap_drop(
+        mut self,
+        capabilities: Vec<&str>,
+    ) -> Self {
+        self.params
+            .insert("HostConfig.CapDrop", json!(capabilities));
+        self
+    }
+
+    /// Security options, like `no-new-privileges` or `seccomp=unconfined`
+    pub fn security_opt(
+        mut self,
+        options: Vec<&str>,
+    ) -> Self {
+        self.params.insert("HostConfig.SecurityOpt", json!(options));
+        self
+    }
+
+    /// Binds, volumes and tmpfs to mount into the container
+    pub fn mounts(
+        mut self,
+        mounts: Vec<MountSpec>,
+    ) -> Self {
+        self.params.insert("HostConfig.Mounts", json!(mounts));
+        self
+    }
+
+    /// Resource limits of the processes in the container, like the number of open files
+    pub fn ulimits(
+        mut self,
+        ulimits: Vec<Ulimit>,
+    ) -> Self {
+        self.params.insert("HostConfig.Ulimits", json!(ulimits));
+        self
+    }
+
+    /// Maximum number of processes in the container. Set to -1 for there to be no limit.
+    pub fn pids_limit(
+        mut self,
+        limit: i64,
+    ) -> Self {
+        self.params.insert("HostConfig.PidsLimit", json!(limit));
+        self
+    }
+
+    /// Size of `/dev/shm` in bytes. Defaults to 64MB.
+    pub fn shm_size(
+        mut self,
+        bytes: u64,
+    ) -> Self {
+        self.params.insert("HostConfig.ShmSize", json!(bytes));
+        self
+    }
+
+    /// Namespaced kernel parameters to set in the container, like `net.ipv4.ip_forward`
+    pub fn sysctls(
+        mut self,
+        sysctls: &HashMap<&str, &str>,
+    ) -> Self {
+        self.params.insert("HostConfig.Sysctls", json!(sysctls));
+        self
+    }
+
+    /// Paths to mount tmpfs at, with their mount options like `rw,size=64m`
+    pub fn tmpfs(
+        mut self,
+        tmpfs: &HashMap<&str, &str>,
+    ) -> Self {
+        self.params.insert("HostConfig.Tmpfs", json!(tmpfs));
+        self
+    }
+
+    /// Mounts the root filesystem of the container read-only
+    pub fn readonly_rootfs(
+        mut self,
+        set: bool,
+    ) -> Self {
+        self.params.insert("HostConfig.ReadonlyRootfs", json!(set));
+        self
+    }
+
+    /// Keeps the kernel from killing processes of the container when it runs out of memory
+    pub fn oom_kill_disable(
+        mut self,
+        set: bool,
+    ) -> Self {
+        self.params.insert("HostConfig.OomKillDisable", json!(set));
+        self
+    }
+
+    /// Runs an init process in the container that forwards signals and reaps processes
+    pub fn init(
+        mut self,
+        set: bool,
+    ) -> Self {
+        self.params.insert("HostConfig.Init", json!(set));
+        self
+    }
+
     pub fn devices(
-        &mut self,
+        mut self,
         devices: Vec<HashMap<String, String>>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.Devices", json!(devices));
         self
     }
 
     pub fn log_driver(
-        &mut self,
+        mut self,
         log_driver: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params
             .insert("HostConfig.LogConfig.Type", json!(log_driver));
         self
     }
 
     pub fn restart_policy(
-        &mut self,
+        mut self,
         name: &str,
         maximum_retry_count: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params
             .insert("HostConfig.RestartPolicy.Name", json!(name));
         if name == "on-failure" {
@@ -951,179 +1765,417 @@ impl ContainerOptionsBuilder {
     }
 
     pub fn auto_remove(
-        &mut self,
+        mut self,
         set: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.AutoRemove", json!(set));
         self
     }
 
     /// Signal to stop a container as a string. Default is "SIGTERM".
     pub fn stop_signal(
-        &mut self,
+        mut self,
         sig: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("StopSignal", json!(sig));
         self
     }
 
     /// Signal to stop a container as an integer. Default is 15 (SIGTERM).
     pub fn stop_signal_num(
-        &mut self,
+        mut self,
         sig: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("StopSignal", json!(sig));
         self
     }
 
-    /// Timeout to stop a container. Only seconds are counted. Default is 10s
+    /// Command to check that the container is still working, like
+    /// `["CMD-SHELL", "curl -f http://localhost/"]`. `["NONE"]` disables the healthcheck of the
+    /// image.
+    pub fn healthcheck(
+        mut self,
+        test: Vec<&str>,
+    ) -> Self {
+        self.params.insert("Healthcheck.Test", json!(test));
+        self
+    }
+
+    /// Time between two healthchecks. Zero keeps the default, other durations are raised to the
+    /// minimum of 1ms.
+    pub fn healthcheck_interval(
+        mut self,
+        interval: Duration,
+    ) -> Self {
+        self.params.insert(
+            "Healthcheck.Interval",
+            json!(duration::healthcheck_nanos(interval)),
+        );
+        self
+    }
+
+    /// Time after which a healthcheck is considered to have failed. Zero keeps the default,
+    /// other durations are raised to the minimum of 1ms.
+    pub fn healthcheck_timeout(
+        mut self,
+        timeout: Duration,
+    ) -> Self {
+        self.params.insert(
+            "Healthcheck.Timeout",
+            json!(duration::healthcheck_nanos(timeout)),
+        );
+        self
+    }
+
+    /// Time for the container to start up before failed healthchecks count. Zero keeps the
+    /// default, other durations are raised to the minimum of 1ms.
+    pub fn healthcheck_start_period(
+        mut self,
+        period: Duration,
+    ) -> Self {
+        self.params.insert(
+            "Healthcheck.StartPeriod",
+            json!(duration::healthcheck_nanos(period)),
+        );
+        self
+    }
+
+    /// Number of consecutive failed healthchecks after which the container is unhealthy
+    pub fn healthcheck_retries(
+        mut self,
+        retries: u32,
+    ) -> Self {
+        self.params.insert("Healthcheck.Retries", json!(retries));
+        self
+    }
+
+    /// Timeout to stop a container, rounded up to whole seconds. Default is 10s
     pub fn stop_timeout(
-        &mut self,
+        mut self,
         timeout: Duration,
-    ) -> &mut Self {
-        self.params.insert("StopTimeout", json!(timeout.as_secs()));
+    ) -> Self {
+        self.params
+            .insert("StopTimeout", json!(duration::whole_secs(timeout)));
         self
     }
 
     pub fn userns_mode(
-        &mut self,
+        mut self,
         mode: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.UsernsMode", json!(mode));
         self
     }
 
     pub fn privileged(
-        &mut self,
+        mut self,
         set: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("HostConfig.Privileged", json!(set));
         self
     }
 
     pub fn user(
-        &mut self,
+        mut self,
         user: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("User", json!(user));
         self
     }
 
-    pub fn build(&self) -> ContainerOptions {
+    pub fn build(self) -> ContainerOptions {
         ContainerOptions {
-            name: self.name.clone(),
-            params: self.params.clone(),
+            name: self.name,
+            params: self.params,
         }
     }
 }
 
-/// Options for controlling log request results
-#[derive(Default, Debug)]
-pub struct LogsOptions {
-    params: HashMap<&'static str, String>,
+/// Options for [Container::commit]
+#[derive(Default, Debug, Serialize)]
+pub struct CommitOptions {
+    repo: Option<String>,
+    tag: Option<String>,
+    comment: Option<String>,
+    author: Option<String>,
+    pause: Option<bool>,
+    changes: Option<String>,
+    #[serde(skip)]
+    config: Option<ImageConfig>,
 }
 
-impl LogsOptions {
+impl CommitOptions {
     /// return a new instance of a builder for options
-    pub fn builder() -> LogsOptionsBuilder {
-        LogsOptionsBuilder::default()
+    pub fn builder() -> CommitOptionsBuilder {
+        CommitOptionsBuilder::default()
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
-/// Builder interface for `LogsOptions`
+/// Builder interface for `CommitOptions`
 #[derive(Default)]
-pub struct LogsOptionsBuilder {
-    params: HashMap<&'static str, String>,
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct CommitOptionsBuilder {
+    options: CommitOptions,
 }
 
-impl LogsOptionsBuilder {
-    pub fn follow(
-        &mut self,
-        f: bool,
-    ) -> &mut Self {
-        self.params.insert("follow", f.to_string());
+impl CommitOptionsBuilder {
+    /// Repository to tag the image in, like `app` or `registry.example.com/app`
+    pub fn repo<R>(
+        mut self,
+        repo: R,
+    ) -> Self
+    where
+        R: Into<String>,
+    {
+        self.options.repo = Some(repo.into());
         self
     }
 
-    pub fn stdout(
-        &mut self,
-        s: bool,
-    ) -> &mut Self {
-        self.params.insert("stdout", s.to_string());
+    pub fn tag<T>(
+        mut self,
+        tag: T,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        self.options.tag = Some(tag.into());
         self
     }
 
-    pub fn stderr(
-        &mut self,
-        s: bool,
-    ) -> &mut Self {
-        self.params.insert("stderr", s.to_string());
+    pub fn comment<C>(
+        mut self,
+        comment: C,
+    ) -> Self
+    where
+        C: Into<String>,
+    {
+        self.options.comment = Some(comment.into());
         self
     }
 
-    pub fn timestamps(
-        &mut self,
-        t: bool,
-    ) -> &mut Self {
-        self.params.insert("timestamps", t.to_string());
+    /// Author of the image, like `Jane Doe <jane@example.com>`
+    pub fn author<A>(
+        mut self,
+        author: A,
+    ) -> Self
+    where
+        A: Into<String>,
+    {
+        self.options.author = Some(author.into());
         self
     }
 
-    /// how_many can either be "all" or a to_string() of the number
-    pub fn tail(
-        &mut self,
-        how_many: &str,
-    ) -> &mut Self {
-        self.params.insert("tail", how_many.to_owned());
+    /// Whether to pause the container while it is committed, which the daemon does by default
+    pub fn pause(
+        mut self,
+        pause: bool,
+    ) -> Self {
+        self.options.pause = Some(pause);
         self
     }
 
-    #[cfg(feature = "chrono")]
-    pub fn since<Tz>(
-        &mut self,
+    /// Dockerfile instructions applied to the image, like `ENV DEBUG=1` or `CMD ["app"]`, as
+    /// with `docker commit --change`
+    pub fn changes<I, S>(
+        mut self,
+        changes: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|change| change.as_ref().to_owned())
+            .collect();
+        self.options.changes = Some(changes.join("\n"));
+        self
+    }
+
+    /// Configuration of the image, like its command, environment and labels, in place of the
+    /// configuration of the container
+    pub fn config(
+        mut self,
+        config: ImageConfig,
+    ) -> Self {
+        self.options.config = Some(config);
+        self
+    }
+
+    pub fn build(self) -> CommitOptions {
+        self.options
+    }
+}
+
+/// Options for controlling log request results
+#[derive(Default, Debug, Serialize)]
+pub struct LogsOptions {
+    follow: Option<bool>,
+    stdout: Option<bool>,
+    stderr: Option<bool>,
+    timestamps: Option<bool>,
+    tail: Option<String>,
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+impl LogsOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> LogsOptionsBuilder {
+        LogsOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Builder interface for `LogsOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct LogsOptionsBuilder {
+    options: LogsOptions,
+}
+
+impl LogsOptionsBuilder {
+    /// Keep the stream open and return new output as the container emits it
+    pub fn follow(
+        mut self,
+        f: bool,
+    ) -> Self {
+        self.options.follow = Some(f);
+        self
+    }
+
+    /// Return the standard output of the container
+    pub fn stdout(
+        mut self,
+        s: bool,
+    ) -> Self {
+        self.options.stdout = Some(s);
+        self
+    }
+
+    /// Return the standard error of the container
+    pub fn stderr(
+        mut self,
+        s: bool,
+    ) -> Self {
+        self.options.stderr = Some(s);
+        self
+    }
+
+    /// Prefix each line with the RFC 3339 timestamp at which it was emitted
+    pub fn timestamps(
+        mut self,
+        t: bool,
+    ) -> Self {
+        self.options.timestamps = Some(t);
+        self
+    }
+
+    /// how_many can either be "all" or a to_string() of the number
+    pub fn tail(
+        mut self,
+        how_many: &str,
+    ) -> Self {
+        self.options.tail = Some(how_many.to_owned());
+        self
+    }
+
+    /// Only return logs since this time
+    #[cfg(feature = "chrono")]
+    pub fn since<Tz>(
+        mut self,
         timestamp: &chrono::DateTime<Tz>,
-    ) -> &mut Self
+    ) -> Self
     where
         Tz: chrono::TimeZone,
     {
-        self.params
-            .insert("since", timestamp.timestamp().to_string());
+        self.options.since = Some(timestamp.timestamp());
         self
     }
 
-    #[cfg(not(feature = "chrono"))]
+    /// Like [since](LogsOptionsBuilder::since) with seconds since the unix epoch, whichever
+    /// timestamps the crate is built with
+    pub(crate) fn since_unix(
+        mut self,
+        secs: i64,
+    ) -> Self {
+        self.options.since = Some(secs);
+        self
+    }
+
+    /// Only return logs since this time
+    #[cfg(feature = "time")]
     pub fn since(
-        &mut self,
+        mut self,
+        timestamp: &OffsetDateTime,
+    ) -> Self {
+        self.options.since = Some(timestamp.unix_timestamp());
+        self
+    }
+
+    /// Only return logs since this time, given as a unix timestamp
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub fn since(
+        mut self,
         timestamp: i64,
-    ) -> &mut Self {
-        self.params.insert("since", timestamp.to_string());
+    ) -> Self {
+        self.options.since = Some(timestamp);
         self
     }
 
-    pub fn build(&self) -> LogsOptions {
-        LogsOptions {
-            params: self.params.clone(),
-        }
+    /// Only return logs before this time
+    #[cfg(feature = "chrono")]
+    pub fn until<Tz>(
+        mut self,
+        timestamp: &chrono::DateTime<Tz>,
+    ) -> Self
+    where
+        Tz: chrono::TimeZone,
+    {
+        self.options.until = Some(timestamp.timestamp());
+        self
+    }
+
+    /// Only return logs before this time
+    #[cfg(feature = "time")]
+    pub fn until(
+        mut self,
+        timestamp: &OffsetDateTime,
+    ) -> Self {
+        self.options.until = Some(timestamp.unix_timestamp());
+        self
+    }
+
+    /// Only return logs before this time, given as a unix timestamp
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub fn until(
+        mut self,
+        timestamp: i64,
+    ) -> Self {
+        self.options.until = Some(timestamp);
+        self
+    }
+
+    pub fn build(self) -> LogsOptions {
+        self.options
     }
 }
 
 /// Options for controlling log request results
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct RmContainerOptions {
-    params: HashMap<&'static str, String>,
+    force: Option<bool>,
+    #[serde(rename = "v")]
+    volumes: Option<bool>,
 }
 
 impl RmContainerOptions {
@@ -1133,62 +2185,57 @@ impl RmContainerOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
 /// Builder interface for `LogsOptions`
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct RmContainerOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: RmContainerOptions,
 }
 
 impl RmContainerOptionsBuilder {
     pub fn force(
-        &mut self,
+        mut self,
         f: bool,
-    ) -> &mut Self {
-        self.params.insert("force", f.to_string());
+    ) -> Self {
+        self.options.force = Some(f);
         self
     }
 
     pub fn volumes(
-        &mut self,
+        mut self,
         s: bool,
-    ) -> &mut Self {
-        self.params.insert("v", s.to_string());
+    ) -> Self {
+        self.options.volumes = Some(s);
         self
     }
 
-    pub fn build(&self) -> RmContainerOptions {
-        RmContainerOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> RmContainerOptions {
+        self.options
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ContainerInfo {
     #[cfg(feature = "chrono")]
     #[serde(deserialize_with = "datetime_from_unix_timestamp")]
     pub created: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created: u64,
     pub command: String,
-    pub id: String,
+    pub id: ContainerId,
     pub image: String,
     #[serde(rename = "ImageID")]
-    pub image_id: String,
+    pub image_id: ImageId,
     pub labels: HashMap<String, String>,
     pub names: Vec<String>,
     pub ports: Vec<Port>,
@@ -1196,20 +2243,28 @@ pub struct ContainerInfo {
     pub status: String,
     pub size_rw: Option<i64>,
     pub size_root_fs: Option<i64>,
+    /// Fields of the listing that aren't modelled, like `Mounts` and `NetworkSettings`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ContainerDetails {
-    pub id: String,
+    pub id: ContainerId,
     #[cfg(feature = "chrono")]
     pub created: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created: String,
     pub path: String,
     pub args: Vec<String>,
     pub state: State,
-    pub image: String,
+    pub image: ImageId,
     pub resolv_conf_path: String,
     pub hostname_path: String,
     pub hosts_path: String,
@@ -1222,16 +2277,21 @@ pub struct ContainerDetails {
     pub process_label: String,
     pub app_armor_profile: String,
     #[serde(rename = "ExecIDs")]
-    pub exec_ids: Option<Vec<String>>,
+    pub exec_ids: Option<Vec<ExecId>>,
     pub host_config: HostConfig,
     pub graph_driver: GraphDriverData,
     pub mounts: Vec<Mount>,
     pub config: ContainerConfig,
     pub network_settings: NetworkSettings,
+    /// Inspect fields that aren't modelled, like `SizeRw` of an inspect with sizes
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct GraphDriverData {
     pub name: String,
     pub data: HashMap<String, String>,
@@ -1239,6 +2299,7 @@ pub struct GraphDriverData {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Mount {
     pub source: String,
     pub destination: String,
@@ -1249,12 +2310,16 @@ pub struct Mount {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct State {
     pub error: String,
     pub exit_code: u64,
     #[cfg(feature = "chrono")]
     pub finished_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub finished_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub finished_at: String,
     #[serde(rename = "OOMKilled")]
     pub oom_killed: bool,
@@ -1264,13 +2329,136 @@ pub struct State {
     pub running: bool,
     #[cfg(feature = "chrono")]
     pub started_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub started_at: String,
     pub status: String,
+    /// The results of the healthcheck of the container, if it has one
+    #[serde(default)]
+    pub health: Option<Health>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct Health {
+    pub status: HealthStatus,
+    /// Number of consecutive failed healthchecks
+    pub failing_streak: u64,
+    /// The last few healthchecks, oldest first
+    #[serde(default, deserialize_with = "crate::image::null_as_default")]
+    pub log: Vec<HealthcheckResult>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// The container has no healthcheck
+    None,
+    /// The container is in its start period, or no healthcheck completed yet
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+/// The result of a single healthcheck
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct HealthcheckResult {
+    #[cfg(feature = "chrono")]
+    pub start: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub start: String,
+    #[cfg(feature = "chrono")]
+    pub end: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub end: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub end: String,
+    pub exit_code: i64,
+    /// The start of the output of the check
+    pub output: String,
+}
+
+/// A state of a container, see [Container::watch]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContainerState {
+    Created,
+    Running,
+    Paused,
+    /// Running, but failing its healthcheck
+    Unhealthy,
+    Exited {
+        exit_code: u64,
+    },
+    Removed,
+}
+
+impl ContainerState {
+    /// The state a container is in after `event`, or `None` if the event does not change it
+    fn after(
+        event: &Event,
+        current: &ContainerState,
+    ) -> Option<ContainerState> {
+        let state = match event.action.as_str() {
+            "create" => ContainerState::Created,
+            "start" | "restart" | "unpause" => ContainerState::Running,
+            "pause" => ContainerState::Paused,
+            "die" => ContainerState::Exited {
+                exit_code: event
+                    .actor
+                    .attributes
+                    .get("exitCode")
+                    .and_then(|code| code.parse().ok())
+                    .unwrap_or_default(),
+            },
+            "destroy" => ContainerState::Removed,
+            "health_status: unhealthy" => ContainerState::Unhealthy,
+            "health_status: healthy" if *current == ContainerState::Unhealthy => {
+                ContainerState::Running
+            }
+            _ => return None,
+        };
+        Some(state)
+    }
+}
+
+impl From<&State> for ContainerState {
+    fn from(state: &State) -> Self {
+        let unhealthy = matches!(
+            state.health,
+            Some(Health {
+                status: HealthStatus::Unhealthy,
+                ..
+            })
+        );
+        if state.paused {
+            ContainerState::Paused
+        } else if state.running && unhealthy {
+            ContainerState::Unhealthy
+        } else if state.running || state.restarting {
+            ContainerState::Running
+        } else if state.status == "created" {
+            ContainerState::Created
+        } else {
+            ContainerState::Exited {
+                exit_code: state.exit_code,
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct HostConfig {
     pub cpu_shares: Option<i64>,
     pub memory: Option<i64>,
@@ -1318,7 +2506,7 @@ pub struct HostConfig {
     pub auto_remove: bool,
     pub volume_driver: String,
     pub volumes_from: Option<Vec<String>>,
-    pub mounts: Option<Vec<Mount>>,
+    pub mounts: Option<Vec<MountSpec>>,
     pub cap_add: Option<Vec<String>>,
     pub cap_drop: Option<Vec<String>>,
     pub dns: Option<Vec<String>>,
@@ -1349,8 +2537,178 @@ pub struct HostConfig {
     pub readonly_paths: Option<Vec<String>>,
 }
 
+/// A mount of a bind, volume or tmpfs into a container, see
+/// [ContainerOptionsBuilder::mounts]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct MountSpec {
+    /// Path of the mount in the container
+    pub target: String,
+    /// Path on the host for binds, name of the volume for volumes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(rename = "Type")]
+    pub typ: MountType,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_options: Option<BindOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_options: Option<VolumeOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmpfs_options: Option<TmpfsOptions>,
+}
+
+impl MountSpec {
+    /// Mounts the path `source` of the host at `target`
+    pub fn bind<S, T>(
+        source: S,
+        target: T,
+    ) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Self::new(MountType::Bind, Some(source.into()), target.into())
+    }
+
+    /// Mounts the volume `name` at `target`, creating the volume if it doesn't exist
+    pub fn volume<S, T>(
+        name: S,
+        target: T,
+    ) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Self::new(MountType::Volume, Some(name.into()), target.into())
+    }
+
+    /// Mounts an empty in-memory filesystem at `target`
+    pub fn tmpfs<T>(target: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self::new(MountType::Tmpfs, None, target.into())
+    }
+
+    fn new(
+        typ: MountType,
+        source: Option<String>,
+        target: String,
+    ) -> Self {
+        MountSpec {
+            target,
+            source,
+            typ,
+            read_only: false,
+            bind_options: None,
+            volume_options: None,
+            tmpfs_options: None,
+        }
+    }
+
+    /// Mounts the source read-only
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// How mounts below a bind propagate between the host and the container
+    pub fn propagation(
+        mut self,
+        propagation: Propagation,
+    ) -> Self {
+        self.bind_options = Some(BindOptions {
+            propagation: Some(propagation),
+        });
+        self
+    }
+
+    /// Doesn't populate a new volume with the contents of the image at the target
+    pub fn no_copy(mut self) -> Self {
+        self.volume_options = Some(VolumeOptions {
+            no_copy: Some(true),
+            ..self.volume_options.unwrap_or_default()
+        });
+        self
+    }
+
+    /// Limits the size of a tmpfs to `bytes`, which is unlimited by default
+    pub fn size_bytes(
+        mut self,
+        bytes: u64,
+    ) -> Self {
+        self.tmpfs_options = Some(TmpfsOptions {
+            size_bytes: Some(bytes),
+            ..self.tmpfs_options.unwrap_or_default()
+        });
+        self
+    }
+
+    /// Permissions of a tmpfs, like `0o1777`
+    pub fn mode(
+        mut self,
+        mode: u32,
+    ) -> Self {
+        self.tmpfs_options = Some(TmpfsOptions {
+            mode: Some(mode),
+            ..self.tmpfs_options.unwrap_or_default()
+        });
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountType {
+    Bind,
+    Volume,
+    Tmpfs,
+    Npipe,
+}
+
+/// Propagation of mounts below a bind mount, see `mount(8)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Propagation {
+    Private,
+    Rprivate,
+    Shared,
+    Rshared,
+    Slave,
+    Rslave,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BindOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub propagation: Option<Propagation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VolumeOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_copy: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TmpfsOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ThrottleDevice {
     pub path: String,
     pub rate: u64,
@@ -1358,6 +2716,7 @@ pub struct ThrottleDevice {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct RestartPolicy {
     pub name: String,
     pub maximum_retry_count: u64,
@@ -1367,12 +2726,14 @@ pub type PortMap = HashMap<String, Option<Vec<PortBinding>>>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct PortBinding {
     pub host_ip: String,
     pub host_port: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct LogConfig {
     #[serde(rename = "Type")]
     pub type_: String,
@@ -1390,6 +2751,7 @@ pub struct Ulimit {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct DeviceMapping {
     pub path_on_host: Option<String>,
     pub path_in_container: Option<String>,
@@ -1398,6 +2760,7 @@ pub struct DeviceMapping {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct DeviceRequest {
     pub driver: String,
     pub count: u64,
@@ -1409,6 +2772,7 @@ pub struct DeviceRequest {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Port {
     pub ip: Option<String>,
     pub private_port: u64,
@@ -1417,16 +2781,29 @@ pub struct Port {
     pub typ: String,
 }
 
+/// Resource usage of a container
+///
+/// The daemon reports some of the statistics only on hosts with cgroup v1, and none of them for
+/// stopped containers. These are `None`, or empty or zero where the statistic is a list or a
+/// counter.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Stats {
     pub read: String,
+    /// Empty for containers without network interfaces and for stopped containers
+    #[serde(default, deserialize_with = "crate::image::null_as_default")]
     pub networks: HashMap<String, NetworkStats>,
     pub memory_stats: MemoryStats,
     pub blkio_stats: BlkioStats,
     pub cpu_stats: CpuStats,
+    /// Statistics that aren't modelled, like `pids_stats` and `precpu_stats`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct NetworkStats {
     pub rx_dropped: u64,
     pub rx_bytes: u64,
@@ -1438,84 +2815,146 @@ pub struct NetworkStats {
     pub tx_bytes: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct MemoryStats {
-    pub max_usage: u64,
+    /// Only reported with cgroup v1
+    pub max_usage: Option<u64>,
     pub usage: u64,
+    /// Only reported with cgroup v1
     pub failcnt: Option<u64>,
     pub limit: u64,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub stats: MemoryStat,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl MemoryStats {
+    /// The memory used without the inactive page cache, which `docker stats` reports as the
+    /// usage of the container
+    pub fn usage_without_cache(&self) -> u64 {
+        // cgroup v1 reports the inactive page cache of the hierarchy, cgroup v2 of the cgroup
+        let inactive_file = self
+            .stats
+            .total_inactive_file
+            .unwrap_or(self.stats.inactive_file);
+        self.usage.saturating_sub(inactive_file)
+    }
+}
+
+/// Statistics of the memory controller of the cgroup of a container. Those that only one of
+/// cgroup v1 and v2 reports are `None` with the other one.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct MemoryStat {
-    pub total_pgmajfault: u64,
-    pub cache: u64,
-    pub mapped_file: u64,
-    pub total_inactive_file: u64,
-    pub pgpgout: u64,
-    pub rss: u64,
-    pub total_mapped_file: u64,
-    pub writeback: u64,
+    pub total_pgmajfault: Option<u64>,
+    pub cache: Option<u64>,
+    pub mapped_file: Option<u64>,
+    pub total_inactive_file: Option<u64>,
+    pub pgpgout: Option<u64>,
+    pub rss: Option<u64>,
+    pub total_mapped_file: Option<u64>,
+    pub writeback: Option<u64>,
     pub unevictable: u64,
-    pub pgpgin: u64,
-    pub total_unevictable: u64,
+    pub pgpgin: Option<u64>,
+    pub total_unevictable: Option<u64>,
     pub pgmajfault: u64,
-    pub total_rss: u64,
-    pub total_rss_huge: u64,
-    pub total_writeback: u64,
-    pub total_inactive_anon: u64,
-    pub rss_huge: u64,
-    pub hierarchical_memory_limit: u64,
-    pub hierarchical_memsw_limit: u64,
-    pub total_pgfault: u64,
-    pub total_active_file: u64,
+    pub total_rss: Option<u64>,
+    pub total_rss_huge: Option<u64>,
+    pub total_writeback: Option<u64>,
+    pub total_inactive_anon: Option<u64>,
+    pub rss_huge: Option<u64>,
+    pub hierarchical_memory_limit: Option<u64>,
+    pub hierarchical_memsw_limit: Option<u64>,
+    pub total_pgfault: Option<u64>,
+    pub total_active_file: Option<u64>,
     pub active_anon: u64,
-    pub total_active_anon: u64,
-    pub total_pgpgout: u64,
-    pub total_cache: u64,
+    pub total_active_anon: Option<u64>,
+    pub total_pgpgout: Option<u64>,
+    pub total_cache: Option<u64>,
     pub inactive_anon: u64,
     pub active_file: u64,
     pub pgfault: u64,
     pub inactive_file: u64,
-    pub total_pgpgin: u64,
+    pub total_pgpgin: Option<u64>,
+    /// Anonymous memory, only reported with cgroup v2
+    pub anon: Option<u64>,
+    /// Page cache, only reported with cgroup v2
+    pub file: Option<u64>,
+    /// Only reported with cgroup v2
+    pub kernel_stack: Option<u64>,
+    /// Only reported with cgroup v2
+    pub slab: Option<u64>,
+    /// Only reported with cgroup v2
+    pub sock: Option<u64>,
+    /// Only reported with cgroup v2
+    pub shmem: Option<u64>,
+    /// Only reported with cgroup v2
+    pub file_mapped: Option<u64>,
+    /// Only reported with cgroup v2
+    pub file_dirty: Option<u64>,
+    /// Only reported with cgroup v2
+    pub file_writeback: Option<u64>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct CpuStats {
     pub cpu_usage: CpuUsage,
-    pub system_cpu_usage: u64,
+    /// Not reported for stopped containers
+    pub system_cpu_usage: Option<u64>,
     pub throttling_data: ThrottlingData,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct CpuUsage {
+    /// Empty with cgroup v2
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub percpu_usage: Vec<u64>,
     pub usage_in_usermode: u64,
     pub total_usage: u64,
     pub usage_in_kernelmode: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct ThrottlingData {
     pub periods: u64,
     pub throttled_periods: u64,
     pub throttled_time: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Block IO statistics, which are lists of counters by device. cgroup v2 only reports
+/// `io_service_bytes_recursive` and `io_serviced_recursive`, the other lists are empty.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct BlkioStats {
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_service_bytes_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_serviced_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_queue_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_service_time_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_wait_time_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_merged_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub io_time_recursive: Vec<BlkioStat>,
+    #[serde(deserialize_with = "crate::image::null_as_default")]
     pub sectors_recursive: Vec<BlkioStat>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct BlkioStat {
     pub major: u64,
     pub minor: u64,
@@ -1525,6 +2964,7 @@ pub struct BlkioStat {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Change {
     pub kind: u64,
     pub path: String,
@@ -1532,6 +2972,7 @@ pub struct Change {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Top {
     pub titles: Vec<String>,
     pub processes: Vec<Vec<String>>,
@@ -1539,13 +2980,15 @@ pub struct Top {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ContainerCreateInfo {
-    pub id: String,
+    pub id: ContainerId,
     pub warnings: Option<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Exit {
     pub status_code: u64,
 }
@@ -1593,6 +3036,555 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ensure_container() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let opts = ContainerOptions::builder("alpine:latest")
+            .name("web")
+            .cmd(vec!["sleep", "3600"])
+            .build();
+        let details = |hash: &str, running: bool| {
+            let mut details = fixtures::container_details("c0", "web");
+            details["Config"]["Labels"] = json!({ CONFIG_HASH_LABEL: hash });
+            details["State"]["Running"] = json!(running);
+            MockResponse::json(&details)
+        };
+        let hash = config_hash(&opts.to_json().unwrap());
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "GET",
+                "/containers/web/json",
+                MockResponse::error(404, "No such container: web"),
+            )
+            .respond_once("GET", "/containers/web/json", details(&hash, true))
+            .respond("GET", "/containers/web/json", details("0", false))
+            .respond(
+                "GET",
+                "/images/alpine:latest/json",
+                MockResponse::json(&fixtures::image_details(
+                    "sha256:14119a10abf4669e8cdbdff324a9f9605d99697215a0d21c360fe8dfa8471bab",
+                    "alpine:latest",
+                )),
+            )
+            .respond_once(
+                "POST",
+                "/containers/create",
+                MockResponse::json(&fixtures::container_created("c1")),
+            )
+            .respond_once(
+                "POST",
+                "/containers/create",
+                MockResponse::json(&fixtures::container_created("c2")),
+            )
+            .respond("POST", "/containers/c1/start", MockResponse::new(204, ""))
+            .respond("POST", "/containers/c2/start", MockResponse::new(204, ""))
+            .respond("DELETE", "/containers/web", MockResponse::new(204, ""));
+        let docker = Docker::mock(daemon.clone());
+        let containers = docker.containers();
+
+        let outcomes: Vec<_> = futures::executor::block_on(async {
+            let mut outcomes = Vec::new();
+            for _ in 0..3 {
+                let ensured = containers
+                    .ensure("web", &opts, RecreatePolicy::IfChanged)
+                    .await
+                    .unwrap();
+                outcomes.push((ensured.id.to_string(), ensured.outcome));
+            }
+            outcomes
+        });
+        assert_eq!(
+            outcomes,
+            [
+                ("c1".to_owned(), EnsureOutcome::Created),
+                ("c0".to_owned(), EnsureOutcome::Reused),
+                ("c2".to_owned(), EnsureOutcome::Replaced),
+            ]
+        );
+
+        let requests = daemon.requests();
+        let created = requests
+            .iter()
+            .find(|request| request.path.starts_with("/containers/create"))
+            .unwrap();
+        assert_eq!(created.path, "/containers/create?name=web");
+        assert_eq!(created.json().unwrap()["Labels"][CONFIG_HASH_LABEL], hash);
+        assert!(requests
+            .iter()
+            .any(|request| request.path == "/containers/web?force=true"));
+    }
+
+    #[test]
+    fn container_logs() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let frame = |stream: u8, line: &str| {
+            let mut frame = vec![stream, 0, 0, 0];
+            frame.extend_from_slice(&(line.len() as u32).to_be_bytes());
+            frame.extend_from_slice(line.as_bytes());
+            frame
+        };
+        let mut output = frame(1, "listening on :80\n");
+        output.extend(frame(2, "warning: no config\n"));
+        let mut garbled = frame(1, "started\n");
+        garbled.extend(frame(7, "?"));
+        garbled.extend(frame(1, "unreachable\n"));
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/containers/web/logs?follow=true&stdout=true&stderr=true&tail=10",
+                MockResponse::new(200, output),
+            )
+            .respond(
+                "GET",
+                "/containers/garbled/logs",
+                MockResponse::new(200, garbled),
+            )
+            .respond(
+                "GET",
+                "/containers/tty/logs",
+                MockResponse::new(200, frame(7, "$ ")),
+            )
+            .respond(
+                "GET",
+                "/containers/web/json",
+                MockResponse::json(&fixtures::container_details("web", "web")),
+            )
+            .respond(
+                "GET",
+                "/containers/garbled/json",
+                MockResponse::json(&fixtures::container_details("garbled", "garbled")),
+            );
+        let mut details = fixtures::container_details("tty", "tty");
+        details["Config"]["Tty"] = json!(true);
+        daemon.respond("GET", "/containers/tty/json", MockResponse::json(&details));
+        let docker = Docker::mock(daemon);
+
+        let opts = LogsOptions::builder()
+            .follow(true)
+            .stdout(true)
+            .stderr(true)
+            .tail("10")
+            .build();
+        let chunks: Vec<_> =
+            futures::executor::block_on(docker.containers().get("web").logs(&opts).try_collect())
+                .unwrap();
+        assert!(matches!(&chunks[..], [
+            tty::TtyChunk::StdOut(out),
+            tty::TtyChunk::StdErr(err),
+        ] if out == b"listening on :80\n" && err == b"warning: no config\n"));
+
+        let chunks: Vec<_> = futures::executor::block_on(
+            docker
+                .containers()
+                .get("garbled")
+                .logs(&Default::default())
+                .collect(),
+        );
+        assert_eq!(chunks.len(), 2);
+        assert!(matches!(&chunks[0], Ok(tty::TtyChunk::StdOut(out)) if out == b"started\n"));
+        assert!(matches!(chunks[1], Err(Error::InvalidResponse(_))));
+
+        // the output of a TTY is not framed, even if it looks like a frame header
+        let output: Vec<u8> = futures::executor::block_on(
+            docker
+                .containers()
+                .get("tty")
+                .logs(&Default::default())
+                .map_ok(Vec::from)
+                .try_concat(),
+        )
+        .unwrap();
+        assert_eq!(output, frame(7, "$ "));
+    }
+
+    #[test]
+    fn container_stats() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let mut busy = fixtures::stats();
+        busy["memory_stats"]["usage"] = json!(33_554_432);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/containers/c1/stats?stream=true",
+                MockResponse::json_stream(&[fixtures::stats(), busy]),
+            )
+            .respond(
+                "GET",
+                "/containers/c1/stats?stream=false",
+                MockResponse::json(&fixtures::stats()),
+            );
+        let docker = Docker::mock(daemon);
+        let container = docker.containers().get("c1");
+
+        let usage: Vec<u64> = futures::executor::block_on(
+            container
+                .stats()
+                .map_ok(|stats| stats.memory_stats.usage)
+                .try_collect(),
+        )
+        .unwrap();
+        assert_eq!(usage, vec![6_537_216, 33_554_432]);
+
+        let stats = futures::executor::block_on(container.stats_once()).unwrap();
+        assert_eq!(stats.networks["eth0"].rx_bytes, 5_338);
+        assert_eq!(stats.cpu_stats.cpu_usage.percpu_usage.len(), 4);
+    }
+
+    #[test]
+    fn container_stats_cgroup_v2() {
+        use crate::mock::fixtures;
+
+        let stats: Stats = serde_json::from_value(fixtures::stats_cgroup_v2()).unwrap();
+        assert_eq!(stats.memory_stats.max_usage, None);
+        assert_eq!(stats.memory_stats.stats.total_inactive_file, None);
+        assert_eq!(stats.memory_stats.stats.inactive_file, 1_507_328);
+        assert_eq!(stats.memory_stats.stats.anon, Some(495_616));
+        assert_eq!(stats.memory_stats.usage_without_cache(), 1_019_904);
+        // the statistics are read one by one, so the cache may briefly exceed the usage
+        let memory = MemoryStats {
+            usage: 4_096,
+            stats: MemoryStat {
+                inactive_file: 8_192,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(memory.usage_without_cache(), 0);
+        assert!(stats.cpu_stats.cpu_usage.percpu_usage.is_empty());
+        assert_eq!(stats.blkio_stats.io_service_bytes_recursive.len(), 2);
+        assert!(stats.blkio_stats.io_serviced_recursive.is_empty());
+
+        // what the daemon reports for a stopped container
+        let stats: Stats = serde_json::from_value(serde_json::json!({
+            "read": "0001-01-01T00:00:00Z",
+            "pids_stats": {},
+            "blkio_stats": {
+                "io_service_bytes_recursive": null,
+                "io_serviced_recursive": null,
+                "io_queue_recursive": null,
+                "io_service_time_recursive": null,
+                "io_wait_time_recursive": null,
+                "io_merged_recursive": null,
+                "io_time_recursive": null,
+                "sectors_recursive": null
+            },
+            "cpu_stats": {
+                "cpu_usage": { "total_usage": 0, "usage_in_kernelmode": 0, "usage_in_usermode": 0 },
+                "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+            },
+            "memory_stats": {}
+        }))
+        .unwrap();
+        assert!(stats.networks.is_empty());
+        assert_eq!(stats.memory_stats.usage, 0);
+        assert_eq!(stats.memory_stats.usage_without_cache(), 0);
+        assert_eq!(stats.cpu_stats.system_cpu_usage, None);
+    }
+
+    #[test]
+    fn watch_container() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let event = |action: &str, attributes: serde_json::Value| {
+            json!({
+                "Type": "container",
+                "Action": action,
+                "Actor": { "ID": "c1", "Attributes": attributes },
+                "time": 1_625_140_010,
+                "timeNano": 1_625_140_010_000_000_000_u64,
+            })
+        };
+        let mut unhealthy = fixtures::container_details("c2", "db");
+        unhealthy["State"]["Health"] = json!({
+            "Status": "unhealthy",
+            "FailingStreak": 3,
+            "Log": null,
+        });
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/info",
+                MockResponse::json(&json!({ "SystemTime": "2021-07-01T13:46:45.5+02:00" })),
+            )
+            .respond(
+                "GET",
+                "/containers/web/json",
+                MockResponse::json(&fixtures::container_details("c1", "web")),
+            )
+            .respond("GET", "/containers/db/json", MockResponse::json(&unhealthy))
+            .respond_once(
+                "GET",
+                "/events",
+                MockResponse::json_stream(&[
+                    event("start", json!({})),
+                    event("pause", json!({})),
+                    event("unpause", json!({})),
+                    event("health_status: healthy", json!({})),
+                    event("health_status: unhealthy", json!({})),
+                    event("exec_start: sh", json!({})),
+                    event("kill", json!({ "signal": "15" })),
+                    event("die", json!({ "exitCode": "143" })),
+                    event("destroy", json!({})),
+                    event("create", json!({})),
+                ]),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        let states: Vec<_> =
+            futures::executor::block_on(docker.containers().get("web").watch().try_collect())
+                .unwrap();
+        assert_eq!(
+            states,
+            [
+                ContainerState::Running,
+                ContainerState::Paused,
+                ContainerState::Running,
+                ContainerState::Unhealthy,
+                ContainerState::Exited { exit_code: 143 },
+                ContainerState::Removed,
+            ]
+        );
+        // events are requested from the time of the daemon, not from the last change
+        let events = &daemon.requests()[2];
+        assert!(events.path.contains("since=1625140005.500000000"));
+
+        daemon.respond(
+            "GET",
+            "/events",
+            MockResponse::json_stream(&[json!({
+                "Type": "container",
+                "Action": "destroy",
+                "Actor": { "ID": "c2", "Attributes": {} },
+                "time": 1_625_140_010,
+                "timeNano": 1_625_140_010_000_000_000_u64,
+            })]),
+        );
+        let states: Vec<_> =
+            futures::executor::block_on(docker.containers().get("db").watch().try_collect())
+                .unwrap();
+        assert_eq!(states, [ContainerState::Unhealthy, ContainerState::Removed]);
+    }
+
+    #[tokio::test]
+    async fn wait_healthy() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let with_health = |status: &str, output: &str| {
+            let mut details = fixtures::container_details("c1", "web");
+            details["State"]["Health"] = json!({
+                "Status": status,
+                "FailingStreak": 1,
+                "Log": [{
+                    "Start": "2021-07-01T11:46:45.000000000Z",
+                    "End": "2021-07-01T11:46:45.100000000Z",
+                    "ExitCode": 1,
+                    "Output": output,
+                }],
+            });
+            MockResponse::json(&details)
+        };
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once("GET", "/containers/web/json", with_health("starting", ""))
+            .respond_once(
+                "GET",
+                "/containers/web/json",
+                with_health("unhealthy", "connection refused\n"),
+            )
+            .respond_once("GET", "/containers/web/json", with_health("healthy", "ok"))
+            .respond(
+                "GET",
+                "/containers/db/json",
+                MockResponse::json(&fixtures::container_details("c2", "db")),
+            );
+        let docker = Docker::mock(daemon);
+
+        let details: ContainerDetails = crate::mock::model({
+            let mut details = fixtures::container_details("c1", "web");
+            details["State"]["Health"] = json!({
+                "Status": "unhealthy",
+                "FailingStreak": 3,
+                "Log": null,
+            });
+            details
+        });
+        assert_eq!(
+            ContainerState::from(&details.state),
+            ContainerState::Unhealthy
+        );
+
+        let web = docker.containers().get("web");
+        web.wait_healthy(Duration::from_secs(5)).await.unwrap();
+
+        let err = docker
+            .containers()
+            .get("db")
+            .wait_healthy(Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::NotHealthy { ref reason, .. } if reason == "it has no healthcheck"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn tail_file() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let frame = |line: &str| {
+            let mut frame = vec![1, 0, 0, 0];
+            frame.extend_from_slice(&(line.len() as u32).to_be_bytes());
+            frame.extend_from_slice(line.as_bytes());
+            frame
+        };
+        let exec_details = |id: &str, exit_code: u64| {
+            MockResponse::json(&json!({
+                "CanRemove": false,
+                "ContainerID": "c1",
+                "DetachKeys": "",
+                "ExitCode": exit_code,
+                "ID": id,
+                "OpenStderr": true,
+                "OpenStdin": false,
+                "OpenStdout": true,
+                "ProcessConfig": {
+                    "arguments": [],
+                    "entrypoint": "tail",
+                    "privileged": false,
+                    "tty": false,
+                    "user": "",
+                },
+                "Running": false,
+                "Pid": 42,
+            }))
+        };
+        let mut stopped = fixtures::container_details("c1", "web");
+        stopped["State"]["Running"] = json!(false);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "POST",
+                "/containers/web/exec",
+                MockResponse::json(&json!({ "Id": "e1" })),
+            )
+            .respond_once(
+                "POST",
+                "/containers/web/exec",
+                MockResponse::json(&json!({ "Id": "e2" })),
+            )
+            .respond(
+                "POST",
+                "/exec/e1/start",
+                MockResponse::new(200, [frame("one\n"), frame("two\n")].concat()),
+            )
+            .respond(
+                "POST",
+                "/exec/e2/start",
+                MockResponse::new(200, frame("three\n")),
+            )
+            .respond("GET", "/exec/e1/json", exec_details("e1", 137))
+            .respond("GET", "/exec/e2/json", exec_details("e2", 143))
+            .respond_once(
+                "GET",
+                "/containers/web/json",
+                MockResponse::json(&fixtures::container_details("c1", "web")),
+            )
+            .respond("GET", "/containers/web/json", MockResponse::json(&stopped));
+        let docker = Docker::mock(daemon.clone());
+
+        let lines: Vec<_> = docker
+            .containers()
+            .get("web")
+            .tail_file("/var/log/app.log", true)
+            .map_ok(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(lines, ["one\n", "two\n", "three\n"]);
+
+        let cmds: Vec<_> = daemon
+            .requests()
+            .iter()
+            .filter(|request| request.path == "/containers/web/exec")
+            .map(|request| request.json().unwrap()["Cmd"].clone())
+            .collect();
+        assert_eq!(
+            cmds,
+            [
+                json!(["tail", "-n", "+1", "-F", "--", "/var/log/app.log"]),
+                json!(["tail", "-n", "0", "-F", "--", "/var/log/app.log"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn container_port_address() {
+        use crate::mock::{fixtures, model};
+
+        let mut details = fixtures::container_details("4fa6e0f0c678", "web");
+        let settings = &mut details["NetworkSettings"];
+        settings["Ports"] = serde_json::json!({
+            "80/tcp": [{"HostIp": "0.0.0.0", "HostPort": "8080"}],
+            "443/tcp": null,
+        });
+        settings["Networks"]["app"] = serde_json::json!({
+            "NetworkID": "n1",
+            "EndpointID": "e1",
+            "Gateway": "172.18.0.1",
+            "IPAddress": "172.18.0.5",
+            "IPPrefixLen": 16,
+            "IPv6Gateway": "",
+            "GlobalIPv6Address": "",
+            "GlobalIPv6PrefixLen": 0,
+            "MacAddress": "02:42:ac:12:00:05",
+        });
+        let details: ContainerDetails = model(details);
+        let settings = &details.network_settings;
+
+        assert_eq!(
+            port_address(settings, 80, None),
+            Some(("127.0.0.1".to_owned(), 8080))
+        );
+        assert_eq!(
+            port_address(settings, 80, Some("docker.example.com")),
+            Some(("docker.example.com".to_owned(), 8080))
+        );
+        assert_eq!(
+            port_address(settings, 443, None),
+            Some(("172.18.0.5".to_owned(), 443))
+        );
+        assert_eq!(
+            port_address(settings, 443, Some("docker.example.com")),
+            None
+        );
+    }
+
+    #[test]
+    fn container_options_healthcheck() {
+        let options = ContainerOptionsBuilder::new("test_image")
+            .healthcheck(vec!["CMD", "true"])
+            .healthcheck_interval(Duration::from_secs(30))
+            .healthcheck_timeout(Duration::from_micros(1))
+            .healthcheck_retries(3)
+            .stop_timeout(Duration::from_millis(500))
+            .build();
+
+        assert_eq!(
+            r#"{"Healthcheck":{"Interval":30000000000,"Retries":3,"Test":["CMD","true"],"Timeout":1000000},"HostConfig":{},"Image":"test_image","StopTimeout":1}"#,
+            options.serialize().unwrap()
+        );
+    }
+
     #[test]
     fn container_options_user() {
         let options = ContainerOptionsBuilder::new("test_image")
@@ -1605,6 +3597,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn container_options_resources() {
+        let mounts = vec![
+            MountSpec::bind("/srv/config", "/etc/app")
+                .read_only()
+                .propagation(Propagation::Rslave),
+            MountSpec::volume("data", "/var/lib/app").no_copy(),
+            MountSpec::tmpfs("/run").size_bytes(1 << 20).mode(0o1777),
+        ];
+        let options = ContainerOptionsBuilder::new("test_image")
+            .mounts(mounts.clone())
+            .cap_drop(vec!["ALL"])
+            .security_opt(vec!["no-new-privileges"])
+            .ulimits(vec![Ulimit {
+                name: "nofile".to_owned(),
+                soft: 1024,
+                hard: 4096,
+            }])
+            .pids_limit(100)
+            .shm_size(128 << 20)
+            .sysctls(&vec![("net.ipv4.ip_forward", "1")].into_iter().collect())
+            .tmpfs(&vec![("/tmp", "rw,size=64m")].into_iter().collect())
+            .readonly_rootfs(true)
+            .oom_kill_disable(true)
+            .init(true)
+            .build();
+
+        let body: Value = serde_json::from_str(&options.serialize().unwrap()).unwrap();
+        assert_eq!(
+            body["HostConfig"],
+            json!({
+                "Mounts": [
+                    {
+                        "Target": "/etc/app",
+                        "Source": "/srv/config",
+                        "Type": "bind",
+                        "ReadOnly": true,
+                        "BindOptions": { "Propagation": "rslave" },
+                    },
+                    {
+                        "Target": "/var/lib/app",
+                        "Source": "data",
+                        "Type": "volume",
+                        "VolumeOptions": { "NoCopy": true },
+                    },
+                    {
+                        "Target": "/run",
+                        "Type": "tmpfs",
+                        "TmpfsOptions": { "SizeBytes": 1_048_576, "Mode": 1023 },
+                    },
+                ],
+                "CapDrop": ["ALL"],
+                "SecurityOpt": ["no-new-privileges"],
+                "Ulimits": [{ "Name": "nofile", "Soft": 1024, "Hard": 4096 }],
+                "PidsLimit": 100,
+                "ShmSize": 134_217_728,
+                "Sysctls": { "net.ipv4.ip_forward": "1" },
+                "Tmpfs": { "/tmp": "rw,size=64m" },
+                "ReadonlyRootfs": true,
+                "OomKillDisable": true,
+                "Init": true,
+            })
+        );
+
+        let parsed: Vec<MountSpec> =
+            serde_json::from_value(body["HostConfig"]["Mounts"].clone()).unwrap();
+        assert_eq!(parsed, mounts);
+    }
+
     #[test]
     fn container_options_host_config() {
         let options = ContainerOptionsBuilder::new("test_image")
@@ -1720,7 +3781,21 @@ mod tests {
             .append_pair("filters", r#"{"label":["label1=value","label2"]}"#)
             .finish();
 
-        assert_eq!(form, options.serialize().unwrap())
+        assert_eq!(form, options.serialize().unwrap().unwrap())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn container_list_options_in_place() {
+        use crate::InPlace;
+
+        let mut builder = ContainerListOptions::builder();
+        builder.in_place(|b| b.all()).in_place(|b| b.sized());
+
+        assert_eq!(
+            Some("all=true&size=true".to_owned()),
+            builder.build().serialize().unwrap()
+        );
     }
 
     #[test]
@@ -1733,7 +3808,7 @@ mod tests {
             .append_pair("filters", r#"{"exited":["0"]}"#)
             .finish();
 
-        assert_eq!(form, options.serialize().unwrap())
+        assert_eq!(form, options.serialize().unwrap().unwrap())
     }
 
     #[test]
@@ -1746,7 +3821,7 @@ mod tests {
             .append_pair("filters", r#"{"status":["running"]}"#)
             .finish();
 
-        assert_eq!(form, options.serialize().unwrap())
+        assert_eq!(form, options.serialize().unwrap().unwrap())
     }
 
     #[test]
@@ -1761,7 +3836,7 @@ mod tests {
             ])
             .build();
 
-        let serialized = options.serialize().unwrap();
+        let serialized = options.serialize().unwrap().unwrap();
 
         assert!(serialized.contains("all=true"));
         assert!(serialized.contains("filters="));
@@ -1773,8 +3848,7 @@ mod tests {
     #[cfg(feature = "chrono")]
     #[test]
     fn logs_options() {
-        let timestamp = chrono::NaiveDateTime::from_timestamp(2_147_483_647, 0);
-        let since = chrono::DateTime::<chrono::Utc>::from_utc(timestamp, chrono::Utc);
+        let since = chrono::DateTime::<chrono::Utc>::from_timestamp(2_147_483_647, 0).unwrap();
 
         let options = LogsOptionsBuilder::default()
             .follow(true)
@@ -1785,7 +3859,7 @@ mod tests {
             .since(&since)
             .build();
 
-        let serialized = options.serialize().unwrap();
+        let serialized = options.serialize().unwrap().unwrap();
 
         assert!(serialized.contains("follow=true"));
         assert!(serialized.contains("stdout=true"));
@@ -1795,7 +3869,31 @@ mod tests {
         assert!(serialized.contains("since=2147483647"));
     }
 
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[test]
+    fn logs_options() {
+        let since = OffsetDateTime::from_unix_timestamp(2_147_483_647).unwrap();
+
+        let options = LogsOptionsBuilder::default()
+            .follow(true)
+            .stdout(true)
+            .stderr(true)
+            .timestamps(true)
+            .tail("all")
+            .since(&since)
+            .build();
+
+        let serialized = options.serialize().unwrap().unwrap();
+
+        assert!(serialized.contains("follow=true"));
+        assert!(serialized.contains("stdout=true"));
+        assert!(serialized.contains("stderr=true"));
+        assert!(serialized.contains("timestamps=true"));
+        assert!(serialized.contains("tail=all"));
+        assert!(serialized.contains("since=2147483647"));
+    }
+
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     #[test]
     fn logs_options() {
         let options = LogsOptionsBuilder::default()
@@ -1807,7 +3905,7 @@ mod tests {
             .since(2_147_483_647)
             .build();
 
-        let serialized = options.serialize().unwrap();
+        let serialized = options.serialize().unwrap().unwrap();
 
         assert!(serialized.contains("follow=true"));
         assert!(serialized.contains("stdout=true"));
@@ -1816,4 +3914,153 @@ mod tests {
         assert!(serialized.contains("tail=all"));
         assert!(serialized.contains("since=2147483647"));
     }
+
+    #[test]
+    fn commit() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/commit",
+            MockResponse::new(201, r#"{"Id":"sha256:1a2b"}"#),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let opts = CommitOptions::builder()
+            .repo("app")
+            .tag("snapshot")
+            .author("Jane Doe <jane@example.com>")
+            .pause(false)
+            .changes(vec!["ENV DEBUG=1", "EXPOSE 8080"])
+            .config(ImageConfig {
+                cmd: Some(vec!["app".to_owned(), "--serve".to_owned()]),
+                stop_signal: Some("SIGINT".to_owned()),
+                ..ImageConfig::default()
+            })
+            .build();
+        let id = futures::executor::block_on(docker.containers().get("web").commit(&opts)).unwrap();
+
+        assert_eq!(id, "sha256:1a2b");
+        let request = &daemon.requests()[0];
+        assert_eq!(
+            request.path,
+            "/commit?container=web&repo=app&tag=snapshot&author=Jane+Doe+%3Cjane%40example.com%3E\
+             &pause=false&changes=ENV+DEBUG%3D1%0AEXPOSE+8080"
+        );
+        assert_eq!(
+            request.json().unwrap(),
+            serde_json::json!({ "Cmd": ["app", "--serve"], "StopSignal": "SIGINT" })
+        );
+    }
+
+    #[test]
+    fn remove_older_than() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let container = |id: &str, age: u64| {
+            let mut summary = fixtures::container_summary(id, id);
+            summary["Created"] = json!(now - age);
+            summary["State"] = json!("exited");
+            summary
+        };
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/containers/json",
+                MockResponse::json(&json!([
+                    container("old", 7200),
+                    container("recent", 60),
+                    container("restarted", 7200),
+                    container("gone", 7200),
+                ])),
+            )
+            .respond("DELETE", "/containers/old", MockResponse::new(204, ""))
+            .respond(
+                "DELETE",
+                "/containers/restarted",
+                MockResponse::error(409, "cannot remove a running container"),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        let report = futures::executor::block_on(docker.containers().remove_older_than(
+            Duration::from_secs(3600),
+            vec![ContainerFilter::Label("ci".to_owned(), "true".to_owned())],
+            &[StoppedState::Exited, StoppedState::Dead],
+            true,
+        ))
+        .unwrap();
+        assert_eq!(report.removed, ["old"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "restarted");
+        assert!(report.failed[0].1.is_conflict());
+
+        let requests = daemon.requests();
+        let (_, query) = requests[0].path.split_once('?').unwrap();
+        let (_, filters) = form_urlencoded::parse(query.as_bytes())
+            .find(|(name, _)| name == "filters")
+            .unwrap();
+        let filters: Value = serde_json::from_str(&filters).unwrap();
+        assert_eq!(
+            filters,
+            json!({ "label": ["ci=true"], "status": ["exited", "dead"] })
+        );
+        assert_eq!(requests[1].path, "/containers/old?v=true");
+        assert!(!requests
+            .iter()
+            .any(|request| request.path.starts_with("/containers/recent")));
+    }
+
+    #[tokio::test]
+    async fn stop_all() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/containers/json",
+                MockResponse::json(&json!([
+                    fixtures::container_summary("web", "web"),
+                    fixtures::container_summary("worker", "worker"),
+                    fixtures::container_summary("db", "db"),
+                ])),
+            )
+            .respond("POST", "/containers/web/stop", MockResponse::new(204, ""))
+            .respond(
+                "POST",
+                "/containers/worker/stop",
+                MockResponse::new(304, ""),
+            )
+            .respond(
+                "POST",
+                "/containers/db/stop",
+                MockResponse::error(500, "cannot stop container"),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        let mut reports: Vec<_> = docker
+            .containers()
+            .stop_all(
+                vec![ContainerFilter::LabelName("drain".to_owned())],
+                Duration::from_secs(10),
+                2,
+            )
+            .try_collect()
+            .await
+            .unwrap();
+        reports.sort_by(|a, b| a.id.as_ref().cmp(b.id.as_ref()));
+        assert_eq!(reports.len(), 3);
+        assert!(matches!(reports[0].outcome, StopOutcome::Failed(_)));
+        assert!(matches!(reports[1].outcome, StopOutcome::Stopped));
+        assert!(matches!(reports[2].outcome, StopOutcome::AlreadyStopped));
+        assert!(daemon
+            .requests()
+            .iter()
+            .any(|request| request.path == "/containers/web/stop?t=10"));
+    }
 }
@@ -0,0 +1,197 @@
+//! Keep registry credentials in the configuration file of the docker CLI.
+//!
+//! After [RegistryAuth::login], [store] writes the credentials the way `docker login` does, so
+//! that the docker CLI and other tools pick them up: in the credential store configured by
+//! `credsStore` or `credHelpers`, or else in the `auths` of the configuration file.
+//!
+//! ```no_run
+//! use shiplift::{credentials, Docker, RegistryAuth};
+//! # async {
+//! let docker = Docker::new();
+//! let auth = RegistryAuth::login(&docker, "registry.example.com", "user", "secret").await?;
+//! if let Some(path) = credentials::config_path() {
+//!     credentials::store(&path, "registry.example.com", &auth)?;
+//! }
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{errors::Result, image::RegistryAuth};
+
+/// The configuration file of the docker CLI: `config.json` in `$DOCKER_CONFIG`, or else in
+/// `~/.docker`
+pub fn config_path() -> Option<PathBuf> {
+    let dir = match env::var_os("DOCKER_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".docker"),
+    };
+    Some(dir.join("config.json"))
+}
+
+/// Stores the credentials `auth` of `registry` for the docker CLI configured by the file at
+/// `path`, which is created if it does not exist
+///
+/// If the configuration names a credential helper for the registry in `credHelpers`, or a
+/// credential store for all registries in `credsStore`, the credentials are handed to its
+/// `docker-credential-<name>` program and the `auths` entry of the registry is left empty, like
+/// `docker login` does. Otherwise they are written to the `auths` entry of the registry. Other
+/// settings of the configuration are kept.
+pub fn store(
+    path: &Path,
+    registry: &str,
+    auth: &RegistryAuth,
+) -> Result<()> {
+    let mut config = match fs::read(path) {
+        Ok(config) => serde_json::from_slice(&config)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => json!({}),
+        Err(e) => return Err(e.into()),
+    };
+    let helper = config["credHelpers"][registry]
+        .as_str()
+        .or_else(|| config["credsStore"].as_str())
+        .filter(|helper| !helper.is_empty())
+        .map(str::to_owned);
+
+    let (username, secret) = match auth {
+        RegistryAuth::Password {
+            username, password, ..
+        } => (username.as_str(), password.as_str()),
+        // the placeholder username the docker CLI stores identity tokens with
+        RegistryAuth::Token { identity_token } => ("<token>", identity_token.as_str()),
+    };
+    let entry = match (&helper, auth) {
+        (Some(helper), _) => {
+            run_helper(helper, registry, username, secret)?;
+            json!({})
+        }
+        (None, RegistryAuth::Password { .. }) => {
+            json!({ "auth": base64::encode(format!("{}:{}", username, secret)) })
+        }
+        (None, RegistryAuth::Token { identity_token }) => {
+            json!({ "identitytoken": identity_token })
+        }
+    };
+
+    let config = config.as_object_mut().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a JSON object", path.display()),
+        )
+    })?;
+    let auths = config
+        .entry("auths")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !auths.is_object() {
+        *auths = Value::Object(Map::new());
+    }
+    auths[registry] = entry;
+    write_config(path, &serde_json::to_vec_pretty(config)?)?;
+    Ok(())
+}
+
+/// Stores credentials with the `docker-credential-<helper>` program
+fn run_helper(
+    helper: &str,
+    registry: &str,
+    username: &str,
+    secret: &str,
+) -> io::Result<()> {
+    let program = format!("docker-credential-{}", helper);
+    let mut child = Command::new(&program)
+        .arg("store")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let credentials = json!({ "ServerURL": registry, "Username": username, "Secret": secret });
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(credentials.to_string().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    // helpers report errors on stdout
+    let message = String::from_utf8_lossy(if output.stdout.is_empty() {
+        &output.stderr
+    } else {
+        &output.stdout
+    })
+    .trim()
+    .to_owned();
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "{} failed to store the credentials of {}: {}",
+            program, registry, message
+        ),
+    ))
+}
+
+/// Replaces the configuration file, readable only by its owner as it holds credentials
+fn write_config(
+    path: &Path,
+    config: &[u8],
+) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    file.write_all(config)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_auths() {
+        let dir = env::temp_dir().join(format!("shiplift-credentials-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.json");
+
+        let password = RegistryAuth::builder()
+            .username("user")
+            .password("secret")
+            .build();
+        store(&path, "registry.example.com", &password).unwrap();
+        fs::write(
+            &path,
+            r#"{"auths":{"registry.example.com":{"auth":"old"}},"detachKeys":"ctrl-x"}"#,
+        )
+        .unwrap();
+        store(&path, "registry.example.com", &password).unwrap();
+        store(&path, "ghcr.io", &RegistryAuth::token("abc")).unwrap();
+
+        let config: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "auths": {
+                    "registry.example.com": { "auth": base64::encode("user:secret") },
+                    "ghcr.io": { "identitytoken": "abc" },
+                },
+                "detachKeys": "ctrl-x",
+            })
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -7,8 +7,9 @@ pub(crate) fn datetime_from_unix_timestamp<'de, D>(
 where
     D: serde::Deserializer<'de>,
 {
-    let timestamp = chrono::NaiveDateTime::from_timestamp(i64::deserialize(deserializer)?, 0);
-    Ok(DateTime::<Utc>::from_utc(timestamp, Utc))
+    let timestamp = i64::deserialize(deserializer)?;
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .ok_or_else(|| serde::de::Error::custom("timestamp out of range"))
 }
 
 pub(crate) fn datetime_from_nano_timestamp<'de, D>(
@@ -18,9 +19,9 @@ where
     D: serde::Deserializer<'de>,
 {
     let timestamp_nano = u64::deserialize(deserializer)?;
-    let timestamp = chrono::NaiveDateTime::from_timestamp(
+    DateTime::<Utc>::from_timestamp(
         (timestamp_nano / 1_000_000_000) as i64,
         (timestamp_nano % 1_000_000_000) as u32,
-    );
-    Ok(DateTime::<Utc>::from_utc(timestamp, Utc))
+    )
+    .ok_or_else(|| serde::de::Error::custom("timestamp out of range"))
 }
@@ -2,21 +2,25 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/>
 
-use std::{collections::HashMap, env, io, path::Path};
+use std::{collections::HashMap, env, io};
 
-use futures_util::{stream::Stream, TryStreamExt};
-use hyper::{client::HttpConnector, Body, Client, Method};
+use futures_util::{stream::Stream, TryFutureExt, TryStreamExt};
+use hyper::{body::Bytes, client::HttpConnector, Body, Client, Method};
 use mime::Mime;
 use serde::{de, Deserialize, Serialize};
-use url::form_urlencoded;
 
 use crate::{
     container::Containers,
     errors::{Error, Result},
     image::Images,
+    json_stream,
     network::Networks,
+    node::Nodes,
+    plugin::Plugins,
+    query::{self, Filters},
     service::Services,
-    transport::{Headers, Payload, Transport},
+    task::Tasks,
+    transport::{Headers, Payload, Transport, Upgraded},
     volume::Volumes,
     Uri,
 };
@@ -25,11 +29,15 @@ use crate::{
 use crate::datetime::{datetime_from_nano_timestamp, datetime_from_unix_timestamp};
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
 
 #[cfg(feature = "tls")]
 use hyper_openssl::HttpsConnector;
 #[cfg(feature = "tls")]
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+#[cfg(feature = "tls")]
+use std::path::Path;
 
 #[cfg(feature = "unix-socket")]
 use hyperlocal::UnixConnector;
@@ -58,14 +66,14 @@ fn get_docker_for_tcp(tcp_host_str: String) -> Docker {
         let cert = &format!("{}/cert.pem", certs);
         let key = &format!("{}/key.pem", certs);
         connector
-            .set_certificate_file(&Path::new(cert), SslFiletype::PEM)
+            .set_certificate_file(Path::new(cert), SslFiletype::PEM)
             .unwrap();
         connector
-            .set_private_key_file(&Path::new(key), SslFiletype::PEM)
+            .set_private_key_file(Path::new(key), SslFiletype::PEM)
             .unwrap();
         if env::var("DOCKER_TLS_VERIFY").is_ok() {
             let ca = &format!("{}/ca.pem", certs);
-            connector.set_ca_file(&Path::new(ca)).unwrap();
+            connector.set_ca_file(Path::new(ca)).unwrap();
         }
 
         // If we are attempting to connec to the docker daemon via tcp
@@ -169,6 +177,20 @@ impl Docker {
         }
     }
 
+    /// Creates a new docker instance whose requests are answered by an in-process
+    /// [MockDaemon](crate::mock::MockDaemon), for tests
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn mock(daemon: crate::mock::MockDaemon) -> Docker {
+        Docker {
+            transport: Transport::Mock { daemon },
+        }
+    }
+
+    /// The host name of a daemon that is reached over the network, `None` for a local daemon
+    pub(crate) fn remote_host(&self) -> Option<String> {
+        self.transport.remote_host()
+    }
+
     /// Exports an interface for interacting with docker images
     pub fn images(&'_ self) -> Images<'_> {
         Images::new(self)
@@ -184,10 +206,25 @@ impl Docker {
         Services::new(self)
     }
 
+    /// Exports an interface for interacting with docker swarm tasks
+    pub fn tasks(&'_ self) -> Tasks<'_> {
+        Tasks::new(self)
+    }
+
+    /// Exports an interface for interacting with docker swarm nodes
+    pub fn nodes(&'_ self) -> Nodes<'_> {
+        Nodes::new(self)
+    }
+
     pub fn networks(&'_ self) -> Networks<'_> {
         Networks::new(self)
     }
 
+    /// Exports an interface for interacting with docker plugins
+    pub fn plugins(&'_ self) -> Plugins<'_> {
+        Plugins::new(self)
+    }
+
     pub fn volumes(&'_ self) -> Volumes<'_> {
         Volumes::new(self)
     }
@@ -202,6 +239,28 @@ impl Docker {
         self.get_json("/info").await
     }
 
+    /// The current time of the daemon, in seconds and nanoseconds since the unix epoch, or the
+    /// time of this host if the daemon doesn't tell
+    pub(crate) async fn system_time(&self) -> Result<(i64, u32)> {
+        #[derive(Deserialize)]
+        struct SystemTime {
+            #[serde(rename = "SystemTime")]
+            system_time: Option<String>,
+        }
+
+        let info: SystemTime = self.get_json("/info").await?;
+        Ok(info
+            .system_time
+            .as_deref()
+            .and_then(crate::log_files::parse_timestamp)
+            .unwrap_or_else(|| {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                (now.as_secs() as i64, now.subsec_nanos())
+            }))
+    }
+
     /// Returns a simple ping response indicating the docker daemon is accessible
     pub async fn ping(&self) -> Result<String> {
         self.get("/_ping").await
@@ -212,27 +271,174 @@ impl Docker {
         &'docker self,
         opts: &EventsOptions,
     ) -> impl Stream<Item = Result<Event>> + Unpin + 'docker {
-        let mut path = vec!["/events".to_owned()];
-        if let Some(query) = opts.serialize() {
-            path.push(query);
-        }
-        let reader = Box::pin(
-            self.stream_get(path.join("?"))
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
-        )
-        .into_async_read();
+        let query = opts.serialize();
+        Box::pin(
+            async move {
+                let mut path = vec!["/events".to_owned()];
+                if let Some(query) = query? {
+                    path.push(query);
+                }
+                let path = path.join("?");
+                let reader = Box::pin(
+                    self.stream_get(path.clone())
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+                )
+                .into_async_read();
 
-        let codec = futures_codec::LinesCodec {};
+                let codec = futures_codec::LinesCodec {};
 
-        Box::pin(
-            futures_codec::FramedRead::new(reader, codec)
-                .map_err(Error::IO)
-                .and_then(|s: String| async move {
-                    serde_json::from_str(&s).map_err(Error::SerdeJsonError)
-                }),
+                Ok(futures_codec::FramedRead::new(reader, codec)
+                    .map_err(Error::IO)
+                    .and_then(move |s: String| {
+                        let result = decode_json(Method::GET, &path, &s);
+                        async move { result }
+                    }))
+            }
+            .try_flatten_stream(),
         )
     }
 
+    /// Sends a request to an endpoint of the daemon that this crate has no interface for, like
+    /// one of a newer API version, and returns the body of the response.
+    ///
+    /// `path` is relative to the root of the API, like `/containers/json`, or
+    /// `/v1.43/containers/json` for a specific API version. `query` is an encoded query string
+    /// without the leading `?`. `body` is sent with the given content type.
+    ///
+    /// Responses with an error status fail with [Error::Fault] and its kin, like the requests
+    /// of this crate.
+    ///
+    /// ```no_run
+    /// # async {
+    /// use shiplift::{Docker, Method};
+    ///
+    /// let docker = Docker::new();
+    /// let usage = docker
+    ///     .request(
+    ///         Method::GET,
+    ///         "/system/df",
+    ///         Some("type=volume"),
+    ///         None::<(Vec<u8>, shiplift::Mime)>,
+    ///         vec![],
+    ///     )
+    ///     .await?;
+    /// # Ok::<_, shiplift::Error>(())
+    /// # };
+    /// ```
+    pub async fn request<B, H>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&str>,
+        body: Option<(B, Mime)>,
+        headers: H,
+    ) -> Result<Bytes>
+    where
+        B: Into<Body>,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        self.transport
+            .request_bytes(method, with_query(path, query), body, Some(headers))
+            .await
+    }
+
+    /// Like [request](Docker::request), but decodes the JSON body of the response
+    pub async fn request_json<T, B, H>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&str>,
+        body: Option<(B, Mime)>,
+        headers: H,
+    ) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+        B: Into<Body>,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let endpoint = with_query(path, query);
+        let bytes = self
+            .transport
+            .request_bytes(method.clone(), &endpoint, body, Some(headers))
+            .await?;
+
+        serde_json::from_slice(&bytes)
+            .map_err(|e| Error::decode(method, &endpoint, &String::from_utf8_lossy(&bytes), e))
+    }
+
+    /// Like [request](Docker::request), but streams the body of the response in the chunks it
+    /// is received in, for endpoints that stream their response like `/events`. Use
+    /// [json_stream::decode] to decode streamed JSON values.
+    pub fn stream_request<'docker, B, H>(
+        &'docker self,
+        method: Method,
+        path: &str,
+        query: Option<&str>,
+        body: Option<(B, Mime)>,
+        headers: H,
+    ) -> impl Stream<Item = Result<Bytes>> + 'docker
+    where
+        B: Into<Body> + 'docker,
+        H: IntoIterator<Item = (&'static str, String)> + 'docker,
+    {
+        self.transport
+            .stream_chunks(method, with_query(path, query), body, Some(headers))
+    }
+
+    /// Sends a request to an endpoint that switches the connection from HTTP to `protocol`,
+    /// and returns the connection as a raw stream of bytes in both directions, to drive
+    /// endpoints like attach, exec or `/session` with a protocol of your own.
+    ///
+    /// The daemon expects `tcp` as the protocol of attach and exec, and `h2c` for `/session`.
+    /// `path`, `query`, `body` and `headers` are as for [request](Docker::request). Responses
+    /// with an error status fail like requests do, and a response that does not switch the
+    /// connection fails with [Error::ConnectionNotUpgraded].
+    ///
+    /// ```no_run
+    /// # async {
+    /// use futures_util::io::AsyncReadExt;
+    /// use shiplift::{Docker, Method};
+    ///
+    /// let docker = Docker::new();
+    /// let mut connection = docker
+    ///     .upgrade(
+    ///         Method::POST,
+    ///         "/containers/app/attach",
+    ///         Some("stream=1&stdout=1"),
+    ///         None::<(Vec<u8>, shiplift::Mime)>,
+    ///         vec![],
+    ///         "tcp",
+    ///     )
+    ///     .await?;
+    /// let mut output = Vec::new();
+    /// connection.read_to_end(&mut output).await?;
+    /// # Ok::<_, shiplift::Error>(())
+    /// # };
+    /// ```
+    pub async fn upgrade<B, H>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&str>,
+        body: Option<(B, Mime)>,
+        headers: H,
+        protocol: &str,
+    ) -> Result<Upgraded>
+    where
+        B: Into<Body>,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        self.transport
+            .upgrade(
+                method,
+                with_query(path, query),
+                body,
+                Some(headers),
+                protocol,
+            )
+            .await
+    }
+
     //
     // Utility functions to make requests
     //
@@ -255,7 +461,7 @@ impl Docker {
             .request(Method::GET, endpoint, Payload::None, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&raw_string)?)
+        decode_json(Method::GET, endpoint, &raw_string)
     }
 
     pub(crate) async fn post(
@@ -268,17 +474,6 @@ impl Docker {
             .await
     }
 
-    pub(crate) async fn post_with_headers<'a, H>(
-        &self,
-        endpoint: &str,
-        body: Option<(Body, Mime)>,
-        headers: Option<H>,
-    ) -> Result<String> where H: IntoIterator<Item = (&'static str, String)> + 'a{
-        self.transport
-            .request(Method::POST, endpoint, body, headers)
-            .await
-    }
-    
     pub(crate) async fn put(
         &self,
         endpoint: &str,
@@ -300,10 +495,10 @@ impl Docker {
     {
         let string = self
             .transport
-            .request(Method::POST, endpoint, body, Headers::None)
+            .request(Method::POST, endpoint.as_ref(), body, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        decode_json(Method::POST, endpoint.as_ref(), &string)
     }
 
     pub(crate) async fn post_json_headers<'a, T, B, H>(
@@ -319,10 +514,10 @@ impl Docker {
     {
         let string = self
             .transport
-            .request(Method::POST, endpoint, body, headers)
+            .request(Method::POST, endpoint.as_ref(), body, headers)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        decode_json(Method::POST, endpoint.as_ref(), &string)
     }
 
     pub(crate) async fn delete(
@@ -343,7 +538,7 @@ impl Docker {
             .request(Method::DELETE, endpoint, Payload::None, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        decode_json(Method::DELETE, endpoint, &string)
     }
 
     /// Send a streaming post request.
@@ -363,8 +558,6 @@ impl Docker {
     }
 
     /// Send a streaming post request that returns a stream of JSON values
-    ///
-    /// Assumes that each received chunk contains one or more JSON values
     pub(crate) fn stream_post_into<'a, H, T>(
         &'a self,
         endpoint: impl AsRef<str> + 'a,
@@ -373,20 +566,13 @@ impl Docker {
     ) -> impl Stream<Item = Result<T>> + 'a
     where
         H: IntoIterator<Item = (&'static str, String)> + 'a,
-        T: de::DeserializeOwned,
+        T: de::DeserializeOwned + 'a,
     {
-        self.stream_post(endpoint, body, headers)
-            .and_then(|chunk| async move {
-                let stream = futures_util::stream::iter(
-                    serde_json::Deserializer::from_slice(&chunk)
-                        .into_iter()
-                        .collect::<Vec<_>>(),
-                )
-                .map_err(Error::from);
-
-                Ok(stream)
-            })
-            .try_flatten()
+        let path = endpoint.as_ref().to_owned();
+        json_stream::decode_with(
+            self.stream_post(endpoint, body, headers),
+            move |input, e| Error::decode(Method::POST, &path, &String::from_utf8_lossy(input), e),
+        )
     }
 
     pub(crate) fn stream_get<'a>(
@@ -398,17 +584,40 @@ impl Docker {
             .stream_chunks(Method::GET, endpoint, Option::<(Body, Mime)>::None, headers)
     }
 
-    pub(crate) async fn stream_post_upgrade<'a>(
-        &'a self,
-        endpoint: impl AsRef<str> + 'a,
+    pub(crate) async fn stream_post_upgrade(
+        &self,
+        endpoint: impl AsRef<str>,
         body: Option<(Body, Mime)>,
-    ) -> Result<impl futures_util::io::AsyncRead + futures_util::io::AsyncWrite + 'a> {
+    ) -> Result<Upgraded> {
         self.transport
             .stream_upgrade(Method::POST, endpoint, body)
             .await
     }
 }
 
+/// Appends an encoded `query` to `path`
+fn with_query(
+    path: &str,
+    query: Option<&str>,
+) -> String {
+    match query {
+        Some(query) if !query.is_empty() => format!("{}?{}", path, query),
+        _ => path.to_owned(),
+    }
+}
+
+/// Decodes the JSON response `body` to a request
+fn decode_json<T>(
+    method: Method,
+    endpoint: &str,
+    body: &str,
+) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    serde_json::from_str(body).map_err(|e| Error::decode(method, endpoint, body, e))
+}
+
 impl Default for Docker {
     fn default() -> Self {
         Self::new()
@@ -416,9 +625,13 @@ impl Default for Docker {
 }
 
 /// Options for filtering streams of Docker events
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct EventsOptions {
-    params: HashMap<&'static str, String>,
+    /// Seconds since the unix epoch, with an optional fraction
+    since: Option<String>,
+    until: Option<u64>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl EventsOptions {
@@ -427,16 +640,8 @@ impl EventsOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
@@ -473,93 +678,68 @@ pub enum EventFilter {
 
 /// Builder interface for `EventOptions`
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct EventsOptionsBuilder {
-    params: HashMap<&'static str, String>,
-    events: Vec<String>,
-    containers: Vec<String>,
-    images: Vec<String>,
-    labels: Vec<String>,
-    volumes: Vec<String>,
-    networks: Vec<String>,
-    daemons: Vec<String>,
-    types: Vec<String>,
+    options: EventsOptions,
 }
 
 impl EventsOptionsBuilder {
     /// Filter events since a given timestamp
     pub fn since(
-        &mut self,
+        mut self,
         ts: &u64,
-    ) -> &mut Self {
-        self.params.insert("since", ts.to_string());
+    ) -> Self {
+        self.options.since = Some(ts.to_string());
+        self
+    }
+
+    /// Filter events since a timestamp with nanosecond precision
+    pub(crate) fn since_nanos(
+        mut self,
+        secs: i64,
+        nanos: u32,
+    ) -> Self {
+        self.options.since = Some(format!("{}.{:09}", secs, nanos));
         self
     }
 
     /// Filter events until a given timestamp
     pub fn until(
-        &mut self,
+        mut self,
         ts: &u64,
-    ) -> &mut Self {
-        self.params.insert("until", ts.to_string());
+    ) -> Self {
+        self.options.until = Some(*ts);
         self
     }
 
     pub fn filter(
-        &mut self,
+        mut self,
         filters: Vec<EventFilter>,
-    ) -> &mut Self {
-        let mut params = HashMap::new();
+    ) -> Self {
         for f in filters {
-            match f {
-                EventFilter::Container(n) => {
-                    self.containers.push(n);
-                    params.insert("container", self.containers.clone())
-                }
-                EventFilter::Event(n) => {
-                    self.events.push(n);
-                    params.insert("event", self.events.clone())
-                }
-                EventFilter::Image(n) => {
-                    self.images.push(n);
-                    params.insert("image", self.images.clone())
-                }
-                EventFilter::Label(n) => {
-                    self.labels.push(n);
-                    params.insert("label", self.labels.clone())
-                }
-                EventFilter::Volume(n) => {
-                    self.volumes.push(n);
-                    params.insert("volume", self.volumes.clone())
-                }
-                EventFilter::Network(n) => {
-                    self.networks.push(n);
-                    params.insert("network", self.networks.clone())
-                }
-                EventFilter::Daemon(n) => {
-                    self.daemons.push(n);
-                    params.insert("daemon", self.daemons.clone())
-                }
-                EventFilter::Type(n) => {
-                    let event_type = event_filter_type_to_string(n).to_string();
-                    self.types.push(event_type);
-                    params.insert("type", self.types.clone())
-                }
+            let (key, value) = match f {
+                EventFilter::Container(n) => ("container", n),
+                EventFilter::Event(n) => ("event", n),
+                EventFilter::Image(n) => ("image", n),
+                EventFilter::Label(n) => ("label", n),
+                EventFilter::Volume(n) => ("volume", n),
+                EventFilter::Network(n) => ("network", n),
+                EventFilter::Daemon(n) => ("daemon", n),
+                EventFilter::Type(n) => ("type", event_filter_type_to_string(n).to_owned()),
             };
+            self.options.filters.add(key, value);
         }
-        self.params
-            .insert("filters", serde_json::to_string(&params).unwrap());
         self
     }
 
-    pub fn build(&self) -> EventsOptions {
-        EventsOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> EventsOptions {
+        self.options
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Version {
     pub version: String,
     pub api_version: String,
@@ -570,12 +750,20 @@ pub struct Version {
     pub kernel_version: String,
     #[cfg(feature = "chrono")]
     pub build_time: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub build_time: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub build_time: String,
+    /// Parts of the version report that aren't modelled, like `Platform` and `Components`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Info {
     pub containers: u64,
     pub images: u64,
@@ -594,12 +782,53 @@ pub struct Info {
     pub n_goroutines: u64,
     pub name: String,
     pub operating_system: String,
-    // pub RegistryConfig:???
+    pub registry_config: Option<RegistryConfig>,
     pub swap_limit: bool,
     pub system_time: Option<String>,
+    /// Daemon settings that aren't modelled, like `Swarm` and `Runtimes`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The registries the daemon is configured to pull from
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct RegistryConfig {
+    /// The networks, like `127.0.0.0/8`, of the registries that are reached over plain HTTP if
+    /// they don't serve HTTPS
+    #[serde(
+        rename = "InsecureRegistryCIDRs",
+        default,
+        deserialize_with = "crate::image::null_as_default"
+    )]
+    pub insecure_registry_cidrs: Vec<String>,
+    /// The registries the daemon has a configuration for, by their host
+    #[serde(default, deserialize_with = "crate::image::null_as_default")]
+    pub index_configs: HashMap<String, IndexInfo>,
+    /// The mirrors of Docker Hub, like `https://mirror.gcr.io/`
+    #[serde(default, deserialize_with = "crate::image::null_as_default")]
+    pub mirrors: Vec<String>,
+}
+
+/// The configuration of the daemon for a registry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct IndexInfo {
+    pub name: String,
+    /// The mirrors images of the registry are pulled from first
+    #[serde(default, deserialize_with = "crate::image::null_as_default")]
+    pub mirrors: Vec<String>,
+    /// Whether the registry is verified over HTTPS, instead of being an insecure registry
+    pub secure: bool,
+    /// Whether this is Docker Hub
+    pub official: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Event {
     #[serde(rename = "Type")]
     pub typ: String,
@@ -613,17 +842,28 @@ pub struct Event {
     #[cfg(feature = "chrono")]
     #[serde(deserialize_with = "datetime_from_unix_timestamp")]
     pub time: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::timestamp")]
+    pub time: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub time: u64,
     #[cfg(feature = "chrono")]
     #[serde(deserialize_with = "datetime_from_nano_timestamp", rename = "timeNano")]
     pub time_nano: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::timestamp::nanoseconds", rename = "timeNano")]
+    pub time_nano: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     #[serde(rename = "timeNano")]
     pub time_nano: u64,
+    /// Fields of the event that aren't modelled, like `scope`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Actor {
     #[serde(rename = "ID")]
     pub id: String,
@@ -659,4 +899,130 @@ mod tests {
             }
         }
     }
+    #[cfg(feature = "time")]
+    #[test]
+    fn event_timestamps() {
+        let event: super::Event = serde_json::from_str(
+            r#"{
+                "Type": "container",
+                "Action": "start",
+                "Actor": { "ID": "4fa6e0f0c678", "Attributes": {} },
+                "time": 1625140000,
+                "timeNano": 1625140000123456789
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(event.time.unix_timestamp(), 1_625_140_000);
+        assert_eq!(event.time_nano.nanosecond(), 123_456_789);
+    }
+
+    #[test]
+    fn raw_requests() {
+        use crate::mock::{MockDaemon, MockResponse};
+        use futures_util::TryStreamExt;
+        use hyper::Method;
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/v1.43/system/df",
+                MockResponse::json(&serde_json::json!({ "LayersSize": 1024 })),
+            )
+            .respond("POST", "/plugins/pull", MockResponse::new(200, "pulled"))
+            .respond("GET", "/stream", MockResponse::json_stream(&[1, 2]))
+            .respond("GET", "/missing", MockResponse::error(404, "no such thing"));
+        let docker = super::Docker::mock(daemon.clone());
+
+        futures::executor::block_on(async {
+            let usage: serde_json::Value = docker
+                .request_json(
+                    Method::GET,
+                    "/v1.43/system/df",
+                    Some("type=image"),
+                    None::<(Vec<u8>, mime::Mime)>,
+                    vec![],
+                )
+                .await
+                .unwrap();
+            assert_eq!(usage["LayersSize"], 1024);
+
+            let body = docker
+                .request(
+                    Method::POST,
+                    "/plugins/pull",
+                    None,
+                    Some((r#"[]"#, mime::APPLICATION_JSON)),
+                    vec![("X-Registry-Auth", "e30=".to_owned())],
+                )
+                .await
+                .unwrap();
+            assert_eq!(body, "pulled");
+
+            let chunks: Vec<_> = docker
+                .stream_request(
+                    Method::GET,
+                    "/stream",
+                    None,
+                    None::<(Vec<u8>, mime::Mime)>,
+                    vec![],
+                )
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(chunks, ["1\r\n", "2\r\n"]);
+
+            let err = docker
+                .request(
+                    Method::GET,
+                    "/missing",
+                    None,
+                    None::<(Vec<u8>, mime::Mime)>,
+                    vec![],
+                )
+                .await
+                .unwrap_err();
+            assert!(err.is_not_found());
+        });
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].path, "/v1.43/system/df?type=image");
+        assert_eq!(requests[1].headers["X-Registry-Auth"], "e30=");
+        assert_eq!(requests[1].body, "[]");
+    }
+
+    #[test]
+    fn upgrade_errors() {
+        use super::Docker;
+        use crate::{
+            mock::{MockDaemon, MockResponse},
+            Error,
+        };
+        use hyper::Method;
+
+        let daemon = MockDaemon::new();
+        daemon.respond("POST", "/containers/app/attach", MockResponse::new(200, ""));
+        let docker = Docker::mock(daemon.clone());
+        let upgrade = |path| {
+            futures::executor::block_on(docker.upgrade(
+                Method::POST,
+                path,
+                Some("stream=1"),
+                None::<(Vec<u8>, mime::Mime)>,
+                vec![],
+                "tcp",
+            ))
+        };
+
+        assert!(upgrade("/containers/missing/attach")
+            .unwrap_err()
+            .is_not_found());
+        assert!(matches!(
+            upgrade("/containers/app/attach"),
+            Err(Error::ConnectionNotUpgraded)
+        ));
+        let request = &daemon.requests()[1];
+        assert_eq!(request.path, "/containers/app/attach?stream=1");
+        assert_eq!(request.headers["Upgrade"], "tcp");
+    }
 }
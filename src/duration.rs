@@ -0,0 +1,57 @@
+//! Conversion of durations to the representations the daemon expects.
+
+use std::{convert::TryFrom, time::Duration};
+
+/// Shortest positive duration the daemon accepts for the intervals of a healthcheck
+const MIN_HEALTHCHECK_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Whole seconds of `duration`, rounded up so that a short but positive timeout doesn't turn
+/// into no timeout at all
+pub(crate) fn whole_secs(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_add(u64::from(duration.subsec_nanos() > 0))
+}
+
+/// `duration` in the format of Go's `time.ParseDuration`, like `90s` or `1500ms`, as used by
+/// filters relative to the time of the daemon
+pub(crate) fn go_duration(duration: Duration) -> String {
+    if duration.subsec_nanos() == 0 {
+        format!("{}s", duration.as_secs())
+    } else if duration.subsec_nanos() % 1_000_000 == 0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{}ns", duration.as_nanos())
+    }
+}
+
+/// Nanoseconds of an interval of a healthcheck. Zero keeps the default of the daemon, other
+/// durations are raised to the minimum of 1ms and capped at the largest value the daemon can
+/// represent.
+pub(crate) fn healthcheck_nanos(duration: Duration) -> i64 {
+    if duration.is_zero() {
+        return 0;
+    }
+    i64::try_from(duration.max(MIN_HEALTHCHECK_INTERVAL).as_nanos()).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_conversions() {
+        assert_eq!(whole_secs(Duration::from_secs(10)), 10);
+        assert_eq!(whole_secs(Duration::from_millis(1)), 1);
+        assert_eq!(whole_secs(Duration::from_millis(2500)), 3);
+
+        assert_eq!(go_duration(Duration::from_secs(3600)), "3600s");
+        assert_eq!(go_duration(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(go_duration(Duration::from_nanos(10)), "10ns");
+
+        assert_eq!(healthcheck_nanos(Duration::ZERO), 0);
+        assert_eq!(healthcheck_nanos(Duration::from_micros(10)), 1_000_000);
+        assert_eq!(healthcheck_nanos(Duration::from_secs(30)), 30_000_000_000);
+        assert_eq!(healthcheck_nanos(Duration::MAX), i64::MAX);
+    }
+}
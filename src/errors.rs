@@ -1,15 +1,23 @@
 //! Representations of various client errors
 
-use hyper::{self, http, StatusCode};
+use hyper::{self, http, Method, StatusCode};
 use serde_json::Error as SerdeError;
 use std::{error::Error as StdError, fmt, string::FromUtf8Error};
 
-use futures_util::io::Error as IoError;
+use futures_util::{
+    io::{Error as IoError, ErrorKind},
+    stream::{Stream, StreamExt},
+};
+
+#[cfg(feature = "stack")]
+use crate::stack::StackError;
+use crate::{image::LayerTally, tarball::ContextTooLarge};
 
 /// Represents the result of all docker operations
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     SerdeJsonError(SerdeError),
     Hyper(hyper::Error),
@@ -17,12 +25,448 @@ pub enum Error {
     #[allow(clippy::upper_case_acronyms)]
     IO(IoError),
     Encoding(FromUtf8Error),
+    /// Options could not be encoded as the query parameters of a request
+    Query(serde_urlencoded::ser::Error),
     InvalidResponse(String),
+    /// The daemon responded to a request with an error status
     Fault {
         code: StatusCode,
+        /// The `message` of the error body returned by the daemon, or the reason phrase of the
+        /// status code if the body could not be parsed
+        message: String,
+        /// Method of the failed request
+        method: Method,
+        /// Path and query of the failed request
+        path: String,
+    },
+    /// The object a request referred to does not exist (404)
+    NotFound {
+        message: String,
+        /// Method of the failed request
+        method: Method,
+        /// Path and query of the failed request
+        path: String,
+    },
+    /// A request conflicts with the current state of an object, for example because an object of
+    /// the same name already exists or the object is in use (409)
+    Conflict {
+        message: String,
+        /// Method of the failed request
+        method: Method,
+        /// Path and query of the failed request
+        path: String,
+    },
+    /// A request had no effect because the object was already in the requested state, like
+    /// starting a running container (304)
+    NotModified {
+        message: String,
+        /// Method of the failed request
+        method: Method,
+        /// Path and query of the failed request
+        path: String,
+    },
+    /// The response to a request could not be decoded, typically because the daemon returned
+    /// fields or values this crate does not know about
+    Decode {
+        /// Method of the request
+        method: Method,
+        /// Path and query of the request
+        path: String,
+        /// The start of the response body that could not be decoded
+        body: String,
+        source: SerdeError,
+    },
+    /// The daemon reported an error partway through a streamed response whose request had
+    /// succeeded, like a push that was denied by the registry
+    StreamFault {
         message: String,
+        /// Method of the failed request
+        method: Method,
+        /// Path and query of the failed request
+        path: String,
+    },
+    /// A streamed pull, build, import or export failed after part of it had completed
+    Interrupted {
+        /// What had been received before the stream failed
+        progress: StreamProgress,
+        source: Box<Error>,
     },
     ConnectionNotUpgraded,
+    /// A swarm object was updated against a stale version. Inspect the object again to obtain
+    /// the current version and retry.
+    UpdateOutOfSequence(String),
+    /// The privileges requested by the named plugin were not accepted, so it was not installed.
+    PluginPrivilegesRejected(String),
+    /// The daemon rejected an option that is only supported in its experimental mode, like
+    /// [squashing](crate::BuildOptionsBuilder::squash) a build, because the mode is off
+    ExperimentalRequired(String),
+    /// The [verifier](crate::PullOptionsBuilder::verify) of a pull rejected the manifest the
+    /// image resolved to, so it was not pulled.
+    PullRejected {
+        image: String,
+        digest: String,
+        reason: String,
+    },
+    /// The image was not available for the platform it was
+    /// [resolved](crate::Images::resolve_platform) for.
+    PlatformNotAvailable {
+        image: String,
+        platform: String,
+        /// The platforms the image is available for
+        available: Vec<String>,
+    },
+    /// The registry has no manifest of the pulled tag or digest
+    ManifestNotFound {
+        image: String,
+        message: String,
+    },
+    /// The registry refused to serve the pulled image without valid credentials. Registries
+    /// report repositories that do not exist this way as well, to not reveal which exist.
+    Unauthorized {
+        image: String,
+        message: String,
+    },
+    /// The registry refused the pull because its rate limit was exceeded
+    RateLimited {
+        image: String,
+        message: String,
+    },
+    /// The layers of an image pulled [with a limit](crate::Images::pull_with_limit) exceeded it,
+    /// so the pull was cancelled.
+    PullTooLarge {
+        image: String,
+        /// The limit on the compressed size of the layers, in bytes
+        limit: u64,
+        /// The compressed size of the layers the daemon reported before the pull was cancelled
+        size: u64,
+    },
+    /// An image pulled by [digest](crate::PullOptionsBuilder::digest) does not have the
+    /// digest among its repository digests after the pull.
+    DigestMismatch {
+        image: String,
+        digest: String,
+        /// The repository digests of the image that was pulled, if any
+        repo_digests: Vec<String>,
+    },
+    /// The build context exceeded the configured maximum size.
+    ContextTooLarge(ContextTooLarge),
+    /// A container [waited on](crate::Container::wait_healthy) can't become healthy, because
+    /// it has no healthcheck or stopped
+    NotHealthy {
+        container: String,
+        reason: String,
+    },
+    /// A [Stack](crate::stack::Stack) could not be brought up
+    #[cfg(feature = "stack")]
+    Stack(StackError),
+}
+
+/// How far a streamed operation got before it failed, see [Error::Interrupted]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamProgress {
+    /// Bytes transferred: the size of the data received for exports, the size of the layers
+    /// downloaded so far for pulls, as reported by the daemon
+    pub bytes: u64,
+    /// The ID of the last layer the daemon reported on
+    pub layer: Option<String>,
+    /// The last build step the daemon started, like `Step 2/5 : RUN make`
+    pub step: Option<String>,
+    /// Bytes downloaded per layer, which the daemon reports cumulatively
+    layers: LayerTally,
+}
+
+impl StreamProgress {
+    /// Whether nothing had been received yet
+    pub fn is_empty(&self) -> bool {
+        self == &StreamProgress::default()
+    }
+
+    pub(crate) fn record_bytes(
+        &mut self,
+        len: usize,
+    ) {
+        self.bytes = self.bytes.saturating_add(len as u64);
+    }
+
+    /// Records that the daemon reported on `layer`, updating the bytes downloaded of the layers
+    /// with `record`
+    pub(crate) fn record_layer<F>(
+        &mut self,
+        layer: &str,
+        record: F,
+    ) where
+        F: FnOnce(&mut LayerTally),
+    {
+        let previous = self.layers.downloaded();
+        record(&mut self.layers);
+        self.bytes = self
+            .bytes
+            .saturating_sub(previous)
+            .saturating_add(self.layers.downloaded());
+        if self.layer.as_deref() != Some(layer) {
+            self.layer = Some(layer.to_owned());
+        }
+    }
+
+    pub(crate) fn record_step(
+        &mut self,
+        step: &str,
+    ) {
+        self.step = Some(step.trim_end().to_owned());
+    }
+}
+
+impl fmt::Display for StreamProgress {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "{} bytes received", self.bytes)?;
+        if let Some(ref layer) = self.layer {
+            write!(f, ", last layer {}", layer)?;
+        }
+        if let Some(ref step) = self.step {
+            write!(f, ", last step {:?}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the progress made by `stream` to the error it fails with. Each item is passed to
+/// `record` to update the progress.
+pub(crate) fn track_progress<S, T, F>(
+    stream: S,
+    mut record: F,
+) -> impl Stream<Item = Result<T>>
+where
+    S: Stream<Item = Result<T>>,
+    F: FnMut(&mut StreamProgress, &T),
+{
+    let mut progress = StreamProgress::default();
+    stream.map(move |item| match item {
+        Ok(item) => {
+            record(&mut progress, &item);
+            Ok(item)
+        }
+        Err(e) => Err(e.interrupted(&progress)),
+    })
+}
+
+/// Whether an operation that failed may succeed when it is retried, see [Error::retry_advice]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAdvice {
+    /// The failure is likely temporary and the request was not acted on, so it may be retried
+    Retry,
+    /// The failure is likely temporary, but the request may have been acted on before it
+    /// failed, so it should only be retried if repeating it is harmless
+    RetryIfIdempotent,
+    /// Retrying will fail the same way
+    DoNotRetry,
+}
+
+impl Error {
+    /// The error for a response with the error status `code`
+    pub(crate) fn from_status(
+        code: StatusCode,
+        message: String,
+        method: Method,
+        path: String,
+    ) -> Self {
+        match code {
+            StatusCode::NOT_FOUND => Error::NotFound {
+                message,
+                method,
+                path,
+            },
+            StatusCode::CONFLICT => Error::Conflict {
+                message,
+                method,
+                path,
+            },
+            StatusCode::NOT_MODIFIED => Error::NotModified {
+                message,
+                method,
+                path,
+            },
+            code => Error::Fault {
+                code,
+                message,
+                method,
+                path,
+            },
+        }
+    }
+
+    /// The status code of the daemon's response, if the request failed with an error status
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            Error::Fault { code, .. } => Some(*code),
+            Error::NotFound { .. } => Some(StatusCode::NOT_FOUND),
+            Error::Conflict { .. } => Some(StatusCode::CONFLICT),
+            Error::NotModified { .. } => Some(StatusCode::NOT_MODIFIED),
+            _ => None,
+        }
+    }
+
+    /// Classifies whether the failed operation may succeed when it is retried
+    ///
+    /// Refused connections, rate limiting (429) and an unavailable daemon (503) can always be
+    /// retried. Connections that were reset or timed out and other server errors (5xx) may have
+    /// been acted on already.
+    pub fn retry_advice(&self) -> RetryAdvice {
+        match self {
+            Error::Hyper(err) if err.is_connect() => RetryAdvice::Retry,
+            Error::Hyper(err)
+                if err.is_timeout()
+                    || err.is_closed()
+                    || err.is_incomplete_message()
+                    || err.is_canceled() =>
+            {
+                RetryAdvice::RetryIfIdempotent
+            }
+            Error::Hyper(err) => match std::error::Error::source(err)
+                .and_then(|source| source.downcast_ref::<IoError>())
+            {
+                Some(err) => io_retry_advice(err),
+                None => RetryAdvice::DoNotRetry,
+            },
+            Error::IO(err) => io_retry_advice(err),
+            // the interrupted operation had an effect already
+            Error::Interrupted { source, .. } => match source.retry_advice() {
+                RetryAdvice::Retry => RetryAdvice::RetryIfIdempotent,
+                advice => advice,
+            },
+            Error::Fault { code, .. } => match *code {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                    RetryAdvice::Retry
+                }
+                code if code.is_server_error() => RetryAdvice::RetryIfIdempotent,
+                _ => RetryAdvice::DoNotRetry,
+            },
+            Error::RateLimited { .. } => RetryAdvice::Retry,
+            _ => RetryAdvice::DoNotRetry,
+        }
+    }
+
+    /// Whether the failure is likely temporary, so that the operation may succeed when it is
+    /// retried. See [retry_advice](Error::retry_advice) for whether retrying is safe.
+    pub fn is_transient(&self) -> bool {
+        self.retry_advice() != RetryAdvice::DoNotRetry
+    }
+
+    /// Whether the object a request referred to does not exist
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::NotFound { .. } | Error::ManifestNotFound { .. } => true,
+            Error::Interrupted { source, .. } => source.is_not_found(),
+            _ => false,
+        }
+    }
+
+    /// Whether a request conflicts with the current state of an object, for example because an
+    /// object of the same name already exists
+    pub fn is_conflict(&self) -> bool {
+        match self {
+            Error::Conflict { .. } => true,
+            Error::Interrupted { source, .. } => source.is_conflict(),
+            _ => false,
+        }
+    }
+
+    /// Whether a request had no effect because the object was already in the requested state
+    pub fn is_not_modified(&self) -> bool {
+        matches!(self, Error::NotModified { .. })
+    }
+
+    /// What a streamed operation had received before it failed, if it failed partway through
+    pub fn progress(&self) -> Option<&StreamProgress> {
+        match self {
+            Error::Interrupted { progress, .. } => Some(progress),
+            _ => None,
+        }
+    }
+
+    /// Wraps the error a stream failed with in [Interrupted](Error::Interrupted), unless the
+    /// stream failed before anything was received
+    pub(crate) fn interrupted(
+        self,
+        progress: &StreamProgress,
+    ) -> Self {
+        match self {
+            Error::Interrupted { .. } => self,
+            _ if progress.is_empty() => self,
+            source => Error::Interrupted {
+                progress: progress.clone(),
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Converts the swarm's "update out of sequence" fault into
+    /// [UpdateOutOfSequence](Error::UpdateOutOfSequence), leaving other errors untouched.
+    pub(crate) fn into_update_error(self) -> Self {
+        match self {
+            Error::Fault { message, .. } | Error::Conflict { message, .. }
+                if message.contains("update out of sequence") =>
+            {
+                Error::UpdateOutOfSequence(message)
+            }
+            e => e,
+        }
+    }
+
+    /// Converts the fault for a build option that requires the daemon's experimental mode into
+    /// [ExperimentalRequired](Error::ExperimentalRequired), leaving other errors untouched.
+    pub(crate) fn into_build_error(self) -> Self {
+        match self {
+            Error::Fault { message, .. } if message.contains("experimental mode") => {
+                Error::ExperimentalRequired(message)
+            }
+            e => e,
+        }
+    }
+}
+
+/// How much of a response body is kept in [Error::Decode]
+const DECODE_BODY_LIMIT: usize = 1024;
+
+impl Error {
+    /// The error for a response `body` that could not be decoded
+    pub(crate) fn decode(
+        method: Method,
+        path: impl Into<String>,
+        body: &str,
+        source: SerdeError,
+    ) -> Self {
+        let body = if body.len() > DECODE_BODY_LIMIT {
+            let mut end = DECODE_BODY_LIMIT;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            format!("{}...", &body[..end])
+        } else {
+            body.to_owned()
+        };
+        Error::Decode {
+            method,
+            path: path.into(),
+            body,
+            source,
+        }
+    }
+}
+
+fn io_retry_advice(error: &IoError) -> RetryAdvice {
+    match error.kind() {
+        ErrorKind::ConnectionRefused => RetryAdvice::Retry,
+        ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::BrokenPipe
+        | ErrorKind::TimedOut
+        | ErrorKind::UnexpectedEof
+        | ErrorKind::Interrupted => RetryAdvice::RetryIfIdempotent,
+        _ => RetryAdvice::DoNotRetry,
+    }
 }
 
 impl From<SerdeError> for Error {
@@ -52,6 +496,14 @@ impl From<http::uri::InvalidUri> for Error {
 
 impl From<IoError> for Error {
     fn from(error: IoError) -> Error {
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<ContextTooLarge>())
+        {
+            // checked above, so neither unwrap can fail
+            let inner = error.into_inner().unwrap();
+            return Error::ContextTooLarge(*inner.downcast::<ContextTooLarge>().unwrap());
+        }
         Error::IO(error)
     }
 }
@@ -62,6 +514,12 @@ impl From<FromUtf8Error> for Error {
     }
 }
 
+impl From<serde_urlencoded::ser::Error> for Error {
+    fn from(error: serde_urlencoded::ser::Error) -> Error {
+        Error::Query(error)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(
         &self,
@@ -74,14 +532,123 @@ impl fmt::Display for Error {
             Error::Hyper(ref err) => err.fmt(f),
             Error::IO(ref err) => err.fmt(f),
             Error::Encoding(ref err) => err.fmt(f),
+            Error::Query(ref err) => write!(f, "failed to encode query parameters: {}", err),
             Error::InvalidResponse(ref cause) => {
                 write!(f, "Response doesn't have the expected format: {}", cause)
             }
-            Error::Fault { code, message } => write!(f, "{}: {}", code, message),
+            Error::Fault {
+                code,
+                message,
+                method,
+                path,
+            } => write!(f, "{} {} failed with {}: {}", method, path, code, message),
+            Error::NotFound {
+                message,
+                method,
+                path,
+            } => write!(f, "{} {} failed, not found: {}", method, path, message),
+            Error::Conflict {
+                message,
+                method,
+                path,
+            } => write!(f, "{} {} failed with a conflict: {}", method, path, message),
+            Error::NotModified {
+                message,
+                method,
+                path,
+            } => write!(f, "{} {} had no effect: {}", method, path, message),
+            Error::Decode {
+                method,
+                path,
+                body,
+                source,
+            } => write!(
+                f,
+                "failed to decode the response to {} {}: {}, response: {}",
+                method, path, source, body
+            ),
+            Error::StreamFault {
+                message,
+                method,
+                path,
+            } => write!(f, "{} {} failed: {}", method, path, message),
+            Error::Interrupted { progress, source } => {
+                let source = source.to_string();
+                write!(
+                    f,
+                    "interrupted after {}: {}",
+                    progress,
+                    source.trim_start_matches("Docker Error: ")
+                )
+            }
             Error::ConnectionNotUpgraded => write!(
                 f,
                 "expected the docker host to upgrade the HTTP connection but it did not"
             ),
+            Error::UpdateOutOfSequence(ref message) => {
+                write!(f, "update out of sequence: {}", message)
+            }
+            Error::PluginPrivilegesRejected(ref plugin) => {
+                write!(f, "privileges requested by plugin {} were rejected", plugin)
+            }
+            Error::ExperimentalRequired(ref message) => {
+                write!(f, "the daemon's experimental mode is required: {}", message)
+            }
+            Error::PullRejected {
+                ref image,
+                ref digest,
+                ref reason,
+            } => write!(f, "pull of {} ({}) was rejected: {}", image, digest, reason),
+            Error::PlatformNotAvailable {
+                ref image,
+                ref platform,
+                ref available,
+            } => write!(
+                f,
+                "{} is not available for {}, only for {}",
+                image,
+                platform,
+                available.join(", ")
+            ),
+            Error::ManifestNotFound {
+                ref image,
+                ref message,
+            } => write!(f, "no manifest of {} found: {}", image, message),
+            Error::Unauthorized {
+                ref image,
+                ref message,
+            } => write!(f, "pull of {} is unauthorized: {}", image, message),
+            Error::RateLimited {
+                ref image,
+                ref message,
+            } => write!(f, "pull of {} was rate limited: {}", image, message),
+            Error::PullTooLarge {
+                ref image,
+                limit,
+                size,
+            } => write!(
+                f,
+                "pull of {} was cancelled: its layers of at least {} bytes exceed the limit of {} bytes",
+                image, size, limit
+            ),
+            Error::DigestMismatch {
+                ref image,
+                ref digest,
+                ref repo_digests,
+            } => write!(
+                f,
+                "pulled image {} does not have digest {}, only [{}]",
+                image,
+                digest,
+                repo_digests.join(", ")
+            ),
+            Error::ContextTooLarge(ref err) => err.fmt(f),
+            Error::NotHealthy {
+                ref container,
+                ref reason,
+            } => write!(f, "container {} can't become healthy: {}", container, reason),
+            #[cfg(feature = "stack")]
+            Error::Stack(ref err) => err.fmt(f),
         }
     }
 }
@@ -93,7 +660,143 @@ impl StdError for Error {
             Error::Http(ref err) => Some(err),
             Error::IO(ref err) => Some(err),
             Error::Encoding(e) => Some(e),
+            Error::Query(e) => Some(e),
+            Error::ContextTooLarge(e) => Some(e),
+            #[cfg(feature = "stack")]
+            Error::Stack(e) => Some(e),
+            Error::Decode { source, .. } => Some(source),
+            Error::Interrupted { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(code: StatusCode) -> Error {
+        Error::from_status(
+            code,
+            "message".to_owned(),
+            Method::DELETE,
+            "/containers/app".to_owned(),
+        )
+    }
+
+    #[test]
+    fn error_from_status() {
+        assert!(error(StatusCode::NOT_FOUND).is_not_found());
+        assert!(error(StatusCode::CONFLICT).is_conflict());
+        assert!(error(StatusCode::NOT_MODIFIED).is_not_modified());
+
+        let fault = error(StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!fault.is_not_found() && !fault.is_conflict() && !fault.is_not_modified());
+        assert_eq!(fault.status_code(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(
+            error(StatusCode::NOT_FOUND).status_code(),
+            Some(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[test]
+    fn error_retry_advice() {
+        assert_eq!(
+            error(StatusCode::TOO_MANY_REQUESTS).retry_advice(),
+            RetryAdvice::Retry
+        );
+        assert_eq!(
+            error(StatusCode::BAD_GATEWAY).retry_advice(),
+            RetryAdvice::RetryIfIdempotent
+        );
+        assert!(!error(StatusCode::NOT_FOUND).is_transient());
+        assert!(!error(StatusCode::BAD_REQUEST).is_transient());
+
+        let io = |kind| Error::IO(IoError::new(kind, "io"));
+        assert_eq!(
+            io(ErrorKind::ConnectionRefused).retry_advice(),
+            RetryAdvice::Retry
+        );
+        assert_eq!(
+            io(ErrorKind::ConnectionReset).retry_advice(),
+            RetryAdvice::RetryIfIdempotent
+        );
+        assert!(!io(ErrorKind::PermissionDenied).is_transient());
+    }
+
+    #[test]
+    fn error_interrupted() {
+        let mut progress = StreamProgress::default();
+        assert!(error(StatusCode::NOT_FOUND)
+            .interrupted(&progress)
+            .is_not_found());
+
+        progress.record_layer("a", |layers| {
+            layers.record_download("a", Some(10), None);
+        });
+        progress.record_layer("b", |_| ());
+        progress.record_layer("a", |layers| {
+            layers.record_download("a", Some(25), None);
+        });
+        progress.record_step("Step 2/3 : RUN make\n");
+        let io = Error::IO(IoError::new(ErrorKind::ConnectionRefused, "io"));
+        let err = io.interrupted(&progress);
+        assert_eq!(err.progress().map(|p| p.bytes), Some(25));
+        assert_eq!(err.progress().unwrap().layer.as_deref(), Some("a"));
+        assert_eq!(err.retry_advice(), RetryAdvice::RetryIfIdempotent);
+        assert!(error(StatusCode::NOT_FOUND)
+            .interrupted(&progress)
+            .is_not_found());
+        assert!(error(StatusCode::CONFLICT)
+            .interrupted(&progress)
+            .is_conflict());
+        assert_eq!(
+            err.to_string(),
+            "Docker Error: interrupted after 25 bytes received, last layer a, last step \"Step \
+             2/3 : RUN make\": io"
+        );
+    }
+
+    #[test]
+    fn error_into_update_error() {
+        let out_of_sequence = Error::from_status(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "rpc error: code = Unknown desc = update out of sequence".to_owned(),
+            Method::POST,
+            "/services/web/update".to_owned(),
+        );
+        match out_of_sequence.into_update_error() {
+            Error::UpdateOutOfSequence(message) => assert!(message.ends_with("out of sequence")),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let conflict = Error::from_status(
+            StatusCode::CONFLICT,
+            "update out of sequence".to_owned(),
+            Method::POST,
+            "/services/web/update".to_owned(),
+        );
+        assert!(matches!(
+            conflict.into_update_error(),
+            Error::UpdateOutOfSequence(_)
+        ));
+        assert!(error(StatusCode::NOT_FOUND)
+            .into_update_error()
+            .is_not_found());
+    }
+
+    #[test]
+    fn error_decode_truncates_body() {
+        let body = format!(r#"{{"Id": "{}"}}"#, "é".repeat(DECODE_BODY_LIMIT));
+        let source = serde_json::from_str::<u64>(&body).unwrap_err();
+        match Error::decode(Method::GET, "/containers/app/json", &body, source) {
+            Error::Decode { path, body, .. } => {
+                assert_eq!(path, "/containers/app/json");
+                assert!(body.starts_with(r#"{"Id": "é"#));
+                assert!(body.ends_with("é..."));
+                assert!(body.len() <= DECODE_BODY_LIMIT + 3);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
@@ -15,6 +15,7 @@ use serde_json::{json, Value};
 
 use crate::{
     errors::{Error, Result},
+    id::{ContainerId, ExecId},
     tty, Docker,
 };
 
@@ -32,11 +33,11 @@ impl<'docker> Exec<'docker> {
         id: S,
     ) -> Self
     where
-        S: Into<String>,
+        S: Into<ExecId>,
     {
         Exec {
             docker,
-            id: id.into(),
+            id: id.into().into_string(),
         }
     }
 
@@ -130,7 +131,7 @@ impl<'docker> Exec<'docker> {
         id: S,
     ) -> Exec<'docker>
     where
-        S: Into<String>,
+        S: Into<ExecId>,
     {
         Exec::new(docker, id)
     }
@@ -222,6 +223,7 @@ impl ExecContainerOptions {
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ExecContainerOptionsBuilder {
     params: HashMap<&'static str, Vec<String>>,
     params_bool: HashMap<&'static str, bool>,
@@ -230,54 +232,48 @@ pub struct ExecContainerOptionsBuilder {
 impl ExecContainerOptionsBuilder {
     /// Command to run, as an array of strings
     pub fn cmd(
-        &mut self,
+        mut self,
         cmds: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         for cmd in cmds {
-            self.params
-                .entry("Cmd")
-                .or_insert_with(Vec::new)
-                .push(cmd.to_owned());
+            self.params.entry("Cmd").or_default().push(cmd.to_owned());
         }
         self
     }
 
     /// A list of environment variables in the form "VAR=value"
     pub fn env(
-        &mut self,
+        mut self,
         envs: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         for env in envs {
-            self.params
-                .entry("Env")
-                .or_insert_with(Vec::new)
-                .push(env.to_owned());
+            self.params.entry("Env").or_default().push(env.to_owned());
         }
         self
     }
 
     /// Attach to stdout of the exec command
     pub fn attach_stdout(
-        &mut self,
+        mut self,
         stdout: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params_bool.insert("AttachStdout", stdout);
         self
     }
 
     /// Attach to stderr of the exec command
     pub fn attach_stderr(
-        &mut self,
+        mut self,
         stderr: bool,
-    ) -> &mut Self {
+    ) -> Self {
         self.params_bool.insert("AttachStderr", stderr);
         self
     }
 
-    pub fn build(&self) -> ExecContainerOptions {
+    pub fn build(self) -> ExecContainerOptions {
         ExecContainerOptions {
-            params: self.params.clone(),
-            params_bool: self.params_bool.clone(),
+            params: self.params,
+            params_bool: self.params_bool,
         }
     }
 }
@@ -298,17 +294,19 @@ impl ExecResizeOptions {
         &self,
         params: &'a HashMap<K, V>,
         body: &mut BTreeMap<String, Value>,
-    ) where
+    ) -> Result<()>
+    where
         &'a HashMap<K, V>: IntoIterator,
         K: ToString + Eq + Hash,
         V: Serialize,
     {
         for (k, v) in params.iter() {
             let key = k.to_string();
-            let value = serde_json::to_value(v).unwrap();
+            let value = serde_json::to_value(v)?;
 
             body.insert(key, value);
         }
+        Ok(())
     }
 
     /// return a new instance of a builder for options
@@ -318,6 +316,7 @@ impl ExecResizeOptions {
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ExecResizeOptionsBuilder {
     params: HashMap<&'static str, Value>,
 }
@@ -329,47 +328,54 @@ impl ExecResizeOptionsBuilder {
     }
 
     pub fn height(
-        &mut self,
+        mut self,
         height: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Name", json!(height));
         self
     }
 
     pub fn width(
-        &mut self,
+        mut self,
         width: u64,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Name", json!(width));
         self
     }
 
-    pub fn build(&self) -> ExecResizeOptions {
+    pub fn build(self) -> ExecResizeOptions {
         ExecResizeOptions {
-            params: self.params.clone(),
+            params: self.params,
         }
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ExecDetails {
     pub can_remove: bool,
     #[serde(rename = "ContainerID")]
-    pub container_id: String,
+    pub container_id: ContainerId,
     pub detach_keys: String,
     pub exit_code: Option<u64>,
     #[serde(rename = "ID")]
-    pub id: String,
+    pub id: ExecId,
     pub open_stderr: bool,
     pub open_stdin: bool,
     pub open_stdout: bool,
     pub process_config: ProcessConfig,
     pub running: bool,
     pub pid: u64,
+    /// Fields newer API versions add to the inspect response, as 1.41 has none that aren't
+    /// modelled here
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ProcessConfig {
     pub arguments: Vec<String>,
     pub entrypoint: String,
@@ -0,0 +1,173 @@
+//! Typed identifiers of docker objects.
+//!
+//! Each kind of object has its own identifier type, so that an identifier of one kind can't be
+//! passed where another kind is expected, like a container ID to [Images::get](crate::Images::get).
+//! Where a name is accepted as well, it converts into the same type.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of hex digits in the short form of an ID, as shown by the docker CLI
+const SHORT_ID_LEN: usize = 12;
+/// Minimum number of hex digits in a digest, to tell digests from names with a tag
+const MIN_DIGEST_LEN: usize = 32;
+
+macro_rules! impl_id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_string(self) -> String {
+                self.0
+            }
+
+            /// Splits a digest like `sha256:4f5e...` into its algorithm and hex encoded value.
+            /// Returns `None` if the ID is not given in this form, like a name or a plain ID.
+            pub fn digest(&self) -> Option<(&str, &str)> {
+                self.0.split_once(':').filter(|(algorithm, hex)| {
+                    !algorithm.is_empty()
+                        && algorithm
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                        && hex.len() >= MIN_DIGEST_LEN
+                        && hex.chars().all(|c| c.is_ascii_hexdigit())
+                })
+            }
+
+            /// The ID without the algorithm of a digest, like `4f5e...` for `sha256:4f5e...`
+            pub fn hex(&self) -> &str {
+                self.digest().map_or(&self.0, |(_, hex)| hex)
+            }
+
+            /// The first 12 digits of the ID, as shown by the docker CLI
+            pub fn short(&self) -> &str {
+                let hex = self.hex();
+                hex.char_indices()
+                    .nth(SHORT_ID_LEN)
+                    .map_or(hex, |(end, _)| &hex[..end])
+            }
+        }
+
+        impl fmt::Display for $name {
+            /// Displays the full ID, or the [short](Self::short) ID with `{:#}`
+            fn fmt(
+                &self,
+                f: &mut fmt::Formatter,
+            ) -> fmt::Result {
+                if f.alternate() {
+                    f.write_str(self.short())
+                } else {
+                    f.write_str(&self.0)
+                }
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<&String> for $name {
+            fn from(id: &String) -> Self {
+                $name(id.clone())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                $name(id.to_owned())
+            }
+        }
+
+        impl From<&$name> for $name {
+            fn from(id: &$name) -> Self {
+                id.clone()
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(
+                &self,
+                other: &str,
+            ) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(
+                &self,
+                other: &&str,
+            ) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+impl_id!(
+    /// ID or name of an image
+    ImageId
+);
+impl_id!(
+    /// ID or name of a container
+    ContainerId
+);
+impl_id!(
+    /// ID or name of a network
+    NetworkId
+);
+impl_id!(
+    /// ID of an exec instance
+    ExecId
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_digest_and_short() {
+        let image: ImageId = serde_json::from_str(
+            r#""sha256:4f5e0bd3e9cd8a4b1d8e6f3d8c51d7c0c4a9d2e1f0b3c5a7e9d1f2a3b4c5d6e7""#,
+        )
+        .unwrap();
+        assert_eq!(
+            image.digest(),
+            Some((
+                "sha256",
+                "4f5e0bd3e9cd8a4b1d8e6f3d8c51d7c0c4a9d2e1f0b3c5a7e9d1f2a3b4c5d6e7"
+            ))
+        );
+        assert_eq!(image.short(), "4f5e0bd3e9cd");
+        assert_eq!(format!("{:#}", image), "4f5e0bd3e9cd");
+        assert_eq!(
+            image.to_string(),
+            "sha256:4f5e0bd3e9cd8a4b1d8e6f3d8c51d7c0c4a9d2e1f0b3c5a7e9d1f2a3b4c5d6e7"
+        );
+
+        let container = ContainerId::from("web");
+        assert_eq!(container.digest(), None);
+        assert_eq!(container.short(), "web");
+        assert_eq!(container, "web");
+
+        let tagged = ImageId::from("registry.example.org:5000/app:1.0");
+        assert_eq!(tagged.digest(), None);
+        assert_eq!(tagged.hex(), "registry.example.org:5000/app:1.0");
+    }
+}
@@ -2,21 +2,56 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Image>
 
-use std::{collections::HashMap, io::Read, iter};
-
-use futures_util::{stream::Stream, TryFutureExt, TryStreamExt};
-use hyper::Body;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt,
+    io::{self, Read},
+    iter,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::{
+    future::{self, BoxFuture, Either, Future},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    stream::Stream,
+    FutureExt, StreamExt, TryFutureExt, TryStreamExt,
+};
+use hyper::{Body, Method};
+use mime::Mime;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
 use url::form_urlencoded;
 
-use crate::{docker::Docker, errors::Result, tarball, transport::tar};
+use crate::{
+    buildkit::{AbortOnDrop, BuildKitChunk, RawChunk, Session},
+    container::GraphDriverData,
+    docker::Docker,
+    duration,
+    errors::{self, Error, Result, StreamProgress},
+    id::ImageId,
+    query::{self, Filters, Repeated},
+    rate_limit::RateLimit,
+    rootfs,
+    tarball::{self, Compression, ProgressCallback, Symlinks, TarballOptions, TarballProgress},
+    transport::tar,
+};
 
 #[cfg(feature = "chrono")]
 use crate::datetime::datetime_from_unix_timestamp;
-use crate::Error;
+use bytes::Bytes;
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
-use bytes::Bytes;
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
 
 /// Interface for accessing and manipulating a named docker image
 ///
@@ -33,11 +68,11 @@ impl<'docker> Image<'docker> {
         name: S,
     ) -> Self
     where
-        S: Into<String>,
+        S: Into<ImageId>,
     {
         Image {
             docker,
-            name: name.into(),
+            name: name.into().into_string(),
         }
     }
 
@@ -63,10 +98,13 @@ impl<'docker> Image<'docker> {
     /// # Arguments
     /// delete_options - delete operation options as described in API reference
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePrune)
-    pub async fn delete_with_options(&self, delete_options: &DeleteOptions) -> Result<Vec<Status>> {
+    pub async fn delete_with_options(
+        &self,
+        delete_options: &DeleteOptions,
+    ) -> Result<Vec<Status>> {
         let mut path = vec![format!("/images/{}", self.name)];
 
-        if let Some(query) = delete_options.serialize() {
+        if let Some(query) = delete_options.serialize()? {
             path.push(query)
         }
 
@@ -88,29 +126,143 @@ impl<'docker> Image<'docker> {
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageGet)
     pub fn export(&self) -> impl Stream<Item = Result<Vec<u8>>> + Unpin + 'docker {
-        Box::pin(
+        Box::pin(errors::track_progress(
             self.docker
                 .stream_get(format!("/images/{}/get", self.name))
                 .map_ok(|c| c.to_vec()),
+            |progress, chunk| progress.record_bytes(chunk.len()),
+        ))
+    }
+
+    /// Exports this image as a tarball written to `writer`, returning the number of bytes written
+    ///
+    /// Each chunk is written before the next one is read from the daemon, so a slow writer slows
+    /// down the export rather than having it buffered in memory. `progress` is called with the
+    /// total number of bytes written after every chunk. The writer is flushed, but not closed,
+    /// once the export is complete.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageGet)
+    pub async fn export_to_writer<W, F>(
+        &self,
+        mut writer: W,
+        mut progress: F,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(u64),
+    {
+        let mut export = Box::pin(errors::track_progress(
+            self.docker.stream_get(format!("/images/{}/get", self.name)),
+            |progress, chunk| progress.record_bytes(chunk.len()),
+        ));
+        let mut written = 0;
+        while let Some(chunk) = export.try_next().await? {
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+            progress(written);
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Exports the filesystem of this image as a tarball, with its layers merged the way they
+    /// are when a container is run from it
+    ///
+    /// Files deleted or replaced by upper layers are left out, so that the tarball holds the
+    /// files that are effectively part of the image. The export is spooled to a temporary file
+    /// and its layers are merged one at a time, so the image doesn't have to fit in memory. The
+    /// stream has to be polled in a tokio runtime.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageGet)
+    pub fn export_rootfs(&self) -> impl Stream<Item = Result<Vec<u8>>> + Unpin + 'docker {
+        let mut export = self.export();
+        Box::pin(
+            async move {
+                let mut spool = tempfile::tempfile()?;
+                while let Some(chunk) = export.try_next().await? {
+                    io::Write::write_all(&mut spool, &chunk)?;
+                }
+                io::Seek::rewind(&mut spool)?;
+
+                let (tx, mut rx) = mpsc::channel(ROOTFS_CHUNKS_AHEAD);
+                tokio::task::spawn_blocking(move || {
+                    let mut chunks = ChunkWriter {
+                        tx: tx.clone(),
+                        chunk: Vec::with_capacity(ROOTFS_CHUNK_SIZE),
+                    };
+                    let flattened = rootfs::flatten(io::BufReader::new(spool), &mut chunks)
+                        .and_then(io::Write::flush);
+                    if let Err(e) = flattened {
+                        let _ = tx.blocking_send(Err(Error::from(e)));
+                    }
+                });
+                Ok(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)))
+            }
+            .try_flatten_stream(),
         )
     }
 
-    /// Adds a tag to an image
+    /// Adds a tag to an image, returning the reference the image is now known by
+    ///
+    /// The tag defaults to `latest` when the options don't set one, as the daemon does.
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageTag)
     pub async fn tag(
         &self,
         opts: &TagOptions,
-    ) -> Result<()> {
+    ) -> Result<String> {
         let mut path = vec![format!("/images/{}/tag", self.name)];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query)
         }
         let _ = self.docker.post(&path.join("?"), None).await?;
-        Ok(())
+        Ok(opts.reference())
+    }
+}
+
+/// Size of the chunks [Image::export_rootfs] yields the merged filesystem in
+const ROOTFS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks [Image::export_rootfs] merges ahead of the consumer of the stream
+const ROOTFS_CHUNKS_AHEAD: usize = 16;
+
+/// Sends what is written to it in chunks of [ROOTFS_CHUNK_SIZE], blocking while the channel is
+/// full
+struct ChunkWriter {
+    tx: mpsc::Sender<Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+}
+
+impl io::Write for ChunkWriter {
+    fn write(
+        &mut self,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        let len = buf.len().min(ROOTFS_CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..len]);
+        if self.chunk.len() == ROOTFS_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(ROOTFS_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the stream was dropped"))
     }
 }
 
+/// Size of the chunks [Images::import_from_async_read] uploads a tarball in
+const IMPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks [Images::copy_to] downloads ahead of the upload
+const COPY_CHUNKS_AHEAD: usize = 16;
+
 /// Interface for docker images
 pub struct Images<'docker> {
     docker: &'docker Docker,
@@ -124,40 +276,232 @@ impl<'docker> Images<'docker> {
 
     /// Builds a new image by reading a Dockerfile in a target directory
     ///
+    /// The directory is uploaded as it is packed into the build context, so that contexts larger
+    /// than the available memory can be built.
+    ///
+    /// If the stream fails after the build started, the error is [Error::Interrupted] with the
+    /// last build step the daemon reported.
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
     pub fn build(
         &self,
         opts: &BuildOptions,
     ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
-        let mut endpoint = vec!["/build".to_owned()];
-        if let Some(query) = opts.serialize() {
-            endpoint.push(query)
-        }
-
-        // To not tie the lifetime of `opts` to the 'stream, we do the tarring work outside of the
-        // stream. But for backwards compatability, we have to return the error inside of the
-        // stream.
-        let mut bytes = Vec::default();
-        let tar_result = tarball::dir(&mut bytes, opts.path.as_str(), opts.skip_gzip);
+        let query = opts.serialize();
+        let (body, packing_error) = context_body(&opts.path, &opts.tarball);
 
         // We must take ownership of the Docker reference. If we don't then the lifetime of 'stream
         // is incorrectly tied to `self`.
         let docker = self.docker;
+        Box::pin(errors::track_progress(
+            future::ready(query)
+                .map_ok(move |query| {
+                    let mut endpoint = vec!["/build".to_owned()];
+                    endpoint.extend(query);
+                    docker
+                        .stream_post_into(
+                            endpoint.join("?"),
+                            Some((body, tar())),
+                            None::<iter::Empty<_>>,
+                        )
+                        .map_err(move |e| packing_error.take(e).into_build_error())
+                })
+                .try_flatten_stream(),
+            ImageBuildChunk::record_progress,
+        ))
+    }
+
+    /// Builds an image like [build](Self::build), but only returns once the build finished,
+    /// with the ID of the built image and the output of the build
+    ///
+    /// The build fails with [Error::StreamFault] if the daemon reports an error.
+    pub async fn build_and_wait(
+        &self,
+        opts: &BuildOptions,
+    ) -> Result<BuiltImage> {
+        let (mut id, mut logs) = (None, Vec::new());
+        let mut chunks = self.build(opts);
+        while let Some(chunk) = chunks.try_next().await? {
+            match chunk.into_result("/build")? {
+                ImageBuildChunk::Update { stream } => {
+                    // the legacy builder of old daemons only reports the image in the output
+                    if let Some(built) = stream.trim_end().strip_prefix("Successfully built ") {
+                        id = id.or_else(|| Some(built.to_owned()));
+                    }
+                    logs.push(stream);
+                }
+                chunk => {
+                    if let Some(built) = chunk.built_image_id() {
+                        id = Some(built.to_owned());
+                    }
+                }
+            }
+        }
+        match id {
+            Some(id) => Ok(BuiltImage {
+                id: id.into(),
+                logs,
+            }),
+            None => Err(Error::InvalidResponse(
+                "the build finished without reporting the built image".to_owned(),
+            )),
+        }
+    }
+
+    /// Builds an image like [Images::build], reporting its progress as [ProgressEvent]s
+    ///
+    /// The stream fails with [Error::StreamFault] when the daemon reports that the build
+    /// failed.
+    pub fn build_events(
+        &self,
+        opts: &BuildOptions,
+    ) -> impl Stream<Item = Result<ProgressEvent>> + Unpin + 'docker {
+        let stream = self.build(opts);
+        future::ready(opts.serialize())
+            .map_ok(move |query| {
+                let mut path = vec!["/build".to_owned()];
+                path.extend(query);
+                ProgressReporter::new().events(stream, path.join("?"))
+            })
+            .try_flatten_stream()
+    }
+
+    /// Builds a new image with BuildKit by reading a Dockerfile in a target directory
+    ///
+    /// The build reports the progress of its steps as [BuildKitChunk]s, and fails with an
+    /// [Error::StreamFault] with the error of the first step that fails. Builds that mount
+    /// secrets or SSH agents need a session to serve them, see [Images::build_with_session].
+    ///
+    /// If the stream fails after the build started, the error is [Error::Interrupted] with the
+    /// last step the daemon started.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
+    pub fn build_with_buildkit(
+        &self,
+        opts: &BuildOptions,
+    ) -> impl Stream<Item = Result<BuildKitChunk>> + Unpin + 'docker {
+        self.buildkit_build(opts, None)
+    }
+
+    /// Builds a new image with BuildKit like [Images::build_with_buildkit], serving the secrets
+    /// and SSH agents of `session` to the steps of the build that mount them
+    ///
+    /// The session is served over a connection to `/session` for the duration of the build,
+    /// which has to run in a tokio runtime.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/Session)
+    pub fn build_with_session(
+        &self,
+        opts: &BuildOptions,
+        session: Session,
+    ) -> impl Stream<Item = Result<BuildKitChunk>> + Unpin + 'docker {
+        let build = self.buildkit_build(opts, Some(session.id().to_owned()));
+        let docker = self.docker;
         Box::pin(
             async move {
-                // Bubble up error inside the stream for backwards compatability
-                tar_result?;
+                let connection = docker
+                    .upgrade(
+                        Method::POST,
+                        "/session",
+                        None,
+                        None::<(Body, Mime)>,
+                        session.headers(),
+                        "h2c",
+                    )
+                    .await?;
+                // a session that fails fails the steps that use it, which the build reports
+                let server = AbortOnDrop(tokio::spawn(
+                    session.serve(connection.into_inner()).map(drop),
+                ));
+                Ok(build.inspect(move |_| {
+                    let _ = &server;
+                }))
+            }
+            .try_flatten_stream(),
+        )
+    }
 
-                let value_stream = docker.stream_post_into(
-                    endpoint.join("?"),
-                    Some((Body::from(bytes), tar())),
-                    None::<iter::Empty<_>>,
-                );
+    fn buildkit_build(
+        &self,
+        opts: &BuildOptions,
+        session: Option<String>,
+    ) -> impl Stream<Item = Result<BuildKitChunk>> + Unpin + 'docker {
+        let params = BuildParams {
+            version: Some("2"),
+            session: session.or_else(|| opts.params.session.clone()),
+            ..opts.params.clone()
+        };
+        let query = params.serialize();
 
-                Ok(value_stream)
+        let (body, packing_error) = context_body(&opts.path, &opts.tarball);
+
+        let docker = self.docker;
+        Box::pin(errors::track_progress(
+            future::ready(query)
+                .map_ok(move |query| {
+                    let endpoint = format!("/build?{}", query.unwrap_or_default());
+                    docker
+                        .stream_post_into(endpoint, Some((body, tar())), None::<iter::Empty<_>>)
+                        .map_err(move |e| packing_error.take(e).into_build_error())
+                })
+                .try_flatten_stream()
+                .try_filter_map(|raw: RawChunk| {
+                    future::ready(BuildKitChunk::from_raw(raw).transpose())
+                }),
+            BuildKitChunk::record_progress,
+        ))
+    }
+
+    /// Builds a new image from a build context of `files`, each a path relative to the root of
+    /// the context with its contents, like a Dockerfile generated by the caller
+    ///
+    /// The context is packed in memory without touching the filesystem, so the path of `opts`
+    /// and its options selecting the entries of a directory don't apply.
+    /// [Context files](BuildOptionsBuilder::context_file) are added to `files`, replacing those
+    /// with the same path.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
+    pub fn build_from_files<I, P, C>(
+        &self,
+        opts: &BuildOptions,
+        files: I,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        I: IntoIterator<Item = (P, C)>,
+        P: Into<PathBuf>,
+        C: Into<Bytes>,
+    {
+        let query = opts.serialize();
+        let mut context = Vec::new();
+        let files: Vec<(PathBuf, Bytes)> = files
+            .into_iter()
+            .map(|(path, contents)| (path.into(), contents.into()))
+            .chain(
+                opts.tarball
+                    .overlay
+                    .iter()
+                    .map(|(path, contents)| (path.into(), contents.clone().into())),
+            )
+            .collect();
+        let packed = tarball::files_with_options(&mut context, files, &opts.tarball);
+
+        let docker = self.docker;
+        Box::pin(errors::track_progress(
+            async move {
+                packed?;
+                let mut endpoint = vec!["/build".to_owned()];
+                endpoint.extend(query?);
+                Ok(docker
+                    .stream_post_into(
+                        endpoint.join("?"),
+                        Some((Body::from(context), tar())),
+                        None::<iter::Empty<_>>,
+                    )
+                    .map_err(Error::into_build_error))
             }
             .try_flatten_stream(),
-        )
+            ImageBuildChunk::record_progress,
+        ))
     }
 
     /// Builds a new image by reading a build context
@@ -169,26 +513,31 @@ impl<'docker> Images<'docker> {
         &self,
         opts: &BuildParams,
         build_context: impl Iterator<Item = Result<T>> + Send + 'static,
-    )-> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker where T : Into<Bytes> + 'static {
-        let mut endpoint = vec!["/build".to_owned()];
-        if let Some(query) = opts.serialize() {
-            endpoint.push(query)
-        }
-
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        T: Into<Bytes> + 'static,
+    {
+        let query = opts.serialize();
         let request_stream = futures_util::stream::iter(build_context);
         let docker = self.docker;
 
-        Box::pin(
+        Box::pin(errors::track_progress(
             async move {
-                let value_stream = docker.stream_post_into(
-                    endpoint.join("?"),
-                    Some((Body::wrap_stream(request_stream), tar())),
-                    None::<iter::Empty<_>>,
-                );
+                let mut endpoint = vec!["/build".to_owned()];
+                endpoint.extend(query?);
+                let value_stream = docker
+                    .stream_post_into(
+                        endpoint.join("?"),
+                        Some((Body::wrap_stream(request_stream), tar())),
+                        None::<iter::Empty<_>>,
+                    )
+                    .map_err(Error::into_build_error);
 
                 Ok(value_stream)
-            }.try_flatten_stream(),
-        )
+            }
+            .try_flatten_stream(),
+            ImageBuildChunk::record_progress,
+        ))
     }
 
     /// Lists the docker images on the current docker host
@@ -199,7 +548,7 @@ impl<'docker> Images<'docker> {
         opts: &ImageListOptions,
     ) -> Result<Vec<ImageInfo>> {
         let mut path = vec!["/images/json".to_owned()];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query);
         }
         self.docker
@@ -213,11 +562,64 @@ impl<'docker> Images<'docker> {
         name: S,
     ) -> Image<'docker>
     where
-        S: Into<String>,
+        S: Into<ImageId>,
     {
         Image::new(self.docker, name)
     }
 
+    /// Tags the image `source` as `repo:tag`, returning the new reference
+    ///
+    /// This is a shorthand for [Image::tag] with [TagOptions] setting both the repository and
+    /// the tag.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageTag)
+    pub async fn tag<S, R, T>(
+        &self,
+        source: S,
+        repo: R,
+        tag: T,
+    ) -> Result<String>
+    where
+        S: Into<ImageId>,
+        R: Into<String>,
+        T: Into<String>,
+    {
+        let opts = TagOptions::builder().repo(repo).tag(tag).build();
+        self.get(source).tag(&opts).await
+    }
+
+    /// Compares the layers of the images `a` and `b`, to tell how much of `b` is missing on a
+    /// host that has `a`
+    ///
+    /// Layers are identified by the digests of the root filesystems of the images. Their sizes
+    /// are taken from the histories of the images, and are unknown if the history does not
+    /// match the layers.
+    pub async fn diff<A, B>(
+        &self,
+        a: A,
+        b: B,
+    ) -> Result<ImageDiff>
+    where
+        A: Into<ImageId>,
+        B: Into<ImageId>,
+    {
+        let (a, b) = (self.get(a), self.get(b));
+        let (details_a, history_a, details_b, history_b) =
+            futures_util::try_join!(a.inspect(), a.history(), b.inspect(), b.history())?;
+
+        let layers = |details: &ImageDetails| {
+            details
+                .root_fs
+                .as_ref()
+                .and_then(|root_fs| root_fs.layers.clone())
+                .unwrap_or_default()
+        };
+        Ok(ImageDiff::new(
+            (&layers(&details_a), &history_a),
+            (&layers(&details_b), &history_b),
+        ))
+    }
+
     /// Search for docker images by term
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageSearch)
@@ -225,111 +627,1024 @@ impl<'docker> Images<'docker> {
         &self,
         term: &str,
     ) -> Result<Vec<SearchResult>> {
-        let query = form_urlencoded::Serializer::new(String::new())
-            .append_pair("term", term)
-            .finish();
+        self.search_with(&SearchOptions::builder(term).build())
+            .await
+    }
+
+    /// Search for docker images with the term, limit, filters and credentials of `opts`
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageSearch)
+    pub async fn search_with(
+        &self,
+        opts: &SearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        let mut path = vec!["/images/search".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
+        self.docker
+            .request_json(
+                Method::GET,
+                &path.join("?"),
+                None,
+                None::<(Body, Mime)>,
+                opts.auth_header().map(|auth| ("X-Registry-Auth", auth)),
+            )
+            .await
+    }
+
+    /// Resolves the image `name` to the manifest in its registry without pulling it, with
+    /// `auth` if it is given
+    ///
+    /// The descriptor tells the digest and size of the manifest, and the platforms tell which
+    /// platforms the image is available for.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/DistributionInspect)
+    pub async fn distribution_inspect(
+        &self,
+        name: &str,
+        auth: Option<&RegistryAuth>,
+    ) -> Result<DistributionInspect> {
         self.docker
-            .get_json::<Vec<SearchResult>>(&format!("/images/search?{}", query)[..])
+            .request_json(
+                Method::GET,
+                &format!("/distribution/{}/json", name),
+                None,
+                None::<(Body, Mime)>,
+                auth.map(|auth| ("X-Registry-Auth", auth.serialize())),
+            )
             .await
     }
 
     /// Pull and create a new docker images from an existing image
     ///
+    /// If the stream fails after the pull started, the error is [Error::Interrupted] with the
+    /// bytes downloaded and the last layer the daemon reported.
+    ///
+    /// With a [verifier](PullOptionsBuilder::verify), the reference is first resolved to the
+    /// digest of its manifest, which the verifier has to accept. The image is then pulled by
+    /// that digest, so that the registry can't serve anything else, and tagged once the pull
+    /// succeeded. A rejected image fails the stream with [Error::PullRejected].
+    ///
+    /// With a pinned [digest](PullOptionsBuilder::digest), the pulled image is inspected once
+    /// the pull succeeded, and the stream fails with [Error::DigestMismatch] unless the image
+    /// has that digest.
+    ///
+    /// Pulls the registry refuses fail with [Error::ManifestNotFound], [Error::Unauthorized] or
+    /// [Error::RateLimited]. Other failures the daemon reports are passed on as
+    /// [ImageBuildChunk::Error].
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePull)
     pub fn pull(
         &self,
         opts: &PullOptions,
     ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
-        let mut path = vec!["/images/create".to_owned()];
-        if let Some(query) = opts.serialize() {
-            path.push(query);
-        }
+        let query = opts.serialize();
         let headers = opts
             .auth_header()
             .map(|a| iter::once(("X-Registry-Auth", a)));
 
-        Box::pin(self.docker.stream_post_into(path.join("?"), None, headers))
+        let docker = self.docker;
+        let pull = match (&opts.verifier, opts.reference()) {
+            (Some(verifier), Some(reference)) => Either::Right(Box::pin(self.pull_verified(
+                opts,
+                reference,
+                verifier.clone(),
+            ))),
+            _ => Either::Left(Box::pin(errors::track_progress(
+                future::ready(query)
+                    .map_ok(move |query| {
+                        let mut path = vec!["/images/create".to_owned()];
+                        path.extend(query);
+                        docker.stream_post_into(path.join("?"), None, headers)
+                    })
+                    .try_flatten_stream(),
+                ImageBuildChunk::record_progress,
+            ))),
+        };
+        let pull = match (&opts.digest, opts.reference()) {
+            (Some(digest), Some(reference)) => Either::Right(Box::pin(self.check_digest(
+                pull,
+                reference.repo,
+                digest.clone(),
+            ))),
+            _ => Either::Left(pull),
+        };
+
+        let image = opts
+            .reference()
+            .map(|reference| reference.to_string())
+            .unwrap_or_default();
+        pull.map(move |chunk| match chunk {
+            Ok(ImageBuildChunk::Error {
+                error,
+                error_detail,
+            }) => match pull_error(&image, &error) {
+                Some(e) => Err(e),
+                None => Ok(ImageBuildChunk::Error {
+                    error,
+                    error_detail,
+                }),
+            },
+            Err(e) => Err(typed_pull_error(&image, e)),
+            chunk => chunk,
+        })
     }
 
-    pub async fn push(&self, image : &str, push_options : &PushOptions) -> Result<()> {
-        let mut path = vec![format!("/images/{}/push", image)];
-        if let Some(query) = push_options.serialize() {
-            path.push(query)
-        }
+    /// Passes on the chunks of `pull`, then fails with [Error::DigestMismatch] unless the pull
+    /// succeeded and the image `repo@digest` has `digest` among its repository digests
+    fn check_digest<S>(
+        &self,
+        pull: S,
+        repo: String,
+        digest: String,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + 'docker
+    where
+        S: Stream<Item = Result<ImageBuildChunk>> + 'docker,
+    {
+        let docker = self.docker;
+        let failed = Arc::new(AtomicBool::new(false));
+        let (failed_chunk, failed_stream, pull_failed) = (failed.clone(), failed.clone(), failed);
 
-        let headers = push_options
-            .auth_header()
-            .map(|a| iter::once(("X-Registry-Auth", a)));
+        let pull = pull
+            .inspect_ok(move |chunk| {
+                if let ImageBuildChunk::Error { .. } = chunk {
+                    failed_chunk.store(true, Ordering::Relaxed);
+                }
+            })
+            .inspect_err(move |_| failed_stream.store(true, Ordering::Relaxed));
+        let check = futures_util::stream::once(async move {
+            if pull_failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let image = format!("{}@{}", repo, digest);
+            let repo_digests = match Images::new(docker).get(&image).inspect().await {
+                Ok(details) => details.repo_digests.unwrap_or_default(),
+                Err(e) if e.is_not_found() => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            // the daemon names repositories of Docker Hub in their short form, like `alpine`
+            let pinned = split_repository(&repo);
+            if repo_digests.iter().any(|repo_digest| {
+                matches!(
+                    repo_digest.split_once('@'),
+                    Some((name, found)) if found == digest && split_repository(name) == pinned
+                )
+            }) {
+                Ok(())
+            } else {
+                Err(Error::DigestMismatch {
+                    image,
+                    digest,
+                    repo_digests,
+                })
+            }
+        })
+        .try_filter_map(|()| async { Ok(None) });
+
+        pull.chain(check)
+    }
+
+    /// Pulls an image like [Images::pull], cancelling the pull once the compressed size of its
+    /// layers exceeds `max_bytes`
+    ///
+    /// The size of each layer is known once the daemon starts downloading it. When the sizes
+    /// reported so far add up to more than `max_bytes`, the stream fails with
+    /// [Error::PullTooLarge] and closes the connection to the daemon, which cancels the pull.
+    /// Layers that exist locally count towards the limit only if the daemon reports their size.
+    pub fn pull_with_limit(
+        &self,
+        opts: &PullOptions,
+        max_bytes: u64,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
+        let image = opts
+            .reference()
+            .map(|reference| reference.to_string())
+            .unwrap_or_default();
+        let pull = self.pull(opts);
+        Box::pin(futures_util::stream::unfold(
+            (Some(pull), LayerTally::default()),
+            move |(pull, mut layers)| {
+                let image = image.clone();
+                async move {
+                    let mut pull = pull?;
+                    let chunk = pull.next().await?;
+                    if let Ok(chunk) = &chunk {
+                        layers.record(chunk);
+                    }
+                    let size = layers.size();
+                    if size > max_bytes {
+                        // dropping the pull closes the connection, which cancels it
+                        let exceeded = Error::PullTooLarge {
+                            image,
+                            limit: max_bytes,
+                            size,
+                        };
+                        return Some((Err(exceeded), (None, layers)));
+                    }
+                    Some((chunk, (Some(pull), layers)))
+                }
+            },
+        ))
+    }
+
+    /// Pulls an image like [Images::pull], reporting its progress as [ProgressEvent]s
+    ///
+    /// The stream fails with [Error::StreamFault] when the daemon reports that the pull failed.
+    pub fn pull_events(
+        &self,
+        opts: &PullOptions,
+    ) -> impl Stream<Item = Result<ProgressEvent>> + Unpin + 'docker {
+        let stream = self.pull(opts);
+        future::ready(opts.serialize())
+            .map_ok(move |query| {
+                let mut path = vec!["/images/create".to_owned()];
+                path.extend(query);
+                ProgressReporter::new().events(stream, path.join("?"))
+            })
+            .try_flatten_stream()
+    }
 
-        let res = self.docker.post_with_headers(&path.join("?"), None, headers).await?;
-        let lines = res.split("\r\n");
-        for line in lines {
-            if line.contains("errorDetail") {
-                return Err(Error::InvalidResponse(line.to_string()))
+    /// Pulls the image `reference` of `opts` by the digest it resolves to, if `verifier` accepts
+    /// the manifest of the digest
+    fn pull_verified(
+        &self,
+        opts: &PullOptions,
+        reference: ImageReference,
+        verifier: PullVerifier,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + 'docker {
+        let docker = self.docker;
+        let opts = opts.clone();
+        let auth = opts.auth_header();
+        let headers = move || auth.clone().map(|a| ("X-Registry-Auth", a));
+        let failed = Arc::new(AtomicBool::new(false));
+        let (failed_chunk, failed_stream, pull_failed) = (failed.clone(), failed.clone(), failed);
+
+        let tag_reference = reference.clone();
+        async move {
+            let distribution = Images::new(docker)
+                .distribution_inspect(&reference.to_string(), opts.auth.as_ref())
+                .await?;
+            let digest = distribution.descriptor.digest.clone();
+            if let Err(reason) = (verifier.0)(distribution).await {
+                return Err(Error::PullRejected {
+                    image: reference.to_string(),
+                    digest,
+                    reason,
+                });
             }
+
+            // the other options of the caller, like the platform, apply to the pull by digest
+            let verified = PullOptions {
+                from_image: Some(reference.repo.clone()),
+                tag: Some(digest.clone()),
+                ..opts
+            };
+            let path = format!(
+                "/images/create?{}",
+                verified.serialize()?.unwrap_or_default()
+            );
+            let pull = errors::track_progress(
+                docker.stream_post_into(path, None, Some(headers())),
+                ImageBuildChunk::record_progress,
+            )
+            .inspect_ok(move |chunk| {
+                if let ImageBuildChunk::Error { .. } = chunk {
+                    failed_chunk.store(true, Ordering::Relaxed);
+                }
+            })
+            .inspect_err(move |_| failed_stream.store(true, Ordering::Relaxed));
+
+            // the pull by digest leaves the image untagged
+            let tag = futures_util::stream::once(async move {
+                match tag_reference.tag {
+                    Some(ref tag) if !pull_failed.load(Ordering::Relaxed) => {
+                        let opts = TagOptions::builder()
+                            .repo(&tag_reference.repo)
+                            .tag(tag)
+                            .build();
+                        Images::new(docker)
+                            .get(format!("{}@{}", tag_reference.repo, digest))
+                            .tag(&opts)
+                            .await
+                            .map(|_| ())
+                    }
+                    _ => Ok(()),
+                }
+            })
+            .try_filter_map(|()| async { Ok(None) });
+
+            Ok(pull.chain(tag))
         }
-        Ok(())
+        .try_flatten_stream()
     }
-    
-    /// exports a collection of named images,
-    /// either by name, name:tag, or image id, into a tarball
+
+    /// Makes the image `reference` available locally, pulling it as `policy` requires
     ///
-    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageGetAll)
-    pub fn export(
+    /// The image is pulled with `auth` if it is given. A reference without a tag or digest
+    /// refers to its `latest` tag. With [PullPolicy::Never], a missing image fails with
+    /// [Error::NotFound].
+    pub async fn ensure(
         &self,
-        names: Vec<&str>,
-    ) -> impl Stream<Item = Result<Vec<u8>>> + 'docker {
-        let params = names.iter().map(|n| ("names", *n));
-        let query = form_urlencoded::Serializer::new(String::new())
-            .extend_pairs(params)
-            .finish();
-        self.docker
-            .stream_get(format!("/images/get?{}", query))
-            .map_ok(|c| c.to_vec())
+        reference: &str,
+        policy: PullPolicy,
+        auth: Option<RegistryAuth>,
+    ) -> Result<EnsuredImage> {
+        let image = self.get(reference);
+        let local = match policy {
+            PullPolicy::Always => None,
+            PullPolicy::IfNotPresent | PullPolicy::Never => match image.inspect().await {
+                Ok(details) => Some(details),
+                Err(e) if e.is_not_found() && policy == PullPolicy::IfNotPresent => None,
+                Err(e) => return Err(e),
+            },
+        };
+
+        let reference = ImageReference::parse(reference);
+        let (details, pulled) = match local {
+            Some(details) => (details, false),
+            None => {
+                self.pull_reference(&reference, auth, |_| true).await?;
+                (image.inspect().await?, true)
+            }
+        };
+
+        // the daemon names repositories of Docker Hub in their short form, like `alpine`
+        let repo = split_repository(&reference.repo);
+        let digest = details.repo_digests.and_then(|digests| {
+            digests.into_iter().find(|digest| {
+                matches!(
+                    digest.split_once('@'),
+                    Some((name, _)) if split_repository(name) == repo
+                )
+            })
+        });
+        Ok(EnsuredImage {
+            id: details.id,
+            digest,
+            pulled,
+        })
     }
 
-    /// imports an image or set of images from a given tarball source
-    /// source can be uncompressed on compressed via gzip, bzip2 or xz
+    /// Resolves the image `reference` to the image of `platform`, like `linux/arm64`, without
+    /// pulling it, so that exactly that image can be pulled
     ///
-    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
-    pub fn import<R>(
-        self,
-        mut tarball: R,
-    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    /// The manifest of the reference and its platforms are looked up through the daemon, with
+    /// `auth` if it is given, so that the registry is reached through the mirrors, insecure
+    /// registries and proxy the daemon is configured with. A platform that names no variant
+    /// matches any variant. If the image is not available for `platform`, this fails with
+    /// [Error::PlatformNotAvailable].
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/DistributionInspect)
+    pub async fn resolve_platform(
+        &self,
+        reference: &str,
+        platform: &str,
+        auth: Option<RegistryAuth>,
+    ) -> Result<PlatformImage> {
+        let wanted = Platform::parse(platform);
+        let distribution = self
+            .distribution_inspect(&ImageReference::parse(reference).to_string(), auth.as_ref())
+            .await?;
+        let platforms = distribution.platforms.unwrap_or_default();
+        let platform = match platforms.iter().find(|platform| platform.matches(&wanted)) {
+            Some(platform) => platform.clone(),
+            None => {
+                return Err(Error::PlatformNotAvailable {
+                    image: reference.to_owned(),
+                    platform: wanted.to_string(),
+                    available: platforms.iter().map(Platform::to_string).collect(),
+                })
+            }
+        };
+        Ok(PlatformImage {
+            digest: distribution.descriptor.digest,
+            size: distribution.descriptor.size,
+            media_type: distribution.descriptor.media_type,
+            platform,
+        })
+    }
+
+    /// Pulls `reference` to the end, while `proceed` accepts the chunks of the pull. Returns
+    /// whether the pull completed, and fails with the error the daemon reports in a chunk.
+    async fn pull_reference<F>(
+        &self,
+        reference: &ImageReference,
+        auth: Option<RegistryAuth>,
+        mut proceed: F,
+    ) -> Result<bool>
     where
-        R: Read + Send + 'docker,
+        F: FnMut(&ImageBuildChunk) -> bool,
     {
-        Box::pin(
-            async move {
-                let mut bytes = Vec::default();
-
-                tarball.read_to_end(&mut bytes)?;
+        let mut opts = PullOptions::builder().image(&reference.repo);
+        if let Some(tag) = reference.digest.as_ref().or(reference.tag.as_ref()) {
+            opts = opts.tag(tag);
+        }
+        if let Some(auth) = auth {
+            opts = opts.auth(auth);
+        }
+        let opts = opts.build();
+        let path = format!("/images/create?{}", opts.serialize()?.unwrap_or_default());
+        let mut pull = self.pull(&opts);
+        while let Some(chunk) = pull.try_next().await? {
+            if !proceed(&chunk.into_result(&path)?) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
 
-                let value_stream = self.docker.stream_post_into(
-                    "/images/load",
-                    Some((Body::from(bytes), tar())),
-                    None::<iter::Empty<_>>,
-                );
-                Ok(value_stream)
+    /// Pulls the images of `opts`, `concurrency` at a time, and merges the chunks of the pulls
+    /// into one stream as the daemon reports them
+    ///
+    /// Each chunk comes with the image it is for and the progress of all pulls. A failed pull
+    /// ends with its error, while the other pulls go on.
+    pub fn pull_many(
+        &self,
+        opts: Vec<PullOptions>,
+        concurrency: usize,
+    ) -> impl Stream<Item = PullManyChunk> + Unpin + 'docker {
+        let docker = self.docker;
+        let mut progress = PullManyProgress {
+            images: opts.len(),
+            ..PullManyProgress::default()
+        };
+        let pulls = futures_util::stream::iter(opts)
+            .map(move |opts| {
+                let image = opts
+                    .reference()
+                    .map(|reference| reference.to_string())
+                    .unwrap_or_default();
+                // each chunk is held back until the next one, or the end of the pull, shows
+                // whether it is the last of its pull
+                Images::new(docker)
+                    .pull(&opts)
+                    .map(Some)
+                    .chain(futures_util::stream::once(future::ready(None)))
+                    .scan(None, |held, next| {
+                        let last = next.is_none();
+                        let chunk = std::mem::replace(held, next);
+                        future::ready(Some(chunk.map(|chunk| (chunk, last))))
+                    })
+                    .filter_map(future::ready)
+                    .map(move |(chunk, last)| (image.clone(), chunk, last))
+            })
+            .flatten_unordered(concurrency.max(1));
+
+        let mut layers = LayerTally::default();
+        Box::pin(pulls.map(move |(image, chunk, last)| {
+            if let Ok(chunk) = &chunk {
+                layers.record(chunk);
+                progress.downloaded = layers.downloaded();
+                progress.total = layers.size();
             }
-            .try_flatten_stream(),
-        )
+            if last {
+                progress.finished += 1;
+            }
+            PullManyChunk {
+                image,
+                chunk,
+                progress,
+            }
+        }))
     }
 
-    /// Deletes unused images
+    /// Pulls the images of `references` that are missing, `concurrency` at a time, and reports
+    /// what was done for each of them in the order of `references`
     ///
-    /// [Api Reference](https://docs.docker.com/engine/api/v1.42/#tag/Image/operation/ImagePrune)
-    pub async fn prune(
+    /// With `max_total_bytes`, the pulls are limited to download that many bytes together.
+    /// The sizes of layers are known once the daemon starts downloading them, and a pull that
+    /// would exceed the budget is cancelled and reported as [WarmOutcome::OverBudget]. Layers
+    /// that exist already don't count.
+    pub async fn warm_cache<I, S>(
         &self,
-        opts: &PruneOptions,
-    ) -> Result<String> {
-        let mut path = vec!["/images/prune".to_string()];
+        references: I,
+        concurrency: usize,
+        max_total_bytes: Option<u64>,
+    ) -> Vec<WarmReport>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let downloaded = AtomicU64::new(0);
+        let downloaded = &downloaded;
+        futures_util::stream::iter(references)
+            .map(|reference| async move {
+                let reference = reference.into();
+                let outcome = match self.get(&reference).inspect().await {
+                    Ok(_) => WarmOutcome::Present,
+                    Err(e) if e.is_not_found() => {
+                        self.warm(&reference, downloaded, max_total_bytes).await
+                    }
+                    Err(e) => WarmOutcome::Failed(e),
+                };
+                WarmReport { reference, outcome }
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
 
-        if let Ok(Some(query)) = opts.serialize() {
-            path.push(query)
+    async fn warm(
+        &self,
+        reference: &str,
+        downloaded: &AtomicU64,
+        max_total_bytes: Option<u64>,
+    ) -> WarmOutcome {
+        let mut layers = LayerTally::default();
+        let parsed = ImageReference::parse(reference);
+        let pull = self.pull_reference(&parsed, None, |chunk| {
+            let grown = layers.record(chunk);
+            if grown == 0 {
+                return true;
+            }
+            let total = downloaded.fetch_add(grown, Ordering::Relaxed) + grown;
+            max_total_bytes.map_or(true, |max| total <= max)
+        });
+        let outcome = match pull.await {
+            Ok(true) => match self.get(reference).inspect().await {
+                Ok(details) => WarmOutcome::Pulled { size: details.size },
+                Err(e) => WarmOutcome::Failed(e),
+            },
+            Ok(false) => WarmOutcome::OverBudget,
+            Err(e) => WarmOutcome::Failed(e),
+        };
+        // the layers of a pull that did not complete are not downloaded anymore
+        if !matches!(outcome, WarmOutcome::Pulled { .. }) {
+            downloaded.fetch_sub(layers.size(), Ordering::Relaxed);
+        }
+        outcome
+    }
+
+    /// Pushes `image` to its registry and streams the progress the daemon reports for each
+    /// layer, ending with the digest of the pushed manifest as [ImageBuildChunk::Pushed]
+    ///
+    /// Pushes the registry refuses fail with [Error::Unauthorized] or [Error::RateLimited].
+    /// Other failures the daemon reports are passed on as [ImageBuildChunk::Error].
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePush)
+    pub fn push(
+        &self,
+        image: &str,
+        push_options: &PushOptions,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
+        let image = image.to_owned();
+        let messages = self.push_messages(push_path(&image, push_options), push_options);
+        // messages the daemon adds in newer versions are no chunks
+        Box::pin(
+            messages
+                .try_filter_map(|message| future::ready(Ok(serde_json::from_value(message).ok())))
+                .map(move |chunk| match chunk {
+                    Ok(ImageBuildChunk::Error {
+                        error,
+                        error_detail,
+                    }) => match pull_error(&image, &error) {
+                        Some(e) => Err(e),
+                        None => Ok(ImageBuildChunk::Error {
+                            error,
+                            error_detail,
+                        }),
+                    },
+                    chunk => chunk,
+                }),
+        )
+    }
+
+    /// Pushes `image` like [push](Self::push), but only returns once the push finished. The
+    /// push fails with [Error::StreamFault] if the daemon reports an error.
+    pub async fn push_and_wait(
+        &self,
+        image: &str,
+        push_options: &PushOptions,
+    ) -> Result<()> {
+        let path = push_path(image, push_options)?;
+        let mut chunks = self.push(image, push_options);
+        while let Some(chunk) = chunks.try_next().await? {
+            chunk.into_result(&path)?;
+        }
+        Ok(())
+    }
+
+    /// The messages the daemon reports for the push at `path`
+    fn push_messages(
+        &self,
+        path: Result<String>,
+        opts: &PushOptions,
+    ) -> impl Stream<Item = Result<serde_json::Value>> + 'docker {
+        let headers = opts
+            .auth_header()
+            .map(|a| iter::once(("X-Registry-Auth", a)));
+        let docker = self.docker;
+        future::ready(path)
+            .map_ok(move |path| docker.stream_post_into(path, None, headers))
+            .try_flatten_stream()
+    }
+
+    /// Pushes `image` to each of `targets`, a reference like `registry.example.com/app:1.0`
+    /// with the authentication for its registry, `concurrency` at a time, and reports the
+    /// outcome for each of them in the order of `targets`
+    ///
+    /// `image` is tagged as each target for its push. Once the push finished, whether it
+    /// succeeded or not, the tag is removed again if the target did not exist locally, or moved
+    /// back to the image it referred to before. A target without a tag refers to its `latest`
+    /// tag. `progress` is called with the target and each chunk of
+    /// progress the daemon reports for its push.
+    pub async fn push_all<F>(
+        &self,
+        image: &str,
+        targets: Vec<(String, RegistryAuth)>,
+        concurrency: usize,
+        progress: F,
+    ) -> Vec<PushReport>
+    where
+        F: Fn(&str, &ImageBuildChunk) + Sync,
+    {
+        let progress = &progress;
+        futures_util::stream::iter(targets)
+            .map(|(reference, auth)| async move {
+                let outcome = self.push_target(image, &reference, auth, progress).await;
+                PushReport { reference, outcome }
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn push_target<F>(
+        &self,
+        image: &str,
+        target: &str,
+        auth: RegistryAuth,
+        progress: &F,
+    ) -> Result<PushedImage>
+    where
+        F: Fn(&str, &ImageBuildChunk),
+    {
+        let reference = ImageReference::parse(target);
+        let tag = reference.tag.clone().unwrap_or_else(|| "latest".to_owned());
+        let target = format!("{}:{}", reference.repo, tag);
+        // the image the target refers to already, which gets the target back after the push
+        let previous = match self.get(&target).inspect().await {
+            Ok(details) => Some(details.id),
+            Err(e) if e.is_not_found() => None,
+            Err(e) => return Err(e),
+        };
+        let id = self.get(image).inspect().await?.id;
+        let tag_opts = TagOptions::builder()
+            .repo(&reference.repo)
+            .tag(&tag)
+            .build();
+        if previous.as_ref() != Some(&id) {
+            self.get(image).tag(&tag_opts).await?;
+        }
+
+        let opts = PushOptions::builder().tag(tag.clone()).auth(auth).build();
+        let pushed = self
+            .push_progress(&reference.repo, &opts, |chunk| progress(&target, chunk))
+            .await;
+        let restored = match previous {
+            Some(previous) if previous == id => Ok(()),
+            Some(previous) => self.get(previous.as_ref()).tag(&tag_opts).await.map(|_| ()),
+            None => self.get(&target).delete().await.map(|_| ()),
+        };
+        // a failed push is the more useful error to report
+        match (pushed, restored) {
+            (Ok(_), Err(e)) => Err(e),
+            (pushed, _) => pushed,
+        }
+    }
+
+    /// Pushes `repo` like [push](Self::push), but passes the progress to `progress` as the
+    /// daemon reports it and returns the digest of the pushed manifest
+    async fn push_progress<F>(
+        &self,
+        repo: &str,
+        opts: &PushOptions,
+        mut progress: F,
+    ) -> Result<PushedImage>
+    where
+        F: FnMut(&ImageBuildChunk),
+    {
+        let mut pushed = PushedImage {
+            digest: None,
+            size: None,
+        };
+        let path = push_path(repo, opts)?;
+        let mut chunks = self.push(repo, opts);
+        while let Some(chunk) = chunks.try_next().await? {
+            match chunk.into_result(&path)? {
+                ImageBuildChunk::Pushed { aux } => pushed = aux,
+                chunk => progress(&chunk),
+            }
+        }
+        Ok(pushed)
+    }
+
+    /// exports a collection of named images,
+    /// either by name, name:tag, or image id, into a tarball
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageGetAll)
+    pub fn export(
+        &self,
+        names: Vec<&str>,
+    ) -> impl Stream<Item = Result<Vec<u8>>> + 'docker {
+        let params = names.iter().map(|n| ("names", *n));
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        errors::track_progress(
+            self.docker
+                .stream_get(format!("/images/get?{}", query))
+                .map_ok(|c| c.to_vec()),
+            |progress, chunk| progress.record_bytes(chunk.len()),
+        )
+    }
+
+    /// Copies the images `names` to the daemon `other`, uploading the archive exported by this
+    /// daemon to `other` as it is downloaded, without holding it in memory or on disk
+    ///
+    /// Returns the size and the SHA-256 digest of the transferred archive with the images
+    /// `other` loaded. A failed export fails the copy with its error, even if `other` already
+    /// loaded part of the archive.
+    pub async fn copy_to(
+        &self,
+        other: &Docker,
+        names: Vec<&str>,
+    ) -> Result<ImageCopy> {
+        let (tx, mut rx) = mpsc::channel(COPY_CHUNKS_AHEAD);
+        let (body, _) = upload_body(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)));
+
+        let mut exported = Box::pin(self.export(names));
+        let export = async move {
+            let (mut size, mut hasher) = (0, Sha256::new());
+            while let Some(chunk) = exported.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        size += chunk.len() as u64;
+                        hasher.update(&chunk);
+                        // the import failed if it stopped reading, which is its error to report
+                        if tx.send(Ok(Bytes::from(chunk))).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let aborted = io::Error::new(io::ErrorKind::Other, e.to_string());
+                        let _ = tx.send(Err(aborted)).await;
+                        return Err(e);
+                    }
+                }
+            }
+            Ok((size, format!("sha256:{:x}", hasher.finalize())))
+        };
+
+        let path = "/images/load";
+        let import = async move {
+            let mut import = ImportStream {
+                chunks: Box::pin(errors::track_progress(
+                    other.stream_post_into(path, Some((body, tar())), None::<iter::Empty<_>>),
+                    ImageBuildChunk::record_progress,
+                )),
+                loaded: LoadedImage::default(),
+            };
+            while let Some(chunk) = import.try_next().await? {
+                chunk.into_result(path)?;
+            }
+            Ok::<_, Error>(import.loaded)
+        };
+
+        let (exported, loaded) = future::join(export, import).await;
+        let (size, digest) = exported?;
+        Ok(ImageCopy {
+            size,
+            digest,
+            loaded: loaded?,
+        })
+    }
+
+    /// imports an image or set of images from a given tarball source
+    /// source can be uncompressed on compressed via gzip, bzip2 or xz
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub fn import<R>(
+        self,
+        tarball: R,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        R: Read + Send + 'docker,
+    {
+        self.import_with(tarball, Ok("/images/load".to_owned()), None)
+    }
+
+    /// Imports images from a tarball like [import](Self::import), uploading it at most at the
+    /// rate of `limit`
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub fn import_rate_limited<R>(
+        self,
+        tarball: R,
+        limit: RateLimit,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        R: Read + Send + 'docker,
+    {
+        self.import_with(tarball, Ok("/images/load".to_owned()), Some(limit))
+    }
+
+    /// Imports images from a tarball like [import](Self::import), but uploads it as it is read
+    /// from `reader` instead of reading all of it into memory first. An error reading the
+    /// tarball fails the stream with that error.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub fn import_from_async_read<R>(
+        self,
+        reader: R,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let (body, read_error) = upload_body(read_chunks(reader));
+        let docker = self.docker;
+        Box::pin(errors::track_progress(
+            docker
+                .stream_post_into("/images/load", Some((body, tar())), None::<iter::Empty<_>>)
+                .map_err(move |e| read_error.take(e)),
+            ImageBuildChunk::record_progress,
+        ))
+    }
+
+    /// Imports images from a tarball like [import](Self::import) with `opts`. The returned
+    /// stream also collects the images the daemon reports as loaded.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub fn import_with_options<R>(
+        self,
+        tarball: R,
+        opts: &ImportOptions,
+    ) -> ImportStream<'docker>
+    where
+        R: Read + Send + 'docker,
+    {
+        let path = opts.serialize().map(|query| {
+            let mut path = vec!["/images/load".to_owned()];
+            path.extend(query);
+            path.join("?")
+        });
+        ImportStream {
+            chunks: Box::pin(self.import_with(tarball, path, None)),
+            loaded: LoadedImage::default(),
+        }
+    }
+
+    fn import_with<R>(
+        self,
+        mut tarball: R,
+        path: Result<String>,
+        limit: Option<RateLimit>,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        R: Read + Send + 'docker,
+    {
+        Box::pin(errors::track_progress(
+            async move {
+                let path = path?;
+                let mut bytes = Vec::default();
+
+                tarball.read_to_end(&mut bytes)?;
+
+                let body = match limit {
+                    Some(limit) => limit.body(Body::from(bytes)),
+                    None => Body::from(bytes),
+                };
+                let value_stream =
+                    self.docker
+                        .stream_post_into(path, Some((body, tar())), None::<iter::Empty<_>>);
+                Ok(value_stream)
+            }
+            .try_flatten_stream(),
+            ImageBuildChunk::record_progress,
+        ))
+    }
+
+    /// Deletes unused images
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.42/#tag/Image/operation/ImagePrune)
+    pub async fn prune(
+        &self,
+        opts: &PruneOptions,
+    ) -> Result<PruneInfo> {
+        let mut path = vec!["/images/prune".to_string()];
+
+        if let Ok(Some(query)) = opts.serialize() {
+            path.push(query)
+        }
+
+        self.docker
+            .post_json(&path.join("?"), None::<(Body, Mime)>)
+            .await
+    }
+}
+
+/// The progress of an import, which collects the images the daemon reports as loaded
+///
+/// Returned by [Images::import_with_options].
+pub struct ImportStream<'docker> {
+    chunks: Pin<Box<dyn Stream<Item = Result<ImageBuildChunk>> + Send + 'docker>>,
+    loaded: LoadedImage,
+}
+
+impl<'docker> ImportStream<'docker> {
+    /// The images loaded so far, which are all loaded images once the stream ended
+    pub fn loaded(&self) -> &LoadedImage {
+        &self.loaded
+    }
+
+    /// Drives the import to its end and returns the loaded images
+    pub async fn finish(mut self) -> Result<LoadedImage> {
+        while self.try_next().await?.is_some() {}
+        Ok(self.loaded)
+    }
+}
+
+impl<'docker> Stream for ImportStream<'docker> {
+    type Item = Result<ImageBuildChunk>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let chunk = self.chunks.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(ImageBuildChunk::Update { stream }))) = &chunk {
+            self.loaded.record(stream);
+        }
+        chunk
+    }
+}
+
+/// The image [Images::build_and_wait] built
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuiltImage {
+    pub id: ImageId,
+    /// The output of the build steps, like `Step 1/2 : FROM alpine\n`
+    pub logs: Vec<String>,
+}
+
+/// A chunk of one of the pulls of [Images::pull_many]
+#[derive(Debug)]
+pub struct PullManyChunk {
+    /// The image the chunk is for, like `alpine:3.14`
+    pub image: String,
+    pub chunk: Result<ImageBuildChunk>,
+    /// The progress of all pulls when the chunk arrived
+    pub progress: PullManyProgress,
+}
+
+/// The progress of all pulls of [Images::pull_many]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PullManyProgress {
+    /// Bytes downloaded of the layers whose download started, counting layers that several
+    /// images share once
+    pub downloaded: u64,
+    /// Size of the layers whose download started
+    pub total: u64,
+    /// Number of pulls that ended with or before the chunk, whether they succeeded or not, so
+    /// that the last chunk reports all pulls finished
+    pub finished: usize,
+    /// Number of pulls
+    pub images: usize,
+}
+
+/// The images [Images::copy_to] copied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageCopy {
+    /// Size of the transferred archive in bytes
+    pub size: u64,
+    /// SHA-256 digest of the transferred archive, like `sha256:4f5e...`
+    pub digest: String,
+    /// The images the receiving daemon loaded
+    pub loaded: LoadedImage,
+}
+
+/// The images an import loaded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadedImage {
+    /// Tags of the loaded images, like `alpine:3.14`
+    pub tags: Vec<String>,
+    /// IDs of loaded images without a tag
+    pub ids: Vec<String>,
+}
+
+impl LoadedImage {
+    /// Records the image the daemon reports as loaded in the `message` of a chunk
+    fn record(
+        &mut self,
+        message: &str,
+    ) {
+        let message = message.trim_end();
+        if let Some(id) = message.strip_prefix("Loaded image ID: ") {
+            self.ids.push(id.to_owned());
+        } else if let Some(tag) = message.strip_prefix("Loaded image: ") {
+            self.tags.push(tag.to_owned());
         }
-
-        self.docker.post(&path.join("?"), None).await
     }
 }
 
@@ -371,13 +1686,72 @@ impl RegistryAuth {
 
     /// serialize authentication as JSON in base64
     pub fn serialize(&self) -> String {
-        serde_json::to_string(self)
-            .map(|c| base64::encode_config(&c, base64::URL_SAFE))
-            .unwrap()
+        let fields = match self {
+            RegistryAuth::Password {
+                username,
+                password,
+                email,
+                server_address,
+            } => {
+                let mut fields = vec![("username", username), ("password", password)];
+                fields.extend(email.as_ref().map(|email| ("email", email)));
+                fields.extend(
+                    server_address
+                        .as_ref()
+                        .map(|address| ("serveraddress", address)),
+                );
+                fields
+            }
+            RegistryAuth::Token { identity_token } => vec![("identitytoken", identity_token)],
+        };
+        // assembled from JSON strings, which unlike serializing the enum can't fail
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|(name, value)| {
+                format!("\"{}\":{}", name, serde_json::Value::from(value.as_str()))
+            })
+            .collect();
+        let json = format!("{{{}}}", fields.join(","));
+        base64::encode_config(&json, base64::URL_SAFE)
+    }
+
+    /// Logs in to `registry` through the daemon, like `docker login`, and returns the
+    /// authentication to use with it: the identity token if the registry returned one, or else
+    /// the username and password. Invalid credentials fail with [Error::Fault]. Use
+    /// [credentials::store](crate::credentials::store) to keep the credentials for the docker
+    /// CLI.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/SystemAuth)
+    pub async fn login(
+        docker: &Docker,
+        registry: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<RegistryAuth> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Response {
+            identity_token: Option<String>,
+        }
+
+        let auth = RegistryAuth::builder()
+            .username(username)
+            .password(password)
+            .server_address(registry)
+            .build();
+        let body = serde_json::to_string(&auth)?;
+        let response: Response = docker
+            .post_json("/auth", Some((body, mime::APPLICATION_JSON)))
+            .await?;
+        Ok(match response.identity_token {
+            Some(token) if !token.is_empty() => RegistryAuth::token(token),
+            _ => auth,
+        })
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct RegistryAuthBuilder {
     username: Option<String>,
     password: Option<String>,
@@ -387,9 +1761,9 @@ pub struct RegistryAuthBuilder {
 
 impl RegistryAuthBuilder {
     pub fn username<I>(
-        &mut self,
+        mut self,
         username: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: Into<String>,
     {
@@ -398,9 +1772,9 @@ impl RegistryAuthBuilder {
     }
 
     pub fn password<I>(
-        &mut self,
+        mut self,
         password: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: Into<String>,
     {
@@ -409,9 +1783,9 @@ impl RegistryAuthBuilder {
     }
 
     pub fn email<I>(
-        &mut self,
+        mut self,
         email: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: Into<String>,
     {
@@ -420,9 +1794,9 @@ impl RegistryAuthBuilder {
     }
 
     pub fn server_address<I>(
-        &mut self,
+        mut self,
         server_address: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: Into<String>,
     {
@@ -430,19 +1804,20 @@ impl RegistryAuthBuilder {
         self
     }
 
-    pub fn build(&self) -> RegistryAuth {
+    pub fn build(self) -> RegistryAuth {
         RegistryAuth::Password {
-            username: self.username.clone().unwrap_or_else(String::new),
-            password: self.password.clone().unwrap_or_else(String::new),
-            email: self.email.clone(),
-            server_address: self.server_address.clone(),
+            username: self.username.unwrap_or_default(),
+            password: self.password.unwrap_or_default(),
+            email: self.email,
+            server_address: self.server_address,
         }
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct TagOptions {
-    pub params: HashMap<&'static str, String>,
+    repo: Option<String>,
+    tag: Option<String>,
 }
 
 impl TagOptions {
@@ -452,58 +1827,110 @@ impl TagOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+
+    /// The `repo:tag` reference these options tag an image as
+    fn reference(&self) -> String {
+        format!(
+            "{}:{}",
+            self.repo.as_deref().unwrap_or_default(),
+            self.tag.as_deref().unwrap_or("latest")
+        )
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct TagOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: TagOptions,
 }
 
 impl TagOptionsBuilder {
     pub fn repo<R>(
-        &mut self,
+        mut self,
         r: R,
-    ) -> &mut Self
+    ) -> Self
     where
         R: Into<String>,
     {
-        self.params.insert("repo", r.into());
+        self.options.repo = Some(r.into());
         self
     }
 
     pub fn tag<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
+    ) -> Self
     where
         T: Into<String>,
     {
-        self.params.insert("tag", t.into());
+        self.options.tag = Some(t.into());
         self
     }
 
-    pub fn build(&self) -> TagOptions {
-        TagOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> TagOptions {
+        self.options
     }
 }
 
-#[derive(Default, Debug)]
+/// Options for importing images, see [Images::import_with_options]
+#[derive(Default, Debug, Serialize)]
+pub struct ImportOptions {
+    quiet: Option<bool>,
+}
+
+impl ImportOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> ImportOptionsBuilder {
+        ImportOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Builder interface for `ImportOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct ImportOptionsBuilder {
+    options: ImportOptions,
+}
+
+impl ImportOptionsBuilder {
+    /// Suppresses the progress of loading the layers, so that only the loaded images are
+    /// reported
+    pub fn quiet(
+        mut self,
+        quiet: bool,
+    ) -> Self {
+        self.options.quiet = Some(quiet);
+        self
+    }
+
+    pub fn build(self) -> ImportOptions {
+        self.options
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize)]
 pub struct PullOptions {
+    #[serde(skip)]
     auth: Option<RegistryAuth>,
-    params: HashMap<&'static str, String>,
+    #[serde(rename = "fromImage")]
+    from_image: Option<String>,
+    #[serde(rename = "fromSrc")]
+    from_src: Option<String>,
+    repo: Option<String>,
+    tag: Option<String>,
+    #[serde(skip)]
+    verifier: Option<PullVerifier>,
+    #[serde(skip)]
+    digest: Option<String>,
+    platform: Option<String>,
 }
 
 impl PullOptions {
@@ -513,62 +1940,244 @@ impl PullOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+    pub fn serialize(&self) -> Result<Option<String>> {
+        match &self.digest {
+            // the daemon takes a digest in place of the tag
+            Some(digest) => query::encode(&PullOptions {
+                tag: Some(digest.clone()),
+                ..self.clone()
+            }),
+            None => query::encode(self),
         }
     }
 
     pub(crate) fn auth_header(&self) -> Option<String> {
         self.auth.clone().map(|a| a.serialize())
     }
+
+    /// The reference of the image to pull. Like the daemon does, the tag option replaces a tag
+    /// in the image name, and may be a digest. A pinned digest replaces both.
+    pub(crate) fn reference(&self) -> Option<ImageReference> {
+        let mut reference = ImageReference::parse(self.from_image.as_deref()?);
+        match self.digest.as_deref().or(self.tag.as_deref()) {
+            Some(digest) if digest.contains(':') => {
+                reference.tag = None;
+                reference.digest = Some(digest.to_owned());
+            }
+            Some(tag) if !tag.is_empty() => reference.tag = Some(tag.to_owned()),
+            _ => (),
+        }
+        if reference.tag.is_none() && reference.digest.is_none() {
+            reference.tag = Some("latest".to_owned());
+        }
+        Some(reference)
+    }
 }
 
-pub struct PullOptionsBuilder {
-    auth: Option<RegistryAuth>,
-    params: HashMap<&'static str, String>,
+/// When [Images::ensure] pulls an image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PullPolicy {
+    /// Pull the image only if there is no local image of the reference
+    #[default]
+    IfNotPresent,
+    /// Always pull the image, to update a local image of the reference
+    Always,
+    /// Never pull the image, failing if there is no local image of the reference
+    Never,
 }
 
-impl Default for PullOptionsBuilder {
-    fn default() -> Self {
-        let mut params = HashMap::new();
-        params.insert("tag", "latest".to_string());
+/// An image made available by [Images::ensure]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnsuredImage {
+    pub id: ImageId,
+    /// The repository digest of the image in the repository of the reference, like
+    /// `alpine@sha256:...`, if it was pulled from or pushed to that repository
+    pub digest: Option<String>,
+    /// Whether the image was pulled
+    pub pulled: bool,
+}
 
-        PullOptionsBuilder { auth: None, params }
-    }
+/// What [Images::warm_cache] did for an image
+#[derive(Debug)]
+pub struct WarmReport {
+    pub reference: String,
+    pub outcome: WarmOutcome,
 }
 
-impl PullOptionsBuilder {
-    ///  Name of the image to pull. The name may include a tag or digest.
-    /// This parameter may only be used when pulling an image.
-    /// If an untagged value is provided and no `tag` is provided, _all_
-    /// tags will be pulled
-    /// The pull is cancelled if the HTTP connection is closed.
-    pub fn image<I>(
-        &mut self,
-        img: I,
-    ) -> &mut Self
-    where
-        I: Into<String>,
-    {
-        self.params.insert("fromImage", img.into());
-        self
-    }
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WarmOutcome {
+    /// The image was present already
+    Present,
+    /// The image was pulled, and takes `size` bytes
+    Pulled {
+        size: u64,
+    },
+    /// The pull was cancelled as it would have exceeded the size budget
+    OverBudget,
+    Failed(Error),
+}
+
+/// What [Images::push_all] did for a target
+#[derive(Debug)]
+pub struct PushReport {
+    pub reference: String,
+    pub outcome: Result<PushedImage>,
+}
+
+/// An image pushed by [Images::push] or [Images::push_all]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", from = "PushAux")]
+#[non_exhaustive]
+pub struct PushedImage {
+    /// The digest of the manifest in the registry, like `sha256:...`, if the daemon reported it
+    pub digest: Option<String>,
+    /// The size of the manifest in bytes, if the daemon reported it
+    pub size: Option<u64>,
+}
+
+/// An image reference, split into the repository and its tag or digest
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ImageReference {
+    repo: String,
+    tag: Option<String>,
+    digest: Option<String>,
+}
+
+impl ImageReference {
+    /// Splits a reference like `registry:5000/app:1.0` or `alpine@sha256:...`
+    pub(crate) fn parse(image: &str) -> Self {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_owned())),
+            None => (image, None),
+        };
+        // a colon before the last slash separates the port of the registry
+        let name_start = name.rfind('/').map_or(0, |i| i + 1);
+        let (repo, tag) = match name[name_start..].split_once(':') {
+            Some((path, tag)) => (&name[..name_start + path.len()], Some(tag.to_owned())),
+            None => (name, None),
+        };
+        ImageReference {
+            repo: repo.to_owned(),
+            tag,
+            digest,
+        }
+    }
+}
+
+impl fmt::Display for ImageReference {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str(&self.repo)?;
+        if let Some(ref digest) = self.digest {
+            write!(f, "@{}", digest)
+        } else if let Some(ref tag) = self.tag {
+            write!(f, ":{}", tag)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The registry of images whose reference does not name one
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Splits the repository of an image reference, like `alpine` or `registry:5000/team/app`, into
+/// the host of its registry and its path in the registry
+fn split_repository(repo: &str) -> (String, String) {
+    match repo.split_once('/') {
+        // like the docker CLI, a first component that looks like a host name names the registry
+        Some((host, path)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            let host = match host {
+                "index.docker.io" => DEFAULT_REGISTRY,
+                host => host,
+            };
+            let path = if path.contains('/') || host != DEFAULT_REGISTRY {
+                path.to_owned()
+            } else {
+                format!("library/{}", path)
+            };
+            (host.to_owned(), path)
+        }
+        Some(_) => (DEFAULT_REGISTRY.to_owned(), repo.to_owned()),
+        None => (DEFAULT_REGISTRY.to_owned(), format!("library/{}", repo)),
+    }
+}
+
+/// Callback deciding whether an image may be pulled, given the manifest its reference resolved
+/// to. It returns the reason for a rejection as the error.
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct PullVerifier(
+    Arc<
+        dyn Fn(DistributionInspect) -> BoxFuture<'static, std::result::Result<(), String>>
+            + Send
+            + Sync,
+    >,
+);
+
+impl PullVerifier {
+    pub fn new<F, Fut>(verifier: F) -> Self
+    where
+        F: Fn(DistributionInspect) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = std::result::Result<(), String>> + Send + 'static,
+    {
+        PullVerifier(Arc::new(move |distribution| verifier(distribution).boxed()))
+    }
+}
+
+impl fmt::Debug for PullVerifier {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str("PullVerifier")
+    }
+}
+
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct PullOptionsBuilder {
+    options: PullOptions,
+}
+
+impl Default for PullOptionsBuilder {
+    fn default() -> Self {
+        PullOptionsBuilder {
+            options: PullOptions {
+                tag: Some("latest".to_owned()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl PullOptionsBuilder {
+    ///  Name of the image to pull. The name may include a tag or digest.
+    /// This parameter may only be used when pulling an image.
+    /// If an untagged value is provided and no `tag` is provided, _all_
+    /// tags will be pulled
+    /// The pull is cancelled if the HTTP connection is closed.
+    pub fn image<I>(
+        mut self,
+        img: I,
+    ) -> Self
+    where
+        I: Into<String>,
+    {
+        self.options.from_image = Some(img.into());
+        self
+    }
 
     pub fn src<S>(
-        &mut self,
+        mut self,
         s: S,
-    ) -> &mut Self
+    ) -> Self
     where
         S: Into<String>,
     {
-        self.params.insert("fromSrc", s.into());
+        self.options.from_src = Some(s.into());
         self
     }
 
@@ -578,42 +2187,78 @@ impl PullOptionsBuilder {
     /// By default a `latest` tag is added when calling
     /// [PullOptionsBuilder::default](PullOptionsBuilder::default].
     pub fn repo<R>(
-        &mut self,
+        mut self,
         r: R,
-    ) -> &mut Self
+    ) -> Self
     where
         R: Into<String>,
     {
-        self.params.insert("repo", r.into());
+        self.options.repo = Some(r.into());
         self
     }
 
     /// Tag or digest. If empty when pulling an image,
     /// this causes all tags for the given image to be pulled.
     pub fn tag<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
+    ) -> Self
     where
         T: Into<String>,
     {
-        self.params.insert("tag", t.into());
+        self.options.tag = Some(t.into());
         self
     }
 
     pub fn auth(
-        &mut self,
+        mut self,
         auth: RegistryAuth,
-    ) -> &mut Self {
-        self.auth = Some(auth);
+    ) -> Self {
+        self.options.auth = Some(auth);
         self
     }
 
-    pub fn build(&mut self) -> PullOptions {
-        PullOptions {
-            auth: self.auth.take(),
-            params: self.params.clone(),
-        }
+    /// Have `verifier` accept the digest of the manifest before the image is pulled, for
+    /// example against an allow-list of digests or a signature verification service. See
+    /// [Images::pull]. Imports from a source are not verified.
+    pub fn verify(
+        mut self,
+        verifier: PullVerifier,
+    ) -> Self {
+        self.options.verifier = Some(verifier);
+        self
+    }
+
+    /// Pulls the image of `platform`, like `linux/arm64` or `linux/arm/v7`, rather than the one of
+    /// the platform of the daemon. The daemon needs emulation to run containers of other
+    /// platforms.
+    pub fn platform<P>(
+        mut self,
+        platform: P,
+    ) -> Self
+    where
+        P: Into<String>,
+    {
+        self.options.platform = Some(platform.into());
+        self
+    }
+
+    /// Pins the pull to the manifest `digest`, like `sha256:...`, in place of a tag. Once the
+    /// pull succeeded, [Images::pull] verifies that the pulled image has this digest. The pin
+    /// takes precedence over a tag, whether it is set before or after it.
+    pub fn digest<D>(
+        mut self,
+        digest: D,
+    ) -> Self
+    where
+        D: Into<String>,
+    {
+        self.options.digest = Some(digest.into());
+        self
+    }
+
+    pub fn build(self) -> PullOptions {
+        self.options
     }
 }
 
@@ -621,11 +2266,9 @@ impl PullOptionsBuilder {
 pub struct BuildOptions {
     path: String,
 
-    params: HashMap<&'static str, String>,
-    // Custom parameter to avoid creating a tar object of the docker
-    // image when you do an image build. Instead work with a normal
-    // archive buffer.
-    skip_gzip: bool,
+    params: BuildParams,
+    // How the build context is packed
+    tarball: TarballOptions,
 }
 
 impl BuildOptions {
@@ -640,34 +2283,27 @@ impl BuildOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        self.params.serialize()
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct BuildOptionsBuilder {
     path: String,
 
     build_params: BuildParams,
 
-    skip_gzip: bool,
+    tarball: TarballOptions,
 }
 
 impl BuildOptionsBuilder {
     /// path is expected to be a file path to a directory containing a Dockerfile
     /// describing how to build a Docker image
     pub(crate) fn new<S>(path: S) -> Self
-        where
-            S: Into<String>,
+    where
+        S: Into<String>,
     {
         BuildOptionsBuilder {
             path: path.into(),
@@ -675,328 +2311,688 @@ impl BuildOptionsBuilder {
         }
     }
 
+    /// Avoid gzipping the build context, sending a plain tar archive instead
     pub fn set_skip_gzip(
-        &mut self,
+        mut self,
         skip_gzip: bool,
-    ) -> &mut Self {
-        self.skip_gzip = skip_gzip;
+    ) -> Self {
+        self.tarball.compression = if skip_gzip {
+            Compression::None
+        } else {
+            Compression::default()
+        };
+        self
+    }
+
+    /// How the build context is compressed, which is gzip at the highest level by default
+    pub fn compression(
+        mut self,
+        compression: Compression,
+    ) -> Self {
+        self.tarball.compression = compression;
+        self
+    }
+
+    /// Whether to exclude the entries matched by the `.dockerignore` file in the build context,
+    /// which is done by default
+    pub fn dockerignore(
+        mut self,
+        dockerignore: bool,
+    ) -> Self {
+        self.tarball.dockerignore = dockerignore;
+        self
+    }
+
+    /// Only send the entries of the build context matching one of `patterns`, which follow the
+    /// `.dockerignore` syntax. The Dockerfile is always sent.
+    pub fn include<I, S>(
+        mut self,
+        patterns: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tarball
+            .include
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Don't send the entries of the build context matching one of `patterns`, which follow the
+    /// `.dockerignore` syntax, in addition to those excluded by the `.dockerignore` file
+    pub fn exclude<I, S>(
+        mut self,
+        patterns: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tarball
+            .exclude
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Fail the build with [Error::ContextTooLarge](crate::Error::ContextTooLarge) instead of
+    /// sending a build context larger than `max_bytes` before compression
+    pub fn max_context_bytes(
+        mut self,
+        max_bytes: u64,
+    ) -> Self {
+        self.tarball.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Adds a file with the given contents to the build context, replacing any file at `path`
+    /// relative to its root. Useful to supply a generated Dockerfile without writing it to the
+    /// build directory.
+    pub fn context_file<P, C>(
+        mut self,
+        path: P,
+        contents: C,
+    ) -> Self
+    where
+        P: Into<String>,
+        C: Into<Vec<u8>>,
+    {
+        self.tarball.overlay.insert(path.into(), contents.into());
+        self
+    }
+
+    /// Called with the progress of packing the build context before it is sent to the daemon
+    pub fn context_progress<F>(
+        mut self,
+        callback: F,
+    ) -> Self
+    where
+        F: Fn(TarballProgress) + Send + Sync + 'static,
+    {
+        self.tarball.progress = Some(ProgressCallback::new(callback));
+        self
+    }
+
+    /// How symbolic links in the build context are archived, preserving them by default
+    pub fn symlinks(
+        mut self,
+        symlinks: Symlinks,
+    ) -> Self {
+        self.tarball.symlinks = symlinks;
         self
     }
 
     pub fn dockerfile<P>(
-        &mut self,
+        mut self,
         path: P,
-    ) -> &mut Self
-        where
-            P: Into<String>,
+    ) -> Self
+    where
+        P: Into<String>,
     {
-        self.build_params.dockerfile(path);
+        let path = path.into();
+        self.tarball.dockerfile = path.clone();
+        self.build_params = self.build_params.dockerfile(path);
         self
     }
 
+    /// Tags the built image with `t`. May be called repeatedly to tag it with several names.
     pub fn tag<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
-        where
-            T: Into<String>,
+    ) -> Self
+    where
+        T: Into<String>,
     {
-        self.build_params.tag(t);
+        self.build_params = self.build_params.tag(t);
         self
     }
 
     pub fn remote<R>(
-        &mut self,
+        mut self,
         r: R,
-    ) -> &mut Self
-        where
-            R: Into<String>,
+    ) -> Self
+    where
+        R: Into<String>,
     {
-        self.build_params.remote(r);
+        self.build_params = self.build_params.remote(r);
         self
     }
 
     pub fn nocache(
-        &mut self,
+        mut self,
         nc: bool,
-    ) -> &mut Self {
-        self.build_params.nocache(nc);
+    ) -> Self {
+        self.build_params = self.build_params.nocache(nc);
         self
     }
 
     pub fn rm(
-        &mut self,
+        mut self,
         r: bool,
-    ) -> &mut Self {
-        self.build_params.rm(r);
+    ) -> Self {
+        self.build_params = self.build_params.rm(r);
         self
     }
 
     pub fn forcerm(
-        &mut self,
+        mut self,
         fr: bool,
-    ) -> &mut Self {
-        self.build_params.forcerm(fr);
+    ) -> Self {
+        self.build_params = self.build_params.forcerm(fr);
+        self
+    }
+
+    /// Squashes the layers of the build into one, see [BuildParams::squash]
+    pub fn squash(
+        mut self,
+        squash: bool,
+    ) -> Self {
+        self.build_params = self.build_params.squash(squash);
+        self
+    }
+
+    /// Adds `host:ip` entries to `/etc/hosts` of the build containers, see
+    /// [BuildParams::extra_hosts]
+    pub fn extra_hosts<I, S>(
+        mut self,
+        hosts: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.build_params = self.build_params.extra_hosts(hosts);
         self
     }
 
     pub fn network_mode<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
-        where
-            T: Into<String>,
+    ) -> Self
+    where
+        T: Into<String>,
     {
-        self.build_params.network_mode(t);
+        self.build_params = self.build_params.network_mode(t);
         self
     }
 
     pub fn memory(
-        &mut self,
+        mut self,
         memory: u64,
-    ) -> &mut Self {
-        self.build_params.memory(memory);
+    ) -> Self {
+        self.build_params = self.build_params.memory(memory);
         self
     }
 
     pub fn cpu_shares(
-        &mut self,
+        mut self,
         cpu_shares: u32,
-    ) -> &mut Self {
-        self.build_params.cpu_shares(cpu_shares);
+    ) -> Self {
+        self.build_params = self.build_params.cpu_shares(cpu_shares);
+        self
+    }
+
+    /// Sets the values of build-time variables declared with `ARG` in the Dockerfile, like
+    /// `docker build --build-arg`
+    pub fn build_args<I, K, V>(
+        mut self,
+        args: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.build_params = self.build_params.build_args(args);
+        self
+    }
+
+    /// Sets labels of the built image, like `docker build --label`
+    pub fn labels<I, K, V>(
+        mut self,
+        labels: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.build_params = self.build_params.labels(labels);
+        self
+    }
+
+    /// Images to use as cache sources, like `docker build --cache-from`
+    pub fn cache_from<I, S>(
+        mut self,
+        images: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.build_params = self.build_params.cache_from(images);
+        self
+    }
+
+    /// Stops a multi-stage build at the stage `stage`, like `docker build --target`
+    pub fn target<T>(
+        mut self,
+        stage: T,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        self.build_params = self.build_params.target(stage);
+        self
+    }
+
+    /// Builds the image for `platform`, see [BuildParams::platform]
+    pub fn platform<P>(
+        mut self,
+        platform: P,
+    ) -> Self
+    where
+        P: Into<String>,
+    {
+        self.build_params = self.build_params.platform(platform);
         self
     }
 
-    pub fn build(&self) -> BuildOptions {
+    /// Runs a BuildKit build in the session `id`, see [BuildParams::session]
+    pub fn session<S>(
+        mut self,
+        id: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        self.build_params = self.build_params.session(id);
+        self
+    }
+
+    pub fn build(self) -> BuildOptions {
         BuildOptions {
-            path: self.path.clone(),
-            params: self.build_params.params.clone(),
-            skip_gzip: self.skip_gzip
+            path: self.path,
+            params: self.build_params,
+            tarball: self.tarball,
         }
     }
 }
 
 /// Describes arguments for [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
-#[derive(Default)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BuildParams {
-    params: HashMap<&'static str, String>,
+    dockerfile: Option<String>,
+    /// The names the build tags its image with
+    #[serde(flatten)]
+    t: Repeated,
+    remote: Option<String>,
+    nocache: Option<bool>,
+    rm: Option<bool>,
+    forcerm: Option<bool>,
+    squash: Option<bool>,
+    networkmode: Option<String>,
+    /// The `host:ip` entries the build adds to `/etc/hosts`
+    #[serde(flatten)]
+    extrahosts: Repeated,
+    memory: Option<u64>,
+    cpushares: Option<u32>,
+    #[serde(
+        serialize_with = "query::json",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    buildargs: BTreeMap<String, String>,
+    #[serde(
+        serialize_with = "query::json",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    labels: BTreeMap<String, String>,
+    #[serde(serialize_with = "query::json", skip_serializing_if = "Vec::is_empty")]
+    cachefrom: Vec<String>,
+    target: Option<String>,
+    platform: Option<String>,
+    session: Option<String>,
+    version: Option<&'static str>,
+}
+
+impl Default for BuildParams {
+    fn default() -> Self {
+        BuildParams {
+            dockerfile: None,
+            t: Repeated::new("t"),
+            remote: None,
+            nocache: None,
+            rm: None,
+            forcerm: None,
+            squash: None,
+            networkmode: None,
+            extrahosts: Repeated::new("extrahosts"),
+            memory: None,
+            cpushares: None,
+            buildargs: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            cachefrom: Vec::new(),
+            target: None,
+            platform: None,
+            session: None,
+            version: None,
+        }
+    }
 }
 
 impl BuildParams {
     /// set the name of the docker file. defaults to "DockerFile"
     pub fn dockerfile<P>(
-        &mut self,
+        mut self,
         path: P,
-    ) -> &mut Self
+    ) -> Self
     where
         P: Into<String>,
     {
-        self.params.insert("dockerfile", path.into());
+        self.dockerfile = Some(path.into());
         self
     }
 
-    /// tag this image with a name after building it
+    /// tag this image with a name after building it. may be called repeatedly to tag the
+    /// image with several names, like `docker build -t a -t b`
     pub fn tag<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
+    ) -> Self
     where
         T: Into<String>,
     {
-        self.params.insert("t", t.into());
+        self.t.1.push(t.into());
         self
     }
 
     pub fn remote<R>(
-        &mut self,
+        mut self,
         r: R,
-    ) -> &mut Self
+    ) -> Self
     where
         R: Into<String>,
     {
-        self.params.insert("remote", r.into());
+        self.remote = Some(r.into());
         self
     }
 
     /// don't use the image cache when building image
     pub fn nocache(
-        &mut self,
+        mut self,
         nc: bool,
-    ) -> &mut Self {
-        self.params.insert("nocache", nc.to_string());
+    ) -> Self {
+        self.nocache = Some(nc);
         self
     }
 
     pub fn rm(
-        &mut self,
+        mut self,
         r: bool,
-    ) -> &mut Self {
-        self.params.insert("rm", r.to_string());
+    ) -> Self {
+        self.rm = Some(r);
         self
     }
 
     pub fn forcerm(
-        &mut self,
+        mut self,
         fr: bool,
-    ) -> &mut Self {
-        self.params.insert("forcerm", fr.to_string());
+    ) -> Self {
+        self.forcerm = Some(fr);
+        self
+    }
+
+    /// Squashes the new layers of the build into a single layer
+    ///
+    /// Daemons only support this in experimental mode, others reject the build with
+    /// [Error::ExperimentalRequired].
+    pub fn squash(
+        mut self,
+        squash: bool,
+    ) -> Self {
+        self.squash = Some(squash);
+        self
+    }
+
+    /// Adds `host:ip` entries to `/etc/hosts` of the build containers, like
+    /// `docker build --add-host`
+    pub fn extra_hosts<I, S>(
+        mut self,
+        hosts: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.extrahosts.1.extend(hosts.into_iter().map(Into::into));
         self
     }
 
     /// `bridge`, `host`, `none`, `container:<name|id>`, or a custom network name.
     pub fn network_mode<T>(
-        &mut self,
+        mut self,
         t: T,
-    ) -> &mut Self
+    ) -> Self
     where
         T: Into<String>,
     {
-        self.params.insert("networkmode", t.into());
+        self.networkmode = Some(t.into());
         self
     }
 
     pub fn memory(
-        &mut self,
+        mut self,
         memory: u64,
-    ) -> &mut Self {
-        self.params.insert("memory", memory.to_string());
+    ) -> Self {
+        self.memory = Some(memory);
         self
     }
 
     pub fn cpu_shares(
-        &mut self,
+        mut self,
         cpu_shares: u32,
-    ) -> &mut Self {
-        self.params.insert("cpushares", cpu_shares.to_string());
+    ) -> Self {
+        self.cpushares = Some(cpu_shares);
         self
     }
 
-    // todo: memswap
-    // todo: cpusetcpus
-    // todo: cpuperiod
-    // todo: cpuquota
-    // todo: buildargs
-
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    /// Sets the values of build-time variables declared with `ARG` in the Dockerfile, like
+    /// `docker build --build-arg`
+    pub fn build_args<I, K, V>(
+        mut self,
+        args: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.buildargs
+            .extend(args.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
     }
-}
 
-/// Filter options for image listings
-pub enum ImageFilter {
-    Dangling,
-    LabelName(String),
-    Label(String, String),
-}
+    /// Sets labels of the built image, like `docker build --label`
+    pub fn labels<I, K, V>(
+        mut self,
+        labels: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.labels
+            .extend(labels.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
 
-/// Options for filtering image list results
-#[derive(Default, Debug)]
+    /// Images to use as cache sources, like `docker build --cache-from`
+    pub fn cache_from<I, S>(
+        mut self,
+        images: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cachefrom.extend(images.into_iter().map(Into::into));
+        self
+    }
+
+    /// Stops a multi-stage build at the stage `stage`, like `docker build --target`
+    pub fn target<T>(
+        mut self,
+        stage: T,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        self.target = Some(stage.into());
+        self
+    }
+
+    /// Builds the image for `platform`, like `linux/arm64`, which needs an emulator for
+    /// platforms the daemon doesn't run on
+    pub fn platform<P>(
+        mut self,
+        platform: P,
+    ) -> Self
+    where
+        P: Into<String>,
+    {
+        self.platform = Some(platform.into());
+        self
+    }
+
+    /// Runs a BuildKit build in the session `id`, which the caller established on `/session`
+    /// with [Docker::upgrade]. [Images::build_with_session] serves a [Session] instead.
+    pub fn session<S>(
+        mut self,
+        id: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        self.session = Some(id.into());
+        self
+    }
+
+    // todo: memswap
+    // todo: cpusetcpus
+    // todo: cpuperiod
+    // todo: cpuquota
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for image listings
+pub enum ImageFilter {
+    /// Images not referenced by any tag
+    Dangling,
+    /// Images with a label of the given name
+    LabelName(String),
+    /// Images with a label of the given name and value
+    Label(String, String),
+    /// Images created before the given image, named by id or reference
+    Before(String),
+    /// Images created after the given image, named by id or reference
+    Since(String),
+    /// Images whose reference matches the given pattern, like `alpine:*`
+    Reference(String),
+}
+
+impl query::Filter for ImageFilter {
+    fn query(self) -> (&'static str, String) {
+        match self {
+            ImageFilter::Dangling => ("dangling", true.to_string()),
+            ImageFilter::LabelName(n) => ("label", n),
+            ImageFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
+            ImageFilter::Before(i) => ("before", i),
+            ImageFilter::Since(i) => ("since", i),
+            ImageFilter::Reference(r) => ("reference", r),
+        }
+    }
+}
+
+/// Options for filtering image list results
+#[derive(Default, Debug, Serialize)]
 pub struct ImageListOptions {
-    params: HashMap<&'static str, String>,
+    digests: Option<bool>,
+    all: Option<bool>,
+    filter: Option<String>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl ImageListOptions {
     pub fn builder() -> ImageListOptionsBuilder {
         ImageListOptionsBuilder::default()
     }
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
 /// Builder interface for `ImageListOptions`
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ImageListOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: ImageListOptions,
 }
 
 impl ImageListOptionsBuilder {
     pub fn digests(
-        &mut self,
+        mut self,
         d: bool,
-    ) -> &mut Self {
-        self.params.insert("digests", d.to_string());
+    ) -> Self {
+        self.options.digests = Some(d);
         self
     }
 
-    pub fn all(&mut self) -> &mut Self {
-        self.params.insert("all", "true".to_owned());
+    pub fn all(mut self) -> Self {
+        self.options.all = Some(true);
         self
     }
 
     pub fn filter_name(
-        &mut self,
+        mut self,
         name: &str,
-    ) -> &mut Self {
-        self.params.insert("filter", name.to_owned());
+    ) -> Self {
+        self.options.filter = Some(name.to_owned());
         self
     }
 
     pub fn filter(
-        &mut self,
+        mut self,
         filters: Vec<ImageFilter>,
-    ) -> &mut Self {
-        let mut param = HashMap::new();
-        for f in filters {
-            match f {
-                ImageFilter::Dangling => param.insert("dangling", vec![true.to_string()]),
-                ImageFilter::LabelName(n) => param.insert("label", vec![n]),
-                ImageFilter::Label(n, v) => param.insert("label", vec![format!("{}={}", n, v)]),
-            };
-        }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
+    ) -> Self {
+        self.options.filters = Filters::typed(filters);
         self
     }
 
-    pub fn build(&self) -> ImageListOptions {
-        ImageListOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> ImageListOptions {
+        self.options
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct PushOptions {
+    #[serde(skip)]
     auth: Option<RegistryAuth>,
-    params: HashMap<&'static str, String>,
+    tag: Option<String>,
 }
 
 impl PushOptions {
-
     pub fn builder() -> PushOptionsBuilder {
         PushOptionsBuilder::default()
     }
 
-    fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 
     fn auth_header(&self) -> Option<String> {
@@ -1005,32 +3001,133 @@ impl PushOptions {
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct PushOptionsBuilder {
-    auth: Option<RegistryAuth>,
-    params: HashMap<&'static str, String>,
+    options: PushOptions,
 }
 
 impl PushOptionsBuilder {
-
-    pub fn tag(&mut self, t: String) -> &mut Self {
-        self.params.insert("tag", t);
+    pub fn tag(
+        mut self,
+        t: String,
+    ) -> Self {
+        self.options.tag = Some(t);
         self
     }
 
-    pub fn auth(&mut self, auth: RegistryAuth) -> &mut Self {
-        self.auth = Some(auth);
+    pub fn auth(
+        mut self,
+        auth: RegistryAuth,
+    ) -> Self {
+        self.options.auth = Some(auth);
         self
     }
 
-    pub fn build(&mut self) -> PushOptions {
-        PushOptions {
-            auth: self.auth.take(),
-            params: self.params.clone(),
+    pub fn build(self) -> PushOptions {
+        self.options
+    }
+}
+
+/// Options for searching images in a registry
+#[derive(Debug, Serialize)]
+pub struct SearchOptions {
+    #[serde(skip)]
+    auth: Option<RegistryAuth>,
+    term: String,
+    limit: Option<u64>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
+}
+
+impl SearchOptions {
+    /// return a new instance of a builder for a search for `term`
+    pub fn builder<T>(term: T) -> SearchOptionsBuilder
+    where
+        T: Into<String>,
+    {
+        SearchOptionsBuilder {
+            options: SearchOptions {
+                auth: None,
+                term: term.into(),
+                limit: None,
+                filters: Filters::default(),
+            },
         }
     }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+
+    fn auth_header(&self) -> Option<String> {
+        self.auth.as_ref().map(RegistryAuth::serialize)
+    }
+}
+
+/// Builder interface for `SearchOptions`
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct SearchOptionsBuilder {
+    options: SearchOptions,
+}
+
+impl SearchOptionsBuilder {
+    /// Returns at most `limit` results
+    pub fn limit(
+        mut self,
+        limit: u64,
+    ) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    /// Only returns official images, or only other images
+    pub fn is_official(
+        mut self,
+        official: bool,
+    ) -> Self {
+        self.options
+            .filters
+            .set("is-official", official.to_string());
+        self
+    }
+
+    /// Only returns automated builds, or only other images
+    pub fn is_automated(
+        mut self,
+        automated: bool,
+    ) -> Self {
+        self.options
+            .filters
+            .set("is-automated", automated.to_string());
+        self
+    }
+
+    /// Only returns images with at least `stars` stars
+    pub fn stars(
+        mut self,
+        stars: u64,
+    ) -> Self {
+        self.options.filters.set("stars", stars.to_string());
+        self
+    }
+
+    /// Authenticates the search with the registry, to search private registries and mirrors
+    pub fn auth(
+        mut self,
+        auth: RegistryAuth,
+    ) -> Self {
+        self.options.auth = Some(auth);
+        self
+    }
+
+    pub fn build(self) -> SearchOptions {
+        self.options
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct SearchResult {
     pub description: String,
     pub is_official: bool,
@@ -1041,22 +3138,31 @@ pub struct SearchResult {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ImageInfo {
     #[cfg(feature = "chrono")]
     #[serde(deserialize_with = "datetime_from_unix_timestamp")]
     pub created: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::timestamp")]
+    pub created: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created: u64,
-    pub id: String,
+    pub id: ImageId,
     pub parent_id: String,
     pub labels: Option<HashMap<String, String>>,
     pub repo_tags: Option<Vec<String>>,
     pub repo_digests: Option<Vec<String>>,
     pub virtual_size: Option<u64>,
+    /// Fields of the listing that aren't modelled, like `Size` and `Containers`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ImageDetails {
     pub architecture: String,
     pub author: String,
@@ -1064,20 +3170,150 @@ pub struct ImageDetails {
     pub config: ContainerConfig,
     #[cfg(feature = "chrono")]
     pub created: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created: String,
     pub docker_version: String,
-    pub id: String,
+    pub id: ImageId,
     pub os: String,
+    /// Version of the operating system, which is only set for Windows images
+    pub os_version: Option<String>,
+    /// Variant of the architecture, like `v7` for `arm`
+    pub variant: Option<String>,
     pub parent: String,
     pub repo_tags: Option<Vec<String>>,
     pub repo_digests: Option<Vec<String>>,
+    #[serde(rename = "RootFS")]
+    pub root_fs: Option<RootFs>,
+    /// The storage driver holding the layers of the image, with its driver specific data
+    pub graph_driver: Option<GraphDriverData>,
+    pub metadata: Option<ImageMetadata>,
     pub size: u64,
     pub virtual_size: Option<u64>,
+    /// Inspect fields that aren't modelled, like the `Descriptor` of the containerd image store
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ImageDetails {
+    /// The platform of the image, like `linux/arm64`
+    pub fn platform(&self) -> Platform {
+        Platform {
+            architecture: self.architecture.clone(),
+            os: self.os.clone(),
+            variant: self.variant.clone(),
+        }
+    }
+}
+
+/// The filesystem of an image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct RootFs {
+    #[serde(rename = "Type")]
+    pub type_: String,
+    /// Digests of the uncompressed layers, from the base layer up
+    pub layers: Option<Vec<String>>,
+}
+
+/// Metadata the daemon keeps about an image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct ImageMetadata {
+    /// When the image was last tagged, which is the zero time `0001-01-01T00:00:00Z` if it was
+    /// never tagged after it was pulled or built
+    #[cfg(feature = "chrono")]
+    pub last_tag_time: Option<DateTime<Utc>>,
+    #[cfg(feature = "time")]
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub last_tag_time: Option<OffsetDateTime>,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub last_tag_time: Option<String>,
+}
+
+/// The manifest an image reference resolves to in its registry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct DistributionInspect {
+    pub descriptor: Descriptor,
+    /// Platforms the image is available for, if the manifest is a manifest list
+    pub platforms: Option<Vec<Platform>>,
+}
+
+/// Descriptor of the content of an OCI image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    pub variant: Option<String>,
+}
+
+impl Platform {
+    /// Parses a platform like `linux/amd64` or `linux/arm/v7`
+    pub fn parse(platform: &str) -> Self {
+        let mut parts = platform.splitn(3, '/');
+        Platform {
+            os: parts.next().unwrap_or_default().to_owned(),
+            architecture: parts.next().unwrap_or_default().to_owned(),
+            variant: parts.next().map(str::to_owned),
+        }
+    }
+
+    /// Whether this platform is `wanted`, which matches any variant if it names none
+    fn matches(
+        &self,
+        wanted: &Platform,
+    ) -> bool {
+        self.os == wanted.os
+            && self.architecture == wanted.architecture
+            && (wanted.variant.is_none() || self.variant == wanted.variant)
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        match self.variant {
+            Some(ref variant) => write!(f, "/{}", variant),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The image of a platform that [Images::resolve_platform] resolved a reference to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlatformImage {
+    /// The digest of the manifest the reference resolved to, to pull the image by as
+    /// `repository@digest` with the [platform](PullOptionsBuilder::platform) of the image. For
+    /// images of several platforms, this is the digest of their manifest list.
+    pub digest: String,
+    /// The size of the manifest in bytes
+    pub size: u64,
+    pub media_type: String,
+    pub platform: Platform,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ContainerConfig {
     pub attach_stderr: bool,
     pub attach_stdin: bool,
@@ -1098,37 +3334,391 @@ pub struct ContainerConfig {
     pub tty: bool,
     pub user: String,
     pub working_dir: String,
+    pub healthcheck: Option<HealthConfig>,
+    /// The signal that stops the container, like `SIGTERM`
+    pub stop_signal: Option<String>,
+    /// Paths of the anonymous volumes of the container, like `/data`
+    pub volumes: Option<HashMap<String, HashMap<String, String>>>,
+    /// The shell of the shell form of `RUN`, `CMD` and `ENTRYPOINT`, like `["/bin/sh", "-c"]`
+    pub shell: Option<Vec<String>>,
+}
+
+/// The healthcheck of a container
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HealthConfig {
+    /// The command of the check, like `["CMD-SHELL", "curl -f http://localhost/"]`. `["NONE"]`
+    /// disables the healthcheck, and an empty or missing test inherits it from the image.
+    pub test: Option<Vec<String>>,
+    /// Time between two checks in nanoseconds, zero to inherit it
+    pub interval: Option<u64>,
+    /// Time after which a check is considered to have failed in nanoseconds, zero to inherit it
+    pub timeout: Option<u64>,
+    /// Time for the container to start up before failed checks count in nanoseconds, zero to
+    /// inherit it
+    pub start_period: Option<u64>,
+    /// Number of consecutive failed checks after which the container is unhealthy, zero to
+    /// inherit it
+    pub retries: Option<u64>,
+}
+
+/// The configuration an image records for the containers created from it, for
+/// [CommitOptionsBuilder::config](crate::container::CommitOptionsBuilder::config). Settings
+/// left `None` are taken from the committed container.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entrypoint: Option<Vec<String>>,
+    /// Environment variables, like `KEY=value`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    /// Ports the containers listen on, like `8080/tcp`, each mapped to an empty object
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposed_ports: Option<HashMap<String, HashMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_build: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthConfig>,
+    /// The signal that stops the containers, like `SIGTERM`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<String>,
+    /// Paths of the anonymous volumes of the containers, like `/data`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<HashMap<String, HashMap<String, String>>>,
+    /// The shell of the shell form of `RUN`, `CMD` and `ENTRYPOINT`, like `["/bin/sh", "-c"]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Vec<String>>,
 }
 
 impl ContainerConfig {
+    /// The environment variables by name. A variable given without `=` maps to an empty value,
+    /// use [env_vars](ContainerConfig::env_vars) to tell these apart.
     pub fn env(&self) -> HashMap<String, String> {
         let mut map = HashMap::new();
         if let Some(ref vars) = self.env {
             for e in vars {
-                let pair: Vec<&str> = e.split('=').collect();
-                map.insert(pair[0].to_owned(), pair[1].to_owned());
+                let (key, value) = e.split_once('=').unwrap_or((e, ""));
+                map.insert(key.to_owned(), value.to_owned());
             }
         }
         map
     }
+
+    /// The environment variables in the order they were given
+    pub fn env_vars(&self) -> impl Iterator<Item = EnvVar> + '_ {
+        self.env.iter().flatten().map(|var| EnvVar::parse(var))
+    }
+}
+
+/// An environment variable of a container, like `KEY=value`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvVar {
+    pub key: String,
+    /// `None` for a variable given without `=`, which takes its value from the environment of
+    /// the client that created the container, or is left unset
+    pub value: Option<String>,
+}
+
+impl EnvVar {
+    pub fn new<K, V>(
+        key: K,
+        value: V,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        EnvVar {
+            key: key.into(),
+            value: Some(value.into()),
+        }
+    }
+
+    /// Splits `var` at the first `=`, so that the value may contain `=` itself
+    pub fn parse(var: &str) -> Self {
+        match var.split_once('=') {
+            Some((key, value)) => EnvVar::new(key, value),
+            None => EnvVar {
+                key: var.to_owned(),
+                value: None,
+            },
+        }
+    }
+
+    /// Formats `vars` as the list of `KEY=value` strings the daemon expects, see
+    /// [ContainerOptionsBuilder::env](crate::container::ContainerOptionsBuilder::env)
+    pub fn to_env_list<I>(vars: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = EnvVar>,
+    {
+        vars.into_iter().map(|var| var.to_string()).collect()
+    }
+}
+
+impl fmt::Display for EnvVar {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self.value {
+            Some(ref value) => write!(f, "{}={}", self.key, value),
+            None => f.write_str(&self.key),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct History {
     pub id: String,
+    /// When the step ran, or `None` if the builder didn't record it, which BuildKit does for
+    /// some steps
     #[cfg(feature = "chrono")]
-    #[serde(deserialize_with = "datetime_from_unix_timestamp")]
-    pub created: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
-    pub created: u64,
+    #[serde(default, deserialize_with = "history_created")]
+    pub created: Option<DateTime<Utc>>,
+    #[cfg(feature = "time")]
+    #[serde(default, deserialize_with = "history_created")]
+    pub created: Option<OffsetDateTime>,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    #[serde(default, deserialize_with = "history_created")]
+    pub created: Option<u64>,
     pub created_by: String,
+    /// Size of the layer the step created, 0 for steps that only changed the configuration
+    pub size: u64,
+    /// Tags of the image the step created, if it is tagged
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub comment: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Deserializes the creation time of a [History] step as a unix timestamp, which is zero or
+/// negative if the builder didn't record it
+#[cfg(feature = "chrono")]
+fn history_created<'de, D>(deserializer: D) -> std::result::Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<i64>::deserialize(deserializer)?
+        .filter(|timestamp| *timestamp > 0)
+        .map(|timestamp| {
+            DateTime::<Utc>::from_timestamp(timestamp, 0)
+                .ok_or_else(|| serde::de::Error::custom("timestamp out of range"))
+        })
+        .transpose()
+}
+
+#[cfg(feature = "time")]
+fn history_created<'de, D>(deserializer: D) -> std::result::Result<Option<OffsetDateTime>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<i64>::deserialize(deserializer)?
+        .filter(|timestamp| *timestamp > 0)
+        .map(|timestamp| {
+            OffsetDateTime::from_unix_timestamp(timestamp).map_err(serde::de::Error::custom)
+        })
+        .transpose()
+}
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+fn history_created<'de, D>(deserializer: D) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<i64>::deserialize(deserializer)?
+        .filter(|timestamp| *timestamp > 0)
+        .map(|timestamp| timestamp as u64))
+}
+
+impl History {
+    /// Whether the step created a layer, judging by the command of the legacy builder. Steps
+    /// of BuildKit can only be told apart by their size.
+    fn created_layer(&self) -> bool {
+        self.size > 0
+            || !self.created_by.contains("#(nop) ")
+            || self.created_by.contains("#(nop) ADD")
+            || self.created_by.contains("#(nop) COPY")
+    }
+}
+
+/// A layer compared by [Images::diff]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffLayer {
+    /// Digest of the uncompressed layer
+    pub digest: String,
+    /// Size of the uncompressed layer, if the history of the image could be matched to its
+    /// layers
+    pub size: Option<u64>,
+    /// The command of the step that created the layer, if known
+    pub created_by: Option<String>,
+}
+
+/// The layers two images share and those unique to either, see [Images::diff]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// Layers of both images, in the order of the second image
+    pub shared: Vec<DiffLayer>,
+    /// Layers of only the first image
+    pub only_in_a: Vec<DiffLayer>,
+    /// Layers of only the second image, which have to be transferred to a host that has the
+    /// first image to get the second
+    pub only_in_b: Vec<DiffLayer>,
+}
+
+impl ImageDiff {
+    /// Compares images by the layer digests of their root filesystems and their histories
+    fn new(
+        a: (&[String], &[History]),
+        b: (&[String], &[History]),
+    ) -> Self {
+        let layers_a = diff_layers(a.0, a.1);
+        let layers_b = diff_layers(b.0, b.1);
+
+        let mut diff = ImageDiff::default();
+        for layer in &layers_b {
+            if a.0.contains(&layer.digest) {
+                diff.shared.push(layer.clone());
+            } else {
+                diff.only_in_b.push(layer.clone());
+            }
+        }
+        diff.only_in_a = layers_a
+            .into_iter()
+            .filter(|layer| !b.0.contains(&layer.digest))
+            .collect();
+        diff
+    }
+
+    /// Size of the shared layers, `None` if the size of one is unknown
+    pub fn shared_size(&self) -> Option<u64> {
+        total_size(&self.shared)
+    }
+
+    /// Size of the layers of only the first image, `None` if the size of one is unknown
+    pub fn only_in_a_size(&self) -> Option<u64> {
+        total_size(&self.only_in_a)
+    }
+
+    /// Size of the layers of only the second image, `None` if the size of one is unknown
+    pub fn only_in_b_size(&self) -> Option<u64> {
+        total_size(&self.only_in_b)
+    }
+}
+
+fn total_size(layers: &[DiffLayer]) -> Option<u64> {
+    layers.iter().map(|layer| layer.size).sum()
+}
+
+/// Matches the `history` of an image, which is listed newest first, to its `layers`
+fn diff_layers(
+    layers: &[String],
+    history: &[History],
+) -> Vec<DiffLayer> {
+    let mut steps: Vec<&History> = history
+        .iter()
+        .rev()
+        .filter(|step| step.created_layer())
+        .collect();
+    if steps.len() != layers.len() {
+        steps = history.iter().rev().filter(|step| step.size > 0).collect();
+    }
+    let matched = steps.len() == layers.len();
+
+    layers
+        .iter()
+        .enumerate()
+        .map(|(i, digest)| DiffLayer {
+            digest: digest.clone(),
+            size: steps.get(i).filter(|_| matched).map(|step| step.size),
+            created_by: steps
+                .get(i)
+                .filter(|_| matched)
+                .map(|step| step.created_by.clone()),
+        })
+        .collect()
+}
+
+/// What deleting an image did to a tag or an image
+///
+/// The daemon reports each as an object like `{"Untagged": "app:1.0"}`, which some versions send
+/// with the other field empty.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, Option<String>>",
+    into = "BTreeMap<String, String>"
+)]
 pub enum Status {
+    /// A tag was removed
     Untagged(String),
+    /// An image was deleted
     Deleted(String),
+    /// A field this crate does not know, with its value
+    Other(String, String),
+}
+
+impl TryFrom<BTreeMap<String, Option<String>>> for Status {
+    type Error = String;
+
+    fn try_from(fields: BTreeMap<String, Option<String>>) -> std::result::Result<Self, String> {
+        let mut fields: BTreeMap<_, _> = fields
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value.filter(|value| !value.is_empty())?)))
+            .collect();
+        if let Some(tag) = fields.remove("Untagged") {
+            Ok(Status::Untagged(tag))
+        } else if let Some(id) = fields.remove("Deleted") {
+            Ok(Status::Deleted(id))
+        } else {
+            fields
+                .into_iter()
+                .next()
+                .map(|(name, value)| Status::Other(name, value))
+                .ok_or_else(|| "a delete status without any field".to_owned())
+        }
+    }
+}
+
+impl From<Status> for BTreeMap<String, String> {
+    fn from(status: Status) -> Self {
+        let (name, value) = match status {
+            Status::Untagged(tag) => ("Untagged".to_owned(), tag),
+            Status::Deleted(id) => ("Deleted".to_owned(), id),
+            Status::Other(name, value) => (name, value),
+        };
+        iter::once((name, value)).collect()
+    }
+}
+
+/// What [Images::prune] deleted
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PruneInfo {
+    /// The images that were untagged or deleted
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub images_deleted: Vec<Status>,
+    /// Disk space freed, in bytes
+    pub space_reclaimed: u64,
+}
+
+/// Deserializes `null`, which the daemon sends for empty lists, as the default value
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1146,6 +3736,10 @@ pub enum ImageBuildChunk {
     Digest {
         aux: Aux,
     },
+    /// The manifest of a pushed image, which the daemon reports at the end of a push
+    Pushed {
+        aux: PushedImage,
+    },
     PullStatus {
         status: String,
         id: Option<String>,
@@ -1156,34 +3750,73 @@ pub enum ImageBuildChunk {
 }
 
 impl ImageBuildChunk {
-    /// Return the eventual compressed image layer size during download (if
-    /// available).
-    pub fn total_image_bytes(&self) -> Option<u64> {
-        match self {
-            ImageBuildChunk::PullStatus {
-                status: _,
-                id: _,
-                progress: _,
-                progress_detail,
-            } => {
-                if let Some(detail) = progress_detail {
-                    return detail.total;
-                }
+    /// Updates `progress` with the build step or layer this chunk reports on
+    fn record_progress(
+        progress: &mut StreamProgress,
+        chunk: &ImageBuildChunk,
+    ) {
+        match chunk {
+            ImageBuildChunk::Update { stream } if stream.starts_with("Step ") => {
+                progress.record_step(stream)
+            }
+            ImageBuildChunk::PullStatus { id: Some(id), .. } => {
+                progress.record_layer(id, |layers| {
+                    layers.record(chunk);
+                })
             }
             _ => (),
         }
-        None
     }
 
-    /// Returns the image layer ID and size during download.
-    ///
-    /// If both the layer ID and eventual (compressed) layer size are available
-    /// from the ImageBuildChunk, they will be returned as `Some((layer_id,
-    /// layer_size))`. Otherwise, `None` is returned.
+    /// This chunk, or [Error::StreamFault] with the error it reports for the `POST` request to
+    /// `path`
+    pub(crate) fn into_result(
+        self,
+        path: &str,
+    ) -> Result<Self> {
+        match self {
+            ImageBuildChunk::Error {
+                error,
+                error_detail,
+            } => Err(Error::StreamFault {
+                message: fault_message(error, error_detail),
+                method: Method::POST,
+                path: path.to_owned(),
+            }),
+            chunk => Ok(chunk),
+        }
+    }
+
+    /// The ID of the built image, which the daemon reports at the end of a build
+    pub fn built_image_id(&self) -> Option<&str> {
+        match self {
+            ImageBuildChunk::Digest { aux } => Some(aux.id()),
+            _ => None,
+        }
+    }
+
+    /// Return the eventual compressed image layer size during download (if
+    /// available).
+    pub fn total_image_bytes(&self) -> Option<u64> {
+        match self {
+            ImageBuildChunk::PullStatus {
+                progress_detail: Some(detail),
+                ..
+            } => detail.total,
+            _ => None,
+        }
+    }
+
+    /// Returns the image layer ID and size during download.
+    ///
+    /// If both the layer ID and eventual (compressed) layer size are available
+    /// from the ImageBuildChunk, they will be returned as `Some((layer_id,
+    /// layer_size))`. Otherwise, `None` is returned. Only downloads report the
+    /// compressed size, extractions report the uncompressed size instead.
     pub fn image_layer_bytes(&self) -> Option<(String, u64)> {
         match self {
             ImageBuildChunk::PullStatus {
-                status: _,
+                status,
                 id: Some(id),
                 progress: _,
                 progress_detail:
@@ -1191,37 +3824,484 @@ impl ImageBuildChunk {
                         current: _,
                         total: Some(total),
                     }),
+            } if status == "Downloading" => Some((id.to_string(), *total)),
+            _ => None,
+        }
+    }
+}
+
+/// The bytes of the layers of pulls, counting each layer once
+///
+/// The size of a layer is known once the daemon starts downloading it, see
+/// [ImageBuildChunk::image_layer_bytes]. Layers count as downloaded completely once their
+/// download completed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct LayerTally {
+    layers: HashMap<String, LayerBytes>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct LayerBytes {
+    downloaded: u64,
+    size: Option<u64>,
+}
+
+impl LayerTally {
+    /// Records the layer `chunk` reports on, and returns by how much the size of the layers
+    /// grew
+    pub(crate) fn record(
+        &mut self,
+        chunk: &ImageBuildChunk,
+    ) -> u64 {
+        let (id, status, detail) = match chunk {
+            ImageBuildChunk::PullStatus {
+                id: Some(id),
+                status,
+                progress_detail,
+                ..
+            } => (id, status, progress_detail),
+            _ => return 0,
+        };
+        match status.as_str() {
+            "Downloading" => self.record_download(
+                id,
+                detail.as_ref().and_then(ProgressDetail::current),
+                chunk.image_layer_bytes().map(|(_, size)| size),
+            ),
+            "Verifying Checksum" | "Download complete" | "Extracting" | "Pull complete" => {
+                let layer = self.layers.entry(id.clone()).or_default();
+                layer.downloaded = layer.size.unwrap_or(layer.downloaded);
+                0
+            }
+            _ => self.record_download(id, None, None),
+        }
+    }
+
+    /// Records that `downloaded` bytes of `layer` have been received, and its `size` if it is
+    /// known, returning by how much the size of the layers grew
+    pub(crate) fn record_download(
+        &mut self,
+        layer: &str,
+        downloaded: Option<u64>,
+        size: Option<u64>,
+    ) -> u64 {
+        let layer = self.layers.entry(layer.to_owned()).or_default();
+        if let Some(downloaded) = downloaded {
+            layer.downloaded = downloaded;
+        }
+        match (layer.size, size) {
+            (None, Some(size)) => {
+                layer.size = Some(size);
+                size
+            }
+            _ => 0,
+        }
+    }
+
+    /// The size of `layer`, if its download started
+    pub(crate) fn layer_size(
+        &self,
+        layer: &str,
+    ) -> Option<u64> {
+        self.layers.get(layer).and_then(|layer| layer.size)
+    }
+
+    /// Bytes downloaded of all layers
+    pub(crate) fn downloaded(&self) -> u64 {
+        self.layers.values().map(|layer| layer.downloaded).sum()
+    }
+
+    /// Size of the layers whose download started
+    pub(crate) fn size(&self) -> u64 {
+        self.layers.values().filter_map(|layer| layer.size).sum()
+    }
+}
+
+/// What a [ProgressEvent] reports on
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressPhase {
+    /// The layer waits for another download to finish
+    Waiting,
+    Downloading,
+    /// The checksum of the downloaded layer is verified
+    Verifying,
+    Downloaded,
+    Extracting,
+    /// The layer was pulled
+    Complete,
+    /// The layer exists locally and was not pulled
+    AlreadyExists,
+    /// A build started the step, like `Step 2/5 : RUN make`
+    Step(String),
+    /// Output of a build step
+    Output(String),
+    /// The ID of the image that was built
+    Built(String),
+    /// A status the daemon reported that is none of the above, like `Digest: sha256:...`
+    Other(String),
+}
+
+impl fmt::Display for ProgressPhase {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            ProgressPhase::Waiting => f.write_str("Waiting"),
+            ProgressPhase::Downloading => f.write_str("Downloading"),
+            ProgressPhase::Verifying => f.write_str("Verifying Checksum"),
+            ProgressPhase::Downloaded => f.write_str("Download complete"),
+            ProgressPhase::Extracting => f.write_str("Extracting"),
+            ProgressPhase::Complete => f.write_str("Pull complete"),
+            ProgressPhase::AlreadyExists => f.write_str("Already exists"),
+            ProgressPhase::Step(status)
+            | ProgressPhase::Output(status)
+            | ProgressPhase::Built(status)
+            | ProgressPhase::Other(status) => f.write_str(status),
+        }
+    }
+}
+
+/// The progress of a layer of a pull or of a build, see [ProgressReporter]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgressEvent {
+    /// The layer the event is about, or `None` for events about the pull or build as a whole
+    pub layer_id: Option<String>,
+    pub phase: ProgressPhase,
+    /// Bytes downloaded or extracted so far
+    pub current: Option<u64>,
+    /// Size of the layer, once the daemon reported it
+    pub total: Option<u64>,
+}
+
+/// Turns the [ImageBuildChunk]s of a pull or build into [ProgressEvent]s
+///
+/// The reporter remembers the size of each layer, so that events report it after the daemon
+/// stopped doing so, like when a layer completes. [Images::pull_events] and
+/// [Images::build_events] report the progress of a pull or build this way.
+#[derive(Debug, Default)]
+pub struct ProgressReporter {
+    layers: LayerTally,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        ProgressReporter::default()
+    }
+
+    /// The event `chunk` reports, or `None` for chunks that report nothing, like errors and
+    /// empty output
+    pub fn report(
+        &mut self,
+        chunk: &ImageBuildChunk,
+    ) -> Option<ProgressEvent> {
+        let whole = |phase| ProgressEvent {
+            layer_id: None,
+            phase,
+            current: None,
+            total: None,
+        };
+        match chunk {
+            ImageBuildChunk::Update { stream } if stream.starts_with("Step ") => {
+                Some(whole(ProgressPhase::Step(stream.trim_end().to_owned())))
+            }
+            ImageBuildChunk::Update { stream } if !stream.trim().is_empty() => {
+                Some(whole(ProgressPhase::Output(stream.clone())))
+            }
+            ImageBuildChunk::Update { .. }
+            | ImageBuildChunk::Error { .. }
+            | ImageBuildChunk::Pushed { .. } => None,
+            ImageBuildChunk::Digest { aux } => Some(whole(ProgressPhase::Built(aux.id.clone()))),
+            ImageBuildChunk::PullStatus {
+                status,
+                id,
+                progress_detail,
+                ..
             } => {
-                Some((id.to_string(), *total))
+                let phase = match status.as_str() {
+                    "Waiting" | "Pulling fs layer" => ProgressPhase::Waiting,
+                    "Downloading" => ProgressPhase::Downloading,
+                    "Verifying Checksum" => ProgressPhase::Verifying,
+                    "Download complete" => ProgressPhase::Downloaded,
+                    "Extracting" => ProgressPhase::Extracting,
+                    "Pull complete" => ProgressPhase::Complete,
+                    "Already exists" => ProgressPhase::AlreadyExists,
+                    status => ProgressPhase::Other(status.to_owned()),
+                };
+                // of the pull phases only downloads and extractions report meaningful byte
+                // counts, other statuses like pushes and loads report their own
+                let detail = progress_detail.as_ref().filter(|_| {
+                    matches!(
+                        phase,
+                        ProgressPhase::Downloading
+                            | ProgressPhase::Extracting
+                            | ProgressPhase::Other(_)
+                    )
+                });
+                let mut current = detail.and_then(|detail| detail.current);
+                let mut total = detail.and_then(|detail| detail.total);
+                if let Some(id) = id {
+                    self.layers.record(chunk);
+                    total = total.or_else(|| self.layers.layer_size(id));
+                    if phase == ProgressPhase::Downloaded || phase == ProgressPhase::Complete {
+                        current = total;
+                    }
+                }
+                Some(ProgressEvent {
+                    layer_id: id.clone(),
+                    phase,
+                    current,
+                    total,
+                })
             }
-            _ => None,
         }
     }
+
+    /// The layers reported so far
+    #[cfg(feature = "progress")]
+    pub(crate) fn layers(&self) -> &LayerTally {
+        &self.layers
+    }
+
+    /// Reports the chunks of `stream`, failing with [Error::StreamFault] on the first error the
+    /// daemon reports in it
+    fn events<S>(
+        mut self,
+        stream: S,
+        path: String,
+    ) -> impl Stream<Item = Result<ProgressEvent>> + Unpin
+    where
+        S: Stream<Item = Result<ImageBuildChunk>> + Unpin,
+    {
+        stream
+            .and_then(move |chunk| {
+                future::ready(chunk.into_result(&path).map(|chunk| self.report(&chunk)))
+            })
+            .try_filter_map(future::ok)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[non_exhaustive]
 pub struct Aux {
     #[serde(rename = "ID")]
     id: String,
 }
 
+impl Aux {
+    pub fn new<S>(id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Aux { id: id.into() }
+    }
+
+    /// ID of the built image
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
+#[non_exhaustive]
 pub struct ErrorDetail {
     message: String,
 }
 
+impl ErrorDetail {
+    pub fn new<S>(message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        ErrorDetail {
+            message: message.into(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The result the daemon reports at the end of a push
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PushAux {
+    digest: String,
+    size: Option<u64>,
+}
+
+impl From<PushAux> for PushedImage {
+    fn from(aux: PushAux) -> Self {
+        PushedImage {
+            digest: Some(aux.digest),
+            size: aux.size,
+        }
+    }
+}
+
+/// The error that failed producing the body of an upload, see [upload_body]
+#[derive(Clone, Default)]
+struct UploadError(Arc<Mutex<Option<io::Error>>>);
+
+impl UploadError {
+    /// The error that failed producing the body, which failed the upload with `e`, or `e`
+    fn take(
+        &self,
+        e: Error,
+    ) -> Error {
+        match self.0.lock().unwrap().take() {
+            Some(upload) => upload.into(),
+            None => e,
+        }
+    }
+}
+
+/// The body of a build uploading the context at `path` as it is packed, without holding the
+/// whole context in memory
+fn context_body(
+    path: &str,
+    opts: &TarballOptions,
+) -> (Body, UploadError) {
+    upload_body(tarball::dir_stream(path, opts))
+}
+
+/// The body uploading `chunks` as they are produced
+///
+/// An error producing the chunks aborts the upload, which fails the request with a transport
+/// error. The error is kept, so that the request can fail with it instead.
+fn upload_body<S>(chunks: S) -> (Body, UploadError)
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+{
+    let upload_error = UploadError::default();
+    let slot = upload_error.clone();
+    let chunks = chunks.map_err(move |e| {
+        let aborted = io::Error::new(e.kind(), e.to_string());
+        *slot.0.lock().unwrap() = Some(e);
+        aborted
+    });
+    (Body::wrap_stream(chunks), upload_error)
+}
+
+/// The contents of `reader` in chunks of at most [IMPORT_CHUNK_SIZE] bytes, as they are read
+fn read_chunks<R>(reader: R) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    R: AsyncRead + Send + 'static,
+{
+    futures_util::stream::unfold(Some(Box::pin(reader)), |reader| async move {
+        let mut reader = reader?;
+        let mut buf = vec![0; IMPORT_CHUNK_SIZE];
+        match reader.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(len) => {
+                buf.truncate(len);
+                Some((Ok(Bytes::from(buf)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// The typed error for the `message` of a failed pull of `image`, if the registry refused it
+fn pull_error(
+    image: &str,
+    message: &str,
+) -> Option<Error> {
+    let lower = message.to_ascii_lowercase();
+    let (image, message) = (image.to_owned(), message.to_owned());
+    if lower.contains("toomanyrequests") || lower.contains("rate limit") {
+        Some(Error::RateLimited { image, message })
+    } else if lower.contains("manifest unknown")
+        || (lower.starts_with("manifest for") && lower.contains("not found"))
+    {
+        Some(Error::ManifestNotFound { image, message })
+    } else if lower.contains("unauthorized")
+        || lower.contains("pull access denied")
+        || lower.contains("denied: requested access")
+    {
+        Some(Error::Unauthorized { image, message })
+    } else {
+        None
+    }
+}
+
+/// `error` of a failed pull of `image`, as the typed error of [pull_error] if the registry
+/// refused the pull, also when the pull was interrupted after it had started
+fn typed_pull_error(
+    image: &str,
+    error: Error,
+) -> Error {
+    match error {
+        Error::NotFound { ref message, .. } | Error::Fault { ref message, .. } => {
+            pull_error(image, message).unwrap_or(error)
+        }
+        Error::Interrupted { progress, source } => Error::Interrupted {
+            progress,
+            source: Box::new(typed_pull_error(image, *source)),
+        },
+        error => error,
+    }
+}
+
+/// The path of the push of `image` with `opts`
+fn push_path(
+    image: &str,
+    opts: &PushOptions,
+) -> Result<String> {
+    let mut path = vec![format!("/images/{}/push", image)];
+    if let Some(query) = opts.serialize()? {
+        path.push(query)
+    }
+    Ok(path.join("?"))
+}
+
+/// The message of an error the daemon reported in a progress stream, which is more detailed
+/// in `error_detail` if it is set
+pub(crate) fn fault_message(
+    error: String,
+    error_detail: ErrorDetail,
+) -> String {
+    if error_detail.message.is_empty() {
+        error
+    } else {
+        error_detail.message
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
+#[non_exhaustive]
 pub struct ProgressDetail {
     current: Option<u64>,
     total: Option<u64>,
 }
 
+impl ProgressDetail {
+    pub fn new(
+        current: Option<u64>,
+        total: Option<u64>,
+    ) -> Self {
+        ProgressDetail { current, total }
+    }
+
+    /// Bytes transferred so far
+    pub fn current(&self) -> Option<u64> {
+        self.current
+    }
+
+    /// Bytes to transfer in total
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+}
+
 /// Describes query parameters for DELETE /images/{name}
 /// https://docs.docker.com/engine/api/v1.42/#tag/Image/operation/ImageDelete
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct DeleteOptions {
-
-    params: HashMap<&'static str, String>,
+    force: Option<bool>,
+    noprune: Option<bool>,
 }
 
 impl DeleteOptions {
@@ -1230,104 +4310,131 @@ impl DeleteOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct DeleteOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: DeleteOptions,
 }
 
 impl DeleteOptionsBuilder {
-
     /// Remove the image even if it is being used by stopped containers or has other tags
     pub fn force(mut self) -> Self {
-        self.params.insert("force", true.to_string());
+        self.options.force = Some(true);
         self
     }
 
     /// Do not delete untagged parent images
     pub fn noprune(mut self) -> Self {
-        self.params.insert("noprune", true.to_string());
+        self.options.noprune = Some(true);
         self
     }
 
-    pub fn build(&mut self) -> DeleteOptions {
-        DeleteOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> DeleteOptions {
+        self.options
     }
 }
 
 /// Describes query parameters for POST /images/prune
 /// https://docs.docker.com/engine/api/v1.42/#tag/Image/operation/ImagePrune
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct PruneOptions {
-
-    filters: HashMap<&'static str, Vec<String>>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl PruneOptions {
-
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Result<Option<String>> {
-        if self.filters.is_empty() {
-            Ok(None)
-        } else {
-            let value = serde_json::to_string(&self.filters).map_err(Error::from)?;
-
-            Ok(Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .append_pair("filters", &value)
-                    .finish(),
-            ))
-        }
+        query::encode(self)
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct PruneOptionsBuilder {
-    filters: HashMap<&'static str, Vec<String>>,
+    options: PruneOptions,
 }
 
 impl PruneOptionsBuilder {
-
     /// dangling=<boolean> When set to true (or 1), prune only unused and untagged images.
     /// When set to false (or 0), all unused images are pruned.
-    pub fn dangling(mut self, dangling: bool) -> Self {
-        self.filters.insert("dangling", vec![dangling.to_string()]);
+    pub fn dangling(
+        mut self,
+        dangling: bool,
+    ) -> Self {
+        self.options.filters.set("dangling", dangling.to_string());
         self
     }
 
     /// until=<string> Prune images created before this timestamp.
     /// The <timestamp> can be Unix timestamps, date formatted timestamps, or Go duration strings (e.g. 10m, 1h30m) computed relative to the daemon machine’s time.
-    pub fn until(mut self, until: String) -> Self {
-        self.filters.insert("until", vec![until]);
+    pub fn until(
+        mut self,
+        until: String,
+    ) -> Self {
+        self.options.filters.set("until", until);
         self
     }
 
-    /// label (label=<key>, label=<key>=<value>, label!=<key>, or label!=<key>=<value>) Prune images with (or without, in case label!=... is used) the specified labels.
-    pub fn add_label(mut self, label: String) -> Self {
-        if let Some(label_list) = self.filters.get_mut("label") {
-            label_list.push(label)
-        } else {
-            self.filters.insert("label", vec![label]);
-        }
+    /// Prune images created more than `age` ago, relative to the time of the daemon
+    pub fn older_than(
+        mut self,
+        age: Duration,
+    ) -> Self {
+        self.options
+            .filters
+            .set("until", duration::go_duration(age));
+        self
+    }
+
+    /// Prune images created before `timestamp`
+    #[cfg(feature = "chrono")]
+    pub fn created_before<Tz>(
+        mut self,
+        timestamp: &chrono::DateTime<Tz>,
+    ) -> Self
+    where
+        Tz: chrono::TimeZone,
+    {
+        self.options
+            .filters
+            .set("until", timestamp.timestamp().to_string());
+        self
+    }
+
+    /// Prune images created before `timestamp`
+    #[cfg(feature = "time")]
+    pub fn created_before(
+        mut self,
+        timestamp: &OffsetDateTime,
+    ) -> Self {
+        self.options
+            .filters
+            .set("until", timestamp.unix_timestamp().to_string());
+        self
+    }
 
+    /// label (label=<key>, label=<key>=<value>, label!=<key>, or label!=<key>=<value>) Prune images with (or without, in case label!=... is used) the specified labels.
+    pub fn add_label(
+        mut self,
+        label: String,
+    ) -> Self {
+        self.options.filters.add("label", label);
         self
     }
 
-    pub fn add_labels<T>(mut self, labels: T) -> Self where T: Into<Vec<String>> {
+    pub fn add_labels<T>(
+        mut self,
+        labels: T,
+    ) -> Self
+    where
+        T: Into<Vec<String>>,
+    {
         for label in labels.into() {
             self = self.add_label(label)
         }
@@ -1335,10 +4442,8 @@ impl PruneOptionsBuilder {
         self
     }
 
-    pub fn build(&mut self) -> PruneOptions {
-        PruneOptions {
-            filters: self.filters.clone(),
-        }
+    pub fn build(self) -> PruneOptions {
+        self.options
     }
 }
 
@@ -1346,6 +4451,1243 @@ impl PruneOptionsBuilder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn image_list_options_filters() {
+        let opts = ImageListOptions::builder()
+            .filter(vec![
+                ImageFilter::Reference("alpine:*".to_owned()),
+                ImageFilter::Before("alpine:3.14".to_owned()),
+                ImageFilter::Since("alpine:3.12".to_owned()),
+                ImageFilter::Dangling,
+            ])
+            .build();
+        let query = opts.serialize().unwrap().unwrap();
+        let (name, filters) = url::form_urlencoded::parse(query.as_bytes())
+            .next()
+            .unwrap();
+        assert_eq!(name, "filters");
+        let filters: HashMap<String, Vec<String>> = serde_json::from_str(&filters).unwrap();
+        assert_eq!(filters["reference"], ["alpine:*"]);
+        assert_eq!(filters["before"], ["alpine:3.14"]);
+        assert_eq!(filters["since"], ["alpine:3.12"]);
+        assert_eq!(filters["dangling"], ["true"]);
+    }
+
+    #[test]
+    fn image_diff() {
+        let step = |created_by: &str, size: u64| {
+            serde_json::json!({
+                "Id": "<missing>",
+                "Created": 1625140000,
+                "CreatedBy": created_by,
+                "Size": size,
+            })
+        };
+        let cmd = step("/bin/sh -c #(nop)  CMD [\"/bin/sh\"]", 0);
+        let add = step("/bin/sh -c #(nop) ADD file:1 in / ", 100);
+        let base: Vec<History> = serde_json::from_value(serde_json::json!([cmd, add])).unwrap();
+        let app: Vec<History> = serde_json::from_value(serde_json::json!([
+            step("/bin/sh -c apk add curl", 50),
+            step("/bin/sh -c mkdir /app", 0),
+            cmd,
+            add,
+        ]))
+        .unwrap();
+        let layers =
+            |digests: &[&str]| -> Vec<String> { digests.iter().map(|d| d.to_string()).collect() };
+
+        let diff = ImageDiff::new(
+            (&layers(&["sha256:base"]), &base),
+            (&layers(&["sha256:base", "sha256:app", "sha256:curl"]), &app),
+        );
+        assert!(diff.only_in_a.is_empty());
+        assert_eq!(diff.shared_size(), Some(100));
+        assert_eq!(diff.only_in_b_size(), Some(50));
+        assert_eq!(
+            diff.only_in_b[1].created_by.as_deref(),
+            Some("/bin/sh -c apk add curl")
+        );
+
+        // without a history matching the layers, sizes are unknown
+        let diff = ImageDiff::new(
+            (&layers(&["sha256:base"]), &base),
+            (&layers(&["sha256:other"]), &[]),
+        );
+        assert_eq!(diff.only_in_a_size(), Some(100));
+        assert_eq!(diff.only_in_b[0].size, None);
+        assert_eq!(diff.only_in_b_size(), None);
+    }
+
+    #[test]
+    fn pull_progress_on_error() {
+        let chunks: Vec<Result<ImageBuildChunk>> = vec![
+            serde_json::from_str(r#"{"status":"Pulling fs layer","id":"a1"}"#).map_err(Into::into),
+            serde_json::from_str(
+                r#"{"status":"Downloading","id":"a1","progressDetail":{"current":512,"total":2048}}"#,
+            )
+            .map_err(Into::into),
+            serde_json::from_str(
+                r#"{"status":"Downloading","id":"b2","progressDetail":{"current":100,"total":200}}"#,
+            )
+            .map_err(Into::into),
+            Err(Error::InvalidResponse("connection lost".into())),
+        ];
+        let stream = errors::track_progress(
+            futures_util::stream::iter(chunks),
+            ImageBuildChunk::record_progress,
+        );
+        let result: Result<Vec<_>> = futures::executor::block_on(stream.try_collect());
+
+        let progress = result.unwrap_err().progress().cloned().unwrap();
+        assert_eq!(progress.bytes, 612);
+        assert_eq!(progress.layer.as_deref(), Some("b2"));
+        assert_eq!(progress.step, None);
+    }
+
+    #[test]
+    fn pull_reference() {
+        let reference = |image: &str, tag: Option<&str>| {
+            let mut builder = PullOptions::builder().image(image);
+            if let Some(tag) = tag {
+                builder = builder.tag(tag);
+            }
+            builder.build().reference().unwrap().to_string()
+        };
+        assert_eq!(reference("alpine", None), "alpine:latest");
+        assert_eq!(reference("alpine", Some("3.14")), "alpine:3.14");
+        assert_eq!(reference("alpine:3.13", Some("3.14")), "alpine:3.14");
+        assert_eq!(reference("alpine:3.13", Some("")), "alpine:3.13");
+        assert_eq!(reference("alpine", Some("sha256:abc")), "alpine@sha256:abc");
+        assert_eq!(
+            reference("registry.example.com:5000/app", Some("")),
+            "registry.example.com:5000/app:latest"
+        );
+        assert_eq!(reference("alpine@sha256:abc", None), "alpine@sha256:abc");
+    }
+
+    #[test]
+    fn pull_verified() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/distribution/alpine:3.14/json",
+                MockResponse::json(&serde_json::json!({
+                    "Descriptor": {
+                        "mediaType": "application/vnd.docker.distribution.manifest.list.v2+json",
+                        "digest": "sha256:abc",
+                        "size": 1638,
+                    },
+                    "Platforms": [{"architecture": "amd64", "os": "linux"}],
+                })),
+            )
+            .respond(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            )
+            .respond(
+                "POST",
+                "/images/alpine@sha256:abc/tag",
+                MockResponse::new(201, ""),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let pull = |allowed: &'static str| {
+            let opts = PullOptions::builder()
+                .image("alpine")
+                .tag("3.14")
+                .platform("linux/arm64")
+                .auth(RegistryAuth::builder().username("user").build())
+                .verify(PullVerifier::new(move |distribution| async move {
+                    match distribution.descriptor.digest.as_str() {
+                        digest if digest == allowed => Ok(()),
+                        digest => Err(format!("{} is not allowed", digest)),
+                    }
+                }))
+                .build();
+            futures::executor::block_on(docker.images().pull(&opts).try_collect::<Vec<_>>())
+        };
+
+        match pull("sha256:def") {
+            Err(Error::PullRejected { digest, reason, .. }) => {
+                assert_eq!(digest, "sha256:abc");
+                assert_eq!(reason, "sha256:abc is not allowed");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(daemon.requests().len(), 1);
+
+        assert!(!pull("sha256:abc").unwrap().is_empty());
+        let paths: Vec<_> = daemon.requests()[1..]
+            .iter()
+            .map(|request| request.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "/distribution/alpine:3.14/json",
+                "/images/create?fromImage=alpine&tag=sha256%3Aabc&platform=linux%2Farm64",
+                "/images/alpine@sha256:abc/tag?repo=alpine&tag=3.14",
+            ]
+        );
+        // the manifest is resolved with the credentials of the pull
+        assert!(daemon.requests()[1].headers.contains_key("X-Registry-Auth"));
+    }
+
+    #[test]
+    fn tag_returns_reference() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond("POST", "/images/alpine/tag", MockResponse::new(201, ""));
+        let docker = Docker::mock(daemon.clone());
+
+        let reference = futures::executor::block_on(docker.images().tag(
+            "alpine",
+            "registry.example.com/alpine",
+            "3.14",
+        ))
+        .unwrap();
+        assert_eq!(reference, "registry.example.com/alpine:3.14");
+
+        let opts = TagOptions::builder().repo("mirror/alpine").build();
+        let reference =
+            futures::executor::block_on(docker.images().get("alpine").tag(&opts)).unwrap();
+        assert_eq!(reference, "mirror/alpine:latest");
+
+        let paths: Vec<_> = daemon.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/images/alpine/tag?repo=registry.example.com%2Falpine&tag=3.14",
+                "/images/alpine/tag?repo=mirror%2Falpine",
+            ]
+        );
+    }
+
+    #[test]
+    fn distribution_inspect() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/distribution/alpine:3.14/json",
+            MockResponse::json(&serde_json::json!({
+                "Descriptor": {
+                    "mediaType": "application/vnd.docker.distribution.manifest.list.v2+json",
+                    "digest": "sha256:abc",
+                    "size": 1638,
+                },
+                "Platforms": [
+                    { "architecture": "amd64", "os": "linux" },
+                    { "architecture": "arm", "os": "linux", "variant": "v7" },
+                ],
+            })),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let auth = RegistryAuth::builder().username("user").build();
+
+        let distribution = futures::executor::block_on(
+            docker
+                .images()
+                .distribution_inspect("alpine:3.14", Some(&auth)),
+        )
+        .unwrap();
+        assert_eq!(distribution.descriptor.digest, "sha256:abc");
+        assert_eq!(distribution.descriptor.size, 1638);
+        let platforms: Vec<_> = distribution
+            .platforms
+            .unwrap()
+            .iter()
+            .map(Platform::to_string)
+            .collect();
+        assert_eq!(platforms, ["linux/amd64", "linux/arm/v7"]);
+        assert!(daemon.requests()[0].headers.contains_key("X-Registry-Auth"));
+    }
+
+    #[test]
+    fn pull_pinned_digest() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let mut details = fixtures::image_details("sha256:1a2b", "alpine:3.14");
+        details["RepoDigests"] = serde_json::json!(["alpine@sha256:abc"]);
+        // the same manifest, but of another repository
+        let mut mirrored = details.clone();
+        mirrored["RepoDigests"] = serde_json::json!(["mirror.example.org/alpine@sha256:fed"]);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            )
+            .respond(
+                "GET",
+                "/images/alpine@sha256:abc/json",
+                MockResponse::json(&details),
+            )
+            .respond(
+                "GET",
+                "/images/alpine@sha256:fed/json",
+                MockResponse::json(&mirrored),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let pull = |digest| {
+            let opts = PullOptions::builder()
+                .image("alpine")
+                .digest(digest)
+                .tag("3.14")
+                .build();
+            futures::executor::block_on(docker.images().pull(&opts).try_collect::<Vec<_>>())
+        };
+
+        assert!(!pull("sha256:abc").unwrap().is_empty());
+        assert_eq!(
+            daemon.requests()[0].path,
+            "/images/create?fromImage=alpine&tag=sha256%3Aabc"
+        );
+
+        match pull("sha256:def") {
+            Err(Error::DigestMismatch {
+                image,
+                repo_digests,
+                ..
+            }) => {
+                assert_eq!(image, "alpine@sha256:def");
+                assert!(repo_digests.is_empty());
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        match pull("sha256:fed") {
+            Err(Error::DigestMismatch { repo_digests, .. }) => {
+                assert_eq!(repo_digests, ["mirror.example.org/alpine@sha256:fed"]);
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn typed_pull_errors() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "POST",
+                "/images/create",
+                MockResponse::error(
+                    404,
+                    "manifest for alpine:nope not found: manifest unknown: manifest unknown",
+                ),
+            )
+            .respond_once(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&[serde_json::json!({
+                    "error": "toomanyrequests: You have reached your pull rate limit.",
+                    "errorDetail": { "message": "toomanyrequests: You have reached your pull rate limit." },
+                })]),
+            )
+            .respond_once(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&[serde_json::json!({
+                    "error": "no space left on device",
+                    "errorDetail": { "message": "no space left on device" },
+                })]),
+            );
+        let docker = Docker::mock(daemon);
+        let pull = |tag| {
+            let opts = PullOptions::builder().image("alpine").tag(tag).build();
+            futures::executor::block_on(docker.images().pull(&opts).try_collect::<Vec<_>>())
+        };
+
+        match pull("nope") {
+            Err(e @ Error::ManifestNotFound { .. }) => assert!(e.is_not_found()),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match pull("3.14") {
+            Err(Error::RateLimited { image, .. }) => assert_eq!(image, "alpine:3.14"),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(matches!(
+            pull("3.14").unwrap()[..],
+            [ImageBuildChunk::Error { .. }]
+        ));
+        assert!(matches!(
+            pull_error("app", "pull access denied for app, repository does not exist or may require 'docker login'"),
+            Some(Error::Unauthorized { .. })
+        ));
+
+        // the registry may refuse a layer after the pull started
+        let mut progress = errors::StreamProgress::default();
+        progress.record_layer("a", |_| ());
+        let interrupted = Error::from_status(
+            hyper::StatusCode::NOT_FOUND,
+            "manifest unknown".to_owned(),
+            Method::POST,
+            "/images/create".to_owned(),
+        )
+        .interrupted(&progress);
+        match typed_pull_error("alpine:3.14", interrupted) {
+            e @ Error::Interrupted { .. } => {
+                assert!(e.is_not_found());
+                assert!(matches!(
+                    std::error::Error::source(&e).and_then(|source| source.downcast_ref::<Error>()),
+                    Some(Error::ManifestNotFound { .. })
+                ));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn pull_with_limit() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let downloading = |id, current| {
+            serde_json::json!({
+                "status": "Downloading",
+                "id": id,
+                "progressDetail": { "current": current, "total": 600 },
+            })
+        };
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/images/create",
+            MockResponse::json_stream(&[
+                downloading("a", 100),
+                downloading("a", 300),
+                // extractions report the uncompressed size, which doesn't count
+                serde_json::json!({
+                    "status": "Extracting",
+                    "id": "a",
+                    "progressDetail": { "current": 100, "total": 5000 },
+                }),
+                downloading("b", 100),
+                downloading("b", 200),
+            ]),
+        );
+        let docker = Docker::mock(daemon);
+        let opts = PullOptions::builder().image("app").tag("1.0").build();
+        let pull = |limit| {
+            futures::executor::block_on(
+                docker
+                    .images()
+                    .pull_with_limit(&opts, limit)
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let chunks = pull(1000);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(
+            chunks[0].as_ref().unwrap().image_layer_bytes(),
+            Some(("a".to_owned(), 600))
+        );
+        assert_eq!(chunks[2].as_ref().unwrap().image_layer_bytes(), None);
+        match &chunks[3] {
+            Err(Error::PullTooLarge { image, limit, size }) => {
+                assert_eq!((&image[..], *limit, *size), ("app:1.0", 1000, 1200));
+            }
+            chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+        assert!(pull(1200).into_iter().all(|chunk| chunk.is_ok()));
+    }
+
+    #[test]
+    fn pull_platform() {
+        use crate::mock::fixtures;
+
+        let opts = PullOptions::builder()
+            .image("alpine")
+            .platform("linux/arm64")
+            .build();
+        assert_eq!(
+            opts.serialize().unwrap().unwrap(),
+            "fromImage=alpine&tag=latest&platform=linux%2Farm64"
+        );
+
+        let mut details = fixtures::image_details("sha256:1a2b", "alpine:3.14");
+        details["Architecture"] = "arm".into();
+        details["Variant"] = "v7".into();
+        let details: ImageDetails = serde_json::from_value(details).unwrap();
+        assert_eq!(details.platform().to_string(), "linux/arm/v7");
+    }
+
+    #[test]
+    fn ensure_image() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let mut details = fixtures::image_details("sha256:1a2b", "alpine:3.14");
+        details["RepoDigests"] = serde_json::json!([
+            "registry.example.com/alpine@sha256:def",
+            "alpine@sha256:abc"
+        ]);
+        let mut mirrored = fixtures::image_details("sha256:3c4d", "mirror.example.com/app:1.0");
+        mirrored["RepoDigests"] = serde_json::json!(["registry.example.com/app@sha256:def"]);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "GET",
+                "/images/alpine:3.14/json",
+                MockResponse::error(404, "No such image: alpine:3.14"),
+            )
+            .respond(
+                "GET",
+                "/images/alpine:3.14/json",
+                MockResponse::json(&details),
+            )
+            .respond(
+                "GET",
+                "/images/docker.io/library/alpine:3.14/json",
+                MockResponse::json(&details),
+            )
+            .respond(
+                "GET",
+                "/images/mirror.example.com/app:1.0/json",
+                MockResponse::json(&mirrored),
+            )
+            .respond(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let images = docker.images();
+
+        futures::executor::block_on(async {
+            let err = images
+                .ensure("alpine:3.14", PullPolicy::Never, None)
+                .await
+                .unwrap_err();
+            assert!(err.is_not_found());
+
+            let image = images
+                .ensure("alpine:3.14", PullPolicy::IfNotPresent, None)
+                .await
+                .unwrap();
+            assert_eq!(
+                image,
+                EnsuredImage {
+                    id: "sha256:1a2b".into(),
+                    digest: Some("alpine@sha256:abc".to_owned()),
+                    pulled: false,
+                }
+            );
+
+            let image = images
+                .ensure("alpine:3.14", PullPolicy::Always, None)
+                .await
+                .unwrap();
+            assert!(image.pulled);
+
+            let image = images
+                .ensure("docker.io/library/alpine:3.14", PullPolicy::Never, None)
+                .await
+                .unwrap();
+            assert_eq!(image.digest.as_deref(), Some("alpine@sha256:abc"));
+
+            let image = images
+                .ensure("mirror.example.com/app:1.0", PullPolicy::Never, None)
+                .await
+                .unwrap();
+            assert_eq!(image.digest, None);
+        });
+        let pulls: Vec<_> = daemon
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == Method::POST)
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(pulls, ["/images/create?fromImage=alpine&tag=3.14"]);
+    }
+
+    #[test]
+    fn warm_cache() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/images/busybox/json",
+                MockResponse::json(&fixtures::image_details("sha256:1a2b", "busybox:latest")),
+            )
+            .respond_once(
+                "GET",
+                "/images/alpine:3.14/json",
+                MockResponse::error(404, "No such image: alpine:3.14"),
+            )
+            .respond(
+                "GET",
+                "/images/alpine:3.14/json",
+                MockResponse::json(&fixtures::image_details("sha256:3c4d", "alpine:3.14")),
+            )
+            .respond(
+                "POST",
+                "/images/create?fromImage=alpine&tag=3.14",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            )
+            .respond(
+                "POST",
+                "/images/create?fromImage=nginx&tag=latest",
+                MockResponse::json_stream(&[
+                    serde_json::json!({"status": "Pulling fs layer", "id": "b1"}),
+                    serde_json::json!({
+                        "status": "Downloading",
+                        "id": "b1",
+                        "progressDetail": {"current": 1, "total": 1_000_000},
+                    }),
+                    serde_json::json!({"status": "Pull complete", "id": "b1"}),
+                ]),
+            )
+            .respond_once(
+                "GET",
+                "/images/redis/json",
+                MockResponse::error(404, "No such image: redis"),
+            )
+            .respond(
+                "GET",
+                "/images/redis/json",
+                MockResponse::json(&fixtures::image_details("sha256:5e6f", "redis:latest")),
+            )
+            .respond(
+                "POST",
+                "/images/create?fromImage=redis&tag=latest",
+                MockResponse::json_stream(&[
+                    serde_json::json!({
+                        "status": "Downloading",
+                        "id": "c1",
+                        "progressDetail": {"current": 1, "total": 150_000},
+                    }),
+                    serde_json::json!({
+                        "status": "Extracting",
+                        "id": "c1",
+                        "progressDetail": {"current": 1, "total": 5_000_000},
+                    }),
+                    serde_json::json!({"status": "Pull complete", "id": "c1"}),
+                ]),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        // the budget nginx took is refunded once its pull is cancelled, which leaves enough
+        // for redis
+        let reports = futures::executor::block_on(docker.images().warm_cache(
+            vec!["busybox", "alpine:3.14", "nginx", "missing/app", "redis"],
+            1,
+            Some(3_000_000),
+        ));
+        let outcomes: Vec<_> = reports
+            .iter()
+            .map(|report| (report.reference.as_str(), &report.outcome))
+            .collect();
+        assert!(matches!(
+            outcomes[..],
+            [
+                ("busybox", WarmOutcome::Present),
+                ("alpine:3.14", WarmOutcome::Pulled { size: 5_610_000 }),
+                ("nginx", WarmOutcome::OverBudget),
+                ("missing/app", WarmOutcome::Failed(Error::NotFound { .. })),
+                ("redis", WarmOutcome::Pulled { .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn prune_options_until() {
+        let options = PruneOptionsBuilder::default()
+            .older_than(Duration::from_secs(24 * 60 * 60))
+            .build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "filters=%7B%22until%22%3A%5B%2286400s%22%5D%7D"
+        );
+    }
+
+    #[test]
+    fn build_from_files() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/build",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "stream": "Step 1/2 : FROM alpine\n" }),
+            ]),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let build = |opts: BuildOptionsBuilder| {
+            futures::executor::block_on(
+                docker
+                    .images()
+                    .build_from_files(
+                        &opts.build(),
+                        vec![
+                            ("Dockerfile", Bytes::from_static(b"FROM scratch\n")),
+                            ("app/main.sh", Bytes::from_static(b"echo hi\n")),
+                        ],
+                    )
+                    .try_collect::<Vec<_>>(),
+            )
+        };
+
+        let chunks = build(
+            BuildOptions::builder("unused")
+                .tag("app")
+                .set_skip_gzip(true)
+                .context_file("Dockerfile", "FROM alpine\nCOPY app /app\n"),
+        );
+        assert_eq!(chunks.unwrap().len(), 1);
+        let request = &daemon.requests()[0];
+        assert_eq!(request.path, "/build?t=app");
+        let mut archive = tar::Archive::new(&request.body[..]);
+        let files: Vec<(String, String)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = String::new();
+                io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+                (entry.path().unwrap().display().to_string(), contents)
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (
+                    "Dockerfile".to_owned(),
+                    "FROM alpine\nCOPY app /app\n".to_owned()
+                ),
+                ("app/main.sh".to_owned(), "echo hi\n".to_owned()),
+            ]
+        );
+
+        let too_large = build(BuildOptions::builder("unused").max_context_bytes(1024));
+        assert!(matches!(too_large, Err(Error::ContextTooLarge(_))));
+        assert_eq!(daemon.requests().len(), 1);
+    }
+
+    #[test]
+    fn build_and_wait() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let dir = std::env::temp_dir().join(format!("shiplift-wait-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Dockerfile"), "FROM alpine\n").unwrap();
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "POST",
+                "/build",
+                MockResponse::json_stream(&fixtures::build_output("sha256:1a2b")),
+            )
+            .respond_once(
+                "POST",
+                "/build",
+                MockResponse::json_stream(&[
+                    serde_json::json!({ "stream": "Step 1/2 : FROM alpine\n" }),
+                    serde_json::json!({
+                        "error": "The command '/bin/sh -c false' returned a non-zero code: 1",
+                        "errorDetail": { "message": "The command '/bin/sh -c false' returned a non-zero code: 1" },
+                    }),
+                ]),
+            );
+        let docker = Docker::mock(daemon);
+        let opts = BuildOptions::builder(dir.to_str().unwrap()).build();
+        let built = futures::executor::block_on(docker.images().build_and_wait(&opts));
+        let failed = futures::executor::block_on(docker.images().build_and_wait(&opts));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let built = built.unwrap();
+        assert_eq!(built.id, ImageId::from("sha256:1a2b"));
+        assert_eq!(built.logs.len(), 4);
+        assert_eq!(built.logs[0], "Step 1/2 : FROM alpine\n");
+        match failed {
+            Err(Error::StreamFault { message, .. }) => assert!(message.contains("non-zero code")),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn build_args() {
+        let mut args = HashMap::new();
+        args.insert("VERSION".to_owned(), "1.2".to_owned());
+        let options = BuildOptions::builder(".")
+            .tag("app")
+            .build_args(args)
+            .build_args(vec![("DEBUG", "true")])
+            .build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "t=app&buildargs=%7B%22DEBUG%22%3A%22true%22%2C%22VERSION%22%3A%221.2%22%7D"
+        );
+        assert_eq!(
+            BuildOptions::builder(".").build().serialize().unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn build_tags_and_labels() {
+        let mut labels = HashMap::new();
+        labels.insert("x".to_owned(), "y".to_owned());
+        let options = BuildOptions::builder(".")
+            .tag("a")
+            .tag("b:1.0")
+            .labels(labels)
+            .build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "t=a&t=b%3A1.0&labels=%7B%22x%22%3A%22y%22%7D"
+        );
+    }
+
+    #[test]
+    fn build_cache_from_and_target() {
+        let options = BuildOptions::builder(".")
+            .cache_from(vec!["registry.example.com/app:cache"])
+            .target("builder")
+            .build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "cachefrom=%5B%22registry.example.com%2Fapp%3Acache%22%5D&target=builder"
+        );
+
+        let options = BuildOptions::builder(".").platform("linux/arm64").build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "platform=linux%2Farm64"
+        );
+    }
+
+    #[test]
+    fn build_squash_and_extra_hosts() {
+        let options = BuildOptions::builder(".")
+            .squash(true)
+            .extra_hosts(vec!["db:10.0.0.2", "cache:10.0.0.3"])
+            .build();
+        assert_eq!(
+            options.serialize().unwrap().unwrap(),
+            "squash=true&extrahosts=db%3A10.0.0.2&extrahosts=cache%3A10.0.0.3"
+        );
+    }
+
+    #[test]
+    fn build_squash_requires_experimental() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/build",
+            MockResponse::error(400, "squash is only supported with experimental mode"),
+        );
+        let docker = Docker::mock(daemon);
+
+        let options = BuildOptions::builder(".").squash(true).build();
+        let files = vec![("Dockerfile", "FROM alpine\n")];
+        let err = futures::executor::block_on(
+            docker.images().build_from_files(&options, files).try_next(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::ExperimentalRequired(ref message) if message.contains("squash")),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn export_rootfs() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        fn tar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+            let mut builder = tar::Builder::new(Vec::new());
+            for (path, data) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, path, *data).unwrap();
+            }
+            builder.into_inner().unwrap()
+        }
+        // larger than a chunk, so that the filesystem is streamed in several
+        let data = vec![7; 3 * ROOTFS_CHUNK_SIZE / 2];
+        let layer = tar(&[("data", &data)]);
+        let export = tar(&[
+            ("manifest.json", br#"[{"Layers":["1/layer.tar"]}]"#),
+            ("1/layer.tar", &layer),
+        ]);
+
+        let daemon = MockDaemon::new();
+        daemon.respond("GET", "/images/app/get", MockResponse::new(200, export));
+        let docker = Docker::mock(daemon);
+        let chunks: Vec<Vec<u8>> = docker
+            .images()
+            .get("app")
+            .export_rootfs()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks[0].len(), ROOTFS_CHUNK_SIZE);
+        let rootfs = chunks.concat();
+        let mut archive = tar::Archive::new(&rootfs[..]);
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.path().unwrap().to_str(), Some("data"));
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        assert_eq!(content, data);
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/images/app/get",
+            MockResponse::new(200, tar(&[("1/layer.tar", &layer)])),
+        );
+        let docker = Docker::mock(daemon);
+        let error = docker
+            .images()
+            .get("app")
+            .export_rootfs()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("no manifest.json"), "{}", error);
+    }
+
+    #[test]
+    fn build_with_buildkit() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let dir = std::env::temp_dir().join(format!("shiplift-buildkit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Dockerfile"), "FROM alpine\n").unwrap();
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/build",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "id": "moby.buildkit.trace", "aux": "" }),
+                serde_json::json!({ "id": "moby.image.id", "aux": { "ID": "sha256:1a2b" } }),
+            ]),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let options = BuildOptions::builder(dir.to_str().unwrap())
+            .tag("app")
+            .session("s1")
+            .build();
+        let chunks: Vec<_> = futures::executor::block_on(
+            docker.images().build_with_buildkit(&options).try_collect(),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(&chunks[0], BuildKitChunk::Status(status) if status.vertexes.is_empty()));
+        assert!(matches!(&chunks[1], BuildKitChunk::ImageId(id) if id == "sha256:1a2b"));
+        assert_eq!(
+            daemon.requests()[0].path,
+            "/build?t=app&session=s1&version=2"
+        );
+        assert_eq!(
+            options.serialize().unwrap().as_deref(),
+            Some("t=app&session=s1")
+        );
+    }
+
+    #[test]
+    fn build_with_session() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond("POST", "/session", MockResponse::new(200, ""));
+        let docker = Docker::mock(daemon.clone());
+        let session = Session::new().secret("token", "s3cr3t");
+        let id = session.id().to_owned();
+        let result = futures::executor::block_on(
+            docker
+                .images()
+                .build_with_session(&BuildOptions::builder(".").build(), session)
+                .try_collect::<Vec<_>>(),
+        );
+
+        // the mock can't switch protocols, so the build is never started
+        assert!(matches!(result, Err(Error::ConnectionNotUpgraded)));
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 1);
+        let headers = &requests[0].headers;
+        assert_eq!(headers["upgrade"], "h2c");
+        assert_eq!(headers["x-docker-expose-session-uuid"], id.as_str());
+        assert!(headers
+            .get_all("x-docker-expose-session-grpc-method")
+            .iter()
+            .any(|method| method == "/moby.buildkit.secrets.v1.Secrets/GetSecret"));
+    }
+
+    #[test]
+    fn report_progress_events() {
+        let chunks: Vec<ImageBuildChunk> = serde_json::from_value(serde_json::json!([
+            { "status": "Pulling fs layer", "id": "3f4d", "progressDetail": {} },
+            { "status": "Downloading", "id": "3f4d", "progressDetail": { "current": 512, "total": 1024 }, "progress": "[==>  ]" },
+            { "status": "Extracting", "id": "3f4d", "progressDetail": { "current": 2048, "total": 4096 } },
+            { "status": "Pull complete", "id": "3f4d", "progressDetail": {} },
+            { "status": "Digest: sha256:abc" },
+            { "stream": "Step 1/2 : FROM alpine\n" },
+            { "stream": "\n" },
+            { "aux": { "ID": "sha256:1a2b" } },
+        ]))
+        .unwrap();
+        let mut reporter = ProgressReporter::new();
+        let events: Vec<_> = chunks
+            .iter()
+            .filter_map(|chunk| reporter.report(chunk))
+            .map(|event| (event.layer_id, event.phase, event.current, event.total))
+            .collect();
+        let layer = Some("3f4d".to_owned());
+        assert_eq!(
+            events,
+            [
+                (layer.clone(), ProgressPhase::Waiting, None, None),
+                (
+                    layer.clone(),
+                    ProgressPhase::Downloading,
+                    Some(512),
+                    Some(1024)
+                ),
+                (
+                    layer.clone(),
+                    ProgressPhase::Extracting,
+                    Some(2048),
+                    Some(4096)
+                ),
+                (layer, ProgressPhase::Complete, Some(1024), Some(1024)),
+                (
+                    None,
+                    ProgressPhase::Other("Digest: sha256:abc".to_owned()),
+                    None,
+                    None
+                ),
+                (
+                    None,
+                    ProgressPhase::Step("Step 1/2 : FROM alpine".to_owned()),
+                    None,
+                    None
+                ),
+                (
+                    None,
+                    ProgressPhase::Built("sha256:1a2b".to_owned()),
+                    None,
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_streams_context() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let dir = std::env::temp_dir().join(format!("shiplift-streamed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Dockerfile"), "FROM alpine\n").unwrap();
+        std::fs::write(dir.join("data"), vec![7; 512 * 1024]).unwrap();
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/build",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "stream": "Step 1/1 : FROM alpine\n" }),
+            ]),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let build = |opts: BuildOptionsBuilder| {
+            futures::executor::block_on(
+                docker.images().build(&opts.build()).try_collect::<Vec<_>>(),
+            )
+        };
+        let path = dir.to_str().unwrap();
+        let chunks = build(BuildOptions::builder(path).set_skip_gzip(true));
+        let too_large = build(BuildOptions::builder(path).max_context_bytes(1024));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(chunks.unwrap().len(), 1);
+        let context = &daemon.requests()[0].body;
+        let mut archive = tar::Archive::new(&context[..]);
+        let mut names: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Dockerfile", "data"]);
+        assert!(matches!(too_large, Err(Error::ContextTooLarge(_))));
+    }
+
+    #[test]
+    fn deserialize_oci_metadata() {
+        let mut details = crate::mock::fixtures::image_details("sha256:1a2b", "app:1.0");
+        details["OsVersion"] = serde_json::json!("10.0.17763.1935");
+        details["RootFS"] = serde_json::json!({
+            "Type": "layers",
+            "Layers": ["sha256:e2eb06d8af82", "sha256:3f4d"],
+        });
+        details["GraphDriver"] = serde_json::json!({
+            "Name": "overlay2",
+            "Data": { "MergedDir": "/var/lib/docker/overlay2/1a2b/merged" },
+        });
+        details["Metadata"] = serde_json::json!({ "LastTagTime": "0001-01-01T00:00:00Z" });
+        details["Config"]["Healthcheck"] = serde_json::json!({
+            "Test": ["CMD-SHELL", "curl -f http://localhost/"],
+            "Interval": 30_000_000_000u64,
+            "Retries": 3,
+        });
+        details["Config"]["StopSignal"] = serde_json::json!("SIGQUIT");
+        details["Config"]["Volumes"] = serde_json::json!({ "/data": {} });
+        details["Config"]["Shell"] = serde_json::json!(["/bin/bash", "-c"]);
+
+        let details: ImageDetails = serde_json::from_value(details).unwrap();
+        assert_eq!(details.os_version.as_deref(), Some("10.0.17763.1935"));
+        assert_eq!(
+            details.root_fs.unwrap().layers.unwrap(),
+            ["sha256:e2eb06d8af82", "sha256:3f4d"]
+        );
+        assert_eq!(details.graph_driver.unwrap().name, "overlay2");
+        assert!(details.metadata.unwrap().last_tag_time.is_some());
+        let healthcheck = details.config.healthcheck.unwrap();
+        assert_eq!(healthcheck.interval, Some(30_000_000_000));
+        assert_eq!(healthcheck.timeout, None);
+        assert_eq!(details.config.stop_signal.as_deref(), Some("SIGQUIT"));
+        assert!(details.config.volumes.unwrap().contains_key("/data"));
+        assert_eq!(details.config.shell.unwrap(), ["/bin/bash", "-c"]);
+    }
+
+    #[test]
+    fn deserialize_history() {
+        let history: Vec<History> = serde_json::from_value(serde_json::json!([
+            {
+                "Id": "sha256:1a2b",
+                "Created": 1625140000,
+                "CreatedBy": "RUN /bin/sh -c apk add curl # buildkit",
+                "Tags": ["app:1.0", "app:latest"],
+                "Size": 5_242_880,
+                "Comment": "buildkit.dockerfile.v0",
+            },
+            {
+                "Id": "<missing>",
+                "Created": 0,
+                "CreatedBy": "COPY app /app # buildkit",
+                "Tags": null,
+                "Size": 1024,
+                "Comment": "",
+            },
+            {
+                "Id": "<missing>",
+                "Created": -62135596800i64,
+                "CreatedBy": "/bin/sh -c #(nop)  CMD [\"/bin/sh\"]",
+                "Size": 0,
+            },
+        ]))
+        .unwrap();
+
+        assert!(history[0].created.is_some());
+        assert_eq!(history[0].tags, ["app:1.0", "app:latest"]);
+        assert_eq!(history[0].size, 5_242_880);
+        assert_eq!(history[0].comment, "buildkit.dockerfile.v0");
+        assert!(history[1].created.is_none());
+        assert!(history[1].tags.is_empty());
+        assert!(history[2].created.is_none());
+        assert_eq!(history[2].comment, "");
+    }
+
+    #[test]
+    fn deserialize_delete_status() {
+        let statuses: Vec<Status> = serde_json::from_str(
+            r#"[
+                {"Untagged": "alpine:3.14"},
+                {"Untagged": "alpine@sha256:abc"},
+                {"Deleted": "sha256:1a2b"},
+                {"Untagged": "", "Deleted": "sha256:3c4d"},
+                {"Untagged": "app:1.0", "Deleted": null},
+                {"Replaced": "sha256:5e6f"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            statuses,
+            [
+                Status::Untagged("alpine:3.14".to_owned()),
+                Status::Untagged("alpine@sha256:abc".to_owned()),
+                Status::Deleted("sha256:1a2b".to_owned()),
+                Status::Deleted("sha256:3c4d".to_owned()),
+                Status::Untagged("app:1.0".to_owned()),
+                Status::Other("Replaced".to_owned(), "sha256:5e6f".to_owned()),
+            ]
+        );
+        assert!(serde_json::from_str::<Status>("{}").is_err());
+
+        let status = Status::Deleted("sha256:1a2b".to_owned());
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"Deleted":"sha256:1a2b"}"#
+        );
+    }
+
+    #[test]
+    fn deserialize_prune_info() {
+        let info: PruneInfo = serde_json::from_value(serde_json::json!({
+            "ImagesDeleted": [
+                { "Untagged": "app:old" },
+                { "Deleted": "sha256:1a2b" },
+            ],
+            "SpaceReclaimed": 5_242_880,
+        }))
+        .unwrap();
+        assert!(matches!(&info.images_deleted[0], Status::Untagged(tag) if tag == "app:old"));
+        assert!(matches!(&info.images_deleted[1], Status::Deleted(id) if id == "sha256:1a2b"));
+        assert_eq!(info.space_reclaimed, 5_242_880);
+
+        let info: PruneInfo = serde_json::from_value(serde_json::json!({
+            "ImagesDeleted": null,
+            "SpaceReclaimed": 0,
+        }))
+        .unwrap();
+        assert!(info.images_deleted.is_empty());
+    }
+
+    #[test]
+    fn export_to_writer() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/images/alpine/get",
+            MockResponse::new(200, vec![7; 1000]),
+        );
+        let docker = Docker::mock(daemon);
+        let mut tarball = futures::io::Cursor::new(Vec::new());
+        let mut progress = Vec::new();
+        let written = futures::executor::block_on(
+            docker
+                .images()
+                .get("alpine")
+                .export_to_writer(&mut tarball, |written| progress.push(written)),
+        )
+        .unwrap();
+
+        assert_eq!(written, 1000);
+        assert_eq!(tarball.into_inner(), vec![7; 1000]);
+        assert_eq!(progress.last(), Some(&1000));
+    }
+
     /// Test registry auth with token
     #[test]
     fn registry_auth_token() {
@@ -1369,6 +5711,19 @@ mod tests {
         );
     }
 
+    /// Test registry auth with characters that are escaped in JSON
+    #[test]
+    fn registry_auth_password_escaped() {
+        let options = RegistryAuth::builder()
+            .username("user_abc")
+            .password("pass\"word\\abc")
+            .build();
+        assert_eq!(
+            base64::encode(r#"{"username":"user_abc","password":"pass\"word\\abc"}"#),
+            options.serialize()
+        );
+    }
+
     /// Test registry auth with all fields
     #[test]
     fn registry_auth_password_all() {
@@ -1385,4 +5740,538 @@ mod tests {
             options.serialize()
         );
     }
+
+    #[test]
+    fn container_config_env() {
+        let config = ContainerConfig {
+            attach_stderr: false,
+            attach_stdin: false,
+            attach_stdout: false,
+            cmd: None,
+            domainname: String::new(),
+            entrypoint: None,
+            env: Some(vec![
+                "PATH=/usr/bin".to_owned(),
+                "OPTS=-Dkey=value".to_owned(),
+                "EMPTY=".to_owned(),
+                "UNSET".to_owned(),
+            ]),
+            exposed_ports: None,
+            hostname: String::new(),
+            image: String::new(),
+            labels: None,
+            on_build: None,
+            open_stdin: false,
+            stdin_once: false,
+            tty: false,
+            user: String::new(),
+            working_dir: String::new(),
+            healthcheck: None,
+            stop_signal: None,
+            volumes: None,
+            shell: None,
+        };
+
+        let env = config.env();
+        assert_eq!(env["PATH"], "/usr/bin");
+        assert_eq!(env["OPTS"], "-Dkey=value");
+        assert_eq!(env["EMPTY"], "");
+        assert_eq!(env["UNSET"], "");
+
+        let vars: Vec<_> = config.env_vars().collect();
+        assert_eq!(vars[1], EnvVar::new("OPTS", "-Dkey=value"));
+        assert_eq!(vars[2], EnvVar::new("EMPTY", ""));
+        assert_eq!(vars[3].value, None);
+        assert_eq!(&EnvVar::to_env_list(vars), config.env.as_ref().unwrap());
+    }
+
+    #[test]
+    fn import_loaded_images() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/images/load",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "stream": "Loaded image: alpine:3.14\n" }),
+                serde_json::json!({ "stream": "Loaded image: app:1.0\n" }),
+                serde_json::json!({ "stream": "Loaded image ID: sha256:1a2b\n" }),
+            ]),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let opts = ImportOptions::builder().quiet(true).build();
+
+        let loaded = futures::executor::block_on(
+            docker
+                .images()
+                .import_with_options(&b"tarball"[..], &opts)
+                .finish(),
+        )
+        .unwrap();
+        assert_eq!(
+            loaded,
+            LoadedImage {
+                tags: vec!["alpine:3.14".to_owned(), "app:1.0".to_owned()],
+                ids: vec!["sha256:1a2b".to_owned()],
+            }
+        );
+        assert_eq!(daemon.requests()[0].path, "/images/load?quiet=true");
+    }
+
+    #[test]
+    fn import_from_async_read() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "POST",
+            "/images/load",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "stream": "Loaded image: app:1.0\n" }),
+            ]),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let tarball = vec![7; 3 * IMPORT_CHUNK_SIZE + 1];
+
+        let chunks = futures::executor::block_on(
+            docker
+                .images()
+                .import_from_async_read(futures::io::Cursor::new(tarball.clone()))
+                .try_collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(daemon.requests()[0].body, tarball);
+    }
+
+    #[test]
+    fn copy_to() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let source = MockDaemon::new();
+        source.respond(
+            "GET",
+            "/images/get",
+            MockResponse::new(200, b"archive".to_vec()),
+        );
+        let target = MockDaemon::new();
+        target.respond(
+            "POST",
+            "/images/load",
+            MockResponse::json_stream(&[
+                serde_json::json!({ "stream": "Loaded image: app:1.0\n" }),
+            ]),
+        );
+        let (source_docker, target_docker) =
+            (Docker::mock(source.clone()), Docker::mock(target.clone()));
+
+        let copy = futures::executor::block_on(
+            source_docker
+                .images()
+                .copy_to(&target_docker, vec!["app:1.0"]),
+        )
+        .unwrap();
+        assert_eq!(copy.size, 7);
+        assert_eq!(
+            copy.digest,
+            "sha256:0eb3e36bfb24dcd9bb1d1bece1531216b59539a8fde17ee80224af0653c92aa3"
+        );
+        assert_eq!(copy.loaded.tags, ["app:1.0"]);
+        assert_eq!(source.requests()[0].path, "/images/get?names=app%3A1.0");
+        assert_eq!(&target.requests()[0].body[..], b"archive");
+    }
+
+    #[test]
+    fn search_options() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/images/search",
+            MockResponse::json(&serde_json::json!([{
+                "description": "A minimal image",
+                "is_official": true,
+                "is_automated": false,
+                "name": "alpine",
+                "star_count": 10000,
+            }])),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let opts = SearchOptions::builder("alpine")
+            .limit(5)
+            .is_official(true)
+            .stars(100)
+            .auth(RegistryAuth::token("abc"))
+            .build();
+
+        let results = futures::executor::block_on(docker.images().search_with(&opts)).unwrap();
+        assert_eq!(results[0].name, "alpine");
+        futures::executor::block_on(docker.images().search("redis")).unwrap();
+        let requests = daemon.requests();
+        assert_eq!(requests[1].path, "/images/search?term=redis");
+        let request = &requests[0];
+        assert_eq!(
+            request.path,
+            "/images/search?term=alpine&limit=5&filters=%7B%22is-official%22%3A%5B%22true%22%5D%2C%22stars%22%3A%5B%22100%22%5D%7D"
+        );
+        assert!(request.headers.contains_key("X-Registry-Auth"));
+    }
+
+    #[test]
+    fn pull_many() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "POST",
+                "/images/create?fromImage=missing&tag=latest",
+                MockResponse::error(404, "manifest for missing:latest not found"),
+            )
+            .respond(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            );
+        let docker = Docker::mock(daemon);
+        let opts = ["alpine", "busybox", "missing"]
+            .iter()
+            .map(|image| PullOptions::builder().image(*image).build())
+            .collect();
+
+        let chunks: Vec<_> =
+            futures::executor::block_on(docker.images().pull_many(opts, 2).collect());
+        let count = |image: &str| chunks.iter().filter(|chunk| chunk.image == image).count();
+        assert_eq!(count("alpine:latest"), 5);
+        assert_eq!(count("busybox:latest"), 5);
+        let failed: Vec<_> = chunks
+            .iter()
+            .filter(|chunk| chunk.image == "missing:latest")
+            .collect();
+        assert!(matches!(
+            failed[..],
+            [PullManyChunk {
+                chunk: Err(Error::ManifestNotFound { .. }),
+                ..
+            }]
+        ));
+
+        let last = chunks.last().unwrap().progress;
+        assert_eq!(last.images, 3);
+        assert_eq!((last.downloaded, last.total), (2_811_478, 2_811_478));
+        assert_eq!(last.finished, 3);
+        let finished: Vec<_> = chunks.iter().map(|chunk| chunk.progress.finished).collect();
+        assert!(finished.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn push_streams_chunks() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "POST",
+                "/images/registry.example.org/app/push",
+                MockResponse::json_stream(&[
+                    serde_json::json!({ "status": "The push refers to repository [registry.example.org/app]" }),
+                    serde_json::json!({ "status": "Pushing", "id": "d1ee", "progressDetail": { "current": 512, "total": 1024 } }),
+                    serde_json::json!({ "progressDetail": {}, "aux": { "Tag": "latest", "Digest": "sha256:abc", "Size": 528 } }),
+                ]),
+            )
+            .respond(
+                "POST",
+                "/images/registry.example.org/denied/push",
+                MockResponse::json_stream(&[serde_json::json!({
+                    "error": "denied: requested access to the resource is denied",
+                    "errorDetail": { "message": "denied: requested access to the resource is denied" },
+                })]),
+            )
+            .respond(
+                "POST",
+                "/images/registry.example.org/full/push",
+                MockResponse::json_stream(&[serde_json::json!({
+                    "error": "blob upload failed",
+                    "errorDetail": { "message": "blob upload failed: disk full" },
+                })]),
+            );
+        let docker = Docker::mock(daemon);
+        let images = docker.images();
+        let opts = PushOptions::builder().build();
+
+        let chunks = futures::executor::block_on(
+            images
+                .push("registry.example.org/app", &opts)
+                .try_collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].total_image_bytes(), Some(1024));
+        match &chunks[2] {
+            ImageBuildChunk::Pushed { aux } => assert_eq!(
+                aux,
+                &PushedImage {
+                    digest: Some("sha256:abc".to_owned()),
+                    size: Some(528),
+                }
+            ),
+            chunk => panic!("unexpected chunk {:?}", chunk),
+        }
+
+        match futures::executor::block_on(
+            images
+                .push("registry.example.org/denied", &opts)
+                .try_collect::<Vec<_>>(),
+        ) {
+            Err(Error::Unauthorized { image, .. }) => {
+                assert_eq!(image, "registry.example.org/denied")
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let chunks = futures::executor::block_on(
+            images
+                .push("registry.example.org/full", &opts)
+                .try_collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert!(matches!(chunks[..], [ImageBuildChunk::Error { .. }]));
+        match futures::executor::block_on(images.push_and_wait("registry.example.org/full", &opts))
+        {
+            Err(Error::StreamFault { message, path, .. }) => {
+                assert_eq!(message, "blob upload failed: disk full");
+                assert_eq!(path, "/images/registry.example.org/full/push");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(futures::executor::block_on(
+            images.push_and_wait("registry.example.org/app", &opts)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn login() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "POST",
+                "/auth",
+                MockResponse::json(
+                    &serde_json::json!({ "Status": "Login Succeeded", "IdentityToken": "" }),
+                ),
+            )
+            .respond(
+                "POST",
+                "/auth",
+                MockResponse::json(
+                    &serde_json::json!({ "Status": "Login Succeeded", "IdentityToken": "abc" }),
+                ),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        futures::executor::block_on(async {
+            let auth = RegistryAuth::login(&docker, "registry.example.com", "user", "secret")
+                .await
+                .unwrap();
+            assert!(matches!(
+                auth,
+                RegistryAuth::Password { ref username, ref password, .. }
+                    if username == "user" && password == "secret"
+            ));
+            let auth = RegistryAuth::login(&docker, "registry.example.com", "user", "secret")
+                .await
+                .unwrap();
+            assert!(matches!(
+                auth,
+                RegistryAuth::Token { ref identity_token } if identity_token == "abc"
+            ));
+        });
+        assert_eq!(
+            daemon.requests()[0].json().unwrap(),
+            serde_json::json!({
+                "username": "user",
+                "password": "secret",
+                "serveraddress": "registry.example.com",
+            })
+        );
+    }
+
+    #[test]
+    fn push_all() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+        use std::sync::Mutex;
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/images/app:1.0/json",
+                MockResponse::json(&fixtures::image_details("sha256:9f9f", "app:1.0")),
+            )
+            .respond(
+                "GET",
+                "/images/mirror.example.com/app:1.0/json",
+                MockResponse::json(&fixtures::image_details(
+                    "sha256:1a2b",
+                    "mirror.example.com/app:1.0",
+                )),
+            )
+            .respond(
+                "POST",
+                "/images/registry.example.com/app/push",
+                MockResponse::json_stream(&[
+                    serde_json::json!({ "status": "Pushing", "id": "3f4d", "progressDetail": { "current": 512, "total": 1024 } }),
+                    serde_json::json!({ "progressDetail": {}, "aux": { "Tag": "1.0", "Digest": "sha256:abc", "Size": 528 } }),
+                ]),
+            )
+            .respond(
+                "POST",
+                "/images/mirror.example.com/app/push",
+                MockResponse::json_stream(&[serde_json::json!({
+                    "error": "denied",
+                    "errorDetail": { "message": "denied: requested access to the resource is denied" },
+                })]),
+            )
+            .respond("POST", "/images/app:1.0/tag", MockResponse::new(201, ""))
+            .respond("POST", "/images/sha256:1a2b/tag", MockResponse::new(201, ""))
+            .respond(
+                "DELETE",
+                "/images/registry.example.com/app:1.0",
+                MockResponse::json(&serde_json::json!([
+                    { "Untagged": "registry.example.com/app:1.0" }
+                ])),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        let progress = Mutex::new(Vec::new());
+        let reports = futures::executor::block_on(docker.images().push_all(
+            "app:1.0",
+            vec![
+                (
+                    "registry.example.com/app:1.0".to_owned(),
+                    RegistryAuth::token("abc"),
+                ),
+                (
+                    "mirror.example.com/app:1.0".to_owned(),
+                    RegistryAuth::token("def"),
+                ),
+            ],
+            2,
+            |target, chunk| {
+                progress
+                    .lock()
+                    .unwrap()
+                    .push((target.to_owned(), chunk.total_image_bytes()))
+            },
+        ));
+
+        assert_eq!(reports[0].reference, "registry.example.com/app:1.0");
+        assert_eq!(
+            reports[0].outcome.as_ref().unwrap(),
+            &PushedImage {
+                digest: Some("sha256:abc".to_owned()),
+                size: Some(528),
+            }
+        );
+        match &reports[1].outcome {
+            Err(Error::StreamFault { message, .. }) => {
+                assert_eq!(
+                    message,
+                    "denied: requested access to the resource is denied"
+                )
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(
+            progress.into_inner().unwrap(),
+            [("registry.example.com/app:1.0".to_owned(), Some(1024))]
+        );
+
+        let requests: Vec<_> = daemon
+            .requests()
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.path))
+            .collect();
+        // the image is tagged as each target, which is removed again if it did not exist
+        // before and restored otherwise
+        for request in [
+            "POST /images/app:1.0/tag?repo=registry.example.com%2Fapp&tag=1.0",
+            "DELETE /images/registry.example.com/app:1.0",
+            "POST /images/app:1.0/tag?repo=mirror.example.com%2Fapp&tag=1.0",
+            "POST /images/sha256:1a2b/tag?repo=mirror.example.com%2Fapp&tag=1.0",
+        ] {
+            assert!(requests.contains(&request.to_owned()), "{}", request);
+        }
+        assert!(!requests
+            .iter()
+            .any(|request| request.starts_with("DELETE /images/mirror")));
+    }
+
+    #[test]
+    fn registry_of_repository() {
+        let split = |repo| {
+            let (host, path) = split_repository(repo);
+            format!("{} {}", host, path)
+        };
+        assert_eq!(split("alpine"), "docker.io library/alpine");
+        assert_eq!(split("grafana/grafana"), "docker.io grafana/grafana");
+        assert_eq!(split("index.docker.io/alpine"), "docker.io library/alpine");
+        assert_eq!(split("localhost/app"), "localhost app");
+        assert_eq!(
+            split("registry.example.com:5000/team/app"),
+            "registry.example.com:5000 team/app"
+        );
+    }
+
+    #[test]
+    fn resolve_platform() {
+        use crate::mock::{MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/distribution/alpine:3.14/json",
+            MockResponse::json(&serde_json::json!({
+                "Descriptor": {
+                    "mediaType": "application/vnd.docker.distribution.manifest.list.v2+json",
+                    "digest": "sha256:abc",
+                    "size": 1638,
+                },
+                "Platforms": [
+                    { "architecture": "amd64", "os": "linux" },
+                    { "architecture": "arm", "os": "linux", "variant": "v7" },
+                ],
+            })),
+        );
+        let docker = Docker::mock(daemon.clone());
+
+        let resolved = futures::executor::block_on(docker.images().resolve_platform(
+            "alpine:3.14",
+            "linux/arm",
+            None,
+        ))
+        .unwrap();
+        assert_eq!(resolved.digest, "sha256:abc");
+        assert_eq!(resolved.platform.to_string(), "linux/arm/v7");
+
+        let resolved = futures::executor::block_on(docker.images().resolve_platform(
+            "alpine:3.14",
+            "linux/s390x",
+            Some(RegistryAuth::token("abc")),
+        ));
+        match resolved {
+            Err(e @ Error::PlatformNotAvailable { .. }) => assert_eq!(
+                e.to_string(),
+                "Docker Error: alpine:3.14 is not available for linux/s390x, only for \
+                 linux/amd64, linux/arm/v7"
+            ),
+            resolved => panic!("unexpected result {:?}", resolved),
+        }
+        assert!(daemon.requests()[1].headers.contains_key("X-Registry-Auth"));
+    }
 }
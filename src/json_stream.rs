@@ -0,0 +1,195 @@
+//! Decode streamed responses that consist of a sequence of JSON values.
+//!
+//! Endpoints like `/images/create` or `/build` stream one JSON value after the other, without
+//! regard to the chunks the response arrives in: a chunk may hold several values, or only part
+//! of one. [decode] reassembles the values, for use with
+//! [Docker::stream_request](crate::Docker::stream_request) and other streams of chunks.
+//!
+//! ```no_run
+//! use futures_util::TryStreamExt;
+//! use shiplift::{json_stream, Docker, Method};
+//! # async {
+//! let docker = Docker::new();
+//! let chunks = docker.stream_request(
+//!     Method::GET,
+//!     "/events",
+//!     None,
+//!     None::<(Vec<u8>, shiplift::Mime)>,
+//!     vec![],
+//! );
+//! let mut events = json_stream::decode::<_, serde_json::Value>(chunks);
+//! while let Some(event) = events.try_next().await? {
+//!     println!("{}", event);
+//! }
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{collections::VecDeque, pin::Pin};
+
+use bytes::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::errors::{Error, Result};
+
+/// Decodes the JSON values in a stream of chunks
+///
+/// Values may be separated by whitespace, like the newlines of JSON lines, and span several
+/// chunks. A value that is not valid JSON, or doesn't match `T`, fails with
+/// [Error::SerdeJsonError], after which decoding resumes after the invalid value. Errors of
+/// `chunks` are passed through.
+pub fn decode<S, T>(chunks: S) -> impl Stream<Item = Result<T>> + Unpin
+where
+    S: Stream<Item = Result<Bytes>>,
+    T: DeserializeOwned,
+{
+    Box::pin(decode_with(chunks, |_, e| Error::SerdeJsonError(e)))
+}
+
+/// Like [decode], but creates the error for an invalid value from the undecoded input and the
+/// error of serde
+pub(crate) fn decode_with<S, T, F>(
+    chunks: S,
+    on_error: F,
+) -> impl Stream<Item = Result<T>>
+where
+    S: Stream<Item = Result<Bytes>>,
+    T: DeserializeOwned,
+    F: FnMut(&[u8], serde_json::Error) -> Error,
+{
+    let decoder = Decoder {
+        chunks: Box::pin(chunks),
+        buf: Vec::new(),
+        decoded: VecDeque::new(),
+        done: false,
+        on_error,
+    };
+    stream::unfold(decoder, |mut decoder| async move {
+        loop {
+            if let Some(item) = decoder.decoded.pop_front() {
+                return Some((item, decoder));
+            }
+            if decoder.done {
+                return None;
+            }
+            match decoder.chunks.next().await {
+                Some(Ok(chunk)) => {
+                    decoder.buf.extend_from_slice(&chunk);
+                    decoder.decode(false);
+                }
+                Some(Err(e)) => return Some((Err(e), decoder)),
+                None => {
+                    decoder.done = true;
+                    decoder.decode(true);
+                }
+            }
+        }
+    })
+}
+
+struct Decoder<S, T, F> {
+    chunks: Pin<Box<S>>,
+    /// Input that has not been decoded yet
+    buf: Vec<u8>,
+    decoded: VecDeque<Result<T>>,
+    done: bool,
+    on_error: F,
+}
+
+impl<S, T, F> Decoder<S, T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(&[u8], serde_json::Error) -> Error,
+{
+    /// Decodes the complete values in the buffer. At the end of the input, an incomplete value
+    /// is an error, otherwise it is kept until more input arrives.
+    fn decode(
+        &mut self,
+        at_end: bool,
+    ) {
+        let mut consumed = 0;
+        loop {
+            let mut values =
+                serde_json::Deserializer::from_slice(&self.buf[consumed..]).into_iter::<T>();
+            match values.next() {
+                Some(Ok(value)) => {
+                    consumed += values.byte_offset();
+                    self.decoded.push_back(Ok(value));
+                }
+                Some(Err(e)) if e.is_eof() && !at_end => break,
+                Some(Err(e)) => {
+                    let rest = &self.buf[consumed..];
+                    let start = rest
+                        .iter()
+                        .position(|b| !b.is_ascii_whitespace())
+                        .unwrap_or(rest.len());
+                    let invalid = &rest[start..];
+                    self.decoded.push_back(Err((self.on_error)(invalid, e)));
+                    // skip to the next line, which is where the daemon starts its next value
+                    consumed += start
+                        + invalid
+                            .iter()
+                            .position(|&b| b == b'\n')
+                            .map_or(invalid.len(), |i| i + 1);
+                }
+                None => {
+                    consumed = self.buf.len();
+                    break;
+                }
+            }
+        }
+        self.buf.drain(..consumed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Status {
+        status: String,
+    }
+
+    fn decode_chunks(chunks: &[&'static str]) -> Vec<Result<Status>> {
+        let chunks = stream::iter(chunks.iter().map(|c| Ok(Bytes::from_static(c.as_bytes()))));
+        futures::executor::block_on(decode(chunks).collect())
+    }
+
+    fn status(s: &str) -> Status {
+        Status { status: s.into() }
+    }
+
+    #[test]
+    fn decode_values_across_chunks() {
+        let values: Vec<_> = decode_chunks(&[
+            "{\"status\":\"a\"}\r\n{\"status\":\"b\"}\n{\"sta",
+            "tus\":",
+            "\"c\"}\n  ",
+        ])
+        .into_iter()
+        .collect::<Result<_>>()
+        .unwrap();
+        assert_eq!(values, [status("a"), status("b"), status("c")]);
+    }
+
+    #[test]
+    fn decode_invalid_values() {
+        let values = decode_chunks(&["{\"status\":\"a\"}\n{\"id\":1}\n{\"status\":\"b\"}\n{\"st"]);
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0].as_ref().unwrap(), &status("a"));
+        assert!(matches!(values[1], Err(Error::SerdeJsonError(_))));
+        assert_eq!(values[2].as_ref().unwrap(), &status("b"));
+        assert!(matches!(values[3], Err(Error::SerdeJsonError(_))));
+
+        let chunks = stream::iter(vec![
+            Ok(Bytes::from_static(b"{\"status\":\"a\"}")),
+            Err(Error::ConnectionNotUpgraded),
+        ]);
+        let result: Result<Vec<Status>> = futures::executor::block_on(decode(chunks).try_collect());
+        assert!(matches!(result, Err(Error::ConnectionNotUpgraded)));
+    }
+}
@@ -16,44 +16,100 @@
 //! }
 //! # };
 //! ```
+//!
+//! Options are put together with builders, whose methods take the builder by value and return
+//! it, so that they can be chained. To set an option conditionally, assign the returned builder.
+//!
+//! ```
+//! use shiplift::ContainerListOptions;
+//!
+//! # let all = true;
+//! let mut builder = ContainerListOptions::builder().sized();
+//! if all {
+//!     builder = builder.all();
+//! }
+//! let options = builder.build();
+//! ```
 
 pub mod errors;
 pub mod transport;
 pub mod tty;
 
+pub mod buildkit;
 pub mod container;
+pub mod credentials;
 pub mod docker;
 pub mod exec;
+pub mod id;
 pub mod image;
+pub mod json_stream;
+pub mod log_files;
 pub mod network;
+pub mod node;
+pub mod plugin;
+pub mod rate_limit;
 pub mod service;
+pub mod task;
 pub mod volume;
 
 pub mod tarball;
 
+mod duration;
+mod query;
+mod rootfs;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+
+#[cfg(feature = "progress")]
+pub mod progress;
+
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+#[cfg(feature = "stack")]
+pub mod stack;
+
 #[cfg(feature = "chrono")]
 mod datetime;
 
-pub use hyper::Uri;
+#[cfg(all(feature = "chrono", feature = "time"))]
+compile_error!(
+    "the `chrono` and `time` features are mutually exclusive, disable the default features to use \
+     `time`"
+);
+
+pub use hyper::{Method, Uri};
+pub use mime::Mime;
 
 pub use crate::{
     container::{
-        Container, ContainerFilter, ContainerListOptions, ContainerOptions, Containers,
-        LogsOptions, RmContainerOptions,
+        CommitOptions, Container, ContainerFilter, ContainerListOptions, ContainerOptions,
+        Containers, LogsOptions, MountSpec, MountType, Propagation, RecreatePolicy,
+        RmContainerOptions,
     },
     docker::{Docker, EventsOptions},
-    errors::{Error, Result},
+    errors::{Error, Result, RetryAdvice, StreamProgress},
     exec::{Exec, ExecContainerOptions, ExecResizeOptions},
+    id::{ContainerId, ExecId, ImageId, NetworkId},
     image::{
-        BuildOptions, Image, ImageFilter, ImageListOptions, Images, PullOptions, RegistryAuth,
-        TagOptions,
+        BuildOptions, BuiltImage, EnvVar, Image, ImageCopy, ImageFilter, ImageListOptions, Images,
+        ImportOptions, ImportStream, LoadedImage, PullManyChunk, PullManyProgress, PullOptions,
+        PullPolicy, PullVerifier, RegistryAuth, SearchOptions, TagOptions,
     },
     network::{
-        ContainerConnectionOptions, Network, NetworkCreateOptions, NetworkListOptions, Networks,
+        ContainerConnectionOptions, Network, NetworkCreateOptions, NetworkFilter,
+        NetworkListOptions, Networks,
     },
-    service::{Service, ServiceFilter, ServiceListOptions, ServiceOptions, Services},
-    transport::Transport,
-    volume::{Volume, VolumeCreateOptions, Volumes},
+    node::{Node, NodeAvailability, NodeFilter, NodeListOptions, NodeRole, NodeSpec, Nodes},
+    plugin::{Plugin, PluginFilter, PluginInstallOptions, PluginListOptions, Plugins},
+    service::{
+        RegistryAuthFrom, Service, ServiceConvergence, ServiceFilter, ServiceListOptions,
+        ServiceOptions, Services,
+    },
+    task::{Task, TaskFilter, TaskListOptions, TaskState, Tasks},
+    transport::{Transport, Upgraded},
+    volume::{Volume, VolumeCreateOptions, VolumeFilter, VolumeListOptions, Volumes},
 };
 
 macro_rules! reexport {
@@ -96,17 +152,22 @@ reexport! {
     image::{
         RegistryAuth, RegistryAuthBuilder, TagOptions, TagOptionsBuilder, PullOptions,
         PullOptionsBuilder, BuildOptions, BuildOptionsBuilder, ImageFilter, ImageListOptions,
-        ImageListOptionsBuilder,
+        ImageListOptionsBuilder, ImportOptions, ImportOptionsBuilder, SearchOptions,
+        SearchOptionsBuilder,
     };
     network::{
-        NetworkListOptions, NetworkCreateOptions, NetworkCreateOptionsBuilder,
+        NetworkListOptions, NetworkListOptionsBuilder, NetworkFilter, NetworkCreateOptions,
+        NetworkCreateOptionsBuilder,
         ContainerConnectionOptions, ContainerConnectionOptionsBuilder,
     };
     service::{
         ServiceListOptions, ServiceFilter, ServiceListOptionsBuilder, ServiceOptions,
         ServiceOptionsBuilder,
     };
-    volume::{VolumeCreateOptions, VolumeCreateOptionsBuilder};
+    volume::{
+        VolumeCreateOptions, VolumeCreateOptionsBuilder, VolumeListOptions,
+        VolumeListOptionsBuilder, VolumeFilter,
+    };
 }
 
 reexport! {
@@ -120,6 +181,7 @@ reexport! {
     exec::{ExecDetails, ProcessConfig};
     image::{
         SearchResult, ImageInfo as Image, ImageDetails, ContainerConfig, History, Status,
+        ImageMetadata, HealthConfig,
     };
     network::{
         NetworkSettings, NetworkEntry, Ipam, NetworkDetails,
@@ -133,3 +195,80 @@ reexport! {
     };
     volume::{VolumeCreateInfo, VolumesInfo as Volumes, VolumeInfo as Volume};
 }
+
+/// Updates an options builder in place, for code written against the builder methods of 0.7,
+/// which took `&mut self`
+///
+/// A method called on its own, like `builder.all();`, becomes `builder.in_place(|b| b.all());`.
+#[deprecated(
+    since = "0.8.0",
+    note = "Builder methods take the builder by value, assign the builder they return instead. This will be removed in 0.9.0."
+)]
+pub trait InPlace: Default {
+    /// Replaces the builder with the one `update` returns for it
+    fn in_place<F>(
+        &mut self,
+        update: F,
+    ) -> &mut Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        *self = update(std::mem::take(self));
+        self
+    }
+}
+
+macro_rules! in_place {
+    ($( $module:ident :: { $( $builder:ident ),* $(,)? } ; )*) => {
+        $($(
+            #[allow(deprecated)]
+            impl InPlace for $module::$builder {}
+        )*)*
+    };
+}
+
+in_place! {
+    container::{
+        ContainerListOptionsBuilder, ContainerOptionsBuilder, LogsOptionsBuilder,
+        RmContainerOptionsBuilder,
+    };
+    docker::{EventsOptionsBuilder};
+    exec::{ExecContainerOptionsBuilder, ExecResizeOptionsBuilder};
+    image::{
+        RegistryAuthBuilder, TagOptionsBuilder, PullOptionsBuilder, BuildOptionsBuilder,
+        ImageListOptionsBuilder, PushOptionsBuilder,
+    };
+    network::{NetworkCreateOptionsBuilder, ContainerConnectionOptionsBuilder};
+    service::{ServiceListOptionsBuilder, ServiceOptionsBuilder};
+    volume::{VolumeCreateOptionsBuilder};
+}
+
+#[cfg(test)]
+mod tests {
+    // every response with an `extra` map flattens it the same way, so one of them stands in for
+    // all
+    #[cfg(feature = "extra-fields")]
+    #[test]
+    fn extra_fields() {
+        let json = serde_json::json!({
+            "ID": "0kzzo1i0y4jz6027t0k7aezc7",
+            "Version": {"Index": 71},
+            "CreatedAt": "2016-06-07T21:07:31.171892745Z",
+            "UpdatedAt": "2016-06-07T21:07:31.376370513Z",
+            "Spec": {},
+            "ServiceID": "9mnpnzenvg8p8tdbtq4wvbkcz",
+            "Status": {"Timestamp": "2016-06-07T21:07:31.290032978Z", "State": "new"},
+            "DesiredState": "running",
+            "JobIteration": {"Index": 3}
+        });
+        let task: crate::task::TaskInfo = serde_json::from_value(json).unwrap();
+
+        // only the fields that aren't modelled end up in the map
+        assert_eq!(task.extra.len(), 1);
+        assert_eq!(task.extra["JobIteration"]["Index"], 3);
+        // and they are serialized back next to the modelled ones
+        let serialized = serde_json::to_value(&task).unwrap();
+        assert_eq!(serialized["JobIteration"]["Index"], 3);
+        assert_eq!(serialized["ServiceID"], "9mnpnzenvg8p8tdbtq4wvbkcz");
+    }
+}
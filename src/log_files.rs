@@ -0,0 +1,419 @@
+//! Follow the logs of a container into local files.
+//!
+//! [LogFiles] writes the stdout and stderr of a container to a file, rotating it when it
+//! exceeds a size, like the `json-file` log driver does for the daemon. The timestamp of the
+//! last line written is kept next to the file, so that following the logs again after a
+//! restart resumes where it stopped instead of writing the logs again.
+//!
+//! ```no_run
+//! use shiplift::{log_files::LogFiles, Docker};
+//! # async {
+//! let docker = Docker::new();
+//! let files = LogFiles::builder("/var/log/web.log")
+//!     .max_size(10 * 1024 * 1024)
+//!     .max_files(5)
+//!     .build();
+//! files.follow(&docker.containers().get("web")).await?;
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+
+use crate::{
+    container::{Container, LogsOptions},
+    errors::Result,
+    tty::TtyChunk,
+};
+
+/// Time to wait before following the logs again after a transient failure
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes the logs of a container to rotating files, see the [module docs](self)
+#[derive(Clone, Debug)]
+pub struct LogFiles {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    timestamps: bool,
+}
+
+impl LogFiles {
+    /// return a new instance of a builder for log files written to `path`
+    pub fn builder<P>(path: P) -> LogFilesBuilder
+    where
+        P: Into<PathBuf>,
+    {
+        LogFilesBuilder::new(path.into())
+    }
+
+    /// Follows the logs of `container` until it stops or is removed
+    ///
+    /// The logs are followed again if the container restarts in the meantime, or the stream of
+    /// logs fails with a [transient](crate::Error::is_transient) error. Logs are written as
+    /// they arrive, so the future can be dropped at any time and followed again later.
+    pub async fn follow(
+        &self,
+        container: &Container<'_>,
+    ) -> Result<()> {
+        let mut file = RotatingFile::open(&self.path, self.max_size, self.max_files)?;
+        let position_path = self.position_path();
+        let mut last = match fs::read_to_string(&position_path) {
+            Ok(position) => parse_timestamp(position.trim()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        loop {
+            // timestamps are always requested to know where to resume
+            let mut opts = LogsOptions::builder()
+                .follow(true)
+                .stdout(true)
+                .stderr(true)
+                .timestamps(true);
+            if let Some((secs, _)) = last {
+                opts = opts.since_unix(secs);
+            }
+            let mut logs = container.logs(&opts.build());
+            let failure = loop {
+                match logs.next().await {
+                    Some(Ok(chunk)) => {
+                        if let Some((timestamp, raw)) = self.write_chunk(&mut file, &chunk, last)? {
+                            last = Some(timestamp);
+                            fs::write(&position_path, raw)?;
+                        }
+                    }
+                    Some(Err(e)) => break Some(e),
+                    None => break None,
+                }
+            };
+
+            match failure {
+                Some(e) if e.is_transient() => {}
+                Some(e) => return Err(e),
+                None => match container.inspect().await {
+                    Ok(details) if details.state.running => {}
+                    Ok(_) => return Ok(()),
+                    Err(e) if e.is_not_found() => return Ok(()),
+                    Err(e) => return Err(e),
+                },
+            }
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    /// The file keeping the timestamp of the last line written
+    fn position_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".position");
+        path.into()
+    }
+
+    /// Writes a line of the logs, unless it was written before `last`. Returns the timestamp
+    /// of the line, parsed and as sent by the daemon.
+    fn write_chunk(
+        &self,
+        file: &mut RotatingFile,
+        chunk: &TtyChunk,
+        last: Option<(i64, u32)>,
+    ) -> io::Result<Option<((i64, u32), String)>> {
+        let (raw, line) = match chunk.iter().position(|&b| b == b' ') {
+            Some(i) => (String::from_utf8_lossy(&chunk[..i]), &chunk[i + 1..]),
+            None => return file.write(chunk).map(|()| None),
+        };
+        let timestamp = match parse_timestamp(&raw) {
+            Some(timestamp) => timestamp,
+            None => return file.write(chunk).map(|()| None),
+        };
+        if last.is_some_and(|last| timestamp <= last) {
+            return Ok(None);
+        }
+        file.write(if self.timestamps { chunk } else { line })?;
+        Ok(Some((timestamp, raw.into_owned())))
+    }
+}
+
+/// Builder interface for [LogFiles]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct LogFilesBuilder {
+    files: LogFiles,
+}
+
+impl LogFilesBuilder {
+    fn new(path: PathBuf) -> Self {
+        LogFilesBuilder {
+            files: LogFiles {
+                path,
+                max_size: 10 * 1024 * 1024,
+                max_files: 3,
+                timestamps: false,
+            },
+        }
+    }
+
+    /// Size in bytes after which the file is rotated, 10 MiB by default
+    pub fn max_size(
+        mut self,
+        bytes: u64,
+    ) -> Self {
+        self.files.max_size = bytes;
+        self
+    }
+
+    /// Number of rotated files to keep, as `<path>.1` up to `<path>.<max_files>`, 3 by
+    /// default. With 0 the file is truncated instead.
+    pub fn max_files(
+        mut self,
+        max_files: usize,
+    ) -> Self {
+        self.files.max_files = max_files;
+        self
+    }
+
+    /// Whether to prefix each line with the time the daemon received it
+    pub fn timestamps(
+        mut self,
+        timestamps: bool,
+    ) -> Self {
+        self.files.timestamps = timestamps;
+        self
+    }
+
+    pub fn build(self) -> LogFiles {
+        self.files
+    }
+}
+
+/// A file that is rotated once it exceeds its maximum size
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(
+        path: &Path,
+        max_size: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_owned(),
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn write(
+        &mut self,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if self.size > 0 && self.size + data.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files > 0 {
+            remove_if_exists(&self.rotated(self.max_files))?;
+            for n in (1..self.max_files).rev() {
+                match fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        } else {
+            remove_if_exists(&self.path)?;
+        }
+        *self = RotatingFile::open(&self.path, self.max_size, self.max_files)?;
+        Ok(())
+    }
+
+    fn rotated(
+        &self,
+        n: usize,
+    ) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a timestamp the daemon prefixes lines of logs with, like
+/// `2021-07-01T11:46:41.123456789Z`, into seconds and nanoseconds since the unix epoch
+///
+/// Times in another offset than UTC, like the `SystemTime` of the daemon, are accepted too.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<(i64, u32)> {
+    let (timestamp, offset) = match timestamp.strip_suffix('Z') {
+        Some(timestamp) => (timestamp, 0),
+        None => {
+            let split = timestamp.len().checked_sub(6)?;
+            let (timestamp, offset) = (timestamp.get(..split)?, timestamp.get(split..)?);
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+            (timestamp, sign * (hours * 3_600 + minutes * 60))
+        }
+    };
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", fraction).parse().ok()?;
+
+    // days since the epoch of the proleptic gregorian date
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some((
+        days * 86_400 + hour * 3_600 + minute * 60 + second - offset,
+        nanos,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shiplift-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some((0, 0)));
+        assert_eq!(
+            parse_timestamp("2021-07-01T11:46:41.123456789Z"),
+            Some((1_625_140_001, 123_456_789))
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T00:00:00.5Z"),
+            Some((1_709_164_800, 500_000_000))
+        );
+        assert_eq!(
+            parse_timestamp("2021-07-01T13:46:41.5+02:00"),
+            Some((1_625_140_001, 500_000_000))
+        );
+        assert_eq!(parse_timestamp("2021-07-01T11:46:41+0200"), None);
+        assert_eq!(parse_timestamp("hello"), None);
+    }
+
+    #[test]
+    fn rotate_files() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("app.log");
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            file.write(line.as_bytes()).unwrap();
+        }
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "four\nfive\n");
+        assert_eq!(read(&dir.join("app.log.1")), "three\n");
+        assert_eq!(read(&dir.join("app.log.2")), "one\ntwo\n");
+        assert!(!dir.join("app.log.3").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn follow_and_resume() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker,
+        };
+
+        let frame = |stream: u8, line: &str| {
+            let mut frame = vec![stream, 0, 0, 0];
+            frame.extend_from_slice(&(line.len() as u32).to_be_bytes());
+            frame.extend_from_slice(line.as_bytes());
+            frame
+        };
+        let logs = [
+            frame(1, "2021-07-01T11:46:41.1Z starting\n"),
+            frame(2, "2021-07-01T11:46:41.12Z warning\n"),
+        ]
+        .concat();
+        let mut details = fixtures::container_details("c1", "web");
+        details["State"]["Running"] = serde_json::json!(false);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "GET",
+                "/containers/web/logs",
+                MockResponse::new(200, logs.clone()),
+            )
+            .respond(
+                "GET",
+                "/containers/web/logs",
+                MockResponse::new(
+                    200,
+                    [logs, frame(1, "2021-07-01T11:46:42Z ready\n")].concat(),
+                ),
+            )
+            .respond("GET", "/containers/web/json", MockResponse::json(&details));
+        let docker = Docker::mock(daemon.clone());
+        let container = docker.containers().get("web");
+
+        let dir = temp_dir("follow");
+        let path = dir.join("web.log");
+        let files = LogFiles::builder(&path).build();
+        futures::executor::block_on(async {
+            files.follow(&container).await.unwrap();
+            files.follow(&container).await.unwrap();
+        });
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "starting\nwarning\nready\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("web.log.position")).unwrap(),
+            "2021-07-01T11:46:42Z"
+        );
+        let resumed = daemon
+            .requests()
+            .into_iter()
+            .filter(|request| request.path.starts_with("/containers/web/logs"))
+            .nth(1)
+            .unwrap();
+        assert!(resumed.path.contains("since=1625140001"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,738 @@
+//! An in-process stand-in for the docker daemon, for testing code that uses this crate without
+//! a running daemon.
+//!
+//! A [MockDaemon] is primed with canned responses for the requests it expects and records every
+//! request it receives, so that tests can make assertions about them.
+//!
+//! The response structs of this crate are non-exhaustive, so [model] builds them from the JSON
+//! of the daemon instead, such as the [fixtures], for testing code that consumes them.
+//!
+//! ```
+//! use shiplift::{container::Stats, mock::{fixtures, model}};
+//!
+//! let mut stats: Stats = model(fixtures::stats());
+//! stats.memory_stats.usage = 512 * 1024 * 1024;
+//! ```
+//!
+//! ```no_run
+//! use shiplift::{
+//!     mock::{fixtures, MockDaemon, MockResponse},
+//!     Docker,
+//! };
+//! # async {
+//! let daemon = MockDaemon::new();
+//! daemon.respond("GET", "/version", MockResponse::json(&fixtures::version()));
+//!
+//! let docker = Docker::mock(daemon.clone());
+//! let version = docker.version().await.unwrap();
+//! assert_eq!(version.api_version, "1.41");
+//! assert_eq!(daemon.requests()[0].path, "/version");
+//! # };
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::errors::Result;
+
+/// A canned response of a [MockDaemon]
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    chunks: Vec<Bytes>,
+}
+
+impl MockResponse {
+    /// A response with the given status and body
+    pub fn new<B>(
+        status: u16,
+        body: B,
+    ) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        MockResponse {
+            status: StatusCode::from_u16(status).expect("invalid status code"),
+            chunks: vec![body.into()],
+        }
+    }
+
+    /// A successful response with a JSON body
+    pub fn json<T>(body: &T) -> Self
+    where
+        T: Serialize,
+    {
+        MockResponse::new(
+            200,
+            serde_json::to_vec(body).expect("unserializable mock response"),
+        )
+    }
+
+    /// A successful response streaming one JSON value per chunk, like the progress of a build or
+    /// a pull
+    pub fn json_stream<T>(values: &[T]) -> Self
+    where
+        T: Serialize,
+    {
+        MockResponse {
+            status: StatusCode::OK,
+            chunks: values
+                .iter()
+                .map(|value| {
+                    let mut chunk =
+                        serde_json::to_vec(value).expect("unserializable mock response");
+                    chunk.extend_from_slice(b"\r\n");
+                    chunk.into()
+                })
+                .collect(),
+        }
+    }
+
+    /// An error response with a message in the format of the daemon
+    pub fn error(
+        status: u16,
+        message: &str,
+    ) -> Self {
+        MockResponse::new(
+            status,
+            serde_json::to_vec(&serde_json::json!({ "message": message })).unwrap(),
+        )
+    }
+}
+
+/// A request received by a [MockDaemon]
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    /// Path and query of the request
+    pub path: String,
+    pub headers: hyper::HeaderMap,
+    pub body: Bytes,
+}
+
+impl RecordedRequest {
+    /// The body decoded as JSON
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+struct Route {
+    method: Method,
+    path: String,
+    response: MockResponse,
+    once: bool,
+}
+
+#[derive(Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// An in-process docker daemon answering requests with canned responses. Use it with
+/// [Docker::mock](crate::Docker::mock).
+///
+/// Clones share their responses and recorded requests.
+#[derive(Clone, Default)]
+pub struct MockDaemon {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockDaemon {
+    pub fn new() -> Self {
+        MockDaemon::default()
+    }
+
+    /// Answers every request with `method` to `path` with `response`. A `path` without a query
+    /// matches requests with any query.
+    ///
+    /// Responses are matched in the order they were added. Requests without a matching
+    /// response are answered with a 404 error.
+    pub fn respond(
+        &self,
+        method: &str,
+        path: &str,
+        response: MockResponse,
+    ) -> &Self {
+        self.add_route(method, path, response, false)
+    }
+
+    /// Like [respond](Self::respond), but only answers the first matching request
+    pub fn respond_once(
+        &self,
+        method: &str,
+        path: &str,
+        response: MockResponse,
+    ) -> &Self {
+        self.add_route(method, path, response, true)
+    }
+
+    fn add_route(
+        &self,
+        method: &str,
+        path: &str,
+        response: MockResponse,
+        once: bool,
+    ) -> &Self {
+        self.state.lock().unwrap().routes.push(Route {
+            method: method.parse().expect("invalid method"),
+            path: path.to_owned(),
+            response,
+            once,
+        });
+        self
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub(crate) async fn handle(
+        &self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>> {
+        let (parts, body) = req.into_parts();
+        let request = RecordedRequest {
+            method: parts.method,
+            path: parts
+                .uri
+                .path_and_query()
+                .map_or("/", |path| path.as_str())
+                .to_owned(),
+            headers: parts.headers,
+            body: hyper::body::to_bytes(body).await?,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let route = state.routes.iter().position(|route| {
+            route.method == request.method
+                && (route.path == request.path
+                    || request.path.split('?').next() == Some(route.path.as_str()))
+        });
+        let response = match route {
+            Some(i) if state.routes[i].once => state.routes.remove(i).response,
+            Some(i) => state.routes[i].response.clone(),
+            None => MockResponse::error(
+                404,
+                &format!("no mock response for {} {}", request.method, request.path),
+            ),
+        };
+        state.requests.push(request);
+
+        let body = Body::wrap_stream(futures_util::stream::iter(
+            response
+                .chunks
+                .into_iter()
+                .map(Ok::<_, std::convert::Infallible>),
+        ));
+        Ok(Response::builder()
+            .status(response.status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)?)
+    }
+}
+
+/// Builds a response struct from `json` in the format of the daemon, panicking if it doesn't
+/// match
+pub fn model<T>(json: serde_json::Value) -> T
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value(json)
+        .unwrap_or_else(|e| panic!("invalid model of {}: {}", std::any::type_name::<T>(), e))
+}
+
+/// Responses of a docker daemon to common requests, to prime a [MockDaemon] with or to build
+/// response structs from with [model]
+pub mod fixtures {
+    use serde_json::{json, Value};
+
+    use crate::id::ImageId;
+
+    /// Response to `GET /version`
+    pub fn version() -> Value {
+        json!({
+            "Version": "20.10.7",
+            "ApiVersion": "1.41",
+            "GitCommit": "b0f5bc3",
+            "GoVersion": "go1.13.15",
+            "Os": "linux",
+            "Arch": "amd64",
+            "KernelVersion": "5.10.0",
+            "BuildTime": "2021-06-02T11:54:50.000000000+00:00"
+        })
+    }
+
+    /// An element of the response to `GET /containers/json`
+    pub fn container_summary(
+        id: &str,
+        name: &str,
+    ) -> Value {
+        json!({
+            "Id": id,
+            "Names": [format!("/{}", name)],
+            "Image": "alpine:latest",
+            "ImageID": "sha256:14119a10abf4669e8cdbdff324a9f9605d99697215a0d21c360fe8dfa8471bab",
+            "Command": "sleep 3600",
+            "Created": 1625140000,
+            "Ports": [],
+            "Labels": {},
+            "State": "running",
+            "Status": "Up 5 minutes"
+        })
+    }
+
+    /// Response to `GET /containers/{id}/json` for a running container
+    pub fn container_details(
+        id: &str,
+        name: &str,
+    ) -> Value {
+        json!({
+            "Id": id,
+            "Created": "2021-07-01T11:46:40.000000000Z",
+            "Path": "sleep",
+            "Args": ["3600"],
+            "State": {
+                "Status": "running",
+                "Running": true,
+                "Paused": false,
+                "Restarting": false,
+                "OOMKilled": false,
+                "Pid": 4242,
+                "ExitCode": 0,
+                "Error": "",
+                "StartedAt": "2021-07-01T11:46:41.000000000Z",
+                "FinishedAt": "0001-01-01T00:00:00Z"
+            },
+            "Image": "sha256:14119a10abf4669e8cdbdff324a9f9605d99697215a0d21c360fe8dfa8471bab",
+            "ResolvConfPath": format!("/var/lib/docker/containers/{}/resolv.conf", id),
+            "HostnamePath": format!("/var/lib/docker/containers/{}/hostname", id),
+            "HostsPath": format!("/var/lib/docker/containers/{}/hosts", id),
+            "LogPath": format!("/var/lib/docker/containers/{0}/{0}-json.log", id),
+            "Name": format!("/{}", name),
+            "RestartCount": 0,
+            "Driver": "overlay2",
+            "Platform": "linux",
+            "MountLabel": "",
+            "ProcessLabel": "",
+            "AppArmorProfile": "docker-default",
+            "ExecIDs": null,
+            "HostConfig": {
+                "KernelMemoryTCP": 0,
+                "OomKillDisable": false,
+                "CpuCount": 0,
+                "CpuPercent": 0,
+                "IOMaximumIOps": 0,
+                "ContainerIDFile": "",
+                "LogConfig": { "Type": "json-file", "Config": {} },
+                "NetworkMode": "default",
+                "RestartPolicy": { "Name": "no", "MaximumRetryCount": 0 },
+                "AutoRemove": false,
+                "VolumeDriver": "",
+                "IpcMode": "private",
+                "Cgroup": "",
+                "OomScoreAdj": 0,
+                "Privileged": false,
+                "PublishAllPorts": false,
+                "UTSMode": "",
+                "UsernsMode": "",
+                "ShmSize": 67_108_864,
+                "Runtime": "runc",
+                "Isolation": ""
+            },
+            "GraphDriver": { "Name": "overlay2", "Data": {} },
+            "Mounts": [],
+            "Config": {
+                "AttachStderr": false,
+                "AttachStdin": false,
+                "AttachStdout": false,
+                "Cmd": ["sleep", "3600"],
+                "Domainname": "",
+                "Entrypoint": null,
+                "Env": ["PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin"],
+                "Hostname": &id[..id.len().min(12)],
+                "Image": "alpine:latest",
+                "Labels": {},
+                "OnBuild": null,
+                "OpenStdin": false,
+                "StdinOnce": false,
+                "Tty": false,
+                "User": "",
+                "WorkingDir": ""
+            },
+            "NetworkSettings": {
+                "Bridge": "",
+                "Gateway": "172.17.0.1",
+                "IPAddress": "172.17.0.2",
+                "IPPrefixLen": 16,
+                "MacAddress": "02:42:ac:11:00:02",
+                "Ports": {},
+                "Networks": {}
+            }
+        })
+    }
+
+    /// Response to `POST /containers/create`
+    pub fn container_created(id: &str) -> Value {
+        json!({ "Id": id, "Warnings": [] })
+    }
+
+    /// An element of the response to `GET /images/json`
+    pub fn image_summary(
+        id: &str,
+        tag: &str,
+    ) -> Value {
+        json!({
+            "Id": id,
+            "ParentId": "",
+            "RepoTags": [tag],
+            "RepoDigests": [],
+            "Created": 1625140000,
+            "Size": 5_610_000,
+            "VirtualSize": 5_610_000,
+            "Labels": null
+        })
+    }
+
+    /// Response to `GET /images/{name}/json`
+    pub fn image_details(
+        id: &str,
+        tag: &str,
+    ) -> Value {
+        json!({
+            "Id": id,
+            "RepoTags": [tag],
+            "RepoDigests": [],
+            "Parent": "",
+            "Comment": "",
+            "Created": "2021-06-15T20:19:05.000000000Z",
+            "DockerVersion": "20.10.7",
+            "Author": "",
+            "Config": {
+                "AttachStderr": false,
+                "AttachStdin": false,
+                "AttachStdout": false,
+                "Cmd": ["/bin/sh"],
+                "Domainname": "",
+                "Entrypoint": null,
+                "Env": ["PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin"],
+                "Hostname": "",
+                "Image": "",
+                "Labels": null,
+                "OnBuild": null,
+                "OpenStdin": false,
+                "StdinOnce": false,
+                "Tty": false,
+                "User": "",
+                "WorkingDir": ""
+            },
+            "Architecture": "amd64",
+            "Os": "linux",
+            "RootFS": {
+                "Type": "layers",
+                "Layers": [
+                    "sha256:72e830a4dff5f0d5225cdc0a320e85ab1ce06ea5673acfe8d83a7645cbd0e9cf"
+                ]
+            },
+            "Size": 5_610_000,
+            "VirtualSize": 5_610_000
+        })
+    }
+
+    /// A sample of the response to `GET /containers/{id}/stats`
+    pub fn stats() -> Value {
+        json!({
+            "read": "2021-07-01T11:51:40.000000000Z",
+            "networks": {
+                "eth0": {
+                    "rx_bytes": 5_338,
+                    "rx_dropped": 0,
+                    "rx_errors": 0,
+                    "rx_packets": 36,
+                    "tx_bytes": 648,
+                    "tx_dropped": 0,
+                    "tx_errors": 0,
+                    "tx_packets": 8
+                }
+            },
+            "memory_stats": {
+                "max_usage": 6_651_904,
+                "usage": 6_537_216,
+                "failcnt": 0,
+                "limit": 67_108_864,
+                "stats": {
+                    "total_pgmajfault": 0,
+                    "cache": 0,
+                    "mapped_file": 0,
+                    "total_inactive_file": 0,
+                    "pgpgout": 414,
+                    "rss": 6_537_216,
+                    "total_mapped_file": 0,
+                    "writeback": 0,
+                    "unevictable": 0,
+                    "pgpgin": 477,
+                    "total_unevictable": 0,
+                    "pgmajfault": 0,
+                    "total_rss": 6_537_216,
+                    "total_rss_huge": 6_291_456,
+                    "total_writeback": 0,
+                    "total_inactive_anon": 0,
+                    "rss_huge": 6_291_456,
+                    "hierarchical_memory_limit": 67_108_864,
+                    "hierarchical_memsw_limit": 67_108_864,
+                    "total_pgfault": 964,
+                    "total_active_file": 0,
+                    "active_anon": 6_537_216,
+                    "total_active_anon": 6_537_216,
+                    "total_pgpgout": 414,
+                    "total_cache": 0,
+                    "inactive_anon": 0,
+                    "active_file": 0,
+                    "pgfault": 964,
+                    "inactive_file": 0,
+                    "total_pgpgin": 477
+                }
+            },
+            "blkio_stats": {
+                "io_service_bytes_recursive": [],
+                "io_serviced_recursive": [],
+                "io_queue_recursive": [],
+                "io_service_time_recursive": [],
+                "io_wait_time_recursive": [],
+                "io_merged_recursive": [],
+                "io_time_recursive": [],
+                "sectors_recursive": []
+            },
+            "cpu_stats": {
+                "cpu_usage": {
+                    "percpu_usage": [8_646_879, 24_472_255, 36_438_778, 30_657_443],
+                    "usage_in_usermode": 50_000_000,
+                    "total_usage": 100_215_355,
+                    "usage_in_kernelmode": 30_000_000
+                },
+                "system_cpu_usage": 739_306_590_000_000u64,
+                "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+            }
+        })
+    }
+
+    /// A sample of the response to `GET /containers/{id}/stats` on a host with cgroup v2, which
+    /// reports fewer statistics than cgroup v1
+    pub fn stats_cgroup_v2() -> Value {
+        json!({
+            "read": "2023-03-14T09:26:53.000000000Z",
+            "pids_stats": { "current": 3, "limit": 18_446_744_073_709_551_615u64 },
+            "networks": {
+                "eth0": {
+                    "rx_bytes": 1_466,
+                    "rx_dropped": 0,
+                    "rx_errors": 0,
+                    "rx_packets": 15,
+                    "tx_bytes": 0,
+                    "tx_dropped": 0,
+                    "tx_errors": 0,
+                    "tx_packets": 0
+                }
+            },
+            "memory_stats": {
+                "usage": 2_527_232,
+                "limit": 8_232_759_296u64,
+                "stats": {
+                    "active_anon": 4_096,
+                    "active_file": 0,
+                    "anon": 495_616,
+                    "anon_thp": 0,
+                    "file": 1_507_328,
+                    "file_dirty": 0,
+                    "file_mapped": 1_007_616,
+                    "file_writeback": 0,
+                    "inactive_anon": 491_520,
+                    "inactive_file": 1_507_328,
+                    "kernel_stack": 49_152,
+                    "pgactivate": 0,
+                    "pgfault": 1_353,
+                    "pgmajfault": 7,
+                    "shmem": 0,
+                    "slab": 339_752,
+                    "sock": 0,
+                    "unevictable": 0,
+                    "workingset_refault": 0
+                }
+            },
+            "blkio_stats": {
+                "io_service_bytes_recursive": [
+                    { "major": 8, "minor": 0, "op": "read", "value": 1_507_328 },
+                    { "major": 8, "minor": 0, "op": "write", "value": 0 }
+                ],
+                "io_serviced_recursive": null,
+                "io_queue_recursive": null,
+                "io_service_time_recursive": null,
+                "io_wait_time_recursive": null,
+                "io_merged_recursive": null,
+                "io_time_recursive": null,
+                "sectors_recursive": null
+            },
+            "cpu_stats": {
+                "cpu_usage": {
+                    "total_usage": 35_012_000,
+                    "usage_in_kernelmode": 11_521_000,
+                    "usage_in_usermode": 23_491_000
+                },
+                "system_cpu_usage": 81_437_060_000_000u64,
+                "online_cpus": 4,
+                "throttling_data": { "periods": 0, "throttled_periods": 0, "throttled_time": 0 }
+            }
+        })
+    }
+
+    /// Response to `GET /services/{id}` for a service of two `nginx:alpine` tasks at the spec
+    /// `version`
+    pub fn service_details(
+        id: &str,
+        name: &str,
+        version: u64,
+    ) -> Value {
+        json!({
+            "ID": id,
+            "Version": { "Index": version },
+            "CreatedAt": "2021-07-01T11:46:40.000000000Z",
+            "UpdatedAt": "2021-07-01T11:46:40.000000000Z",
+            "Spec": {
+                "Name": name,
+                "TaskTemplate": { "ContainerSpec": { "Image": "nginx:alpine" } },
+                "Mode": { "Replicated": { "Replicas": 2 } }
+            },
+            "Endpoint": { "Spec": {} }
+        })
+    }
+
+    /// An element of the response to `GET /tasks` for a task of `service_id` running
+    /// `nginx:alpine`, in `state` and meant to run
+    pub fn task(
+        id: &str,
+        service_id: &str,
+        state: &str,
+    ) -> Value {
+        json!({
+            "ID": id,
+            "Version": { "Index": 11 },
+            "CreatedAt": "2021-07-01T11:46:41.000000000Z",
+            "UpdatedAt": "2021-07-01T11:46:42.000000000Z",
+            "Spec": { "ContainerSpec": { "Image": "nginx:alpine" } },
+            "ServiceID": service_id,
+            "Slot": 1,
+            "NodeID": "60gvrl6tm78dmak4yl7srz94v",
+            "Status": { "Timestamp": "2021-07-01T11:46:42.000000000Z", "State": state },
+            "DesiredState": "running"
+        })
+    }
+
+    /// Progress messages streamed in response to `POST /images/create` when pulling `image`
+    pub fn pull_progress(image: &str) -> Vec<Value> {
+        json!([
+            { "status": format!("Pulling from library/{}", image), "id": "latest" },
+            { "status": "Pulling fs layer", "progressDetail": {}, "id": "5843afab3874" },
+            {
+                "status": "Downloading",
+                "progressDetail": { "current": 1_024, "total": 2_811_478 },
+                "progress": "[>                                                  ]  1.024kB/2.811MB",
+                "id": "5843afab3874"
+            },
+            { "status": "Pull complete", "progressDetail": {}, "id": "5843afab3874" },
+            { "status": format!("Status: Downloaded newer image for {}:latest", image) }
+        ])
+        .as_array()
+        .unwrap()
+        .clone()
+    }
+
+    /// Output streamed in response to `POST /build` for a build resulting in the image `id`
+    pub fn build_output(id: &str) -> Vec<Value> {
+        vec![
+            json!({ "stream": "Step 1/2 : FROM alpine\n" }),
+            json!({ "stream": " ---> 14119a10abf4\n" }),
+            json!({ "stream": "Step 2/2 : RUN true\n" }),
+            json!({ "aux": { "ID": id } }),
+            json!({ "stream": format!("Successfully built {:#}\n", ImageId::from(id)) }),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Docker;
+    use futures_util::TryStreamExt;
+
+    #[test]
+    fn mock_daemon_responses_and_requests() {
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/containers/json",
+                MockResponse::json(&vec![fixtures::container_summary("4fa6e0f0c678", "web")]),
+            )
+            .respond_once("DELETE", "/containers/web", MockResponse::new(204, ""))
+            .respond(
+                "POST",
+                "/images/create",
+                MockResponse::json_stream(&fixtures::pull_progress("alpine")),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        futures::executor::block_on(async {
+            let containers = docker
+                .containers()
+                .list(&crate::ContainerListOptions::builder().all().build())
+                .await
+                .unwrap();
+            assert_eq!(containers[0].id, "4fa6e0f0c678");
+
+            docker.containers().get("web").delete().await.unwrap();
+            let err = docker.containers().get("web").delete().await.unwrap_err();
+            assert!(err.is_not_found());
+
+            let progress: Vec<_> = docker
+                .images()
+                .pull(&crate::PullOptions::builder().image("alpine").build())
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(progress.len(), 5);
+        });
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].path, "/containers/json?all=true");
+        assert_eq!(requests[1].method, Method::DELETE);
+        assert!(requests[3].path.starts_with("/images/create?"));
+        assert!(requests[3].path.contains("fromImage=alpine"));
+    }
+
+    #[test]
+    fn models_from_fixtures() {
+        let details: crate::container::ContainerDetails =
+            model(fixtures::container_details("4fa6e0f0c678", "web"));
+        assert_eq!(details.name, "/web");
+        assert!(details.state.running);
+
+        let image: crate::image::ImageDetails = model(fixtures::image_details(
+            "sha256:14119a10abf4",
+            "alpine:latest",
+        ));
+        assert_eq!(image.repo_tags.unwrap(), ["alpine:latest"]);
+
+        let stats: crate::container::Stats = model(fixtures::stats());
+        assert_eq!(stats.networks["eth0"].rx_packets, 36);
+
+        let chunk: crate::image::ImageBuildChunk =
+            model(fixtures::pull_progress("alpine")[2].clone());
+        assert_eq!(chunk.total_image_bytes(), Some(2_811_478));
+    }
+}
@@ -10,11 +10,12 @@ use std::{
 use hyper::Body;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use url::form_urlencoded;
 
 use crate::{
     docker::Docker,
     errors::{Error, Result},
+    id::NetworkId,
+    query::{self, Filters},
 };
 
 /// Interface for docker network
@@ -38,7 +39,7 @@ impl<'docker> Networks<'docker> {
         opts: &NetworkListOptions,
     ) -> Result<Vec<NetworkDetails>> {
         let mut path = vec!["/networks".to_owned()];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query);
         }
         self.docker.get_json(&path.join("?")).await
@@ -50,7 +51,7 @@ impl<'docker> Networks<'docker> {
         id: S,
     ) -> Network<'docker>
     where
-        S: Into<String>,
+        S: Into<NetworkId>,
     {
         Network::new(self.docker, id)
     }
@@ -63,7 +64,7 @@ impl<'docker> Networks<'docker> {
         opts: &NetworkCreateOptions,
     ) -> Result<NetworkCreateInfo> {
         let body: Body = opts.serialize()?.into();
-        let path = vec!["/networks/create".to_owned()];
+        let path = ["/networks/create".to_owned()];
 
         self.docker
             .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
@@ -84,11 +85,11 @@ impl<'docker> Network<'docker> {
         id: S,
     ) -> Self
     where
-        S: Into<String>,
+        S: Into<NetworkId>,
     {
         Network {
             docker,
-            id: id.into(),
+            id: id.into().into_string(),
         }
     }
 
@@ -154,26 +155,75 @@ impl<'docker> Network<'docker> {
 }
 
 /// Options for filtering networks list results
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct NetworkListOptions {
-    params: HashMap<&'static str, String>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl NetworkListOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> NetworkListOptionsBuilder {
+        NetworkListOptionsBuilder::default()
+    }
+
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for network listings
+pub enum NetworkFilter {
+    /// Networks not used by any container
+    Dangling(bool),
+    Driver(String),
+    Id(String),
+    LabelName(String),
+    Label(String, String),
+    Name(String),
+    /// Networks of a scope, one of `swarm`, `global` or `local`
+    Scope(String),
+    /// Networks of a type, `custom` for user defined networks or `builtin`
+    Type(String),
+}
+
+impl query::Filter for NetworkFilter {
+    fn query(self) -> (&'static str, String) {
+        match self {
+            NetworkFilter::Dangling(d) => ("dangling", d.to_string()),
+            NetworkFilter::Driver(d) => ("driver", d),
+            NetworkFilter::Id(i) => ("id", i),
+            NetworkFilter::LabelName(n) => ("label", n),
+            NetworkFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
+            NetworkFilter::Name(n) => ("name", n),
+            NetworkFilter::Scope(s) => ("scope", s),
+            NetworkFilter::Type(t) => ("type", t),
         }
     }
 }
 
+/// Builder interface for `NetworkListOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct NetworkListOptionsBuilder {
+    options: NetworkListOptions,
+}
+
+impl NetworkListOptionsBuilder {
+    pub fn filter(
+        mut self,
+        filters: Vec<NetworkFilter>,
+    ) -> Self {
+        self.options.filters = Filters::typed(filters);
+        self
+    }
+
+    pub fn build(self) -> NetworkListOptions {
+        self.options
+    }
+}
+
 /// Interface for creating new docker network
 #[derive(Serialize, Debug)]
 pub struct NetworkCreateOptions {
@@ -195,21 +245,24 @@ impl NetworkCreateOptions {
         &self,
         params: &'a HashMap<K, V>,
         body: &mut serde_json::Map<String, Value>,
-    ) where
+    ) -> Result<()>
+    where
         &'a HashMap<K, V>: IntoIterator,
         K: ToString + Eq + Hash,
         V: Serialize,
     {
         for (k, v) in params.iter() {
             let key = k.to_string();
-            let value = serde_json::to_value(v).unwrap();
+            let value = serde_json::to_value(v)?;
 
             body.insert(key, value);
         }
+        Ok(())
     }
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct NetworkCreateOptionsBuilder {
     params: HashMap<&'static str, Value>,
 }
@@ -222,9 +275,9 @@ impl NetworkCreateOptionsBuilder {
     }
 
     pub fn driver(
-        &mut self,
+        mut self,
         name: &str,
-    ) -> &mut Self {
+    ) -> Self {
         if !name.is_empty() {
             self.params.insert("Driver", json!(name));
         }
@@ -232,16 +285,16 @@ impl NetworkCreateOptionsBuilder {
     }
 
     pub fn label(
-        &mut self,
+        mut self,
         labels: HashMap<String, String>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Labels", json!(labels));
         self
     }
 
-    pub fn build(&self) -> NetworkCreateOptions {
+    pub fn build(self) -> NetworkCreateOptions {
         NetworkCreateOptions {
-            params: self.params.clone(),
+            params: self.params,
         }
     }
 }
@@ -262,17 +315,19 @@ impl ContainerConnectionOptions {
         &self,
         params: &'a HashMap<K, V>,
         body: &mut BTreeMap<String, Value>,
-    ) where
+    ) -> Result<()>
+    where
         &'a HashMap<K, V>: IntoIterator,
         K: ToString + Eq + Hash,
         V: Serialize,
     {
         for (k, v) in params.iter() {
             let key = k.to_string();
-            let value = serde_json::to_value(v).unwrap();
+            let value = serde_json::to_value(v)?;
 
             body.insert(key, value);
         }
+        Ok(())
     }
 
     /// return a new instance of a builder for options
@@ -282,6 +337,7 @@ impl ContainerConnectionOptions {
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ContainerConnectionOptionsBuilder {
     params: HashMap<&'static str, Value>,
 }
@@ -294,22 +350,22 @@ impl ContainerConnectionOptionsBuilder {
     }
 
     pub fn aliases(
-        &mut self,
+        mut self,
         aliases: Vec<&str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params
             .insert("EndpointConfig", json!({ "Aliases": json!(aliases) }));
         self
     }
 
-    pub fn force(&mut self) -> &mut Self {
+    pub fn force(mut self) -> Self {
         self.params.insert("Force", json!(true));
         self
     }
 
-    pub fn build(&self) -> ContainerConnectionOptions {
+    pub fn build(self) -> ContainerConnectionOptions {
         ContainerConnectionOptions {
-            params: self.params.clone(),
+            params: self.params,
         }
     }
 }
@@ -318,6 +374,7 @@ type PortDescription = HashMap<String, Option<Vec<HashMap<String, String>>>>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct NetworkSettings {
     pub bridge: String,
     pub gateway: String,
@@ -332,6 +389,7 @@ pub struct NetworkSettings {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct NetworkEntry {
     #[serde(rename = "NetworkID")]
     pub network_id: String,
@@ -357,6 +415,7 @@ pub struct NetworkEntry {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[allow(clippy::upper_case_acronyms)]
+#[non_exhaustive]
 pub struct EndpointIPAMConfig {
     #[serde(rename = "IPv4Address")]
     pub ipv4_address: String,
@@ -368,6 +427,7 @@ pub struct EndpointIPAMConfig {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Ipam {
     pub driver: String,
     pub config: Vec<HashMap<String, String>>,
@@ -376,9 +436,10 @@ pub struct Ipam {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct NetworkDetails {
     pub name: String,
-    pub id: String,
+    pub id: NetworkId,
     pub scope: String,
     pub driver: String,
     #[serde(rename = "EnableIPv6")]
@@ -390,10 +451,15 @@ pub struct NetworkDetails {
     pub containers: HashMap<String, NetworkContainerDetails>,
     pub options: Option<HashMap<String, String>>,
     pub labels: Option<HashMap<String, String>>,
+    /// Settings of the network that aren't modelled, like `Ingress` and `ConfigFrom`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct NetworkContainerDetails {
     #[serde(rename = "EndpointID")]
     pub endpoint_id: String,
@@ -406,7 +472,56 @@ pub struct NetworkContainerDetails {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct NetworkCreateInfo {
-    pub id: String,
+    pub id: NetworkId,
     pub warning: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_list_options_filters() {
+        assert_eq!(NetworkListOptions::default().serialize().unwrap(), None);
+
+        let opts = NetworkListOptions::builder()
+            .filter(vec![
+                NetworkFilter::Driver("bridge".to_owned()),
+                NetworkFilter::Label("env".to_owned(), "ci".to_owned()),
+                NetworkFilter::Scope("local".to_owned()),
+            ])
+            .build();
+        assert_eq!(
+            opts.serialize().unwrap().unwrap(),
+            "filters=%7B%22driver%22%3A%5B%22bridge%22%5D%2C%22label%22%3A%5B%22env%3Dci%22%5D%2C%22scope%22%3A%5B%22local%22%5D%7D"
+        );
+    }
+
+    #[test]
+    fn network_filters_query() {
+        use query::Filter;
+
+        assert_eq!(
+            NetworkFilter::Dangling(false).query(),
+            ("dangling", "false".to_owned())
+        );
+        assert_eq!(
+            NetworkFilter::Id("4c7f".to_owned()).query(),
+            ("id", "4c7f".to_owned())
+        );
+        assert_eq!(
+            NetworkFilter::LabelName("keep".to_owned()).query(),
+            ("label", "keep".to_owned())
+        );
+        assert_eq!(
+            NetworkFilter::Name("backend".to_owned()).query(),
+            ("name", "backend".to_owned())
+        );
+        assert_eq!(
+            NetworkFilter::Type("custom".to_owned()).query(),
+            ("type", "custom".to_owned())
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Inspect the nodes that make up a swarm.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Node>
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::{
+    docker::Docker,
+    errors::{Error, Result},
+    query::{self, Filters},
+    service::ObjectVersion,
+    task::{TaskFilter, TaskListOptions, Tasks},
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
+
+/// Interface for docker swarm nodes
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Node>
+pub struct Nodes<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Nodes<'docker> {
+    /// Exports an interface for interacting with docker swarm nodes
+    pub fn new(docker: &'docker Docker) -> Self {
+        Nodes { docker }
+    }
+
+    /// Lists the nodes in the swarm
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NodeList>
+    pub async fn list(
+        &self,
+        opts: &NodeListOptions,
+    ) -> Result<Vec<NodeInfo>> {
+        let mut path = vec!["/nodes".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
+        self.docker.get_json(&path.join("?")).await
+    }
+
+    /// Returns a reference to a set of operations available for a node
+    pub fn get(
+        &self,
+        id: &str,
+    ) -> Node<'docker> {
+        Node::new(self.docker, id)
+    }
+}
+
+/// Interface for accessing and manipulating a docker swarm node
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Node>
+pub struct Node<'docker> {
+    docker: &'docker Docker,
+    id: String,
+}
+
+impl<'docker> Node<'docker> {
+    /// Exports an interface for operations that may be performed against a node, given by ID or
+    /// name
+    pub fn new<S>(
+        docker: &'docker Docker,
+        id: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Node {
+            docker,
+            id: id.into(),
+        }
+    }
+
+    /// Inspects a node
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NodeInspect>
+    pub async fn inspect(&self) -> Result<NodeInfo> {
+        self.docker
+            .get_json(&format!("/nodes/{}", self.id)[..])
+            .await
+    }
+
+    /// Replaces the node's spec, which controls its availability, role and labels
+    ///
+    /// The spec replaces the current one entirely, so it is usually derived from the spec returned
+    /// by [Node::inspect]. Like services, nodes use their version for optimistic concurrency
+    /// control: when `version` is `None` the current version is looked up first, and a concurrent
+    /// modification results in [Error::UpdateOutOfSequence](crate::Error::UpdateOutOfSequence).
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NodeUpdate>
+    pub async fn update(
+        &self,
+        spec: &NodeSpec,
+        version: Option<u64>,
+    ) -> Result<()> {
+        let version = match version {
+            Some(version) => version,
+            None => self.inspect().await?.version.index,
+        };
+
+        let body: Body = serde_json::to_string(spec)?.into();
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("version", &version.to_string())
+            .finish();
+
+        self.docker
+            .post(
+                &format!("/nodes/{}/update?{}", self.id, query),
+                Some((body, mime::APPLICATION_JSON)),
+            )
+            .await
+            .map_err(Error::into_update_error)?;
+        Ok(())
+    }
+
+    /// Removes the node from the swarm
+    ///
+    /// Unless `force` is set the node has to be down before it can be removed.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NodeDelete>
+    pub async fn delete(
+        &self,
+        force: bool,
+    ) -> Result<()> {
+        let mut path = format!("/nodes/{}", self.id);
+        if force {
+            path.push_str("?force=true");
+        }
+        self.docker.delete(&path).await?;
+        Ok(())
+    }
+
+    /// Sets the node's availability to drain and waits until all of its tasks have stopped
+    ///
+    /// Tasks that are still starting count as well as running ones. Returns `false` if tasks
+    /// had not stopped on the node when `timeout` elapsed.
+    pub async fn drain_and_wait(
+        &self,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+
+        let node = self.inspect().await?;
+        if node.spec.availability != NodeAvailability::Drain {
+            let spec = NodeSpec {
+                availability: NodeAvailability::Drain,
+                ..node.spec
+            };
+            self.update(&spec, Some(node.version.index)).await?;
+        }
+
+        let opts = TaskListOptions::builder()
+            .filter(vec![TaskFilter::Node(node.id)])
+            .build();
+        let tasks = Tasks::new(self.docker);
+        loop {
+            let active = tasks
+                .list(&opts)
+                .await?
+                .iter()
+                .any(|task| !task.status.state.is_terminal());
+            if !active {
+                return Ok(true);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+}
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Options for filtering node list results
+#[derive(Default, Debug, Serialize)]
+pub struct NodeListOptions {
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
+}
+
+impl NodeListOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> NodeListOptionsBuilder {
+        NodeListOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for node listings
+pub enum NodeFilter {
+    Id(String),
+    /// Nodes whose engine carries a label, given either as `key` or `key=value`
+    Label(String),
+    /// Nodes carrying a swarm node label, given either as `key` or `key=value`
+    NodeLabel(String),
+    /// Nodes whose membership is `accepted` or `pending`
+    Membership(String),
+    Name(String),
+    Role(NodeRole),
+}
+
+/// Builder interface for `NodeListOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct NodeListOptionsBuilder {
+    options: NodeListOptions,
+}
+
+impl NodeListOptionsBuilder {
+    pub fn filter(
+        mut self,
+        filters: Vec<NodeFilter>,
+    ) -> Self {
+        self.options.filters = filters
+            .into_iter()
+            .map(|f| match f {
+                NodeFilter::Id(i) => ("id", i),
+                NodeFilter::Label(l) => ("label", l),
+                NodeFilter::NodeLabel(l) => ("node.label", l),
+                NodeFilter::Membership(m) => ("membership", m),
+                NodeFilter::Name(n) => ("name", n),
+                NodeFilter::Role(r) => ("role", r.as_ref().to_owned()),
+            })
+            .collect();
+        self
+    }
+
+    pub fn build(self) -> NodeListOptions {
+        self.options
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct NodeInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub updated_at: String,
+    pub spec: NodeSpec,
+    pub description: NodeDescription,
+    pub status: NodeStatus,
+    /// Only set for manager nodes
+    pub manager_status: Option<ManagerStatus>,
+    /// Fields swarm managers newer than API version 1.41 add to a node
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    pub role: NodeRole,
+    pub availability: NodeAvailability,
+}
+
+/// Role of a node within the swarm
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Worker,
+    Manager,
+}
+
+impl AsRef<str> for NodeRole {
+    fn as_ref(&self) -> &str {
+        match self {
+            NodeRole::Worker => "worker",
+            NodeRole::Manager => "manager",
+        }
+    }
+}
+
+/// Whether a node accepts new tasks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeAvailability {
+    Active,
+    Pause,
+    Drain,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct NodeDescription {
+    pub hostname: Option<String>,
+    pub platform: Option<NodePlatform>,
+    pub resources: Option<NodeResources>,
+    pub engine: Option<EngineDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct NodePlatform {
+    pub architecture: Option<String>,
+    #[serde(rename = "OS")]
+    pub os: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct NodeResources {
+    #[serde(rename = "NanoCPUs")]
+    pub nano_cpus: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub generic_resources: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct EngineDescription {
+    pub engine_version: Option<String>,
+    pub labels: Option<HashMap<String, String>>,
+    pub plugins: Option<Vec<EnginePlugin>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct EnginePlugin {
+    /// Kind of plugin, e.g. `Volume`, `Network` or `Log`
+    #[serde(rename = "Type")]
+    pub type_: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct NodeStatus {
+    pub state: NodeState,
+    pub message: Option<String>,
+    /// IP address of the node
+    pub addr: Option<String>,
+}
+
+/// Health of a node as seen by the swarm managers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeState {
+    Unknown,
+    Down,
+    Ready,
+    Disconnected,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct ManagerStatus {
+    #[serde(default)]
+    pub leader: bool,
+    pub reachability: Reachability,
+    /// Address and port of the manager's raft endpoint
+    pub addr: Option<String>,
+}
+
+/// Reachability of a manager node from the other managers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reachability {
+    Unknown,
+    Unreachable,
+    Reachable,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_list_options_filters() {
+        let options = NodeListOptions::builder()
+            .filter(vec![
+                NodeFilter::Role(NodeRole::Manager),
+                NodeFilter::NodeLabel("zone=a".to_owned()),
+                NodeFilter::NodeLabel("ssd".to_owned()),
+            ])
+            .build();
+        let serialized = options.serialize().unwrap().unwrap();
+        let filters: HashMap<String, Vec<String>> = serde_json::from_str(
+            &form_urlencoded::parse(serialized.as_bytes())
+                .find(|(key, _)| key == "filters")
+                .unwrap()
+                .1,
+        )
+        .unwrap();
+
+        assert_eq!(filters["role"], vec!["manager"]);
+        assert_eq!(filters["node.label"], vec!["zone=a", "ssd"]);
+    }
+
+    #[test]
+    fn drain_and_wait() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker,
+        };
+
+        let node = serde_json::json!({
+            "ID": "24ifsmvkjbyhk",
+            "Version": {"Index": 373531},
+            "CreatedAt": "2016-08-18T10:44:24.496525531Z",
+            "UpdatedAt": "2017-08-09T07:09:37.632105588Z",
+            "Spec": {"Availability": "active", "Role": "worker"},
+            "Description": {"Hostname": "bf3067039e47"},
+            "Status": {"State": "ready"}
+        });
+        let daemon = MockDaemon::new();
+        daemon
+            .respond("GET", "/nodes/24ifsmvkjbyhk", MockResponse::json(&node))
+            .respond(
+                "POST",
+                "/nodes/24ifsmvkjbyhk/update",
+                MockResponse::new(200, ""),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&serde_json::json!([
+                    fixtures::task("a1", "9mnp", "shutdown"),
+                    fixtures::task("b2", "9mnp", "starting"),
+                ])),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&serde_json::json!([
+                    fixtures::task("a1", "9mnp", "shutdown"),
+                    fixtures::task("b2", "9mnp", "failed"),
+                ])),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let node = docker.nodes().get("24ifsmvkjbyhk");
+
+        // a task that is still starting keeps the node from being drained
+        assert!(!futures::executor::block_on(node.drain_and_wait(Duration::ZERO)).unwrap());
+        assert!(futures::executor::block_on(node.drain_and_wait(Duration::ZERO)).unwrap());
+        let requests = daemon.requests();
+        assert_eq!(
+            requests[1].path,
+            "/nodes/24ifsmvkjbyhk/update?version=373531"
+        );
+        assert_eq!(requests[1].json().unwrap()["Availability"], "drain");
+    }
+
+    #[test]
+    fn node_info_manager() {
+        let node: NodeInfo = serde_json::from_str(
+            r#"{
+                "ID": "24ifsmvkjbyhk",
+                "Version": {"Index": 373531},
+                "CreatedAt": "2016-08-18T10:44:24.496525531Z",
+                "UpdatedAt": "2017-08-09T07:09:37.632105588Z",
+                "Spec": {
+                    "Availability": "active",
+                    "Name": "node-name",
+                    "Role": "manager",
+                    "Labels": {"foo": "bar"}
+                },
+                "Description": {
+                    "Hostname": "bf3067039e47",
+                    "Platform": {"Architecture": "x86_64", "OS": "linux"},
+                    "Resources": {"NanoCPUs": 4000000000, "MemoryBytes": 8272408576},
+                    "Engine": {
+                        "EngineVersion": "17.06.0",
+                        "Labels": {"foo": "bar"},
+                        "Plugins": [
+                            {"Type": "Log", "Name": "awslogs"},
+                            {"Type": "Volume", "Name": "local"}
+                        ]
+                    }
+                },
+                "Status": {"State": "ready", "Message": "", "Addr": "172.17.0.2"},
+                "ManagerStatus": {
+                    "Leader": true,
+                    "Reachability": "reachable",
+                    "Addr": "10.0.0.46:2377"
+                },
+                "Maintenance": {"Window": "sunday"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(node.spec.role, NodeRole::Manager);
+        assert_eq!(node.spec.availability, NodeAvailability::Active);
+        assert_eq!(node.status.state, NodeState::Ready);
+        let manager = node.manager_status.unwrap();
+        assert!(manager.leader);
+        assert_eq!(manager.reachability, Reachability::Reachable);
+        let engine = node.description.engine.unwrap();
+        assert_eq!(engine.plugins.unwrap()[0].type_, "Log");
+        // a field of a newer swarm manager
+        #[cfg(feature = "extra-fields")]
+        assert_eq!(node.extra["Maintenance"]["Window"], "sunday");
+    }
+
+    #[test]
+    fn node_spec_serialize() {
+        let spec = NodeSpec {
+            name: None,
+            labels: Some(
+                vec![("zone".to_owned(), "a".to_owned())]
+                    .into_iter()
+                    .collect(),
+            ),
+            role: NodeRole::Worker,
+            availability: NodeAvailability::Drain,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            serde_json::json!({
+                "Labels": {"zone": "a"},
+                "Role": "worker",
+                "Availability": "drain"
+            })
+        );
+    }
+}
@@ -0,0 +1,709 @@
+//! Install and manage plugins that extend the docker engine.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Plugin>
+
+use std::{iter, time::Duration};
+
+use futures_util::{stream::Stream, TryFutureExt};
+use hyper::{Body, Method};
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::{
+    docker::Docker,
+    duration,
+    errors::{Error, Result},
+    image::{ImageBuildChunk, RegistryAuth},
+    query::{self, Filters},
+};
+
+/// Interface for docker plugins
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Plugin>
+pub struct Plugins<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Plugins<'docker> {
+    /// Exports an interface for interacting with docker plugins
+    pub fn new(docker: &'docker Docker) -> Self {
+        Plugins { docker }
+    }
+
+    /// Lists the plugins installed on the current docker host
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginList>
+    pub async fn list(
+        &self,
+        opts: &PluginListOptions,
+    ) -> Result<Vec<PluginInfo>> {
+        let mut path = vec!["/plugins".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
+        self.docker.get_json(&path.join("?")).await
+    }
+
+    /// Returns the privileges a plugin requires to be granted before it can be installed, looking
+    /// the plugin up with `auth` if it is given
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/GetPluginPrivileges>
+    pub async fn privileges(
+        &self,
+        remote: &str,
+        auth: Option<&RegistryAuth>,
+    ) -> Result<Vec<PluginPrivilege>> {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("remote", remote)
+            .finish();
+        self.docker
+            .request_json(
+                Method::GET,
+                "/plugins/privileges",
+                Some(&query),
+                None::<(Body, Mime)>,
+                auth.map(|a| ("X-Registry-Auth", a.serialize())),
+            )
+            .await
+    }
+
+    /// Installs the plugin `remote`, a reference such as `vieux/sshfs:latest`
+    ///
+    /// The privileges the plugin requires are looked up first and handed to `accept`. The plugin
+    /// is only pulled if `accept` returns `true`, otherwise the stream yields
+    /// [Error::PluginPrivilegesRejected](crate::Error::PluginPrivilegesRejected). The returned
+    /// stream reports the progress of the pull like [Images::pull](crate::Images::pull). The
+    /// installed plugin is disabled until [Plugin::enable] is called.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginPull>
+    pub fn install<F>(
+        &self,
+        remote: &str,
+        opts: &PluginInstallOptions,
+        accept: F,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        F: FnOnce(&[PluginPrivilege]) -> bool + 'docker,
+    {
+        let docker = self.docker;
+        let remote = remote.to_owned();
+        let params = opts.serialize();
+        let auth = opts.auth.clone();
+        let headers = opts
+            .auth_header()
+            .map(|a| iter::once(("X-Registry-Auth", a)));
+
+        Box::pin(
+            async move {
+                let mut query = form_urlencoded::Serializer::new(String::new());
+                query.append_pair("remote", &remote);
+                if let Some(params) = params? {
+                    query.extend_pairs(form_urlencoded::parse(params.as_bytes()));
+                }
+                let path = format!("/plugins/pull?{}", query.finish());
+
+                let privileges = Plugins::new(docker)
+                    .privileges(&remote, auth.as_ref())
+                    .await?;
+                if !accept(&privileges) {
+                    return Err(Error::PluginPrivilegesRejected(remote));
+                }
+
+                let body: Body = serde_json::to_string(&privileges)?.into();
+                let value_stream =
+                    docker.stream_post_into(path, Some((body, mime::APPLICATION_JSON)), headers);
+
+                Ok(value_stream)
+            }
+            .try_flatten_stream(),
+        )
+    }
+
+    /// Returns a reference to a set of operations available for a named plugin
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Plugin<'docker> {
+        Plugin::new(self.docker, name)
+    }
+}
+
+/// Interface for accessing and manipulating a named docker plugin
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Plugin>
+pub struct Plugin<'docker> {
+    docker: &'docker Docker,
+    name: String,
+}
+
+impl<'docker> Plugin<'docker> {
+    /// Exports an interface for operations that may be performed against a named plugin
+    pub fn new<S>(
+        docker: &'docker Docker,
+        name: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Plugin {
+            docker,
+            name: name.into(),
+        }
+    }
+
+    /// Inspects a plugin
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginInspect>
+    pub async fn inspect(&self) -> Result<PluginInfo> {
+        self.docker
+            .get_json(&format!("/plugins/{}/json", self.name)[..])
+            .await
+    }
+
+    /// Enables the plugin, waiting at most `timeout` for it to come up. The daemon takes whole
+    /// seconds, so a partial second is rounded up.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginEnable>
+    pub async fn enable(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let mut path = vec![format!("/plugins/{}/enable", self.name)];
+        if let Some(t) = timeout {
+            let encoded = form_urlencoded::Serializer::new(String::new())
+                .append_pair("timeout", &duration::whole_secs(t).to_string())
+                .finish();
+
+            path.push(encoded)
+        }
+        self.docker.post(&path.join("?"), None).await?;
+        Ok(())
+    }
+
+    /// Disables the plugin. Unless `force` is set this fails while the plugin is in use.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginDisable>
+    pub async fn disable(
+        &self,
+        force: bool,
+    ) -> Result<()> {
+        let mut path = format!("/plugins/{}/disable", self.name);
+        if force {
+            path.push_str("?force=true");
+        }
+        self.docker.post(&path, None).await?;
+        Ok(())
+    }
+
+    /// Removes the plugin. Unless `force` is set this fails while the plugin is enabled.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginDelete>
+    pub async fn remove(
+        &self,
+        force: bool,
+    ) -> Result<()> {
+        let mut path = format!("/plugins/{}", self.name);
+        if force {
+            path.push_str("?force=true");
+        }
+        self.docker.delete(&path).await?;
+        Ok(())
+    }
+
+    /// Changes the plugin's settings
+    ///
+    /// Each argument is either `KEY=value` to set an environment variable, `source.path=value` to
+    /// change a mount or device, or `args=value` to set the plugin's arguments, e.g.
+    /// `vec!["DEBUG=1".to_owned()]`.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginSet>
+    pub async fn set(
+        &self,
+        args: Vec<String>,
+    ) -> Result<()> {
+        let body: Body = serde_json::to_string(&args)?.into();
+        self.docker
+            .post(
+                &format!("/plugins/{}/set", self.name),
+                Some((body, mime::APPLICATION_JSON)),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Upgrades the plugin to the version referenced by `remote`
+    ///
+    /// The plugin has to be disabled first. `privileges` are the privileges granted to the new
+    /// version, as returned by [Plugins::privileges], and `auth` authenticates the pull with the
+    /// registry. The returned stream reports the progress of the pull.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginUpgrade>
+    pub fn upgrade(
+        &self,
+        remote: &str,
+        privileges: &[PluginPrivilege],
+        auth: Option<&RegistryAuth>,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("remote", remote)
+            .finish();
+        let path = format!("/plugins/{}/upgrade?{}", self.name, query);
+        let body = serde_json::to_string(privileges).map(Body::from);
+        let headers = auth.map(|a| iter::once(("X-Registry-Auth", a.serialize())));
+        let docker = self.docker;
+
+        // Bubble up the serialization error inside the stream like `Images::build` does
+        Box::pin(
+            async move {
+                let value_stream =
+                    docker.stream_post_into(path, Some((body?, mime::APPLICATION_JSON)), headers);
+
+                Ok(value_stream)
+            }
+            .try_flatten_stream(),
+        )
+    }
+}
+
+/// Options for filtering plugin list results
+#[derive(Default, Debug, Serialize)]
+pub struct PluginListOptions {
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
+}
+
+impl PluginListOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> PluginListOptionsBuilder {
+        PluginListOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for plugin listings
+pub enum PluginFilter {
+    /// Plugins providing a capability, e.g. `volumedriver` or `networkdriver`
+    Capability(String),
+    Enabled(bool),
+}
+
+/// Builder interface for `PluginListOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct PluginListOptionsBuilder {
+    options: PluginListOptions,
+}
+
+impl PluginListOptionsBuilder {
+    pub fn filter(
+        mut self,
+        filters: Vec<PluginFilter>,
+    ) -> Self {
+        self.options.filters = filters
+            .into_iter()
+            .map(|f| match f {
+                PluginFilter::Capability(c) => ("capability", c),
+                PluginFilter::Enabled(e) => ("enable", e.to_string()),
+            })
+            .collect();
+        self
+    }
+
+    pub fn build(self) -> PluginListOptions {
+        self.options
+    }
+}
+
+/// Options for installing plugins
+#[derive(Default, Debug, Serialize)]
+pub struct PluginInstallOptions {
+    #[serde(skip)]
+    auth: Option<RegistryAuth>,
+    name: Option<String>,
+}
+
+impl PluginInstallOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> PluginInstallOptionsBuilder {
+        PluginInstallOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+
+    pub(crate) fn auth_header(&self) -> Option<String> {
+        self.auth.clone().map(|a| a.serialize())
+    }
+}
+
+/// Builder interface for `PluginInstallOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct PluginInstallOptionsBuilder {
+    options: PluginInstallOptions,
+}
+
+impl PluginInstallOptionsBuilder {
+    /// Local name of the installed plugin, defaults to the remote reference
+    pub fn name<S>(
+        mut self,
+        name: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    pub fn auth(
+        mut self,
+        auth: RegistryAuth,
+    ) -> Self {
+        self.options.auth = Some(auth);
+        self
+    }
+
+    pub fn build(self) -> PluginInstallOptions {
+        self.options
+    }
+}
+
+/// A privilege a plugin requires, such as access to the host network or a device
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PluginPrivilege {
+    pub name: String,
+    pub description: String,
+    pub value: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginInfo {
+    pub id: Option<String>,
+    pub name: String,
+    pub enabled: bool,
+    pub settings: PluginSettings,
+    /// Reference the plugin was pulled from
+    pub plugin_reference: Option<String>,
+    pub config: PluginConfig,
+    /// Fields daemons newer than API version 1.41 add to a plugin
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Settings that can be changed by the user
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginSettings {
+    pub mounts: Option<Vec<PluginMount>>,
+    /// Environment variables in the form `KEY=value`
+    pub env: Option<Vec<String>>,
+    pub args: Option<Vec<String>>,
+    pub devices: Option<Vec<PluginDevice>>,
+}
+
+/// Configuration of a plugin as published by its author
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginConfig {
+    pub docker_version: Option<String>,
+    pub description: String,
+    pub documentation: String,
+    pub interface: PluginInterface,
+    pub entrypoint: Option<Vec<String>>,
+    pub work_dir: String,
+    pub network: PluginNetwork,
+    pub linux: PluginLinux,
+    pub propagated_mount: String,
+    #[serde(default)]
+    pub ipc_host: bool,
+    #[serde(default)]
+    pub pid_host: bool,
+    pub mounts: Option<Vec<PluginMount>>,
+    pub env: Option<Vec<PluginEnv>>,
+    pub args: PluginArgs,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginInterface {
+    /// Capabilities provided by the plugin, e.g. `docker.volumedriver/1.0`
+    pub types: Vec<String>,
+    /// Name of the socket the engine uses to talk to the plugin
+    pub socket: String,
+    pub protocol_scheme: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginNetwork {
+    #[serde(rename = "Type")]
+    pub type_: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginLinux {
+    pub capabilities: Option<Vec<String>>,
+    #[serde(default)]
+    pub allow_all_devices: bool,
+    pub devices: Option<Vec<PluginDevice>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginMount {
+    pub name: String,
+    pub description: String,
+    pub settable: Option<Vec<String>>,
+    pub source: Option<String>,
+    pub destination: String,
+    #[serde(rename = "Type")]
+    pub type_: String,
+    pub options: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginDevice {
+    pub name: String,
+    pub description: String,
+    pub settable: Option<Vec<String>>,
+    pub path: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginEnv {
+    pub name: String,
+    pub description: String,
+    pub settable: Option<Vec<String>>,
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct PluginArgs {
+    pub name: String,
+    pub description: String,
+    pub settable: Option<Vec<String>>,
+    pub value: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn plugin_list_options_filters() {
+        let options = PluginListOptions::builder()
+            .filter(vec![
+                PluginFilter::Capability("volumedriver".to_owned()),
+                PluginFilter::Enabled(true),
+            ])
+            .build();
+        let serialized = options.serialize().unwrap().unwrap();
+        let filters: HashMap<String, Vec<String>> = serde_json::from_str(
+            &form_urlencoded::parse(serialized.as_bytes())
+                .find(|(key, _)| key == "filters")
+                .unwrap()
+                .1,
+        )
+        .unwrap();
+
+        assert_eq!(filters["capability"], vec!["volumedriver"]);
+        assert_eq!(filters["enable"], vec!["true"]);
+    }
+
+    #[test]
+    fn plugin_install_options() {
+        let options = PluginInstallOptions::builder()
+            .name("sshfs")
+            .auth(RegistryAuth::token("abc"))
+            .build();
+
+        assert_eq!(options.serialize().unwrap().unwrap(), "name=sshfs");
+        assert!(options.auth_header().is_some());
+    }
+
+    fn privileges() -> serde_json::Value {
+        serde_json::json!([{
+            "Name": "network",
+            "Description": "permissions to access a network",
+            "Value": ["host"]
+        }])
+    }
+
+    #[test]
+    fn install_plugin() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+        use futures_util::TryStreamExt;
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/plugins/privileges",
+                MockResponse::json(&privileges()),
+            )
+            .respond(
+                "POST",
+                "/plugins/pull",
+                MockResponse::json_stream(&fixtures::pull_progress("sshfs")),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let opts = PluginInstallOptions::builder()
+            .name("sshfs")
+            .auth(RegistryAuth::token("abc"))
+            .build();
+        let plugins = docker.plugins();
+
+        let rejected = plugins.install("vieux/sshfs:latest", &opts, |privileges| {
+            privileges
+                .iter()
+                .all(|privilege| privilege.name != "network")
+        });
+        match futures::executor::block_on(rejected.try_collect::<Vec<_>>()) {
+            Err(Error::PluginPrivilegesRejected(remote)) => {
+                assert_eq!(remote, "vieux/sshfs:latest")
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(daemon.requests().len(), 1);
+
+        let installed = plugins.install("vieux/sshfs:latest", &opts, |_| true);
+        let chunks = futures::executor::block_on(installed.try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(chunks.len(), 5);
+        let requests = daemon.requests();
+        assert_eq!(
+            requests[1].path,
+            "/plugins/privileges?remote=vieux%2Fsshfs%3Alatest"
+        );
+        // plugins of private registries can't be looked up without the credentials
+        assert!(requests[1].headers.contains_key("X-Registry-Auth"));
+        let pull = &requests[2];
+        assert_eq!(
+            pull.path,
+            "/plugins/pull?remote=vieux%2Fsshfs%3Alatest&name=sshfs"
+        );
+        assert!(pull.headers.contains_key("X-Registry-Auth"));
+        assert_eq!(pull.json().unwrap(), privileges());
+    }
+
+    #[test]
+    fn enable_and_upgrade_plugin() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+        use futures_util::TryStreamExt;
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond("POST", "/plugins/sshfs/enable", MockResponse::new(200, ""))
+            .respond(
+                "POST",
+                "/plugins/sshfs/upgrade",
+                MockResponse::json_stream(&fixtures::pull_progress("sshfs")),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let plugin = docker.plugins().get("sshfs");
+        let granted: Vec<PluginPrivilege> = serde_json::from_value(privileges()).unwrap();
+        let auth = RegistryAuth::token("abc");
+
+        futures::executor::block_on(async {
+            plugin
+                .enable(Some(Duration::from_millis(500)))
+                .await
+                .unwrap();
+            plugin
+                .upgrade("vieux/sshfs:next", &granted, Some(&auth))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        });
+        let requests = daemon.requests();
+        // a partial second is not sent as no timeout at all
+        assert_eq!(requests[0].path, "/plugins/sshfs/enable?timeout=1");
+        assert_eq!(
+            requests[1].path,
+            "/plugins/sshfs/upgrade?remote=vieux%2Fsshfs%3Anext"
+        );
+        assert!(requests[1].headers.contains_key("X-Registry-Auth"));
+        assert_eq!(requests[1].json().unwrap(), privileges());
+    }
+
+    #[test]
+    fn plugin_info_volume_driver() {
+        let plugin: PluginInfo = serde_json::from_str(
+            r#"{
+                "Id": "5724e2c8652da337ab2eedd19fc6fc0ec908e4bd907c7421bf6a8dfc70c4c078",
+                "Name": "tiborvass/sample-volume-plugin",
+                "Enabled": true,
+                "Settings": {
+                    "Mounts": [],
+                    "Env": ["DEBUG=0"],
+                    "Args": [],
+                    "Devices": []
+                },
+                "PluginReference": "localhost:5000/tiborvass/sample-volume-plugin:latest",
+                "Config": {
+                    "DockerVersion": "17.06.0-ce",
+                    "Description": "A sample volume plugin for Docker",
+                    "Documentation": "https://docs.docker.com/engine/extend/plugins/",
+                    "Interface": {
+                        "Types": ["docker.volumedriver/1.0"],
+                        "Socket": "plugins.sock"
+                    },
+                    "Entrypoint": ["/usr/bin/sample-volume-plugin", "/data"],
+                    "WorkDir": "",
+                    "User": {},
+                    "Network": {"Type": ""},
+                    "Linux": {"Capabilities": null, "AllowAllDevices": false, "Devices": null},
+                    "PropagatedMount": "/data",
+                    "IpcHost": false,
+                    "PidHost": false,
+                    "Mounts": [],
+                    "Env": [{
+                        "Name": "DEBUG",
+                        "Description": "If set, prints debug messages",
+                        "Settable": null,
+                        "Value": "0"
+                    }],
+                    "Args": {"Name": "args", "Description": "command line arguments", "Settable": null, "Value": []},
+                    "rootfs": {"type": "layers", "diff_ids": ["sha256:675532206fbf3030b8458f88d6e26d4eb1577688a25efec97154c94e8b6b4887"]}
+                },
+                "Upgradable": true
+            }"#,
+        )
+        .unwrap();
+
+        assert!(plugin.enabled);
+        assert_eq!(plugin.settings.env.unwrap(), vec!["DEBUG=0"]);
+        assert_eq!(
+            plugin.config.interface.types,
+            vec!["docker.volumedriver/1.0"]
+        );
+        assert_eq!(plugin.config.env.unwrap()[0].value.as_deref(), Some("0"));
+        #[cfg(feature = "extra-fields")]
+        assert_eq!(plugin.extra["Upgradable"], true);
+    }
+}
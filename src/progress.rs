@@ -0,0 +1,382 @@
+//! Render the progress of pulls, builds and imports to a terminal.
+//!
+//! A [ProgressRenderer] consumes the [ImageBuildChunk]s of [Images::pull](crate::Images::pull),
+//! [Images::build](crate::Images::build) or [Images::import](crate::Images::import), which it
+//! turns into [ProgressEvent]s with a [ProgressReporter]. On a
+//! terminal it draws a progress bar per layer the way the docker CLI does, redrawing them in
+//! place. Otherwise it writes a line whenever a layer changes its status, so that logs are not
+//! flooded with byte counts. Either way, [ProgressRenderer::summary] sums up the progress in a
+//! [ProgressSummary] that can be serialized for machine-readable output.
+//!
+//! ```no_run
+//! use shiplift::{progress::ProgressRenderer, Docker, PullOptions};
+//! # async {
+//! let docker = Docker::new();
+//! let pull = docker
+//!     .images()
+//!     .pull(&PullOptions::builder().image("alpine").build());
+//! let summary = ProgressRenderer::stderr().run(pull).await?;
+//! if let Some(error) = summary.error {
+//!     eprintln!("pull failed: {}", error);
+//! }
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::io::{self, IsTerminal, Write};
+
+use futures_util::stream::{Stream, StreamExt};
+use serde::Serialize;
+
+use crate::{
+    errors::Result,
+    image::{ImageBuildChunk, ProgressEvent, ProgressPhase, ProgressReporter},
+};
+
+/// Width of a progress bar, between the brackets
+const BAR_WIDTH: usize = 50;
+
+/// The progress of a pull, build or import as a whole
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProgressSummary {
+    /// Layers the daemon reported on
+    pub layers: usize,
+    /// Layers that were pulled or found to exist already
+    pub layers_complete: usize,
+    /// Bytes downloaded so far, over the layers whose size is known
+    pub bytes_current: u64,
+    /// Size of the layers whose size is known
+    pub bytes_total: u64,
+    /// The last build step the daemon started, like `Step 2/5 : RUN make`
+    pub step: Option<String>,
+    /// The ID of the image that was built
+    pub image_id: Option<String>,
+    /// The error the daemon reported, after which it stops sending progress
+    pub error: Option<String>,
+}
+
+struct Layer {
+    id: String,
+    phase: ProgressPhase,
+    current: Option<u64>,
+    total: Option<u64>,
+}
+
+impl Layer {
+    fn is_complete(&self) -> bool {
+        match &self.phase {
+            ProgressPhase::Downloaded | ProgressPhase::Complete | ProgressPhase::AlreadyExists => {
+                true
+            }
+            ProgressPhase::Other(status) => {
+                status == "Loading layer complete" || status.starts_with("Digest:")
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Draws the [ImageBuildChunk]s of a stream, see the [module docs](self)
+pub struct ProgressRenderer<W> {
+    out: W,
+    tty: bool,
+    reporter: ProgressReporter,
+    layers: Vec<Layer>,
+    /// Lines of layers drawn below the output, which are redrawn in place on a terminal
+    lines_drawn: usize,
+    summary: ProgressSummary,
+}
+
+impl ProgressRenderer<io::Stderr> {
+    /// A renderer drawing to stderr, with progress bars if it is a terminal
+    pub fn stderr() -> Self {
+        let tty = io::stderr().is_terminal();
+        ProgressRenderer::new(io::stderr(), tty)
+    }
+}
+
+impl<W> ProgressRenderer<W>
+where
+    W: Write,
+{
+    /// A renderer writing to `out`, drawing progress bars with terminal escape codes if `tty` is
+    /// set and writing a line per status change otherwise
+    pub fn new(
+        out: W,
+        tty: bool,
+    ) -> Self {
+        ProgressRenderer {
+            out,
+            tty,
+            reporter: ProgressReporter::new(),
+            layers: Vec::new(),
+            lines_drawn: 0,
+            summary: ProgressSummary::default(),
+        }
+    }
+
+    /// The progress rendered so far
+    pub fn summary(&self) -> &ProgressSummary {
+        &self.summary
+    }
+
+    /// Renders the chunks of `stream` until it ends, and returns the summary of the progress
+    ///
+    /// The error the daemon reports in a chunk is rendered and kept in
+    /// [ProgressSummary::error]. Errors of the stream itself are returned.
+    pub async fn run<S>(
+        mut self,
+        stream: S,
+    ) -> Result<ProgressSummary>
+    where
+        S: Stream<Item = Result<ImageBuildChunk>>,
+    {
+        futures_util::pin_mut!(stream);
+        while let Some(chunk) = stream.next().await {
+            self.render(&chunk?)?;
+        }
+        Ok(self.summary)
+    }
+
+    /// Renders a single chunk
+    pub fn render(
+        &mut self,
+        chunk: &ImageBuildChunk,
+    ) -> io::Result<()> {
+        if let ImageBuildChunk::Error { error, .. } = chunk {
+            self.summary.error = Some(error.clone());
+            self.output(&format!("ERROR: {}", error))?;
+        } else if let Some(event) = self.reporter.report(chunk) {
+            self.render_event(&event)?;
+        }
+        self.out.flush()
+    }
+
+    fn render_event(
+        &mut self,
+        event: &ProgressEvent,
+    ) -> io::Result<()> {
+        match (&event.layer_id, &event.phase) {
+            (_, ProgressPhase::Step(step)) => {
+                self.summary.step = Some(step.clone());
+                self.output(step)
+            }
+            (_, ProgressPhase::Output(output)) => self.output(output.trim_end_matches('\n')),
+            (_, ProgressPhase::Built(id)) => {
+                self.summary.image_id = Some(id.clone());
+                Ok(())
+            }
+            (Some(id), _) => self.update_layer(id, event),
+            (None, phase) => self.output(&phase.to_string()),
+        }
+    }
+
+    fn update_layer(
+        &mut self,
+        id: &str,
+        event: &ProgressEvent,
+    ) -> io::Result<()> {
+        let (index, phase_changed) = match self.layers.iter().position(|layer| layer.id == id) {
+            Some(index) => (index, self.layers[index].phase != event.phase),
+            None => {
+                self.layers.push(Layer {
+                    id: id.to_owned(),
+                    phase: event.phase.clone(),
+                    current: None,
+                    total: None,
+                });
+                (self.layers.len() - 1, true)
+            }
+        };
+        let layer = &mut self.layers[index];
+        layer.phase = event.phase.clone();
+        // statuses without sizes, like verifying, keep the sizes reported before
+        if event.total.is_some() {
+            layer.current = event.current;
+            layer.total = event.total;
+        }
+        self.summarize_layers();
+
+        if self.tty {
+            self.redraw_layers()
+        } else if phase_changed {
+            writeln!(self.out, "{}: {}", id, event.phase)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn summarize_layers(&mut self) {
+        self.summary.layers = self.layers.len();
+        self.summary.layers_complete = self.layers.iter().filter(|l| l.is_complete()).count();
+        self.summary.bytes_current = self.reporter.layers().downloaded();
+        self.summary.bytes_total = self.reporter.layers().size();
+    }
+
+    /// Writes a line of output, above the layers on a terminal
+    fn output(
+        &mut self,
+        line: &str,
+    ) -> io::Result<()> {
+        if self.tty {
+            self.move_up()?;
+            writeln!(self.out, "\x1b[2K{}", line)?;
+            self.lines_drawn = 0;
+            self.redraw_layers()
+        } else {
+            writeln!(self.out, "{}", line)
+        }
+    }
+
+    fn move_up(&mut self) -> io::Result<()> {
+        if self.lines_drawn > 0 {
+            write!(self.out, "\x1b[{}A", self.lines_drawn)?;
+        }
+        Ok(())
+    }
+
+    fn redraw_layers(&mut self) -> io::Result<()> {
+        self.move_up()?;
+        for layer in &self.layers {
+            write!(self.out, "\x1b[2K{}: {}", layer.id, layer.phase)?;
+            if let (Some(current), Some(total), false) =
+                (layer.current, layer.total, layer.is_complete())
+            {
+                write!(
+                    self.out,
+                    " {} {}/{}",
+                    bar(current, total),
+                    human_size(current),
+                    human_size(total)
+                )?;
+            }
+            writeln!(self.out)?;
+        }
+        self.lines_drawn = self.layers.len();
+        Ok(())
+    }
+}
+
+/// A progress bar like `[=====>      ]`
+fn bar(
+    current: u64,
+    total: u64,
+) -> String {
+    let filled = if total == 0 {
+        BAR_WIDTH
+    } else {
+        (current.min(total) as u128 * BAR_WIDTH as u128 / total as u128) as usize
+    };
+    let mut bar = String::with_capacity(BAR_WIDTH + 2);
+    bar.push('[');
+    for i in 0..BAR_WIDTH {
+        bar.push(match i {
+            i if i + 1 < filled => '=',
+            i if i + 1 == filled || (filled == 0 && i == 0) => '>',
+            _ => ' ',
+        });
+    }
+    bar.push(']');
+    bar
+}
+
+/// A size in decimal units like the docker CLI prints them, like `2.811MB`
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit + 1 < UNITS.len() {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.3}{}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ProgressDetail;
+
+    fn status(
+        id: &str,
+        status: &str,
+        detail: Option<(u64, u64)>,
+    ) -> ImageBuildChunk {
+        ImageBuildChunk::PullStatus {
+            status: status.to_owned(),
+            id: Some(id.to_owned()),
+            progress: None,
+            progress_detail: detail.map(|(c, t)| ProgressDetail::new(Some(c), Some(t))),
+        }
+    }
+
+    fn pull() -> Vec<ImageBuildChunk> {
+        vec![
+            status("a1", "Pulling fs layer", None),
+            status("b2", "Pulling fs layer", None),
+            status("a1", "Downloading", Some((1_000, 4_000))),
+            status("b2", "Already exists", None),
+            status("a1", "Downloading", Some((2_000, 4_000))),
+            status("a1", "Pull complete", None),
+            ImageBuildChunk::PullStatus {
+                status: "Status: Downloaded newer image for alpine:latest".to_owned(),
+                id: None,
+                progress: None,
+                progress_detail: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn render_status_lines() {
+        let mut renderer = ProgressRenderer::new(Vec::new(), false);
+        for chunk in pull() {
+            renderer.render(&chunk).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(renderer.out.clone()).unwrap(),
+            "a1: Waiting\nb2: Waiting\na1: Downloading\nb2: Already exists\n\
+             a1: Pull complete\nStatus: Downloaded newer image for alpine:latest\n"
+        );
+        assert_eq!(
+            renderer.summary(),
+            &ProgressSummary {
+                layers: 2,
+                layers_complete: 2,
+                bytes_current: 4_000,
+                bytes_total: 4_000,
+                ..ProgressSummary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn render_progress_bars() {
+        let mut renderer = ProgressRenderer::new(Vec::new(), true);
+        for chunk in &pull()[..3] {
+            renderer.render(chunk).unwrap();
+        }
+        let out = String::from_utf8(renderer.out).unwrap();
+        let last_frame = out.rsplit("\x1b[2A").next().unwrap();
+        assert_eq!(
+            last_frame,
+            format!(
+                "\x1b[2Ka1: Downloading [{}>{}] 1.000kB/4.000kB\n\x1b[2Kb2: Waiting\n",
+                "=".repeat(11),
+                " ".repeat(38)
+            )
+        );
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human_size(512), "512B");
+        assert_eq!(human_size(2_811_478), "2.811MB");
+        assert_eq!(bar(0, 10), format!("[>{}]", " ".repeat(49)));
+        assert_eq!(bar(10, 10), format!("[{}>]", "=".repeat(49)));
+    }
+}
@@ -0,0 +1,267 @@
+//! Export the stats of containers as Prometheus metrics.
+//!
+//! [Metrics] keeps the latest [Stats] of each container and counts its restarts from the
+//! [Event]s of the daemon, and renders them in the Prometheus text exposition format, ready to
+//! be served on a `/metrics` endpoint. Every metric is labeled with the `id` and `name` of the
+//! container.
+//!
+//! ```no_run
+//! use futures_util::TryStreamExt;
+//! use shiplift::{prometheus::Metrics, Docker};
+//! # async {
+//! let docker = Docker::new();
+//! let mut metrics = Metrics::new();
+//! let mut stats = docker.containers().get("web").stats();
+//! while let Some(stats) = stats.try_next().await? {
+//!     metrics.record_stats("web", "web", &stats);
+//!     println!("{}", metrics.render());
+//! }
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{container::Stats, docker::Event};
+
+/// What is known about a container
+#[derive(Default)]
+struct ContainerMetrics {
+    name: String,
+    stats: Option<Stats>,
+    restarts: u64,
+    /// Whether the container died, so that its next start is a restart
+    died: bool,
+}
+
+/// The metrics of a set of containers, see the [module docs](self)
+#[derive(Default)]
+pub struct Metrics {
+    /// By ID, to render containers in a stable order
+    containers: BTreeMap<String, ContainerMetrics>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Records the latest stats of the container with the given ID and name
+    pub fn record_stats(
+        &mut self,
+        id: &str,
+        name: &str,
+        stats: &Stats,
+    ) {
+        let container = self.containers.entry(id.to_owned()).or_default();
+        container.name = name.trim_start_matches('/').to_owned();
+        container.stats = Some(stats.clone());
+    }
+
+    /// Records an event of the daemon. A container that is started after it died counts as
+    /// restarted, and a container that is removed is no longer exported.
+    pub fn record_event(
+        &mut self,
+        event: &Event,
+    ) {
+        if event.typ != "container" {
+            return;
+        }
+        let id = &event.actor.id;
+        match event.action.as_str() {
+            "destroy" => {
+                self.containers.remove(id);
+            }
+            "die" | "start" => {
+                let container = self.containers.entry(id.clone()).or_default();
+                if let Some(name) = event.actor.attributes.get("name") {
+                    container.name = name.clone();
+                }
+                if event.action == "die" {
+                    container.died = true;
+                } else if container.died {
+                    container.died = false;
+                    container.restarts += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.family(
+            &mut out,
+            "docker_container_cpu_usage_seconds_total",
+            "counter",
+            "Total CPU time consumed by the container in seconds",
+            |stats| vec![(None, stats.cpu_stats.cpu_usage.total_usage as f64 / 1e9)],
+        );
+        self.family(
+            &mut out,
+            "docker_container_memory_usage_bytes",
+            "gauge",
+            "Memory used by the container without the inactive page cache, like docker stats \
+             reports it",
+            |stats| vec![(None, stats.memory_stats.usage_without_cache() as f64)],
+        );
+        self.family(
+            &mut out,
+            "docker_container_memory_limit_bytes",
+            "gauge",
+            "Memory limit of the container",
+            |stats| vec![(None, stats.memory_stats.limit as f64)],
+        );
+        self.family(
+            &mut out,
+            "docker_container_network_receive_bytes_total",
+            "counter",
+            "Bytes received by the container per network interface",
+            |stats| network(stats, |interface| interface.rx_bytes),
+        );
+        self.family(
+            &mut out,
+            "docker_container_network_transmit_bytes_total",
+            "counter",
+            "Bytes sent by the container per network interface",
+            |stats| network(stats, |interface| interface.tx_bytes),
+        );
+
+        out.push_str("# HELP docker_container_restarts_total Restarts of the container\n");
+        out.push_str("# TYPE docker_container_restarts_total counter\n");
+        for (id, container) in &self.containers {
+            sample(
+                &mut out,
+                "docker_container_restarts_total",
+                id,
+                container,
+                None,
+                container.restarts as f64,
+            );
+        }
+        out
+    }
+
+    /// Renders a metric family with the samples `samples` derives from the stats of each
+    /// container, optionally labeled with a network interface
+    fn family<F>(
+        &self,
+        out: &mut String,
+        metric: &str,
+        type_: &str,
+        help: &str,
+        samples: F,
+    ) where
+        F: Fn(&Stats) -> Vec<(Option<&str>, f64)>,
+    {
+        let _ = writeln!(out, "# HELP {} {}", metric, help);
+        let _ = writeln!(out, "# TYPE {} {}", metric, type_);
+        for (id, container) in &self.containers {
+            if let Some(stats) = &container.stats {
+                for (interface, value) in samples(stats) {
+                    sample(out, metric, id, container, interface, value);
+                }
+            }
+        }
+    }
+}
+
+fn network(
+    stats: &Stats,
+    bytes: fn(&crate::container::NetworkStats) -> u64,
+) -> Vec<(Option<&str>, f64)> {
+    let mut samples: Vec<_> = stats
+        .networks
+        .iter()
+        .map(|(interface, network)| (Some(interface.as_str()), bytes(network) as f64))
+        .collect();
+    samples.sort_by(|a, b| a.0.cmp(&b.0));
+    samples
+}
+
+fn sample(
+    out: &mut String,
+    metric: &str,
+    id: &str,
+    container: &ContainerMetrics,
+    interface: Option<&str>,
+    value: f64,
+) {
+    let _ = write!(
+        out,
+        "{}{{id=\"{}\",name=\"{}\"",
+        metric,
+        escape(id),
+        escape(&container.name)
+    );
+    if let Some(interface) = interface {
+        let _ = write!(out, ",interface=\"{}\"", escape(interface));
+    }
+    let _ = writeln!(out, "}} {}", value);
+}
+
+/// Escapes a label value of the text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_metrics() {
+        let event = |action: &str, id: &str| -> Event {
+            serde_json::from_value(serde_json::json!({
+                "Type": "container",
+                "Action": action,
+                "Actor": { "ID": id, "Attributes": { "name": "web" } },
+                "time": 1_625_140_010,
+                "timeNano": 1_625_140_010_000_000_000_u64,
+            }))
+            .unwrap()
+        };
+        let stats: Stats = crate::mock::model(crate::mock::fixtures::stats());
+        let mut metrics = Metrics::new();
+        metrics.record_stats("c1", "/web", &stats);
+        for action in ["start", "die", "start", "die", "start"] {
+            metrics.record_event(&event(action, "c1"));
+        }
+        metrics.record_event(&event("die", "c2"));
+        metrics.record_event(&event("destroy", "c2"));
+
+        let rendered = metrics.render();
+        let samples: Vec<_> = rendered
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                format!(
+                    "docker_container_cpu_usage_seconds_total{{id=\"c1\",name=\"web\"}} {}",
+                    stats.cpu_stats.cpu_usage.total_usage as f64 / 1e9
+                ),
+                "docker_container_memory_usage_bytes{id=\"c1\",name=\"web\"} 6537216".to_owned(),
+                "docker_container_memory_limit_bytes{id=\"c1\",name=\"web\"} 67108864".to_owned(),
+                "docker_container_network_receive_bytes_total{id=\"c1\",name=\"web\",\
+                 interface=\"eth0\"} 5338"
+                    .to_owned(),
+                "docker_container_network_transmit_bytes_total{id=\"c1\",name=\"web\",\
+                 interface=\"eth0\"} 648"
+                    .to_owned(),
+                "docker_container_restarts_total{id=\"c1\",name=\"web\"} 2".to_owned(),
+            ]
+        );
+        assert!(rendered.contains("# TYPE docker_container_restarts_total counter\n"));
+    }
+
+    #[test]
+    fn escape_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
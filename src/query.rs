@@ -0,0 +1,214 @@
+//! Encoding of typed query parameters.
+//!
+//! Options are plain structs deriving `Serialize`, with a field per query parameter. Fields set
+//! to `None` are left out of the query.
+
+use std::{collections::BTreeMap, iter::FromIterator};
+
+use serde::{Serialize, Serializer};
+
+use crate::errors::Result;
+
+/// Encodes `params` as a query string. Returns `None` if no parameter is set.
+///
+/// Fails with [Error::Query](crate::Error::Query) if a parameter is not a plain value, like a
+/// nested struct that is not [encoded as JSON](json).
+pub(crate) fn encode<T>(params: &T) -> Result<Option<String>>
+where
+    T: Serialize,
+{
+    let query = serde_urlencoded::to_string(params)?;
+    if query.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(query))
+    }
+}
+
+/// Serializes a query parameter the daemon expects as a JSON encoded value, for use with
+/// `#[serde(serialize_with = "query::json")]`
+pub(crate) fn json<T, S>(
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    let json = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&json)
+}
+
+/// A query parameter the daemon expects once per value, like the `t` parameters of a build
+/// that tags its image with several names. Use it with `#[serde(flatten)]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Repeated(pub(crate) &'static str, pub(crate) Vec<String>);
+
+impl Repeated {
+    /// The parameter `name` without values, which is left out of the query
+    pub(crate) fn new(name: &'static str) -> Self {
+        Repeated(name, Vec::new())
+    }
+}
+
+impl Serialize for Repeated {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.1.len()))?;
+        for value in &self.1 {
+            map.serialize_entry(self.0, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Values of the `filters` query parameter, keyed by filter name. The daemon expects them as a
+/// JSON encoded object mapping each name to a list of values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Filters(BTreeMap<&'static str, Vec<String>>);
+
+impl Filters {
+    pub(crate) fn add<V>(
+        &mut self,
+        name: &'static str,
+        value: V,
+    ) where
+        V: Into<String>,
+    {
+        self.0.entry(name).or_default().push(value.into());
+    }
+
+    /// Replaces the values of the filter `name` with `value`
+    pub(crate) fn set<V>(
+        &mut self,
+        name: &'static str,
+        value: V,
+    ) where
+        V: Into<String>,
+    {
+        self.0.insert(name, vec![value.into()]);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Collects typed filters of a listing endpoint
+    pub(crate) fn typed<F, I>(filters: I) -> Self
+    where
+        F: Filter,
+        I: IntoIterator<Item = F>,
+    {
+        filters.into_iter().map(Filter::query).collect()
+    }
+}
+
+/// A typed value of the `filters` query parameter
+pub(crate) trait Filter {
+    /// Returns the name of the filter and its value as the daemon expects it
+    fn query(self) -> (&'static str, String);
+}
+
+impl<V> FromIterator<(&'static str, V)> for Filters
+where
+    V: Into<String>,
+{
+    fn from_iter<I>(filters: I) -> Self
+    where
+        I: IntoIterator<Item = (&'static str, V)>,
+    {
+        let mut result = Filters::default();
+        for (name, value) in filters {
+            result.add(name, value);
+        }
+        result
+    }
+}
+
+impl Serialize for Filters {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        json(&self.0, serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Tags {
+        all: Option<bool>,
+        #[serde(flatten)]
+        t: Repeated,
+    }
+
+    #[test]
+    fn encode_repeated_params() {
+        let tags = Tags {
+            all: None,
+            t: Repeated::new("t"),
+        };
+        assert_eq!(encode(&tags).unwrap(), None);
+
+        let tags = Tags {
+            all: Some(false),
+            t: Repeated("t", vec!["app:1".into(), "app:latest".into()]),
+        };
+        assert_eq!(
+            encode(&tags).unwrap().as_deref(),
+            Some("all=false&t=app%3A1&t=app%3Alatest")
+        );
+    }
+
+    #[derive(Default, Serialize)]
+    struct Params {
+        all: Option<bool>,
+        #[serde(rename = "fromImage")]
+        from_image: Option<String>,
+        #[serde(skip_serializing_if = "Filters::is_empty")]
+        filters: Filters,
+    }
+
+    #[test]
+    fn encode_skips_unset_params() {
+        assert_eq!(encode(&Params::default()).unwrap(), None);
+
+        let params = Params {
+            all: Some(true),
+            from_image: Some("alpine & co".into()),
+            filters: vec![("label", "a=b"), ("label", "c"), ("dangling", "true")]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(
+            encode(&params).unwrap().as_deref(),
+            Some("all=true&fromImage=alpine+%26+co&filters=%7B%22dangling%22%3A%5B%22true%22%5D%2C%22label%22%3A%5B%22a%3Db%22%2C%22c%22%5D%7D")
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Nested {
+        params: Params,
+    }
+
+    #[test]
+    fn encode_rejects_nested_params() {
+        let nested = Nested {
+            params: Params::default(),
+        };
+        assert!(matches!(encode(&nested), Err(crate::Error::Query(_))));
+    }
+}
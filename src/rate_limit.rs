@@ -0,0 +1,123 @@
+//! Limiting the rate of transfers, so that large exports, imports and copies don't starve other
+//! traffic on a shared network.
+//!
+//! A [RateLimit] applies to a single call: wrap the stream of an export or a copy out of a
+//! container with [RateLimit::stream], and the body of a copy into a container with
+//! [RateLimit::body]. [Images::import_rate_limited](crate::Images::import_rate_limited) imports
+//! at a limited rate.
+//!
+//! ```no_run
+//! use futures_util::TryStreamExt;
+//! use shiplift::{rate_limit::RateLimit, Docker};
+//! # async {
+//! let docker = Docker::new();
+//! let limit = RateLimit::new(10 * 1024 * 1024);
+//! let export = limit.stream(docker.images().get("alpine").export());
+//! let tarball: Vec<Vec<u8>> = export.try_collect().await?;
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{pin::Pin, time::Duration};
+
+use bytes::Bytes;
+use futures_util::{
+    stream::{self, Stream},
+    StreamExt, TryStreamExt,
+};
+use hyper::Body;
+use tokio::time::Instant;
+
+/// The largest chunk an upload is sent in
+const MAX_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// A limit on the average rate of a transfer, in bytes per second
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    bytes_per_sec: u64,
+}
+
+impl RateLimit {
+    /// A limit of `bytes_per_sec`, which is at least one byte per second
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimit {
+            bytes_per_sec: bytes_per_sec.max(1),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Delays the chunks of `stream` so that they are passed on at most at this rate on
+    /// average, measured from the first chunk
+    ///
+    /// The stream is slowed down as a whole, so chunks should be small compared to the rate,
+    /// like the chunks a download is received in.
+    pub fn stream<'a, S, T, E>(
+        &self,
+        stream: S,
+    ) -> impl Stream<Item = std::result::Result<T, E>> + Unpin + Send + 'a
+    where
+        S: Stream<Item = std::result::Result<T, E>> + Send + 'a,
+        T: AsRef<[u8]> + Send + 'a,
+        E: Send + 'a,
+    {
+        let bytes_per_sec = self.bytes_per_sec;
+        let stream: Pin<Box<dyn Stream<Item = _> + Send + 'a>> = Box::pin(stream);
+        Box::pin(stream::unfold(
+            (stream, None, 0),
+            move |(mut stream, start, sent): (_, Option<Instant>, u64)| async move {
+                let item = stream.next().await?;
+                let start = start.unwrap_or_else(Instant::now);
+                let sent = match item {
+                    Ok(ref chunk) => sent.saturating_add(chunk.as_ref().len() as u64),
+                    Err(_) => sent,
+                };
+                let due = Duration::from_secs_f64(sent as f64 / bytes_per_sec as f64);
+                tokio::time::sleep_until(start + due).await;
+                Some((item, (stream, Some(start), sent)))
+            },
+        ))
+    }
+
+    /// Limits the upload of `body` to this rate, sending it in chunks small enough to keep the
+    /// rate steady
+    pub fn body(
+        &self,
+        body: Body,
+    ) -> Body {
+        let chunk_size = (self.bytes_per_sec / 10).clamp(1, MAX_CHUNK_SIZE) as usize;
+        let chunks = body
+            .map_ok(move |bytes| stream::iter(split(bytes, chunk_size).map(Ok::<_, hyper::Error>)))
+            .try_flatten();
+        Body::wrap_stream(self.stream(chunks))
+    }
+}
+
+/// Splits `bytes` into chunks of at most `size` bytes, without copying them
+fn split(
+    bytes: Bytes,
+    size: usize,
+) -> impl Iterator<Item = Bytes> {
+    (0..bytes.len())
+        .step_by(size)
+        .map(move |start| bytes.slice(start..(start + size).min(bytes.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rate_limited_body() {
+        let limit = RateLimit::new(2000);
+        let start = std::time::Instant::now();
+        let body = limit.body(Body::from(vec![7; 600]));
+        let chunks: Vec<Bytes> = body.try_collect().await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(290));
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.len() == 200));
+    }
+}
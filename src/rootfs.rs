@@ -0,0 +1,374 @@
+//! Flattening of the layers of an exported image into the filesystem they make up.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use tar::{Archive, Builder, Entry};
+
+/// Prefix of the files that delete a file of a lower layer
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Name of the file marking a directory as opaque, hiding the contents of lower layers
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ManifestEntry {
+    layers: Vec<String>,
+}
+
+/// Merges the layers of `export`, an image exported by `GET /images/{name}/get`, into a single
+/// tar archive of the final filesystem of the image, which is written to `out`
+///
+/// The layers are read from `export` one at a time, first from the top layer down to find the
+/// files that end up in the filesystem, then from the bottom layer up to write them.
+pub(crate) fn flatten<R, W>(
+    mut export: R,
+    out: W,
+) -> io::Result<W>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    // where the files of the export are, so that the layers can be read when needed
+    let mut manifest = None;
+    let mut files = HashMap::new();
+    for entry in Archive::new(&mut export).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        if path == "manifest.json" {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            manifest = Some(data);
+        } else {
+            files.insert(path, (entry.raw_file_position(), entry.size()));
+        }
+    }
+
+    let manifest: Vec<ManifestEntry> = manifest
+        .ok_or_else(|| invalid("the export has no manifest.json"))
+        .and_then(|manifest| serde_json::from_slice(&manifest).map_err(io::Error::from))?;
+    let manifest = manifest
+        .first()
+        .ok_or_else(|| invalid("the manifest.json of the export is empty"))?;
+    let layers = manifest
+        .layers
+        .iter()
+        .map(|layer| {
+            files
+                .get(layer)
+                .copied()
+                .ok_or_else(|| invalid(&format!("the export has no layer {}", layer)))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // from the top layer down, find the files that end up in the filesystem
+    let mut hidden = Hidden::default();
+    let mut visible = vec![HashSet::new(); layers.len()];
+    for (&layer, visible) in layers.iter().zip(visible.iter_mut()).rev() {
+        let mut hidden_below = Hidden::default();
+        for entry in Archive::new(read_layer(&mut export, layer)?).entries()? {
+            let entry = entry?;
+            let path = normalize(&entry.path()?.to_string_lossy());
+            let (parent, name) = match path.rsplit_once('/') {
+                Some((parent, name)) => (parent, name),
+                None => ("", path.as_str()),
+            };
+            if name == OPAQUE_WHITEOUT {
+                hidden_below.opaque.insert(parent.to_owned());
+            } else if let Some(deleted) = name.strip_prefix(WHITEOUT_PREFIX) {
+                hidden_below.deleted.insert(join(parent, deleted));
+            } else if !hidden.hides(&path) {
+                if entry.header().entry_type().is_dir() {
+                    hidden_below.replaced.insert(path.clone());
+                } else {
+                    // a file also hides what a lower layer has below a directory of its name
+                    hidden_below.deleted.insert(path.clone());
+                }
+                visible.insert(path);
+            }
+        }
+        hidden.extend(hidden_below);
+    }
+
+    // then write them from the bottom layer up, so that directories come before their contents
+    let mut builder = Builder::new(out);
+    for (&layer, visible) in layers.iter().zip(&visible) {
+        for entry in Archive::new(read_layer(&mut export, layer)?).entries()? {
+            let mut entry = entry?;
+            if visible.contains(&normalize(&entry.path()?.to_string_lossy())) {
+                append_entry(&mut builder, &mut entry)?;
+            }
+        }
+    }
+    builder.into_inner()
+}
+
+/// Appends `entry` of a layer to `builder`, with its PAX extensions and its path and link name
+/// however long they are
+fn append_entry<R, W>(
+    builder: &mut Builder<W>,
+    entry: &mut Entry<R>,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    if let Some(extensions) = entry.pax_extensions()? {
+        let extensions = extensions
+            .map(|extension| {
+                let extension = extension?;
+                let key = extension
+                    .key()
+                    .map_err(|_| invalid("a PAX extension key is not UTF-8"))?;
+                Ok((key.to_owned(), extension.value_bytes().to_vec()))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        builder.append_pax_extensions(
+            extensions
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_slice())),
+        )?;
+    }
+    let mut header = entry.header().clone();
+    let path = entry.path()?.into_owned();
+    match entry.link_name()? {
+        Some(target) => {
+            let target = target.into_owned();
+            builder.append_link(&mut header, path, target)
+        }
+        None => builder.append_data(&mut header, path, entry),
+    }
+}
+
+/// Paths of lower layers that are hidden by upper layers
+#[derive(Default)]
+struct Hidden {
+    /// Directories of upper layers, which replace those of lower layers but not their contents
+    replaced: HashSet<String>,
+    /// Deleted paths and the files of upper layers, hiding everything below them too
+    deleted: HashSet<String>,
+    /// Directories whose contents of lower layers are hidden
+    opaque: HashSet<String>,
+}
+
+impl Hidden {
+    fn hides(
+        &self,
+        path: &str,
+    ) -> bool {
+        if self.replaced.contains(path) || self.deleted.contains(path) {
+            return true;
+        }
+        let mut ancestor = path;
+        while let Some((parent, _)) = ancestor.rsplit_once('/') {
+            if self.deleted.contains(parent) || self.opaque.contains(parent) {
+                return true;
+            }
+            ancestor = parent;
+        }
+        false
+    }
+
+    fn extend(
+        &mut self,
+        other: Hidden,
+    ) {
+        self.replaced.extend(other.replaced);
+        self.deleted.extend(other.deleted);
+        self.opaque.extend(other.opaque);
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./")
+        .trim_start_matches('/')
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+fn join(
+    parent: &str,
+    name: &str,
+) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Reads the layer at `position` of the export, the offset and size of its file. Layers of older
+/// exports are plain tar archives, those of newer ones may be compressed.
+fn read_layer<R>(
+    export: &mut R,
+    (offset, size): (u64, u64),
+) -> io::Result<Box<dyn Read + '_>>
+where
+    R: Read + Seek,
+{
+    export.seek(SeekFrom::Start(offset))?;
+    let mut layer = BufReader::new(export.take(size));
+    if layer.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(GzDecoder::new(layer)))
+    } else {
+        Ok(Box::new(layer))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+    use tar::{EntryType, Header};
+
+    fn tar(entries: &[(&str, Option<&str>)]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (path, content) in entries {
+            let mut header = Header::new_gnu();
+            match content {
+                Some(content) => {
+                    header.set_size(content.len() as u64);
+                    builder
+                        .append_data(&mut header, path, content.as_bytes())
+                        .unwrap();
+                }
+                None => {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_size(0);
+                    builder.append_data(&mut header, path, io::empty()).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// An image export with `layers`, from the bottom one up
+    fn export(layers: &[&[u8]]) -> Vec<u8> {
+        let names: Vec<_> = (1..=layers.len())
+            .map(|layer| format!("{}/layer.tar", layer))
+            .collect();
+        let manifest =
+            serde_json::json!([{ "Config": "config.json", "Layers": names }]).to_string();
+        let mut builder = Builder::new(Vec::new());
+        for (path, data) in iter::once(("manifest.json", manifest.as_bytes())).chain(
+            names
+                .iter()
+                .map(|name| name.as_str())
+                .zip(layers.iter().copied()),
+        ) {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn flatten_layers() {
+        let base = tar(&[
+            ("etc/", None),
+            ("etc/passwd", Some("root")),
+            ("etc/motd", Some("hello")),
+            ("tmp/", None),
+            ("tmp/cache", Some("stale")),
+            ("opt/", None),
+            ("opt/old", Some("old")),
+        ]);
+        let top = tar(&[
+            ("etc/", None),
+            ("etc/motd", Some("welcome")),
+            ("etc/.wh.passwd", Some("")),
+            ("tmp", Some("not a directory")),
+            ("opt/", None),
+            ("opt/.wh..wh..opq", Some("")),
+            ("opt/new", Some("new")),
+        ]);
+        let flattened = flatten(io::Cursor::new(export(&[&base, &top])), Vec::new()).unwrap();
+        let mut files = Vec::new();
+        for entry in Archive::new(&flattened[..]).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            files.push((entry.path().unwrap().display().to_string(), content));
+        }
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (&p[..], &c[..])).collect();
+        assert_eq!(
+            files,
+            [
+                ("etc/", ""),
+                ("etc/motd", "welcome"),
+                ("tmp", "not a directory"),
+                ("opt/", ""),
+                ("opt/new", "new"),
+            ]
+        );
+    }
+
+    #[test]
+    fn flatten_keeps_long_names_and_pax_extensions() {
+        let dir = "a".repeat(120);
+        let file = format!("{}/file", dir);
+        let target = format!("/{}/target", "t".repeat(120));
+        let layer = {
+            let mut builder = Builder::new(Vec::new());
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            builder
+                .append_data(&mut header, format!("{}/", dir), io::empty())
+                .unwrap();
+            builder
+                .append_pax_extensions([("SCHILY.xattr.user.origin", &b"layer"[..])])
+                .unwrap();
+            let mut header = Header::new_gnu();
+            header.set_size(4);
+            builder
+                .append_data(&mut header, &file, &b"data"[..])
+                .unwrap();
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, "link", &target).unwrap();
+            builder.into_inner().unwrap()
+        };
+
+        let flattened = flatten(io::Cursor::new(export(&[&layer])), Vec::new()).unwrap();
+        let mut archive = Archive::new(&flattened[..]);
+        let mut entries = archive.entries().unwrap().map(Result::unwrap);
+        let directory = entries.next().unwrap();
+        assert_eq!(
+            directory.path().unwrap().to_str(),
+            Some(&format!("{}/", dir)[..])
+        );
+        let mut entry = entries.next().unwrap();
+        assert_eq!(entry.path().unwrap().to_str(), Some(&file[..]));
+        let extension = entry
+            .pax_extensions()
+            .unwrap()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(extension.key(), Ok("SCHILY.xattr.user.origin"));
+        assert_eq!(extension.value_bytes(), b"layer");
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "data");
+        let link = entries.next().unwrap();
+        assert_eq!(link.path().unwrap().to_str(), Some("link"));
+        assert_eq!(
+            link.link_name().unwrap().unwrap().to_str(),
+            Some(&target[..])
+        );
+        assert!(entries.next().is_none());
+    }
+}
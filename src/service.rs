@@ -2,9 +2,13 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Service>
 
-use std::{collections::HashMap, iter};
+use std::{
+    collections::HashMap,
+    iter,
+    time::{Duration, Instant},
+};
 
-use futures_util::stream::Stream;
+use futures_util::{stream::Stream, TryFutureExt};
 use hyper::Body;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -15,11 +19,15 @@ use crate::{
     docker::Docker,
     errors::{Error, Result},
     image::RegistryAuth,
+    query::{self, Filters},
+    task::{TaskFilter, TaskInfo, TaskListOptions, TaskState},
     tty,
 };
 
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
 
 /// Interface for docker services
 ///
@@ -42,7 +50,7 @@ impl<'docker> Services<'docker> {
         opts: &ServiceListOptions,
     ) -> Result<Vec<ServiceInfo>> {
         let mut path = vec!["/services".to_owned()];
-        if let Some(query) = opts.serialize() {
+        if let Some(query) = opts.serialize()? {
             path.push(query);
         }
         self.docker
@@ -90,7 +98,7 @@ impl<'docker> Service<'docker> {
         opts: &ServiceOptions,
     ) -> Result<ServiceCreateInfo> {
         let body: Body = opts.serialize()?.into();
-        let path = vec!["/service/create".to_owned()];
+        let path = ["/service/create".to_owned()];
 
         let headers = opts
             .auth_header()
@@ -114,13 +122,188 @@ impl<'docker> Service<'docker> {
             .await
     }
 
+    /// Updates the service with the spec described by `opts`
+    ///
+    /// Swarm uses the service version for optimistic concurrency control. When `version` is
+    /// `None`, the current version is looked up by inspecting the service first. If the service
+    /// was modified concurrently the daemon rejects the update and
+    /// [Error::UpdateOutOfSequence](crate::Error::UpdateOutOfSequence) is returned, in which case
+    /// the update can be retried with a fresh version.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ServiceUpdate>
+    pub async fn update(
+        &self,
+        opts: &ServiceOptions,
+        version: Option<u64>,
+    ) -> Result<ServiceUpdateInfo> {
+        let version = match version {
+            Some(version) => version,
+            None => self.inspect().await?.version.index,
+        };
+
+        let body: Body = opts.serialize()?.into();
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair("version", &version.to_string());
+        if let Some(from) = opts.registry_auth_from {
+            query.append_pair("registryAuthFrom", from.as_ref());
+        }
+        let path = format!("/services/{}/update?{}", self.name, query.finish());
+
+        let headers = opts
+            .auth_header()
+            .map(|a| iter::once(("X-Registry-Auth", a)));
+
+        self.docker
+            .post_json_headers(&path, Some((body, mime::APPLICATION_JSON)), headers)
+            .await
+            .map_err(Error::into_update_error)
+    }
+
+    /// Rolls the service back to the spec it had before its last update
+    ///
+    /// Fails if the service has never been updated.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ServiceUpdate>
+    pub async fn rollback(&self) -> Result<ServiceUpdateInfo> {
+        // inspect into a raw value so the current spec is sent back verbatim, including fields
+        // that are not modelled by `ServiceSpec`
+        let details: serde_json::Value = self
+            .docker
+            .get_json(&format!("/services/{}", self.name)[..])
+            .await?;
+        let version = details["Version"]["Index"].as_u64().ok_or_else(|| {
+            Error::InvalidResponse("service inspect response is missing Version.Index".into())
+        })?;
+
+        let body: Body = serde_json::to_string(&details["Spec"])?.into();
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("version", &version.to_string())
+            .append_pair("rollback", "previous")
+            .finish();
+        let path = format!("/services/{}/update?{}", self.name, query);
+
+        self.docker
+            .post_json(&path, Some((body, mime::APPLICATION_JSON)))
+            .await
+            .map_err(Error::into_update_error)
+    }
+
     /// Deletes a service
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ServiceDelete>
     pub async fn delete(&self) -> Result<()> {
         self.docker
-            .delete_json(&format!("/services/{}", self.name)[..])
-            .await
+            .delete(&format!("/services/{}", self.name)[..])
+            .await?;
+        Ok(())
+    }
+
+    /// Waits until the service has converged on its current spec
+    ///
+    /// The service is polled until its update has finished and every task that should be running
+    /// is running, or until the update is paused or rolled back by the swarm. If none of that
+    /// happens within `timeout`, [ServiceConvergence::TimedOut] is returned.
+    ///
+    /// Only tasks started from the current spec count, and none of an older spec may remain.
+    /// A global service has converged once at least one task runs and all its tasks do. A job
+    /// has converged once its tasks have completed, `total_completions` of them for a replicated
+    /// job and one on every node that runs it for a global job.
+    pub async fn await_converged(
+        &self,
+        timeout: Duration,
+    ) -> Result<ServiceConvergence> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // the raw spec is kept to compare tasks with, as `TaskSpec` doesn't model every field
+            // an update may change, like the resources or the `ForceUpdate` counter
+            let raw: Value = self
+                .docker
+                .get_json(&format!("/services/{}", self.name)[..])
+                .await?;
+            let details: ServiceDetails = serde_json::from_value(raw.clone())?;
+            let update_state = details.update_status.as_ref().map(|status| status.state);
+            let message = details
+                .update_status
+                .as_ref()
+                .and_then(|status| status.message.clone());
+
+            match update_state {
+                Some(UpdateState::Paused) | Some(UpdateState::RollbackPaused) => {
+                    return Ok(ServiceConvergence::UpdatePaused { message })
+                }
+                Some(UpdateState::RollbackCompleted) => {
+                    return Ok(ServiceConvergence::RolledBack { message })
+                }
+                Some(UpdateState::Updating) | Some(UpdateState::RollbackStarted) => {}
+                Some(UpdateState::Completed) | None => {
+                    if self
+                        .tasks_converged(&details, &raw["Spec"]["TaskTemplate"])
+                        .await?
+                    {
+                        return Ok(ServiceConvergence::Converged);
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(ServiceConvergence::TimedOut);
+            }
+            tokio::time::sleep(CONVERGENCE_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    async fn tasks_converged(
+        &self,
+        details: &ServiceDetails,
+        task_template: &Value,
+    ) -> Result<bool> {
+        let job = matches!(
+            details.spec.mode,
+            Mode::ReplicatedJob(_) | Mode::GlobalJob {}
+        );
+        let mut filters = vec![TaskFilter::Service(details.id.clone())];
+        // the tasks of jobs are meant to complete rather than to run
+        if !job {
+            filters.push(TaskFilter::DesiredState(TaskState::Running));
+        }
+        let opts = TaskListOptions::builder().filter(filters).build();
+        let mut path = vec!["/tasks".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
+        let tasks: Vec<Value> = self.docker.get_json(&path.join("?")).await?;
+
+        let mut current = Vec::new();
+        let mut outdated = 0;
+        for task in tasks {
+            let is_current = task["Spec"] == *task_template;
+            let task: TaskInfo = serde_json::from_value(task)?;
+            if is_current {
+                // tasks of jobs that failed and were replaced by another attempt are shut down
+                if !matches!(task.desired_state, TaskState::Shutdown | TaskState::Remove) {
+                    current.push(task.status.state);
+                }
+            } else if !job || !task.status.state.is_terminal() {
+                // tasks started from an older spec are still meant to run until the update
+                // replaced them, while the completed tasks of earlier job runs stay listed
+                outdated += 1;
+            }
+        }
+
+        let count = |state| current.iter().filter(|s| **s == state).count();
+        let converged = match &details.spec.mode {
+            Mode::Replicated(replicated) => count(TaskState::Running) as u64 == replicated.replicas,
+            // the swarm starts a task on every eligible node, which leaves no count to compare
+            // with, but at least one of them has to run
+            Mode::Global {} => !current.is_empty() && count(TaskState::Running) == current.len(),
+            Mode::ReplicatedJob(job) => count(TaskState::Complete) as u64 >= job.total_completions,
+            Mode::GlobalJob {} => {
+                !current.is_empty() && count(TaskState::Complete) == current.len()
+            }
+        };
+
+        Ok(converged && outdated == 0)
     }
 
     /// Returns a stream of logs from a service
@@ -131,20 +314,42 @@ impl<'docker> Service<'docker> {
         opts: &LogsOptions,
     ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
         let mut path = vec![format!("/services/{}/logs", self.name)];
-        if let Some(query) = opts.serialize() {
-            path.push(query)
-        }
+        let query = opts.serialize();
+        let docker = self.docker;
+        Box::pin(
+            async move {
+                if let Some(query) = query? {
+                    path.push(query)
+                }
+                let stream = Box::pin(docker.stream_get(path.join("?")));
+                Ok(tty::decode(stream))
+            }
+            .try_flatten_stream(),
+        )
+    }
+}
 
-        let stream = Box::pin(self.docker.stream_get(path.join("?")));
+const CONVERGENCE_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
-        Box::pin(tty::decode(stream))
-    }
+/// Outcome of [Service::await_converged]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceConvergence {
+    /// All desired tasks are running the current spec
+    Converged,
+    /// The update was paused, usually because too many tasks failed
+    UpdatePaused { message: Option<String> },
+    /// The update failed and the swarm rolled the service back to its previous spec
+    RolledBack { message: Option<String> },
+    /// The service did not converge before the timeout elapsed
+    TimedOut,
 }
 
 /// Options for filtering services list results
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 pub struct ServiceListOptions {
-    params: HashMap<&'static str, String>,
+    status: Option<bool>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
 }
 
 impl ServiceListOptions {
@@ -154,22 +359,15 @@ impl ServiceListOptions {
     }
 
     /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
     }
 }
 
 /// Filter options for services listings
 pub enum ServiceFilter {
     Id(String),
+    /// Services carrying a label, given either as `key` or `key=value`
     Label(String),
     ReplicatedMode,
     GlobalMode,
@@ -178,49 +376,53 @@ pub enum ServiceFilter {
 
 /// Builder interface for `ServicesListOptions`
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ServiceListOptionsBuilder {
-    params: HashMap<&'static str, String>,
+    options: ServiceListOptions,
 }
 
 impl ServiceListOptionsBuilder {
     pub fn filter(
-        &mut self,
+        mut self,
         filters: Vec<ServiceFilter>,
-    ) -> &mut Self {
-        let mut param = HashMap::new();
-        for f in filters {
-            match f {
-                ServiceFilter::Id(i) => param.insert("id", vec![i]),
-                ServiceFilter::Label(l) => param.insert("label", vec![l]),
-                ServiceFilter::ReplicatedMode => {
-                    param.insert("mode", vec!["replicated".to_string()])
-                }
-                ServiceFilter::GlobalMode => param.insert("mode", vec!["global".to_string()]),
-                ServiceFilter::Name(n) => param.insert("name", vec![n.to_string()]),
-            };
-        }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
+    ) -> Self {
+        self.options.filters = filters
+            .into_iter()
+            .map(|f| match f {
+                ServiceFilter::Id(i) => ("id", i),
+                ServiceFilter::Label(l) => ("label", l),
+                ServiceFilter::ReplicatedMode => ("mode", "replicated".to_string()),
+                ServiceFilter::GlobalMode => ("mode", "global".to_string()),
+                ServiceFilter::Name(n) => ("name", n),
+            })
+            .collect();
         self
     }
 
-    pub fn enable_status(&mut self) -> &mut Self {
-        self.params.insert("status", "true".to_owned());
+    /// Include the `ServiceStatus` (running and desired task counts) of each service in the
+    /// listing
+    pub fn enable_status(self) -> Self {
+        self.status(true)
+    }
+
+    /// Whether to include the `ServiceStatus` of each service in the listing
+    pub fn status(
+        mut self,
+        status: bool,
+    ) -> Self {
+        self.options.status = Some(status);
         self
     }
 
-    pub fn build(&self) -> ServiceListOptions {
-        ServiceListOptions {
-            params: self.params.clone(),
-        }
+    pub fn build(self) -> ServiceListOptions {
+        self.options
     }
 }
 
 #[derive(Default, Debug)]
 pub struct ServiceOptions {
     auth: Option<RegistryAuth>,
+    registry_auth_from: Option<RegistryAuthFrom>,
     params: HashMap<&'static str, Value>,
 }
 
@@ -240,17 +442,37 @@ impl ServiceOptions {
     }
 }
 
+/// Source of the registry credentials used when updating a service
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistryAuthFrom {
+    /// Use the credentials stored with the current spec
+    Spec,
+    /// Use the credentials stored with the previous spec
+    PreviousSpec,
+}
+
+impl AsRef<str> for RegistryAuthFrom {
+    fn as_ref(&self) -> &str {
+        match self {
+            RegistryAuthFrom::Spec => "spec",
+            RegistryAuthFrom::PreviousSpec => "previous-spec",
+        }
+    }
+}
+
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct ServiceOptionsBuilder {
     auth: Option<RegistryAuth>,
+    registry_auth_from: Option<RegistryAuthFrom>,
     params: HashMap<&'static str, Result<Value>>,
 }
 
 impl ServiceOptionsBuilder {
     pub fn name<S>(
-        &mut self,
+        mut self,
         name: S,
-    ) -> &mut Self
+    ) -> Self
     where
         S: AsRef<str>,
     {
@@ -259,9 +481,9 @@ impl ServiceOptionsBuilder {
     }
 
     pub fn labels<I>(
-        &mut self,
+        mut self,
         labels: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: IntoIterator<Item = (String, String)>,
     {
@@ -275,41 +497,41 @@ impl ServiceOptionsBuilder {
     }
 
     pub fn task_template(
-        &mut self,
+        mut self,
         spec: &TaskSpec,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("TaskTemplate", to_json_value(spec));
         self
     }
 
     pub fn mode(
-        &mut self,
+        mut self,
         mode: &Mode,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Mode", to_json_value(mode));
         self
     }
 
     pub fn update_config(
-        &mut self,
+        mut self,
         conf: &UpdateConfig,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("UpdateConfig", to_json_value(conf));
         self
     }
 
     pub fn rollback_config(
-        &mut self,
+        mut self,
         conf: &RollbackConfig,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("RollbackConfig", to_json_value(conf));
         self
     }
 
     pub fn networks<I>(
-        &mut self,
+        mut self,
         networks: I,
-    ) -> &mut Self
+    ) -> Self
     where
         I: IntoIterator<Item = NetworkAttachmentConfig>,
     {
@@ -325,29 +547,40 @@ impl ServiceOptionsBuilder {
     }
 
     pub fn endpoint_spec(
-        &mut self,
+        mut self,
         spec: &EndpointSpec,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("EndpointSpec", to_json_value(spec));
         self
     }
 
     pub fn auth(
-        &mut self,
+        mut self,
         auth: RegistryAuth,
-    ) -> &mut Self {
+    ) -> Self {
         self.auth = Some(auth);
         self
     }
 
-    pub fn build(&mut self) -> Result<ServiceOptions> {
-        let params = std::mem::take(&mut self.params);
+    /// Where to take registry credentials from when updating a service without passing
+    /// [auth](ServiceOptionsBuilder::auth). Only used by [Service::update](Service::update).
+    pub fn registry_auth_from(
+        mut self,
+        from: RegistryAuthFrom,
+    ) -> Self {
+        self.registry_auth_from = Some(from);
+        self
+    }
+
+    pub fn build(self) -> Result<ServiceOptions> {
+        let params = self.params;
         let mut new_params = HashMap::new();
         for (k, v) in params.into_iter() {
             new_params.insert(k, v?);
         }
         Ok(ServiceOptions {
-            auth: self.auth.take(),
+            auth: self.auth,
+            registry_auth_from: self.registry_auth_from,
             params: new_params,
         })
     }
@@ -364,37 +597,61 @@ pub type ServicesInfo = Vec<ServiceInfo>;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ServiceInfo {
     #[serde(rename = "ID")]
     pub id: String,
     pub version: ObjectVersion,
     #[cfg(feature = "chrono")]
     pub created_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created_at: String,
     #[cfg(feature = "chrono")]
     pub updated_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub updated_at: String,
     pub endpoint: Endpoint,
     pub update_status: Option<UpdateStatus>,
     pub service_status: Option<ServiceStatus>,
     pub job_status: Option<JobStatus>,
+    /// Fields of the listed service that aren't modelled, like its `Spec` and `PreviousSpec`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ObjectVersion {
     pub index: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct Endpoint {
     pub spec: EndpointSpec,
+    /// Ports published by the service, including the ports assigned by the swarm
     pub ports: Option<Vec<EndpointPortConfig>>,
     #[serde(rename = "VirtualIPs")]
-    pub virtual_ips: Option<serde_json::Value>,
+    pub virtual_ips: Option<Vec<EndpointVirtualIp>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct EndpointVirtualIp {
+    #[serde(rename = "NetworkID")]
+    pub network_id: String,
+    /// Address in CIDR notation, e.g. `10.0.0.5/24`
+    pub addr: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -408,7 +665,9 @@ pub struct EndpointSpec {
 #[serde(rename_all = "PascalCase")]
 pub struct EndpointPortConfig {
     pub name: Option<String>,
+    /// `tcp`, `udp` or `sctp`
     pub protocol: String,
+    /// `ingress` or `host`
     pub publish_mode: String,
     pub published_port: Option<u64>,
     pub target_port: u64,
@@ -416,111 +675,382 @@ pub struct EndpointPortConfig {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct UpdateStatus {
-    pub state: String,
+    pub state: UpdateState,
     #[cfg(feature = "chrono")]
-    pub started_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
-    pub started_at: String,
+    pub started_at: Option<DateTime<Utc>>,
+    #[cfg(feature = "time")]
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub started_at: Option<String>,
+    /// Not set while an update is still in progress
     #[cfg(feature = "chrono")]
-    pub completed_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
-    pub completed_at: String,
-    pub message: String,
+    pub completed_at: Option<DateTime<Utc>>,
+    #[cfg(feature = "time")]
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub completed_at: Option<OffsetDateTime>,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub completed_at: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Progress of a service update or rollback
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateState {
+    Updating,
+    Paused,
+    Completed,
+    RollbackStarted,
+    RollbackPaused,
+    RollbackCompleted,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ServiceStatus {
     pub running_tasks: u64,
     pub desired_tasks: u64,
+    /// Only reported for services in job mode
+    #[serde(default)]
     pub completed_tasks: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct JobStatus {
     pub job_iteration: ObjectVersion,
     #[cfg(feature = "chrono")]
     pub last_execution: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_execution: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub last_execution: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct ServiceDetails {
     #[serde(rename = "ID")]
     pub id: String,
     pub version: ObjectVersion,
     #[cfg(feature = "chrono")]
     pub created_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created_at: String,
     #[cfg(feature = "chrono")]
     pub updated_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub updated_at: String,
     pub spec: ServiceSpec,
+    /// The spec the service had before the last update, if any
+    pub previous_spec: Option<ServiceSpec>,
     pub endpoint: Endpoint,
     pub update_status: Option<UpdateStatus>,
     pub service_status: Option<ServiceStatus>,
     pub job_status: Option<JobStatus>,
+    /// Fields swarm managers newer than API version 1.41 add to an inspected service
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ServiceSpec {
     pub name: String,
-    pub labels: Option<serde_json::Value>,
+    pub labels: Option<HashMap<String, String>>,
     pub task_template: TaskSpec,
     pub mode: Mode,
     pub update_config: Option<UpdateConfig>,
     pub rollback_config: Option<RollbackConfig>,
     pub networks: Option<Vec<NetworkAttachmentConfig>>,
-    pub endpoint_spec: EndpointSpec,
+    pub endpoint_spec: Option<EndpointSpec>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 // #TODO: Add missing fields...
-pub struct TaskSpec {}
+pub struct TaskSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_spec: Option<ContainerSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placement: Option<Placement>,
+}
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Restricts the nodes the tasks of a service may be scheduled on
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-pub struct Mode {
-    pub replicated: Option<Replicated>,
-    pub global: Option<serde_json::Value>,
-    pub replicated_job: Option<ReplicatedJob>,
-    pub global_job: Option<serde_json::Value>,
+pub struct Placement {
+    /// Expressions such as `node.labels.region == east`, see [PlacementConstraint] for building
+    /// them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferences: Option<Vec<PlacementPreference>>,
+    /// Maximum number of replicas per node, 0 meaning unlimited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_replicas: Option<u64>,
+    /// Platforms the service's image supports, used to schedule tasks on compatible nodes only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<Platform>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PlacementPreference {
+    /// Spread tasks evenly over the values of a node attribute, e.g. `node.labels.datacenter`
+    Spread {
+        #[serde(rename = "SpreadDescriptor")]
+        spread_descriptor: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Platform {
+    pub architecture: String,
+    #[serde(rename = "OS")]
+    pub os: String,
+}
+
+/// Builds placement constraint expressions for a node attribute
+///
+/// ```
+/// use shiplift::service::PlacementConstraint;
+///
+/// assert_eq!(
+///     PlacementConstraint::node_label("region").eq("east"),
+///     "node.labels.region == east"
+/// );
+/// assert_eq!(PlacementConstraint::node_role().ne("manager"), "node.role != manager");
+/// ```
+#[derive(Clone, Debug)]
+pub struct PlacementConstraint {
+    attribute: String,
+}
+
+impl PlacementConstraint {
+    /// Constrains an arbitrary attribute, for those not covered by the other constructors
+    pub fn attribute<S>(attribute: S) -> Self
+    where
+        S: Into<String>,
+    {
+        PlacementConstraint {
+            attribute: attribute.into(),
+        }
+    }
+
+    pub fn node_id() -> Self {
+        Self::attribute("node.id")
+    }
+
+    pub fn node_hostname() -> Self {
+        Self::attribute("node.hostname")
+    }
+
+    /// Either `manager` or `worker`
+    pub fn node_role() -> Self {
+        Self::attribute("node.role")
+    }
+
+    pub fn node_platform_os() -> Self {
+        Self::attribute("node.platform.os")
+    }
+
+    pub fn node_platform_arch() -> Self {
+        Self::attribute("node.platform.arch")
+    }
+
+    /// A label set on the node through the swarm, see [NodeSpec](crate::node::NodeSpec)
+    pub fn node_label(key: &str) -> Self {
+        Self::attribute(format!("node.labels.{}", key))
+    }
+
+    /// A label set in the docker daemon configuration of the node
+    pub fn engine_label(key: &str) -> Self {
+        Self::attribute(format!("engine.labels.{}", key))
+    }
+
+    /// Requires the attribute to equal `value`
+    pub fn eq(
+        self,
+        value: &str,
+    ) -> String {
+        format!("{} == {}", self.attribute, value)
+    }
+
+    /// Requires the attribute to differ from `value`
+    pub fn ne(
+        self,
+        value: &str,
+    ) -> String {
+        format!("{} != {}", self.attribute, value)
+    }
+}
+
+/// Container settings of the tasks of a service
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+// #TODO: Add missing fields...
+pub struct ContainerSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Environment variables in the form `KEY=value`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<SecretReference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configs: Option<Vec<ConfigReference>>,
+}
+
+/// A secret exposed to the containers of a service
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretReference {
+    #[serde(rename = "SecretID")]
+    pub secret_id: String,
+    pub secret_name: String,
+    /// Where the secret is mounted inside the container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<ReferenceFile>,
+}
+
+/// A config exposed to the containers of a service
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConfigReference {
+    #[serde(rename = "ConfigID")]
+    pub config_id: String,
+    pub config_name: String,
+    /// Where the config is mounted inside the container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<ReferenceFile>,
+}
+
+/// File a secret or config is mounted as, relative to `/run/secrets/` for secrets and to the
+/// container's root for configs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReferenceFile {
+    pub name: String,
+    #[serde(rename = "UID")]
+    pub uid: String,
+    #[serde(rename = "GID")]
+    pub gid: String,
+    /// File permissions, e.g. `0o444`
+    pub mode: u32,
+}
+
+/// Scheduling mode of a service
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    /// A fixed number of tasks spread across the swarm
+    Replicated(Replicated),
+    /// One task on every node matching the placement constraints
+    Global {},
+    /// Tasks that run to completion, a fixed number of times
+    ReplicatedJob(ReplicatedJob),
+    /// Tasks that run to completion once on every node matching the placement constraints
+    GlobalJob {},
+}
+
+impl Mode {
+    /// A replicated service running `replicas` tasks
+    pub fn replicated(replicas: u64) -> Self {
+        Mode::Replicated(Replicated { replicas })
+    }
+
+    /// A global service running one task per node
+    pub fn global() -> Self {
+        Mode::Global {}
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Replicated {
     pub replicas: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ReplicatedJob {
     pub max_concurrent: u64,
     pub total_completions: u64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Controls how the tasks of a service are replaced during an update
+///
+/// Fields left unset take the daemon's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct UpdateConfig {
-    pub parallelism: u64,
-    pub delay: u64,
-    pub failure_action: String,
-    pub monitor: u64,
-    pub max_failure_ratio: usize,
-    pub order: String,
+    /// Number of tasks updated at once, 0 meaning all at once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallelism: Option<u64>,
+    /// Time between updating batches of tasks, in nanoseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_action: Option<FailureAction>,
+    /// Time to watch each updated task for failures, in nanoseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<u64>,
+    /// Fraction of tasks that may fail during an update before the failure action is taken,
+    /// between 0 and 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_failure_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<UpdateOrder>,
 }
 
+/// Controls how the tasks of a service are replaced during a rollback
+///
+/// [FailureAction::Rollback] is not valid for rollbacks.
 pub type RollbackConfig = UpdateConfig;
 
+/// What to do when an update fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureAction {
+    Continue,
+    Pause,
+    Rollback,
+}
+
+/// Whether old tasks are stopped before or after their replacements are started
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateOrder {
+    StopFirst,
+    StartFirst,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct NetworkAttachmentConfig {
@@ -530,9 +1060,437 @@ pub struct NetworkAttachmentConfig {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ServiceCreateInfo {
     #[serde(rename = "ID")]
     pub id: String,
     #[serde(rename = "Warning")]
     pub warning: Option<String>,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct ServiceUpdateInfo {
+    pub warnings: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_service() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker, Error, RegistryAuth,
+        };
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "GET",
+                "/services/web",
+                MockResponse::json(&fixtures::service_details("9mnp", "web", 19)),
+            )
+            .respond_once(
+                "POST",
+                "/services/web/update",
+                MockResponse::json(&json!({ "Warnings": null })),
+            )
+            .respond_once(
+                "POST",
+                "/services/web/update",
+                MockResponse::json(&json!({ "Warnings": ["image could not be resolved"] })),
+            )
+            .respond_once(
+                "POST",
+                "/services/web/update",
+                MockResponse::error(
+                    500,
+                    "rpc error: code = Unknown desc = update out of sequence",
+                ),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let service = docker.services().get("web");
+        let opts = ServiceOptions::builder()
+            .name("web")
+            .mode(&Mode::replicated(3))
+            .auth(RegistryAuth::token("abc"))
+            .build()
+            .unwrap();
+
+        futures::executor::block_on(async {
+            let info = service.update(&opts, None).await.unwrap();
+            assert!(info.warnings.is_none());
+
+            let opts = ServiceOptions::builder()
+                .name("web")
+                .registry_auth_from(RegistryAuthFrom::PreviousSpec)
+                .build()
+                .unwrap();
+            let info = service.update(&opts, Some(20)).await.unwrap();
+            assert_eq!(info.warnings.unwrap(), ["image could not be resolved"]);
+
+            match service.update(&opts, Some(20)).await {
+                Err(Error::UpdateOutOfSequence(_)) => (),
+                result => panic!("unexpected result {:?}", result),
+            }
+        });
+
+        let requests = daemon.requests();
+        // without a version, the current one is inspected first
+        assert_eq!(requests[0].path, "/services/web");
+        assert_eq!(requests[1].path, "/services/web/update?version=19");
+        assert!(requests[1].headers.contains_key("X-Registry-Auth"));
+        let body = requests[1].json().unwrap();
+        assert_eq!(body["Name"], "web");
+        assert_eq!(body["Mode"], json!({ "Replicated": { "Replicas": 3 } }));
+        assert_eq!(
+            requests[2].path,
+            "/services/web/update?version=20&registryAuthFrom=previous-spec"
+        );
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[test]
+    fn rollback_service() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker,
+        };
+
+        let mut details = fixtures::service_details("9mnp", "web", 23);
+        // fields of the spec this crate doesn't model are sent back as well
+        details["Spec"]["TaskTemplate"]["ForceUpdate"] = json!(2);
+        let daemon = MockDaemon::new();
+        daemon
+            .respond("GET", "/services/web", MockResponse::json(&details))
+            .respond(
+                "POST",
+                "/services/web/update",
+                MockResponse::json(&json!({ "Warnings": null })),
+            );
+        let docker = Docker::mock(daemon.clone());
+
+        futures::executor::block_on(docker.services().get("web").rollback()).unwrap();
+        let requests = daemon.requests();
+        assert_eq!(
+            requests[1].path,
+            "/services/web/update?version=23&rollback=previous"
+        );
+        assert_eq!(requests[1].json().unwrap(), details["Spec"]);
+    }
+
+    #[test]
+    fn await_converged() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker,
+        };
+
+        let mut outdated = fixtures::task("b2", "9mnp", "running");
+        outdated["Spec"]["ContainerSpec"]["Image"] = json!("nginx:1.20");
+        let mut global = fixtures::service_details("9mnp", "web", 20);
+        global["Spec"]["Mode"] = json!({ "Global": {} });
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once(
+                "GET",
+                "/services/web",
+                MockResponse::json(&fixtures::service_details("9mnp", "web", 20)),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([fixtures::task("a1", "9mnp", "running"), outdated])),
+            )
+            .respond_once(
+                "GET",
+                "/services/web",
+                MockResponse::json(&fixtures::service_details("9mnp", "web", 20)),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([
+                    fixtures::task("a1", "9mnp", "running"),
+                    fixtures::task("c3", "9mnp", "running"),
+                ])),
+            )
+            .respond("GET", "/services/web", MockResponse::json(&global))
+            .respond_once("GET", "/tasks", MockResponse::json(&json!([])))
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([
+                    fixtures::task("a1", "9mnp", "running"),
+                    fixtures::task("d4", "9mnp", "preparing"),
+                ])),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([fixtures::task("a1", "9mnp", "running")])),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let service = docker.services().get("web");
+        let converged = || futures::executor::block_on(service.await_converged(Duration::ZERO));
+
+        // a task of the previous spec still runs
+        assert_eq!(converged().unwrap(), ServiceConvergence::TimedOut);
+        assert_eq!(converged().unwrap(), ServiceConvergence::Converged);
+        // the global service has no tasks yet, then one of them is still starting
+        assert_eq!(converged().unwrap(), ServiceConvergence::TimedOut);
+        assert_eq!(converged().unwrap(), ServiceConvergence::TimedOut);
+        assert_eq!(converged().unwrap(), ServiceConvergence::Converged);
+        assert!(daemon.requests()[1]
+            .path
+            .contains("%22desired-state%22%3A%5B%22running%22%5D"));
+    }
+
+    #[test]
+    fn await_converged_forced_update_and_jobs() {
+        use crate::{
+            mock::{fixtures, MockDaemon, MockResponse},
+            Docker,
+        };
+
+        // `docker service update --force` only bumps a counter `TaskSpec` doesn't model
+        let mut forced = fixtures::service_details("9mnp", "web", 21);
+        forced["Spec"]["TaskTemplate"]["ForceUpdate"] = json!(1);
+        let mut job = fixtures::service_details("9mnp", "web", 22);
+        job["Spec"]["Mode"] = json!({
+            "ReplicatedJob": { "MaxConcurrent": 1, "TotalCompletions": 2 }
+        });
+        let mut retried = fixtures::task("b2", "9mnp", "failed");
+        retried["DesiredState"] = json!("shutdown");
+        let daemon = MockDaemon::new();
+        daemon
+            .respond_once("GET", "/services/web", MockResponse::json(&forced))
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([
+                    fixtures::task("a1", "9mnp", "running"),
+                    fixtures::task("c3", "9mnp", "running"),
+                ])),
+            )
+            .respond("GET", "/services/web", MockResponse::json(&job))
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([fixtures::task("a1", "9mnp", "complete"), retried])),
+            )
+            .respond_once(
+                "GET",
+                "/tasks",
+                MockResponse::json(&json!([
+                    fixtures::task("a1", "9mnp", "complete"),
+                    retried,
+                    fixtures::task("d4", "9mnp", "complete"),
+                ])),
+            );
+        let docker = Docker::mock(daemon.clone());
+        let service = docker.services().get("web");
+        let converged = || futures::executor::block_on(service.await_converged(Duration::ZERO));
+
+        // the tasks still run the spec from before the forced update
+        assert_eq!(converged().unwrap(), ServiceConvergence::TimedOut);
+        // the failed attempt of the job doesn't count towards its completions
+        assert_eq!(converged().unwrap(), ServiceConvergence::TimedOut);
+        assert_eq!(converged().unwrap(), ServiceConvergence::Converged);
+        assert!(!daemon.requests()[3].path.contains("desired-state"));
+    }
+
+    #[test]
+    fn service_list_options_filters() {
+        let options = ServiceListOptions::builder()
+            .filter(vec![
+                ServiceFilter::Label("com.example.tier=web".to_string()),
+                ServiceFilter::Label("com.example.owner".to_string()),
+                ServiceFilter::ReplicatedMode,
+            ])
+            .build();
+
+        let serialized = options.serialize().unwrap().unwrap();
+
+        assert!(serialized.contains(
+            "%22label%22%3A%5B%22com.example.tier%3Dweb%22%2C%22com.example.owner%22%5D"
+        ));
+        assert!(serialized.contains("%22mode%22%3A%5B%22replicated%22%5D"));
+    }
+
+    #[test]
+    fn service_list_options_status() {
+        let options = ServiceListOptions::builder()
+            .filter(vec![ServiceFilter::Name("web".to_string())])
+            .enable_status()
+            .build();
+
+        let serialized = options.serialize().unwrap().unwrap();
+
+        assert!(serialized.contains("status=true"));
+        assert!(serialized.contains("filters=%7B%22name%22%3A%5B%22web%22%5D%7D"));
+    }
+
+    #[test]
+    fn service_details_during_update() {
+        let details: ServiceDetails = serde_json::from_str(
+            r#"{
+                "ID": "9mnpnzenvg8p8tdbtq4wvbkcz",
+                "Version": {"Index": 19},
+                "CreatedAt": "2016-06-07T21:05:51.880065305Z",
+                "UpdatedAt": "2016-06-07T21:07:29.962229872Z",
+                "Spec": {
+                    "Name": "hopeful_cori",
+                    "TaskTemplate": {},
+                    "Mode": {"Replicated": {"Replicas": 2}},
+                    "EndpointSpec": {"Mode": "vip"}
+                },
+                "PreviousSpec": {
+                    "Name": "hopeful_cori",
+                    "TaskTemplate": {},
+                    "Mode": {"Replicated": {"Replicas": 1}}
+                },
+                "Endpoint": {
+                    "Spec": {"Mode": "vip"},
+                    "Ports": [{
+                        "Protocol": "tcp",
+                        "TargetPort": 6379,
+                        "PublishedPort": 30001,
+                        "PublishMode": "ingress"
+                    }],
+                    "VirtualIPs": [{
+                        "NetworkID": "4qvuz4ko70xaltuqbt8956gd1",
+                        "Addr": "10.255.0.2/16"
+                    }]
+                },
+                "UpdateStatus": {
+                    "State": "updating",
+                    "StartedAt": "2016-06-07T21:07:29.962229872Z",
+                    "Message": "update in progress"
+                },
+                "PendingDelete": false
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(details.version.index, 19);
+        assert!(details.previous_spec.is_some());
+        assert_eq!(
+            details.endpoint.virtual_ips.unwrap()[0].addr,
+            "10.255.0.2/16"
+        );
+        let update_status = details.update_status.unwrap();
+        assert_eq!(update_status.state, UpdateState::Updating);
+        assert!(update_status.completed_at.is_none());
+        assert_eq!(details.spec.mode, Mode::replicated(2));
+        #[cfg(feature = "extra-fields")]
+        assert_eq!(details.extra["PendingDelete"], false);
+    }
+
+    #[test]
+    fn service_update_config_serialize() {
+        let config = UpdateConfig {
+            parallelism: Some(1),
+            delay: Some(10_000_000_000),
+            failure_action: Some(FailureAction::Rollback),
+            max_failure_ratio: Some(0.2),
+            order: Some(UpdateOrder::StartFirst),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "Parallelism": 1,
+                "Delay": 10_000_000_000u64,
+                "FailureAction": "rollback",
+                "MaxFailureRatio": 0.2,
+                "Order": "start-first"
+            })
+        );
+    }
+
+    #[test]
+    fn service_mode_serialize() {
+        assert_eq!(
+            serde_json::to_value(Mode::replicated(3)).unwrap(),
+            json!({"Replicated": {"Replicas": 3}})
+        );
+        assert_eq!(
+            serde_json::to_value(Mode::global()).unwrap(),
+            json!({"Global": {}})
+        );
+        assert_eq!(
+            serde_json::from_value::<Mode>(json!({"GlobalJob": {}})).unwrap(),
+            Mode::GlobalJob {}
+        );
+    }
+
+    #[test]
+    fn service_placement_serialize() {
+        let placement = Placement {
+            constraints: Some(vec![
+                PlacementConstraint::node_label("region").eq("east"),
+                PlacementConstraint::node_role().ne("manager"),
+            ]),
+            preferences: Some(vec![PlacementPreference::Spread {
+                spread_descriptor: "node.labels.datacenter".to_owned(),
+            }]),
+            max_replicas: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&placement).unwrap(),
+            json!({
+                "Constraints": ["node.labels.region == east", "node.role != manager"],
+                "Preferences": [{"Spread": {"SpreadDescriptor": "node.labels.datacenter"}}],
+                "MaxReplicas": 2
+            })
+        );
+    }
+
+    #[test]
+    fn service_options_task_template_secrets() {
+        let task_template = TaskSpec {
+            container_spec: Some(ContainerSpec {
+                image: Some("nginx:alpine".to_owned()),
+                secrets: Some(vec![SecretReference {
+                    secret_id: "ktnbjxoalbkvbvedmg1urrz8h".to_owned(),
+                    secret_name: "tls_key".to_owned(),
+                    file: Some(ReferenceFile {
+                        name: "server.key".to_owned(),
+                        uid: "0".to_owned(),
+                        gid: "0".to_owned(),
+                        mode: 0o400,
+                    }),
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let options = ServiceOptions::builder()
+            .name("web")
+            .task_template(&task_template)
+            .build()
+            .unwrap();
+        let serialized: Value = serde_json::from_str(&options.serialize().unwrap()).unwrap();
+
+        assert_eq!(
+            serialized["TaskTemplate"],
+            json!({
+                "ContainerSpec": {
+                    "Image": "nginx:alpine",
+                    "Secrets": [{
+                        "SecretID": "ktnbjxoalbkvbvedmg1urrz8h",
+                        "SecretName": "tls_key",
+                        "File": {"Name": "server.key", "UID": "0", "GID": "0", "Mode": 256}
+                    }]
+                }
+            })
+        );
+    }
+}
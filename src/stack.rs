@@ -0,0 +1,606 @@
+//! Bring up a set of containers, networks and volumes as a unit.
+//!
+//! A [Stack] declares the networks and volumes to create and the containers to run, with the
+//! containers each depends on and the condition under which a container counts as ready.
+//! [Stack::up] creates everything, starting each container once its dependencies are ready, and
+//! returns a [RunningStack] that tears it all down again with [RunningStack::down].
+//!
+//! ```no_run
+//! use shiplift::{
+//!     stack::{Readiness, Stack, StackContainer},
+//!     ContainerOptions, Docker, NetworkCreateOptions,
+//! };
+//! # async {
+//! let docker = Docker::new();
+//! let stack = Stack::builder()
+//!     .network(NetworkCreateOptions::builder("app").build())
+//!     .container(
+//!         StackContainer::new("db", ContainerOptions::builder("postgres:13").build())
+//!             .network("app"),
+//!     )
+//!     .container(
+//!         StackContainer::new(
+//!             "migrate",
+//!             ContainerOptions::builder("app:latest")
+//!                 .cmd(vec!["migrate"])
+//!                 .build(),
+//!         )
+//!         .network("app")
+//!         .depends_on("db")
+//!         .ready_when(Readiness::Completed),
+//!     )
+//!     .build();
+//!
+//! let running = stack.up(&docker).await?;
+//! // ...
+//! running.down().await?;
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    container::{Container, ContainerOptions, Containers, RmContainerOptions},
+    docker::Docker,
+    errors::{Error, Result},
+    network::{ContainerConnectionOptions, NetworkCreateOptions, Networks},
+    volume::{VolumeCreateOptions, Volumes},
+};
+
+/// How long [Stack::up] waits for each container to become ready by default
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// When a container of a [Stack] counts as ready, so that the containers depending on it are
+/// started
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Readiness {
+    /// As soon as the container was started
+    Started,
+    /// Once the daemon reports the container as running. Fails if it exits instead.
+    #[default]
+    Running,
+    /// Once the container has exited with status 0, like a container running migrations.
+    /// Fails if it exits with another status.
+    Completed,
+}
+
+/// A container of a [Stack]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct StackContainer {
+    key: String,
+    options: ContainerOptions,
+    depends_on: Vec<String>,
+    networks: Vec<String>,
+    readiness: Readiness,
+}
+
+impl StackContainer {
+    /// A container created from `options`. Other containers of the stack refer to it by `key`,
+    /// which is also its alias on the networks it is connected to.
+    pub fn new<K>(
+        key: K,
+        options: ContainerOptions,
+    ) -> Self
+    where
+        K: Into<String>,
+    {
+        StackContainer {
+            key: key.into(),
+            options,
+            depends_on: Vec::new(),
+            networks: Vec::new(),
+            readiness: Readiness::default(),
+        }
+    }
+
+    /// Starts the container only once the container `key` is ready
+    pub fn depends_on<K>(
+        mut self,
+        key: K,
+    ) -> Self
+    where
+        K: Into<String>,
+    {
+        self.depends_on.push(key.into());
+        self
+    }
+
+    /// Connects the container to the network `name` before it is started
+    pub fn network<N>(
+        mut self,
+        name: N,
+    ) -> Self
+    where
+        N: Into<String>,
+    {
+        self.networks.push(name.into());
+        self
+    }
+
+    /// When the container counts as ready, [Readiness::Running] by default
+    pub fn ready_when(
+        mut self,
+        readiness: Readiness,
+    ) -> Self {
+        self.readiness = readiness;
+        self
+    }
+}
+
+/// A declared set of containers, networks and volumes, see the [module docs](self)
+pub struct Stack {
+    networks: Vec<NetworkCreateOptions>,
+    volumes: Vec<VolumeCreateOptions>,
+    containers: Vec<StackContainer>,
+    ready_timeout: Duration,
+}
+
+impl Stack {
+    /// return a new instance of a builder for a stack
+    pub fn builder() -> StackBuilder {
+        StackBuilder::default()
+    }
+
+    /// Creates the networks and volumes of the stack, then creates and starts its containers
+    /// in the order of their dependencies, waiting for each to become ready before starting the
+    /// containers that depend on it.
+    ///
+    /// If anything fails, whatever was created already is removed again before the error is
+    /// returned.
+    pub async fn up<'docker>(
+        &self,
+        docker: &'docker Docker,
+    ) -> Result<RunningStack<'docker>> {
+        let order = self.start_order()?;
+        let mut running = RunningStack {
+            docker,
+            containers: Vec::new(),
+            networks: Vec::new(),
+            volumes: Vec::new(),
+        };
+        match self.bring_up(&order, &mut running).await {
+            Ok(()) => Ok(running),
+            Err(e) => {
+                // the error that made bringing the stack up fail is the one worth reporting
+                let _ = running.down().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn bring_up(
+        &self,
+        order: &[&StackContainer],
+        running: &mut RunningStack<'_>,
+    ) -> Result<()> {
+        let docker = running.docker;
+        for opts in &self.networks {
+            let info = Networks::new(docker).create(opts).await?;
+            running.networks.push(info.id.into_string());
+        }
+        for opts in &self.volumes {
+            let info = Volumes::new(docker).create(opts).await?;
+            running.volumes.push(info.name);
+        }
+
+        for container in order {
+            let info = Containers::new(docker).create(&container.options).await?;
+            let id = info.id.into_string();
+            running.containers.push((container.key.clone(), id.clone()));
+
+            for network in &container.networks {
+                let opts = ContainerConnectionOptions::builder(&id)
+                    .aliases(vec![&container.key])
+                    .build();
+                Networks::new(docker).get(network).connect(&opts).await?;
+            }
+
+            let handle = Containers::new(docker).get(id);
+            handle.start().await?;
+            wait_until_ready(&handle, container, self.ready_timeout).await?;
+        }
+        Ok(())
+    }
+
+    /// The containers ordered so that each comes after the containers it depends on, keeping
+    /// the declared order otherwise
+    fn start_order(&self) -> Result<Vec<&StackContainer>> {
+        let by_key: HashMap<&str, &StackContainer> = self
+            .containers
+            .iter()
+            .map(|container| (container.key.as_str(), container))
+            .collect();
+
+        let mut order = Vec::with_capacity(self.containers.len());
+        let mut visiting = Vec::new();
+        for container in &self.containers {
+            visit(container, &by_key, &mut visiting, &mut order)?;
+        }
+        Ok(order)
+    }
+}
+
+fn visit<'a>(
+    container: &'a StackContainer,
+    by_key: &HashMap<&str, &'a StackContainer>,
+    visiting: &mut Vec<&'a str>,
+    order: &mut Vec<&'a StackContainer>,
+) -> Result<()> {
+    if order.iter().any(|c| c.key == container.key) {
+        return Ok(());
+    }
+    if visiting.contains(&container.key.as_str()) {
+        return Err(StackError::DependencyCycle(container.key.clone()).into());
+    }
+
+    visiting.push(&container.key);
+    for dependency in &container.depends_on {
+        let dependency =
+            by_key
+                .get(dependency.as_str())
+                .ok_or_else(|| StackError::UnknownDependency {
+                    container: container.key.clone(),
+                    dependency: dependency.clone(),
+                })?;
+        visit(dependency, by_key, visiting, order)?;
+    }
+    visiting.pop();
+
+    order.push(container);
+    Ok(())
+}
+
+async fn wait_until_ready(
+    handle: &Container<'_>,
+    container: &StackContainer,
+    timeout: Duration,
+) -> Result<()> {
+    let not_ready = || StackError::NotReady {
+        container: container.key.clone(),
+    };
+    match container.readiness {
+        Readiness::Started => Ok(()),
+        Readiness::Running => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                let state = handle.inspect().await?.state;
+                if state.running {
+                    return Ok(());
+                }
+                if state.status == "exited" || state.status == "dead" {
+                    return Err(StackError::Exited {
+                        container: container.key.clone(),
+                        status_code: state.exit_code,
+                    }
+                    .into());
+                }
+
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(not_ready().into());
+                }
+                tokio::time::sleep(READY_POLL_INTERVAL.min(deadline - now)).await;
+            }
+        }
+        Readiness::Completed => {
+            let exit = tokio::time::timeout(timeout, handle.wait())
+                .await
+                .map_err(|_| not_ready())??;
+            if exit.status_code == 0 {
+                Ok(())
+            } else {
+                Err(StackError::Exited {
+                    container: container.key.clone(),
+                    status_code: exit.status_code,
+                }
+                .into())
+            }
+        }
+    }
+}
+
+/// Builder interface for [Stack]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct StackBuilder {
+    stack: Stack,
+}
+
+impl Default for StackBuilder {
+    fn default() -> Self {
+        StackBuilder {
+            stack: Stack {
+                networks: Vec::new(),
+                volumes: Vec::new(),
+                containers: Vec::new(),
+                ready_timeout: DEFAULT_READY_TIMEOUT,
+            },
+        }
+    }
+}
+
+impl StackBuilder {
+    pub fn network(
+        mut self,
+        opts: NetworkCreateOptions,
+    ) -> Self {
+        self.stack.networks.push(opts);
+        self
+    }
+
+    pub fn volume(
+        mut self,
+        opts: VolumeCreateOptions,
+    ) -> Self {
+        self.stack.volumes.push(opts);
+        self
+    }
+
+    pub fn container(
+        mut self,
+        container: StackContainer,
+    ) -> Self {
+        self.stack.containers.push(container);
+        self
+    }
+
+    /// How long to wait for each container to become ready, a minute by default
+    pub fn ready_timeout(
+        mut self,
+        timeout: Duration,
+    ) -> Self {
+        self.stack.ready_timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Stack {
+        self.stack
+    }
+}
+
+/// The objects created by [Stack::up]
+pub struct RunningStack<'docker> {
+    docker: &'docker Docker,
+    /// Keys and IDs of the containers, in the order they were started
+    containers: Vec<(String, String)>,
+    networks: Vec<String>,
+    volumes: Vec<String>,
+}
+
+impl<'docker> RunningStack<'docker> {
+    /// The container declared with `key`
+    pub fn container(
+        &self,
+        key: &str,
+    ) -> Option<Container<'docker>> {
+        self.containers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, id)| Containers::new(self.docker).get(id))
+    }
+
+    /// Stops and removes the containers in the reverse order they were started, then removes
+    /// the networks and volumes
+    ///
+    /// Tearing down continues past failures, the first of which is returned. Objects that were
+    /// removed already are skipped.
+    pub async fn down(self) -> Result<()> {
+        let mut result = Ok(());
+        let mut record = |outcome: Result<()>| match outcome {
+            Err(e) if !e.is_not_found() && !e.is_not_modified() && result.is_ok() => {
+                result = Err(e)
+            }
+            _ => (),
+        };
+
+        let containers = Containers::new(self.docker);
+        for (_, id) in self.containers.iter().rev() {
+            let container = containers.get(id);
+            record(container.stop(None).await);
+            record(
+                container
+                    .remove(RmContainerOptions::builder().force(true).build())
+                    .await,
+            );
+        }
+        for id in &self.networks {
+            record(Networks::new(self.docker).get(id).delete().await);
+        }
+        for name in &self.volumes {
+            record(Volumes::new(self.docker).get(name).delete().await);
+        }
+        result
+    }
+}
+
+/// Why a [Stack] could not be brought up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackError {
+    /// A container depends on a key that no container of the stack was declared with
+    UnknownDependency {
+        container: String,
+        dependency: String,
+    },
+    /// The named container depends on itself, directly or through other containers
+    DependencyCycle(String),
+    /// The container did not become ready in time
+    NotReady { container: String },
+    /// The container exited before it became ready, or exited with a status other than 0
+    /// when it was expected to complete
+    Exited { container: String, status_code: u64 },
+}
+
+impl fmt::Display for StackError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            StackError::UnknownDependency {
+                container,
+                dependency,
+            } => write!(
+                f,
+                "container {} depends on {}, which is not part of the stack",
+                container, dependency
+            ),
+            StackError::DependencyCycle(container) => {
+                write!(f, "container {} depends on itself", container)
+            }
+            StackError::NotReady { container } => {
+                write!(f, "container {} did not become ready in time", container)
+            }
+            StackError::Exited {
+                container,
+                status_code,
+            } => write!(
+                f,
+                "container {} exited with status {}",
+                container, status_code
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StackError {}
+
+impl From<StackError> for Error {
+    fn from(error: StackError) -> Error {
+        Error::Stack(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(key: &str) -> StackContainer {
+        StackContainer::new(key, ContainerOptions::builder("alpine").build())
+    }
+
+    fn order(stack: &Stack) -> Result<Vec<&str>> {
+        Ok(stack
+            .start_order()?
+            .into_iter()
+            .map(|c| c.key.as_str())
+            .collect())
+    }
+
+    #[test]
+    fn stack_start_order() {
+        let stack = Stack::builder()
+            .container(container("web").depends_on("api"))
+            .container(container("api").depends_on("db").depends_on("cache"))
+            .container(container("db"))
+            .container(container("cache"))
+            .build();
+        assert_eq!(order(&stack).unwrap(), ["db", "cache", "api", "web"]);
+
+        let stack = Stack::builder()
+            .container(container("a").depends_on("b"))
+            .container(container("b").depends_on("a"))
+            .build();
+        assert!(matches!(
+            order(&stack),
+            Err(Error::Stack(StackError::DependencyCycle(_)))
+        ));
+
+        let stack = Stack::builder()
+            .container(container("a").depends_on("c"))
+            .build();
+        assert!(matches!(
+            order(&stack),
+            Err(Error::Stack(StackError::UnknownDependency { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stack_up_and_down() {
+        use crate::mock::{fixtures, MockDaemon, MockResponse};
+
+        let daemon = MockDaemon::new();
+        daemon
+            .respond(
+                "POST",
+                "/networks/create",
+                MockResponse::json(&serde_json::json!({"Id": "n1", "Warning": ""})),
+            )
+            .respond_once(
+                "POST",
+                "/containers/create",
+                MockResponse::json(&fixtures::container_created("c1")),
+            )
+            .respond_once(
+                "POST",
+                "/containers/create",
+                MockResponse::json(&fixtures::container_created("c2")),
+            )
+            .respond("POST", "/networks/app/connect", MockResponse::new(200, ""))
+            .respond("POST", "/containers/c1/start", MockResponse::new(204, ""))
+            .respond("POST", "/containers/c2/start", MockResponse::new(204, ""))
+            .respond(
+                "GET",
+                "/containers/c1/json",
+                MockResponse::json(&fixtures::container_details("c1", "db")),
+            )
+            .respond(
+                "POST",
+                "/containers/c2/wait",
+                MockResponse::json(&serde_json::json!({"StatusCode": 3})),
+            )
+            .respond("POST", "/containers/c1/stop", MockResponse::new(204, ""))
+            .respond("POST", "/containers/c2/stop", MockResponse::new(304, ""))
+            .respond("DELETE", "/containers/c1", MockResponse::new(204, ""))
+            .respond("DELETE", "/containers/c2", MockResponse::new(204, ""))
+            .respond("DELETE", "/networks/n1", MockResponse::new(204, ""));
+        let docker = Docker::mock(daemon.clone());
+
+        let stack = Stack::builder()
+            .network(NetworkCreateOptions::builder("app").build())
+            .container(
+                container("migrate")
+                    .depends_on("db")
+                    .network("app")
+                    .ready_when(Readiness::Completed),
+            )
+            .container(container("db").network("app"))
+            .build();
+        let err = stack.up(&docker).await.err().unwrap();
+        assert!(matches!(
+            err,
+            Error::Stack(StackError::Exited { status_code: 3, .. })
+        ));
+
+        let paths: Vec<_> = daemon
+            .requests()
+            .into_iter()
+            .map(|request| format!("{} {}", request.method, request.path))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "POST /networks/create",
+                "POST /containers/create",
+                "POST /networks/app/connect",
+                "POST /containers/c1/start",
+                "GET /containers/c1/json",
+                "POST /containers/create",
+                "POST /networks/app/connect",
+                "POST /containers/c2/start",
+                "POST /containers/c2/wait",
+                "POST /containers/c2/stop",
+                "DELETE /containers/c2?force=true",
+                "POST /containers/c1/stop",
+                "DELETE /containers/c1?force=true",
+                "DELETE /networks/n1",
+            ]
+        );
+    }
+}
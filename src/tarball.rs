@@ -1,70 +1,653 @@
-use flate2::{write::GzEncoder, Compression};
+use bytes::Bytes;
+use flate2::write::GzEncoder;
+use futures_util::stream::{self, Stream};
 use std::{
-    fs::{self, File},
+    cell::Cell,
+    collections::BTreeMap,
+    fmt, fs,
     io::{self, Write},
-    path::{Path, MAIN_SEPARATOR},
+    mem,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
 };
-use tar::Builder;
+use tar::{Builder, EntryType, Header, HeaderMode};
+use tokio::sync::mpsc;
 
-fn bundle<F>(
-    dir: &Path,
-    f: &mut F,
-    bundle_dir: bool,
-) -> io::Result<()>
+#[cfg(unix)]
+use std::collections::{hash_map::Entry, HashMap};
+
+mod dockerignore;
+
+use dockerignore::{Patterns, DOCKERIGNORE};
+
+/// Options controlling how a directory is packed into a tarball
+#[derive(Clone, Debug)]
+pub struct TarballOptions {
+    /// How the archive is compressed
+    pub compression: Compression,
+    /// Exclude the entries matched by the `.dockerignore` file in the root of the directory, or
+    /// by the ignore file of the Dockerfile like `app.Dockerfile.dockerignore`, following the
+    /// rules of `docker build`
+    pub dockerignore: bool,
+    /// Path of the Dockerfile relative to the root of the directory. Like `docker build`, it is
+    /// never excluded.
+    pub dockerfile: String,
+    /// If not empty, only entries matching one of these patterns are archived. Patterns follow
+    /// the `.dockerignore` syntax, and a pattern matching a directory matches everything in it.
+    pub include: Vec<String>,
+    /// Entries matching these patterns are not archived, in addition to those excluded by the
+    /// `.dockerignore` file. Patterns follow the `.dockerignore` syntax.
+    pub exclude: Vec<String>,
+    /// How symbolic links are archived
+    pub symlinks: Symlinks,
+    /// Record the owner and group of each file. By default, entries are owned by root like in the
+    /// contexts `docker build` sends.
+    pub preserve_ownership: bool,
+    /// Record every entry with mode `0755` if it is a directory or executable by its owner and
+    /// `0644` otherwise, rather than with its actual permissions
+    pub normalize_permissions: bool,
+    /// Abort packing with a [ContextTooLarge] error once the archive, before compression, would
+    /// grow beyond this many bytes
+    pub max_bytes: Option<u64>,
+    /// Modification time recorded for every entry, in seconds since the epoch, rather than the
+    /// actual modification times. Useful for reproducible contexts.
+    pub mtime: Option<u64>,
+    /// Called with the progress of packing after every entry and once packing is complete
+    pub progress: Option<ProgressCallback>,
+    /// Files archived with the given contents in addition to the contents of the directory,
+    /// keyed by their path relative to its root. They replace files at the same path in the
+    /// directory and are never excluded.
+    pub overlay: BTreeMap<String, Vec<u8>>,
+}
+
+/// How symbolic links are archived
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Archive symbolic links as link entries, like `docker build` does
+    Preserve,
+    /// Archive the files and directories symbolic links point to in their place. Packing fails if
+    /// a link points to one of its own parent directories.
+    Follow,
+}
+
+/// How a tarball is compressed
+///
+/// Compressing pays off when the tarball is sent over a network, while a plain archive is fastest
+/// to produce for a daemon on the local machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// A plain tar archive
+    None,
+    /// Gzip at the given level, from 0 (fastest) to 9 (smallest)
+    Gzip(u32),
+    /// Zstandard at the given level, from 1 (fastest) to 22 (smallest). Requires a daemon that
+    /// accepts zstd compressed build contexts.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Default for Compression {
+    /// Gzip at the highest level
+    fn default() -> Self {
+        Compression::Gzip(9)
+    }
+}
+
+impl Default for TarballOptions {
+    fn default() -> Self {
+        TarballOptions {
+            compression: Compression::default(),
+            dockerignore: true,
+            dockerfile: "Dockerfile".to_owned(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            symlinks: Symlinks::Preserve,
+            preserve_ownership: false,
+            normalize_permissions: false,
+            max_bytes: None,
+            mtime: None,
+            progress: None,
+            overlay: BTreeMap::new(),
+        }
+    }
+}
+
+/// Progress of packing a tarball, as reported to [TarballOptions::progress]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TarballProgress {
+    /// Number of entries archived so far
+    pub entries: u64,
+    /// Number of bytes of the tarball written so far, after compression
+    pub bytes: u64,
+}
+
+/// Callback receiving the [TarballProgress] of packing a tarball
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(TarballProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new<F>(callback: F) -> Self
     where
-        F: FnMut(&Path) -> io::Result<()>,
+        F: Fn(TarballProgress) + Send + Sync + 'static,
+    {
+        ProgressCallback(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Counts what has been packed so far, for [TarballOptions::progress]
+#[derive(Default)]
+struct ProgressCounter {
+    entries: Cell<u64>,
+    bytes: Cell<u64>,
+}
+
+impl ProgressCounter {
+    fn entry_added(
+        &self,
+        callback: Option<&ProgressCallback>,
+    ) {
+        self.entries.set(self.entries.get() + 1);
+        self.report(callback);
+    }
+
+    fn report(
+        &self,
+        callback: Option<&ProgressCallback>,
+    ) {
+        if let Some(ProgressCallback(callback)) = callback {
+            callback(TarballProgress {
+                entries: self.entries.get(),
+                bytes: self.bytes.get(),
+            });
+        }
+    }
+}
+
+/// Counts the bytes written through it in a [ProgressCounter]
+struct CountingWriter<'a, W> {
+    inner: W,
+    counter: &'a ProgressCounter,
+}
+
+impl<W> Write for CountingWriter<'_, W>
+where
+    W: Write,
 {
-    if fs::metadata(dir)?.is_dir() {
-        if bundle_dir {
-            f(&dir)?;
+    fn write(
+        &mut self,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.counter
+            .bytes
+            .set(self.counter.bytes.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Error returned, wrapped in an [io::Error], when a tarball would exceed
+/// [TarballOptions::max_bytes]
+#[derive(Debug)]
+pub struct ContextTooLarge {
+    /// The configured limit in bytes
+    pub limit: u64,
+    /// The largest entries packed before the limit was hit, largest first, with their sizes in
+    /// bytes
+    pub largest_entries: Vec<(PathBuf, u64)>,
+}
+
+impl fmt::Display for ContextTooLarge {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "tarball exceeds the limit of {} bytes", self.limit)?;
+        for (i, (path, size)) in self.largest_entries.iter().enumerate() {
+            let separator = if i == 0 { ", largest entries: " } else { ", " };
+            write!(f, "{}{} ({} bytes)", separator, path.display(), size)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ContextTooLarge {}
+
+/// Number of entries listed by [ContextTooLarge]
+const LARGEST_ENTRIES: usize = 10;
+/// Size of a tar header block, which every entry's data is padded to a multiple of
+const BLOCK_SIZE: u64 = 512;
+
+/// Tracks the size of a tarball against [TarballOptions::max_bytes]
+struct SizeLimit {
+    limit: u64,
+    total: u64,
+    largest_entries: Vec<(PathBuf, u64)>,
+}
+
+impl SizeLimit {
+    /// The limit `opts` sets on the size of the tarball, if any
+    fn new(opts: &TarballOptions) -> Option<Self> {
+        opts.max_bytes.map(|limit| SizeLimit {
+            limit,
+            total: 0,
+            largest_entries: Vec::new(),
+        })
+    }
+
+    fn add(
+        &mut self,
+        path: &Path,
+        size: u64,
+    ) -> io::Result<()> {
+        // every entry takes a header block plus its data padded to whole blocks
+        self.total += BLOCK_SIZE + (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+
+        let position = self
+            .largest_entries
+            .iter()
+            .position(|(_, entry_size)| *entry_size < size)
+            .unwrap_or(self.largest_entries.len());
+        if position < LARGEST_ENTRIES {
+            self.largest_entries
+                .insert(position, (path.to_owned(), size));
+            self.largest_entries.truncate(LARGEST_ENTRIES);
         }
+
+        if self.total > self.limit {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                ContextTooLarge {
+                    limit: self.limit,
+                    largest_entries: mem::take(&mut self.largest_entries),
+                },
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Normalizes a path relative to the root of the directory, dropping `.` and leading `/`.
+/// Components may also be separated by `\`, so that paths written on Windows name the same
+/// entries on every host.
+fn relative_path(path: &str) -> PathBuf {
+    Path::new(&path.replace('\\', "/"))
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Decides which entries of a directory are archived
+struct Filter {
+    dockerignore: Patterns,
+    include: Patterns,
+    exclude: Patterns,
+    /// Paths that are never excluded, like the Dockerfile and the `.dockerignore` file
+    always_included: Vec<PathBuf>,
+}
+
+impl Filter {
+    fn new(
+        dir: &Path,
+        opts: &TarballOptions,
+    ) -> io::Result<Self> {
+        let dockerfile = relative_path(&opts.dockerfile);
+        Ok(Filter {
+            dockerignore: if opts.dockerignore {
+                Patterns::from_dockerignore(dir, &dockerfile)?
+            } else {
+                Patterns::default()
+            },
+            include: Patterns::new(&opts.include),
+            exclude: Patterns::new(&opts.exclude),
+            always_included: vec![dockerfile, PathBuf::from(DOCKERIGNORE)],
+        })
+    }
+
+    fn is_excluded(
+        &self,
+        path: &Path,
+    ) -> bool {
+        if self.always_included.iter().any(|included| included == path) {
+            return false;
+        }
+        self.dockerignore.matches(path)
+            || self.exclude.matches(path)
+            || (!self.include.is_empty() && !self.include.matches(path))
+    }
+
+    /// Whether the excluded directory at `path` may still contain entries that are not, in which
+    /// case it has to be walked rather than skipped as a whole
+    fn may_include_below(
+        &self,
+        path: &Path,
+    ) -> bool {
+        if self
+            .always_included
+            .iter()
+            .any(|included| included.starts_with(path))
+        {
+            return true;
+        }
+        (!self.dockerignore.matches(path) || self.dockerignore.may_unmatch_below(path))
+            && (!self.exclude.matches(path) || self.exclude.may_unmatch_below(path))
+            && (self.include.is_empty()
+                || self.include.matches(path)
+                || self.include.may_match_below(path))
+    }
+}
+
+struct Walk<'a> {
+    filter: &'a Filter,
+    follow_symlinks: bool,
+    /// Canonical paths of the directories being walked, to detect symbolic link loops
+    ancestors: Vec<PathBuf>,
+}
+
+impl Walk<'_> {
+    fn bundle<F>(
+        &mut self,
+        dir: &Path,
+        relative_dir: &Path,
+        f: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(&Path, &Path, &fs::Metadata) -> io::Result<()>,
+    {
+        if self.follow_symlinks {
+            let canonical = dir.canonicalize()?;
+            if self.ancestors.contains(&canonical) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("symbolic link loop at {}", dir.display()),
+                ));
+            }
+            self.ancestors.push(canonical);
+        }
+
         for entry in fs::read_dir(dir)? {
             let entry = entry?;
-            if fs::metadata(entry.path())?.is_dir() {
-                bundle(&entry.path(), f, true)?;
+            let path = entry.path();
+            let relative = relative_dir.join(entry.file_name());
+            let metadata = if self.follow_symlinks {
+                fs::metadata(&path)?
             } else {
-                f(&entry.path().as_path())?;
+                fs::symlink_metadata(&path)?
+            };
+            let is_dir = metadata.is_dir();
+
+            if self.filter.is_excluded(&relative) {
+                // something below an excluded directory may still be included, in which case the
+                // directory is walked without adding an entry for it
+                if is_dir && self.filter.may_include_below(&relative) {
+                    self.bundle(&path, &relative, f)?;
+                }
+                continue;
+            }
+
+            f(&relative, &path, &metadata)?;
+            if is_dir {
+                self.bundle(&path, &relative, f)?;
             }
         }
+
+        if self.follow_symlinks {
+            self.ancestors.pop();
+        }
+        Ok(())
+    }
+}
+
+fn header(
+    metadata: &fs::Metadata,
+    opts: &TarballOptions,
+) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_metadata_in_mode(metadata, HeaderMode::Complete);
+    if !opts.preserve_ownership {
+        header.set_uid(0);
+        header.set_gid(0);
+    }
+    #[cfg(windows)]
+    {
+        // there is no executable bit on Windows, so like `docker build` there mark every entry
+        // executable rather than leaving scripts unusable in the image
+        if let Ok(mode) = header.mode() {
+            header.set_mode(mode & 0o755 | 0o111);
+        }
+    }
+    if opts.normalize_permissions {
+        let executable = header.mode().map(|mode| mode & 0o100 != 0).unwrap_or(false);
+        header.set_mode(if metadata.is_dir() || executable {
+            0o755
+        } else {
+            0o644
+        });
+    }
+    if let Some(mtime) = opts.mtime {
+        header.set_mtime(mtime);
+    }
+    header
+}
+
+/// Remembers the first path archived for each file with several hard links, so that later links
+/// to it are archived as link entries rather than copies of its content
+#[derive(Default)]
+struct Hardlinks {
+    #[cfg(unix)]
+    seen: HashMap<(u64, u64), PathBuf>,
+}
+
+impl Hardlinks {
+    /// The path a link entry for the file at `relative` should point to, if the same file has
+    /// already been archived
+    #[cfg(unix)]
+    fn target(
+        &mut self,
+        relative: &Path,
+        metadata: &fs::Metadata,
+    ) -> Option<PathBuf> {
+        use std::os::unix::fs::MetadataExt;
+
+        if !metadata.is_file() || metadata.nlink() < 2 {
+            return None;
+        }
+        match self.seen.entry((metadata.dev(), metadata.ino())) {
+            Entry::Occupied(first) => Some(first.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(relative.to_owned());
+                None
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn target(
+        &mut self,
+        _relative: &Path,
+        _metadata: &fs::Metadata,
+    ) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Resolves the directory to pack. On Windows, this is its absolute `\\?\` form so that entries
+/// nested deeper than `MAX_PATH` can be opened.
+fn base_path(path: &str) -> io::Result<PathBuf> {
+    if cfg!(windows) {
+        fs::canonicalize(path)
+    } else {
+        Ok(PathBuf::from(path))
     }
-    Ok(())
 }
 
 fn use_archive<W>(
-    mut archive: Builder<W>,
+    archive: &mut Builder<W>,
     path: &str,
+    opts: &TarballOptions,
+    counter: &ProgressCounter,
 ) -> io::Result<()>
-    where
-        W: Write,
+where
+    W: Write,
 {
     {
-        let base_path = Path::new(path).canonicalize()?;
-        // todo: don't unwrap
-        let mut base_path_str = base_path.to_str().unwrap().to_owned();
-        if let Some(last) = base_path_str.chars().last() {
-            if last != MAIN_SEPARATOR {
-                base_path_str.push(MAIN_SEPARATOR)
-            }
-        }
+        let base_path = &base_path(path)?;
+        let filter = Filter::new(base_path, opts)?;
+        let follow_symlinks = opts.symlinks == Symlinks::Follow;
+        let mut size_limit = SizeLimit::new(opts);
 
-        let mut append = |path: &Path| {
-            let canonical = path.canonicalize()?;
-            // todo: don't unwrap
-            let relativized = canonical
-                .to_str()
-                .unwrap()
-                .trim_start_matches(&base_path_str[..]);
-            if path.is_dir() {
-                archive.append_dir(Path::new(relativized), &canonical)?
+        let mut hardlinks = Hardlinks::default();
+        let overlay: Vec<(PathBuf, &Vec<u8>)> = opts
+            .overlay
+            .iter()
+            .map(|(path, contents)| (relative_path(path), contents))
+            .collect();
+
+        let mut append = |relative: &Path, path: &Path, metadata: &fs::Metadata| {
+            if !metadata.is_dir() && overlay.iter().any(|(overlaid, _)| overlaid == relative) {
+                // replaced by the overlay, which is archived after the directory
+                return Ok(());
+            }
+            let mut header = header(metadata, opts);
+            let hardlink = hardlinks.target(relative, metadata);
+            if hardlink.is_some() {
+                header.set_entry_type(EntryType::Link);
+                header.set_size(0);
+            }
+            if let Some(size_limit) = &mut size_limit {
+                size_limit.add(relative, header.size()?)?;
+            }
+            if let Some(target) = hardlink {
+                archive.append_link(&mut header, relative, target)?;
+            } else if metadata.file_type().is_symlink() {
+                archive.append_link(&mut header, relative, fs::read_link(path)?)?;
+            } else if metadata.is_file() {
+                archive.append_data(&mut header, relative, fs::File::open(path)?)?;
             } else {
-                archive.append_file(Path::new(relativized), &mut File::open(&canonical)?)?
+                archive.append_data(&mut header, relative, io::empty())?;
             }
+
+            counter.entry_added(opts.progress.as_ref());
             Ok(())
         };
-        bundle(Path::new(path), &mut append, false)?;
+        Walk {
+            filter: &filter,
+            follow_symlinks,
+            ancestors: Vec::new(),
+        }
+        .bundle(base_path, Path::new(""), &mut append)?;
+
+        let mtime = generated_mtime(opts);
+        for (relative, contents) in overlay {
+            append_generated(
+                archive,
+                &relative,
+                contents,
+                mtime,
+                &mut size_limit,
+                opts,
+                counter,
+            )?;
+        }
     }
-    archive.finish()
+    Ok(())
 }
+
+/// The modification time of files that don't exist on disk, which is the time they are archived
+/// unless `opts` sets one
+fn generated_mtime(opts: &TarballOptions) -> u64 {
+    match opts.mtime {
+        Some(mtime) => mtime,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+    }
+}
+
+/// Archives a file that doesn't exist on disk as a regular file with `contents`
+fn append_generated<W>(
+    archive: &mut Builder<W>,
+    relative: &Path,
+    contents: &[u8],
+    mtime: u64,
+    size_limit: &mut Option<SizeLimit>,
+    opts: &TarballOptions,
+    counter: &ProgressCounter,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Regular);
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    if let Some(size_limit) = size_limit {
+        size_limit.add(relative, header.size()?)?;
+    }
+    archive.append_data(&mut header, relative, contents)?;
+    counter.entry_added(opts.progress.as_ref());
+    Ok(())
+}
+
+/// Writes the tarball `fill` appends the entries of to `buf`, compressed as `opts` asks for,
+/// and reports the progress of `counter` once it is complete
+fn write_archive<W, F>(
+    buf: W,
+    opts: &TarballOptions,
+    counter: &ProgressCounter,
+    fill: F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut Builder<&mut dyn Write>) -> io::Result<()>,
+{
+    let mut buf = CountingWriter {
+        inner: buf,
+        counter,
+    };
+
+    match opts.compression {
+        Compression::None => {
+            let mut archive = Builder::new(&mut buf as &mut dyn Write);
+            fill(&mut archive)?;
+            archive.into_inner()?;
+        }
+        Compression::Gzip(level) => {
+            let mut encoder = GzEncoder::new(buf, flate2::Compression::new(level));
+            let mut archive = Builder::new(&mut encoder as &mut dyn Write);
+            fill(&mut archive)?;
+            archive.into_inner()?;
+            encoder.finish()?;
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => {
+            let mut encoder = zstd::Encoder::new(buf, level)?;
+            let mut archive = Builder::new(&mut encoder as &mut dyn Write);
+            fill(&mut archive)?;
+            archive.into_inner()?;
+            encoder.finish()?;
+        }
+    };
+
+    counter.report(opts.progress.as_ref());
+    Ok(())
+}
+
 // todo: this is pretty involved. (re)factor this into its own crate
 pub fn dir<W>(
     buf: W,
@@ -74,11 +657,675 @@ pub fn dir<W>(
 where
     W: Write,
 {
-    if skip_gzip == true {
-        use_archive(Builder::new(buf), path)?;
-    } else {
-        use_archive(Builder::new(GzEncoder::new(buf, Compression::best())), path)?;
-    };
+    dir_with_options(
+        buf,
+        path,
+        &TarballOptions {
+            compression: if skip_gzip {
+                Compression::None
+            } else {
+                Compression::default()
+            },
+            ..Default::default()
+        },
+    )
+}
 
-    Ok(())
+/// Packs the directory at `path` into a tarball written to `buf`
+pub fn dir_with_options<W>(
+    buf: W,
+    path: &str,
+    opts: &TarballOptions,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let counter = ProgressCounter::default();
+    write_archive(buf, opts, &counter, |archive| {
+        use_archive(archive, path, opts, &counter)
+    })
+}
+
+/// Packs `files`, each a path relative to the root of the tarball with its contents, into a
+/// tarball written to `buf`, without touching the filesystem
+///
+/// The files are archived as regular files in the order of their paths, and a later file
+/// replaces an earlier one with the same path. The options selecting entries of a directory
+/// don't apply, the others do.
+pub fn files_with_options<W, I, P, C>(
+    buf: W,
+    files: I,
+    opts: &TarballOptions,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = (P, C)>,
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let files: BTreeMap<PathBuf, C> = files
+        .into_iter()
+        .map(|(path, contents)| (relative_path(&path.as_ref().to_string_lossy()), contents))
+        .collect();
+    let counter = ProgressCounter::default();
+    write_archive(buf, opts, &counter, |archive| {
+        let mut size_limit = SizeLimit::new(opts);
+        let mtime = generated_mtime(opts);
+        for (relative, contents) in &files {
+            append_generated(
+                archive,
+                relative,
+                contents.as_ref(),
+                mtime,
+                &mut size_limit,
+                opts,
+                &counter,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Size of the chunks produced by [dir_stream]
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+/// Number of chunks [dir_stream] packs ahead of the consumer
+const STREAM_CHUNKS_AHEAD: usize = 16;
+
+/// Packs the directory at `path` into a tarball that is produced as a stream of chunks
+///
+/// The directory is packed on a separate thread while the stream is consumed, holding at most a
+/// few chunks in memory, so the tarball can be larger than the available memory. Packing stops
+/// when the stream is dropped.
+pub fn dir_stream(
+    path: &str,
+    opts: &TarballOptions,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    let (tx, mut rx) = mpsc::channel(STREAM_CHUNKS_AHEAD);
+    let path = path.to_owned();
+    let opts = opts.clone();
+
+    thread::spawn(move || {
+        let mut writer = ChannelWriter {
+            tx: tx.clone(),
+            buf: Vec::with_capacity(STREAM_CHUNK_SIZE),
+        };
+        let result = dir_with_options(&mut writer, &path, &opts).and_then(|_| writer.flush());
+        if let Err(e) = result {
+            // the consumer is gone if this fails, so there is nobody left to tell
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+
+    stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
+
+/// Forwards everything written to it as chunks over a channel
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send_buf(&mut self) -> io::Result<()> {
+        let chunk = mem::replace(&mut self.buf, Vec::with_capacity(STREAM_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk.into()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "tarball stream was dropped"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(
+        &mut self,
+        buf: &[u8],
+    ) -> io::Result<usize> {
+        let len = buf.len().min(STREAM_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() == STREAM_CHUNK_SIZE {
+            self.send_buf()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            self.send_buf()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use std::path::PathBuf;
+
+    /// Creates a scratch directory under the system temp dir containing `files`
+    fn context_dir(
+        name: &str,
+        files: &[(&str, &str)],
+    ) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shiplift-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn entries(
+        dir: &Path,
+        opts: &TarballOptions,
+    ) -> Vec<String> {
+        let mut bytes = Vec::new();
+        dir_with_options(
+            &mut bytes,
+            dir.to_str().unwrap(),
+            &TarballOptions {
+                compression: Compression::None,
+                ..opts.clone()
+            },
+        )
+        .unwrap();
+
+        let mut entries: Vec<String> = tar::Archive::new(&bytes[..])
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .trim_end_matches('/')
+                    .to_owned()
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn tarball_honors_dockerignore() {
+        let dir = context_dir(
+            "dockerignore",
+            &[
+                (
+                    ".dockerignore",
+                    ".git\ntarget\n*.md\n!README.md\nDockerfile\n",
+                ),
+                ("Dockerfile", "FROM scratch"),
+                ("README.md", ""),
+                ("NOTES.md", ""),
+                (".git/HEAD", ""),
+                ("target/debug/app", ""),
+                ("src/main.rs", ""),
+            ],
+        );
+
+        assert_eq!(
+            entries(&dir, &TarballOptions::default()),
+            vec![
+                ".dockerignore",
+                "Dockerfile",
+                "README.md",
+                "src",
+                "src/main.rs"
+            ]
+        );
+        assert_eq!(
+            entries(
+                &dir,
+                &TarballOptions {
+                    dockerignore: false,
+                    ..Default::default()
+                }
+            )
+            .len(),
+            11
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_honors_dockerfile_dockerignore() {
+        let dir = context_dir(
+            "dockerfile-dockerignore",
+            &[
+                (".dockerignore", "docs\n"),
+                ("build/app.Dockerfile", "FROM scratch"),
+                ("build/app.Dockerfile.dockerignore", "target\n"),
+                ("docs/index.md", ""),
+                ("target/app", ""),
+            ],
+        );
+
+        let opts = |dockerfile: &str| TarballOptions {
+            dockerfile: dockerfile.to_owned(),
+            ..Default::default()
+        };
+        assert!(!entries(&dir, &opts("build/app.Dockerfile")).contains(&"target/app".to_owned()));
+        assert!(entries(&dir, &opts("build/app.Dockerfile")).contains(&"docs/index.md".to_owned()));
+        assert!(entries(&dir, &opts("Dockerfile")).contains(&"target/app".to_owned()));
+        assert!(!entries(&dir, &opts("Dockerfile")).contains(&"docs/index.md".to_owned()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_include_exclude() {
+        let dir = context_dir(
+            "include-exclude",
+            &[
+                ("Dockerfile", "FROM scratch"),
+                ("services/api/main.rs", ""),
+                ("services/api/fixtures/big.bin", ""),
+                ("services/web/main.rs", ""),
+                ("libs/core/lib.rs", ""),
+                ("docs/index.md", ""),
+            ],
+        );
+
+        assert_eq!(
+            entries(
+                &dir,
+                &TarballOptions {
+                    include: vec!["services/api".to_owned(), "libs".to_owned()],
+                    exclude: vec!["**/fixtures".to_owned()],
+                    ..Default::default()
+                }
+            ),
+            vec![
+                "Dockerfile",
+                "libs",
+                "libs/core",
+                "libs/core/lib.rs",
+                "services/api",
+                "services/api/main.rs"
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_entry_names() {
+        let dir = context_dir(
+            "entry-names",
+            &[("Dockerfile", ""), ("src/bin/main.rs", "")],
+        );
+        // paths written on Windows replace and add entries like `/`-separated ones
+        let mut overlay = BTreeMap::new();
+        overlay.insert("src\\bin\\main.rs".to_owned(), Vec::new());
+        overlay.insert(".\\src\\lib.rs".to_owned(), Vec::new());
+
+        let mut bytes = Vec::new();
+        dir_with_options(
+            &mut bytes,
+            dir.to_str().unwrap(),
+            &TarballOptions {
+                compression: Compression::None,
+                overlay,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut names: Vec<Vec<u8>> = tar::Archive::new(&bytes[..])
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path_bytes().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                b"Dockerfile".to_vec(),
+                b"src".to_vec(),
+                b"src/bin".to_vec(),
+                b"src/bin/main.rs".to_vec(),
+                b"src/lib.rs".to_vec()
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_overlay() {
+        let dir = context_dir(
+            "overlay",
+            &[
+                ("Dockerfile", "FROM scratch"),
+                ("config/app.toml", "debug = true"),
+            ],
+        );
+        let mut overlay = BTreeMap::new();
+        overlay.insert("./Dockerfile".to_owned(), b"FROM alpine".to_vec());
+        overlay.insert("config/app.toml".to_owned(), b"debug = false".to_vec());
+        overlay.insert("generated.txt".to_owned(), Vec::new());
+
+        let mut bytes = Vec::new();
+        dir_with_options(
+            &mut bytes,
+            dir.to_str().unwrap(),
+            &TarballOptions {
+                compression: Compression::None,
+                exclude: vec!["generated.txt".to_owned()],
+                overlay,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut files: Vec<(String, String)> = tar::Archive::new(&bytes[..])
+            .entries()
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|entry| entry.header().entry_type().is_file())
+            .map(|mut entry| {
+                let mut contents = String::new();
+                io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().into_owned(),
+                    contents,
+                )
+            })
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                ("Dockerfile".to_owned(), "FROM alpine".to_owned()),
+                ("config/app.toml".to_owned(), "debug = false".to_owned()),
+                ("generated.txt".to_owned(), String::new()),
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_max_bytes() {
+        let dir = context_dir(
+            "max-bytes",
+            &[
+                ("Dockerfile", "FROM scratch"),
+                ("small", "x"),
+                ("large", &"x".repeat(4096)),
+            ],
+        );
+        let opts = TarballOptions {
+            max_bytes: Some(4096),
+            ..Default::default()
+        };
+
+        let mut bytes = Vec::new();
+        let err = dir_with_options(&mut bytes, dir.to_str().unwrap(), &opts).unwrap_err();
+        let too_large = err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<ContextTooLarge>())
+            .unwrap();
+        assert_eq!(too_large.limit, 4096);
+        assert_eq!(too_large.largest_entries[0], (PathBuf::from("large"), 4096));
+
+        let opts = TarballOptions {
+            max_bytes: Some(16 * 1024),
+            ..Default::default()
+        };
+        assert_eq!(entries(&dir, &opts).len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tarball_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = context_dir("symlinks", &[("data/file", "contents")]);
+        symlink("data", dir.join("link")).unwrap();
+
+        let mut bytes = Vec::new();
+        dir_with_options(
+            &mut bytes,
+            dir.to_str().unwrap(),
+            &TarballOptions {
+                compression: Compression::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut archive = tar::Archive::new(&bytes[..]);
+        let link = archive
+            .entries()
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|entry| entry.path().unwrap() == Path::new("link"))
+            .unwrap();
+        assert!(link.header().entry_type().is_symlink());
+        assert_eq!(
+            link.link_name().unwrap().unwrap().as_ref(),
+            Path::new("data")
+        );
+
+        let follow = TarballOptions {
+            symlinks: Symlinks::Follow,
+            ..Default::default()
+        };
+        assert_eq!(
+            entries(&dir, &follow),
+            vec!["data", "data/file", "link", "link/file"]
+        );
+
+        symlink("..", dir.join("data/parent")).unwrap();
+        let mut bytes = Vec::new();
+        let err = dir_with_options(&mut bytes, dir.to_str().unwrap(), &follow).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tarball_hardlinks() {
+        let dir = context_dir("hardlinks", &[("a", "contents")]);
+        fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+
+        let mut bytes = Vec::new();
+        dir_with_options(
+            &mut bytes,
+            dir.to_str().unwrap(),
+            &TarballOptions {
+                compression: Compression::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut archive = tar::Archive::new(&bytes[..]);
+        let (links, files): (Vec<_>, Vec<_>) = archive
+            .entries()
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|entry| !entry.header().entry_type().is_dir())
+            .partition(|entry| entry.header().entry_type().is_hard_link());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].size(), 8);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].size(), 0);
+        assert_eq!(
+            links[0].link_name().unwrap().unwrap(),
+            files[0].path().unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tarball_metadata() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = context_dir("metadata", &[("run.sh", "#!/bin/sh"), ("data", "")]);
+        fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o750)).unwrap();
+        fs::set_permissions(dir.join("data"), fs::Permissions::from_mode(0o600)).unwrap();
+        let mtime = fs::metadata(dir.join("data")).unwrap().mtime() as u64;
+
+        let headers = |opts: TarballOptions| {
+            let mut bytes = Vec::new();
+            dir_with_options(
+                &mut bytes,
+                dir.to_str().unwrap(),
+                &TarballOptions {
+                    compression: Compression::None,
+                    ..opts
+                },
+            )
+            .unwrap();
+            let mut headers: Vec<(String, u32, u64, u64)> = tar::Archive::new(&bytes[..])
+                .entries()
+                .unwrap()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    let header = entry.header();
+                    (
+                        entry.path().unwrap().to_string_lossy().into_owned(),
+                        header.mode().unwrap() & 0o777,
+                        header.mtime().unwrap(),
+                        header.uid().unwrap(),
+                    )
+                })
+                .collect();
+            headers.sort();
+            headers
+        };
+
+        assert_eq!(
+            headers(TarballOptions::default()),
+            vec![
+                ("data".to_owned(), 0o600, mtime, 0),
+                ("run.sh".to_owned(), 0o750, mtime, 0)
+            ]
+        );
+        assert_eq!(
+            headers(TarballOptions {
+                normalize_permissions: true,
+                mtime: Some(1),
+                ..Default::default()
+            }),
+            vec![
+                ("data".to_owned(), 0o644, 1, 0),
+                ("run.sh".to_owned(), 0o755, 1, 0)
+            ]
+        );
+        let uid = fs::metadata(dir.join("data")).unwrap().uid() as u64;
+        assert!(headers(TarballOptions {
+            preserve_ownership: true,
+            ..Default::default()
+        })
+        .iter()
+        .all(|(_, _, _, entry_uid)| *entry_uid == uid));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_compression() {
+        let dir = context_dir(
+            "compression",
+            &[("Dockerfile", &"FROM scratch\n".repeat(100))],
+        );
+        let pack = |compression| {
+            let mut bytes = Vec::new();
+            dir_with_options(
+                &mut bytes,
+                dir.to_str().unwrap(),
+                &TarballOptions {
+                    compression,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            bytes
+        };
+
+        let plain = pack(Compression::None);
+        let gzip = pack(Compression::Gzip(1));
+        assert_eq!(&gzip[..2], &[0x1f, 0x8b]);
+        assert!(gzip.len() < plain.len());
+        let mut unpacked = Vec::new();
+        io::Read::read_to_end(&mut flate2::read::GzDecoder::new(&gzip[..]), &mut unpacked).unwrap();
+        assert_eq!(unpacked, plain);
+
+        #[cfg(feature = "zstd")]
+        {
+            let zstd = pack(Compression::Zstd(3));
+            assert_eq!(zstd::decode_all(&zstd[..]).unwrap(), plain);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_progress() {
+        use std::sync::Mutex;
+
+        let dir = context_dir(
+            "progress",
+            &[("Dockerfile", "FROM scratch"), ("src/main.rs", "")],
+        );
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let opts = TarballOptions {
+            progress: Some(ProgressCallback::new({
+                let reports = reports.clone();
+                move |progress| reports.lock().unwrap().push(progress)
+            })),
+            ..Default::default()
+        };
+
+        let mut bytes = Vec::new();
+        dir_with_options(&mut bytes, dir.to_str().unwrap(), &opts).unwrap();
+
+        let reports = reports.lock().unwrap();
+        let entries: Vec<_> = reports.iter().map(|progress| progress.entries).collect();
+        assert_eq!(entries, vec![1, 2, 3, 3]);
+        assert_eq!(reports.last().unwrap().bytes, bytes.len() as u64);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_dir_stream() {
+        let large = "x".repeat(3 * STREAM_CHUNK_SIZE);
+        let dir = context_dir(
+            "stream",
+            &[("Dockerfile", "FROM scratch"), ("data", &large)],
+        );
+        let opts = TarballOptions {
+            compression: Compression::None,
+            ..Default::default()
+        };
+
+        let mut expected = Vec::new();
+        dir_with_options(&mut expected, dir.to_str().unwrap(), &opts).unwrap();
+        let chunks: Vec<Bytes> =
+            futures::executor::block_on(dir_stream(dir.to_str().unwrap(), &opts).try_collect())
+                .unwrap();
+
+        assert!(chunks.len() > 3);
+        assert_eq!(chunks.concat(), expected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tarball_dir_stream_error() {
+        let stream = dir_stream("/nonexistent/shiplift/context", &TarballOptions::default());
+        let result: io::Result<Vec<Bytes>> = futures::executor::block_on(stream.try_collect());
+
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,342 @@
+//! Parsing and matching of `.dockerignore` files, following the rules `docker build` applies.
+//!
+//! Reference: <https://docs.docker.com/engine/reference/builder/#dockerignore-file>
+
+use std::{
+    fs, io,
+    path::{Component, Path},
+};
+
+/// File name of the ignore file in the root of a build context
+pub(crate) const DOCKERIGNORE: &str = ".dockerignore";
+
+/// An ordered list of patterns in which later patterns override earlier ones, as in a
+/// `.dockerignore` file
+#[derive(Debug, Default)]
+pub(crate) struct Patterns {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    segments: Vec<String>,
+    negated: bool,
+}
+
+impl Patterns {
+    /// Reads the ignore file of the Dockerfile at `dockerfile` relative to `dir`, if there is
+    /// one, and the `.dockerignore` file in `dir` otherwise
+    ///
+    /// As with BuildKit, an ignore file next to the Dockerfile and named after it, like
+    /// `app.Dockerfile.dockerignore`, takes precedence over the one in the root of the context.
+    pub(crate) fn from_dockerignore(
+        dir: &Path,
+        dockerfile: &Path,
+    ) -> io::Result<Self> {
+        let mut dockerfile_ignore = dockerfile.as_os_str().to_owned();
+        dockerfile_ignore.push(DOCKERIGNORE);
+        for path in [dir.join(dockerfile_ignore), dir.join(DOCKERIGNORE)] {
+            match fs::read_to_string(path) {
+                Ok(contents) => return Ok(Self::parse(&contents)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Parses the contents of an ignore file
+    pub(crate) fn parse(contents: &str) -> Self {
+        Self::new(
+            contents
+                .trim_start_matches('\u{feff}')
+                .lines()
+                .filter(|line| !line.trim().starts_with('#')),
+        )
+    }
+
+    /// Parses a list of patterns. Patterns starting with `!` negate earlier ones.
+    pub(crate) fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .filter_map(|line| {
+                let line = line.as_ref().trim();
+                let (negated, pattern) = match line.strip_prefix('!') {
+                    Some(pattern) => (true, pattern.trim()),
+                    None => (false, line),
+                };
+                if pattern.is_empty() {
+                    return None;
+                }
+                Some(Pattern {
+                    segments: clean(pattern),
+                    negated,
+                })
+            })
+            .collect();
+
+        Patterns { patterns }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the entry at `path`, relative to the root of the context, is matched
+    ///
+    /// As with `docker build`, an entry is matched if the last pattern matching it or one of its
+    /// parent directories is not negated.
+    pub(crate) fn matches(
+        &self,
+        path: &Path,
+    ) -> bool {
+        let path = components(path);
+        let mut matched = false;
+        for pattern in &self.patterns {
+            // only patterns that could flip the current outcome need to be evaluated
+            if pattern.negated != matched {
+                continue;
+            }
+            if (1..=path.len()).any(|len| match_segments(&pattern.segments, &path[..len])) {
+                matched = !pattern.negated;
+            }
+        }
+        matched
+    }
+
+    /// Whether some entry below the matched directory at `path` may not be matched, because a
+    /// negated pattern could apply to it
+    pub(crate) fn may_unmatch_below(
+        &self,
+        path: &Path,
+    ) -> bool {
+        let path = components(path);
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.negated && match_prefix(&pattern.segments, &path))
+    }
+
+    /// Whether some entry below the unmatched directory at `path` may be matched
+    pub(crate) fn may_match_below(
+        &self,
+        path: &Path,
+    ) -> bool {
+        let path = components(path);
+        self.patterns
+            .iter()
+            .any(|pattern| !pattern.negated && match_prefix(&pattern.segments, &path))
+    }
+}
+
+/// Whether `path` matches the leading segments of `pattern`, meaning that entries below `path`
+/// could match the whole pattern
+fn match_prefix(
+    pattern: &[String],
+    path: &[String],
+) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some((first, _)), Some(_)) if first == "**" => true,
+        (Some((first, rest)), Some((name, path))) => {
+            let pattern: Vec<char> = first.chars().collect();
+            let name: Vec<char> = name.chars().collect();
+            match_name(&pattern, &name) && match_prefix(rest, path)
+        }
+    }
+}
+
+fn match_segments(
+    pattern: &[String],
+    path: &[String],
+) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_name(&pattern, &name) && match_segments(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches a single path segment against a pattern supporting `*`, `?`, `[...]` character classes
+/// and `\` escapes
+fn match_name(
+    pattern: &[char],
+    name: &[char],
+) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_name(rest, &name[1..]),
+        Some(('[', rest)) => match (name.split_first(), match_class(rest)) {
+            (Some((c, name)), Some((class, rest))) => class.matches(*c) && match_name(rest, name),
+            // an unterminated class matches a literal `[`
+            (Some(('[', name)), None) => match_name(rest, name),
+            _ => false,
+        },
+        Some(('\\', rest)) if !rest.is_empty() => {
+            name.first() == Some(&rest[0]) && match_name(&rest[1..], &name[1..])
+        }
+        Some((c, rest)) => name.first() == Some(c) && match_name(rest, &name[1..]),
+    }
+}
+
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn matches(
+        &self,
+        c: char,
+    ) -> bool {
+        self.ranges
+            .iter()
+            .any(|(low, high)| *low <= c && c <= *high)
+            != self.negated
+    }
+}
+
+/// Parses a character class following an opening `[`, returning it along with the rest of the
+/// pattern
+fn match_class(pattern: &[char]) -> Option<(CharClass, &[char])> {
+    let (negated, mut rest) = match pattern.split_first() {
+        Some(('^', rest)) | Some(('!', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut ranges = Vec::new();
+    loop {
+        let (low, after) = class_char(rest)?;
+        if low == ']' && rest[0] == ']' && !ranges.is_empty() {
+            return Some((CharClass { negated, ranges }, after));
+        }
+        rest = after;
+        let high = match rest {
+            ['-', tail @ ..] if tail.first() != Some(&']') => {
+                let (high, after) = class_char(tail)?;
+                rest = after;
+                high
+            }
+            _ => low,
+        };
+        ranges.push((low, high));
+    }
+}
+
+fn class_char(pattern: &[char]) -> Option<(char, &[char])> {
+    match pattern {
+        ['\\', c, rest @ ..] => Some((*c, rest)),
+        [c, rest @ ..] => Some((*c, rest)),
+        [] => None,
+    }
+}
+
+/// Splits a pattern into its segments the way `filepath.Clean` would normalize it, dropping
+/// empty and `.` segments, resolving `..` and ignoring a leading `/`
+fn clean(pattern: &str) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    for segment in pattern.split(['/', std::path::MAIN_SEPARATOR]) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment.to_owned()),
+        }
+    }
+    segments
+}
+
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(
+        patterns: &Patterns,
+        path: &str,
+    ) -> bool {
+        patterns.matches(Path::new(path))
+    }
+
+    #[test]
+    fn dockerignore_patterns() {
+        let ignore = Patterns::parse(
+            "# comment\n\
+             .git\n\
+             target/\n\
+             *.md\n\
+             !README.md\n\
+             **/*.log\n\
+             /tmp?\n\
+             [a-c]*.bak\n",
+        );
+
+        assert!(matches(&ignore, ".git"));
+        assert!(matches(&ignore, ".git/HEAD"));
+        assert!(matches(&ignore, "target/debug/app"));
+        assert!(matches(&ignore, "CHANGELOG.md"));
+        assert!(!matches(&ignore, "README.md"));
+        assert!(!matches(&ignore, "docs/guide.md"));
+        assert!(matches(&ignore, "app.log"));
+        assert!(matches(&ignore, "logs/deep/app.log"));
+        assert!(matches(&ignore, "tmp1/file"));
+        assert!(!matches(&ignore, "tmp12"));
+        assert!(matches(&ignore, "b.bak"));
+        assert!(!matches(&ignore, "d.bak"));
+        assert!(!matches(&ignore, "src/main.rs"));
+    }
+
+    #[test]
+    fn dockerignore_negation_order() {
+        let ignore = Patterns::parse("docs\n!docs/public\ndocs/public/draft*\n");
+
+        assert!(matches(&ignore, "docs/internal.txt"));
+        assert!(!matches(&ignore, "docs/public/index.html"));
+        assert!(matches(&ignore, "docs/public/draft-1.html"));
+        assert!(ignore.may_unmatch_below(Path::new("docs")));
+        assert!(!ignore.may_unmatch_below(Path::new("src")));
+    }
+
+    #[test]
+    fn dockerignore_char_classes() {
+        let ignore = Patterns::parse("[!a]?.txt\n\\*.lit\n");
+
+        assert!(matches(&ignore, "bc.txt"));
+        assert!(!matches(&ignore, "ac.txt"));
+        assert!(matches(&ignore, "*.lit"));
+        assert!(!matches(&ignore, "x.lit"));
+    }
+
+    #[test]
+    fn patterns_may_match_below() {
+        let include = Patterns::new(["services/api", "libs/**/*.rs"]);
+
+        assert!(include.may_match_below(Path::new("services")));
+        assert!(!include.may_match_below(Path::new("services/web")));
+        assert!(include.may_match_below(Path::new("libs/core/src")));
+        assert!(!include.may_match_below(Path::new("docs")));
+    }
+}
@@ -0,0 +1,340 @@
+//! Inspect the tasks that make up swarm services.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Task>
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    docker::Docker,
+    errors::Result,
+    query::{self, Filters},
+    service::{ObjectVersion, TaskSpec},
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
+
+/// Interface for docker swarm tasks
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Task>
+pub struct Tasks<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Tasks<'docker> {
+    /// Exports an interface for interacting with docker swarm tasks
+    pub fn new(docker: &'docker Docker) -> Self {
+        Tasks { docker }
+    }
+
+    /// Lists the tasks in the swarm
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/TaskList>
+    pub async fn list(
+        &self,
+        opts: &TaskListOptions,
+    ) -> Result<Vec<TaskInfo>> {
+        let mut path = vec!["/tasks".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
+        self.docker.get_json(&path.join("?")).await
+    }
+
+    /// Returns a reference to a set of operations available for a task
+    pub fn get(
+        &self,
+        id: &str,
+    ) -> Task<'docker> {
+        Task::new(self.docker, id)
+    }
+}
+
+/// Interface for accessing a docker swarm task
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Task>
+pub struct Task<'docker> {
+    docker: &'docker Docker,
+    id: String,
+}
+
+impl<'docker> Task<'docker> {
+    /// Exports an interface for operations that may be performed against a task
+    pub fn new<S>(
+        docker: &'docker Docker,
+        id: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Task {
+            docker,
+            id: id.into(),
+        }
+    }
+
+    /// Inspects a task
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/TaskInspect>
+    pub async fn inspect(&self) -> Result<TaskInfo> {
+        self.docker
+            .get_json(&format!("/tasks/{}", self.id)[..])
+            .await
+    }
+}
+
+/// Options for filtering task list results
+#[derive(Default, Debug, Serialize)]
+pub struct TaskListOptions {
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
+}
+
+impl TaskListOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> TaskListOptionsBuilder {
+        TaskListOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for task listings
+pub enum TaskFilter {
+    Id(String),
+    Name(String),
+    /// Tasks belonging to a service, given by ID or name
+    Service(String),
+    /// Tasks scheduled on a node, given by ID or name
+    Node(String),
+    DesiredState(TaskState),
+    /// Tasks carrying a label, given either as `key` or `key=value`
+    Label(String),
+}
+
+/// Builder interface for `TaskListOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct TaskListOptionsBuilder {
+    options: TaskListOptions,
+}
+
+impl TaskListOptionsBuilder {
+    pub fn filter(
+        mut self,
+        filters: Vec<TaskFilter>,
+    ) -> Self {
+        self.options.filters = filters
+            .into_iter()
+            .map(|f| match f {
+                TaskFilter::Id(i) => ("id", i),
+                TaskFilter::Name(n) => ("name", n),
+                TaskFilter::Service(s) => ("service", s),
+                TaskFilter::Node(n) => ("node", n),
+                TaskFilter::DesiredState(s) => ("desired-state", s.as_ref().to_owned()),
+                TaskFilter::Label(l) => ("label", l),
+            })
+            .collect();
+        self
+    }
+
+    pub fn build(self) -> TaskListOptions {
+        self.options
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct TaskInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub updated_at: String,
+    pub name: Option<String>,
+    pub labels: Option<HashMap<String, String>>,
+    pub spec: TaskSpec,
+    #[serde(rename = "ServiceID")]
+    pub service_id: String,
+    /// Only set for tasks of replicated services
+    pub slot: Option<u64>,
+    /// Not set until the task has been assigned to a node
+    #[serde(rename = "NodeID")]
+    pub node_id: Option<String>,
+    pub status: TaskStatus,
+    pub desired_state: TaskState,
+    /// Fields of the task that aren't modelled, like the `JobIteration` of job services
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct TaskStatus {
+    #[cfg(feature = "chrono")]
+    pub timestamp: DateTime<Utc>,
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    pub timestamp: String,
+    pub state: TaskState,
+    pub message: Option<String>,
+    /// Error message, only set for failed or rejected tasks
+    pub err: Option<String>,
+    /// Not set until a container has been created for the task
+    pub container_status: Option<TaskContainerStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
+pub struct TaskContainerStatus {
+    #[serde(rename = "ContainerID")]
+    pub container_id: Option<String>,
+    #[serde(rename = "PID")]
+    pub pid: Option<i64>,
+    pub exit_code: Option<i64>,
+}
+
+/// Lifecycle state of a task
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    New,
+    Allocated,
+    Pending,
+    Assigned,
+    Accepted,
+    Preparing,
+    Ready,
+    Starting,
+    Running,
+    Complete,
+    Shutdown,
+    Failed,
+    Rejected,
+    Remove,
+    Orphaned,
+}
+
+impl TaskState {
+    /// Whether the task has stopped for good, so that it never runs again
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            TaskState::Complete
+                | TaskState::Shutdown
+                | TaskState::Failed
+                | TaskState::Rejected
+                | TaskState::Remove
+                | TaskState::Orphaned
+        )
+    }
+}
+
+impl AsRef<str> for TaskState {
+    fn as_ref(&self) -> &str {
+        match self {
+            TaskState::New => "new",
+            TaskState::Allocated => "allocated",
+            TaskState::Pending => "pending",
+            TaskState::Assigned => "assigned",
+            TaskState::Accepted => "accepted",
+            TaskState::Preparing => "preparing",
+            TaskState::Ready => "ready",
+            TaskState::Starting => "starting",
+            TaskState::Running => "running",
+            TaskState::Complete => "complete",
+            TaskState::Shutdown => "shutdown",
+            TaskState::Failed => "failed",
+            TaskState::Rejected => "rejected",
+            TaskState::Remove => "remove",
+            TaskState::Orphaned => "orphaned",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::form_urlencoded;
+
+    #[test]
+    fn task_list_options_filters() {
+        let options = TaskListOptions::builder()
+            .filter(vec![
+                TaskFilter::Service("web".to_owned()),
+                TaskFilter::DesiredState(TaskState::Running),
+            ])
+            .build();
+        let serialized = options.serialize().unwrap().unwrap();
+        let filters: HashMap<String, Vec<String>> = serde_json::from_str(
+            &form_urlencoded::parse(serialized.as_bytes())
+                .find(|(key, _)| key == "filters")
+                .unwrap()
+                .1,
+        )
+        .unwrap();
+
+        assert_eq!(filters["service"], vec!["web"]);
+        assert_eq!(filters["desired-state"], vec!["running"]);
+    }
+
+    #[test]
+    fn task_info_failed_task() {
+        let task: TaskInfo = serde_json::from_str(
+            r#"{
+                "ID": "0kzzo1i0y4jz6027t0k7aezc7",
+                "Version": {"Index": 71},
+                "CreatedAt": "2016-06-07T21:07:31.171892745Z",
+                "UpdatedAt": "2016-06-07T21:07:31.376370513Z",
+                "Spec": {},
+                "ServiceID": "9mnpnzenvg8p8tdbtq4wvbkcz",
+                "Slot": 1,
+                "NodeID": "60gvrl6tm78dmak4yl7srz94v",
+                "Status": {
+                    "Timestamp": "2016-06-07T21:07:31.290032978Z",
+                    "State": "failed",
+                    "Message": "started",
+                    "Err": "task: non-zero exit (1)",
+                    "ContainerStatus": {
+                        "ContainerID": "e5d62702a1b48d01c3e02ca1e0212a250801fa8d67caca0b6f35919ebc12f035",
+                        "PID": 0,
+                        "ExitCode": 1
+                    }
+                },
+                "DesiredState": "shutdown"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(task.status.state, TaskState::Failed);
+        assert!(task.status.state.is_terminal());
+        assert!(!TaskState::Starting.is_terminal());
+        assert_eq!(task.desired_state, TaskState::Shutdown);
+        assert_eq!(task.status.container_status.unwrap().exit_code, Some(1));
+    }
+}
@@ -1,5 +1,7 @@
 //! Transports for communicating with the docker daemon
 
+#[cfg(any(test, feature = "test-util"))]
+use crate::mock::MockDaemon;
 use crate::{Error, Result};
 use futures_util::{
     io::{AsyncRead, AsyncWrite},
@@ -9,7 +11,7 @@ use futures_util::{
 use hyper::{
     body::Bytes,
     client::{Client, HttpConnector},
-    header, Body, Method, Request, StatusCode,
+    header, Body, Method, Request, StatusCode, Uri,
 };
 #[cfg(feature = "tls")]
 use hyper_openssl::HttpsConnector;
@@ -54,6 +56,9 @@ pub enum Transport {
         client: Client<UnixConnector>,
         path: String,
     },
+    /// An in-process daemon answering with canned responses
+    #[cfg(any(test, feature = "test-util"))]
+    Mock { daemon: MockDaemon },
 }
 
 impl fmt::Debug for Transport {
@@ -67,11 +72,27 @@ impl fmt::Debug for Transport {
             Transport::EncryptedTcp { ref host, .. } => write!(f, "EncryptedTcp({})", host),
             #[cfg(feature = "unix-socket")]
             Transport::Unix { ref path, .. } => write!(f, "Unix({})", path),
+            #[cfg(any(test, feature = "test-util"))]
+            Transport::Mock { .. } => write!(f, "Mock"),
         }
     }
 }
 
 impl Transport {
+    /// The host name of a daemon that is reached over the network, `None` for a local daemon
+    pub(crate) fn remote_host(&self) -> Option<String> {
+        let host = match *self {
+            Transport::Tcp { ref host, .. } => host,
+            #[cfg(feature = "tls")]
+            Transport::EncryptedTcp { ref host, .. } => host,
+            #[cfg(feature = "unix-socket")]
+            Transport::Unix { .. } => return None,
+            #[cfg(any(test, feature = "test-util"))]
+            Transport::Mock { .. } => return None,
+        };
+        host.parse::<Uri>().ok()?.host().map(str::to_owned)
+    }
+
     /// Make a request and return the whole response in a `String`
     pub async fn request<B, H>(
         &self,
@@ -84,13 +105,28 @@ impl Transport {
         B: Into<Body>,
         H: IntoIterator<Item = (&'static str, String)>,
     {
-        let body = self.get_body(method, endpoint, body, headers).await?;
-        let bytes = hyper::body::to_bytes(body).await?;
+        let bytes = self.request_bytes(method, endpoint, body, headers).await?;
         let string = String::from_utf8(bytes.to_vec())?;
 
         Ok(string)
     }
 
+    /// Make a request and return the whole response in `Bytes`
+    pub async fn request_bytes<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<Bytes>
+    where
+        B: Into<Body>,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let body = self.get_body(method, endpoint, body, headers).await?;
+        Ok(hyper::body::to_bytes(body).await?)
+    }
+
     async fn get_body<B, H>(
         &self,
         method: Method,
@@ -102,8 +138,9 @@ impl Transport {
         B: Into<Body>,
         H: IntoIterator<Item = (&'static str, String)>,
     {
+        let path = endpoint.as_ref().to_owned();
         let req = self
-            .build_request(method, endpoint, body, headers, Request::builder())
+            .build_request(method.clone(), endpoint, body, headers, Request::builder())
             .expect("Failed to build request!");
 
         let response = self.send_request(req).await?;
@@ -120,15 +157,17 @@ impl Transport {
                 let bytes = hyper::body::to_bytes(response.into_body()).await?;
                 let message_body = String::from_utf8(bytes.to_vec())?;
 
-                Err(Error::Fault {
-                    code: status,
-                    message: Self::get_error_message(&message_body).unwrap_or_else(|| {
+                Err(Error::from_status(
+                    status,
+                    Self::get_error_message(&message_body).unwrap_or_else(|| {
                         status
                             .canonical_reason()
                             .unwrap_or("unknown error code")
                             .to_owned()
                     }),
-                })
+                    method,
+                    path,
+                ))
             }
         }
     }
@@ -191,9 +230,13 @@ impl Transport {
             }
             #[cfg(feature = "unix-socket")]
             Transport::Unix { ref path, .. } => {
-                let uri = DomainUri::new(&path, endpoint.as_ref());
+                let uri = DomainUri::new(path, endpoint.as_ref());
                 builder.method(method).uri(uri)
             }
+            #[cfg(any(test, feature = "test-util"))]
+            Transport::Mock { .. } => builder
+                .method(method)
+                .uri(&format!("http://localhost{}", endpoint.as_ref())),
         };
         let mut req = req.header(header::HOST, "");
 
@@ -222,6 +265,8 @@ impl Transport {
             Transport::EncryptedTcp { ref client, .. } => Ok(client.request(req).await?),
             #[cfg(feature = "unix-socket")]
             Transport::Unix { ref client, .. } => Ok(client.request(req).await?),
+            #[cfg(any(test, feature = "test-util"))]
+            Transport::Mock { ref daemon } => daemon.handle(req).await,
         }
     }
 
@@ -230,47 +275,67 @@ impl Transport {
     ///
     /// This method can be used for operations such as viewing
     /// docker container logs interactively.
-    async fn stream_upgrade_tokio<B>(
+    pub async fn stream_upgrade<B>(
         &self,
         method: Method,
         endpoint: impl AsRef<str>,
         body: Option<(B, Mime)>,
-    ) -> Result<hyper::upgrade::Upgraded>
+    ) -> Result<Upgraded>
     where
         B: Into<Body>,
     {
-        let req = self
-            .build_request(
-                method,
-                endpoint,
-                body,
-                None::<iter::Empty<_>>,
-                Request::builder()
-                    .header(header::CONNECTION, "Upgrade")
-                    .header(header::UPGRADE, "tcp"),
-            )
-            .expect("Failed to build request!");
-
-        let response = self.send_request(req).await?;
-
-        match response.status() {
-            StatusCode::SWITCHING_PROTOCOLS => Ok(hyper::upgrade::on(response).await?),
-            _ => Err(Error::ConnectionNotUpgraded),
-        }
+        self.upgrade(method, endpoint, body, None::<iter::Empty<_>>, "tcp")
+            .await
     }
 
-    pub async fn stream_upgrade<B>(
+    /// Makes an HTTP request asking to switch the connection to `protocol`, and returns the
+    /// connection once the daemon switched it
+    ///
+    /// Responses with an error status fail with the error of the daemon, other responses that
+    /// do not switch the connection with [Error::ConnectionNotUpgraded].
+    pub async fn upgrade<B, H>(
         &self,
         method: Method,
         endpoint: impl AsRef<str>,
         body: Option<(B, Mime)>,
-    ) -> Result<impl AsyncRead + AsyncWrite>
+        headers: Option<H>,
+        protocol: &str,
+    ) -> Result<Upgraded>
     where
         B: Into<Body>,
+        H: IntoIterator<Item = (&'static str, String)>,
     {
-        let tokio_multiplexer = self.stream_upgrade_tokio(method, endpoint, body).await?;
+        let path = endpoint.as_ref().to_owned();
+        let req = self.build_request(
+            method.clone(),
+            endpoint,
+            body,
+            headers,
+            Request::builder()
+                .header(header::CONNECTION, "Upgrade")
+                .header(header::UPGRADE, protocol),
+        )?;
 
-        Ok(Compat { tokio_multiplexer })
+        let response = self.send_request(req).await?;
+
+        let status = response.status();
+        match status {
+            StatusCode::SWITCHING_PROTOCOLS => Ok(Upgraded {
+                connection: hyper::upgrade::on(response).await?,
+            }),
+            _ if status.is_client_error() || status.is_server_error() => {
+                let bytes = hyper::body::to_bytes(response.into_body()).await?;
+                let message = Self::get_error_message(&String::from_utf8_lossy(&bytes))
+                    .unwrap_or_else(|| {
+                        status
+                            .canonical_reason()
+                            .unwrap_or("unknown error code")
+                            .to_owned()
+                    });
+                Err(Error::from_status(status, message, method, path))
+            }
+            _ => Err(Error::ConnectionNotUpgraded),
+        }
     }
 
     /// Extract the error message content from an HTTP response that
@@ -282,23 +347,38 @@ impl Transport {
     }
 }
 
+/// A connection the daemon switched from HTTP to a raw stream of bytes in both directions, like
+/// the connection of an attached container
 #[pin_project]
-struct Compat<S> {
+pub struct Upgraded {
     #[pin]
-    tokio_multiplexer: S,
+    connection: hyper::upgrade::Upgraded,
+}
+
+impl Upgraded {
+    /// The underlying connection, which implements the IO traits of tokio
+    pub fn into_inner(self) -> hyper::upgrade::Upgraded {
+        self.connection
+    }
+}
+
+impl fmt::Debug for Upgraded {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str("Upgraded")
+    }
 }
 
-impl<S> AsyncRead for Compat<S>
-where
-    S: tokio::io::AsyncRead,
-{
+impl AsyncRead for Upgraded {
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
         let mut readbuf = tokio::io::ReadBuf::new(buf);
-        match self.project().tokio_multiplexer.poll_read(cx, &mut readbuf) {
+        match tokio::io::AsyncRead::poll_read(self.project().connection, cx, &mut readbuf) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Ok(())) => Poll::Ready(Ok(readbuf.filled().len())),
             Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
@@ -306,28 +386,25 @@ where
     }
 }
 
-impl<S> AsyncWrite for Compat<S>
-where
-    S: tokio::io::AsyncWrite,
-{
+impl AsyncWrite for Upgraded {
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        self.project().tokio_multiplexer.poll_write(cx, buf)
+        tokio::io::AsyncWrite::poll_write(self.project().connection, cx, buf)
     }
     fn poll_flush(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        self.project().tokio_multiplexer.poll_flush(cx)
+        tokio::io::AsyncWrite::poll_flush(self.project().connection, cx)
     }
     fn poll_close(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<()>> {
-        self.project().tokio_multiplexer.poll_shutdown(cx)
+        tokio::io::AsyncWrite::poll_shutdown(self.project().connection, cx)
     }
 }
 
@@ -49,15 +49,19 @@ impl std::ops::DerefMut for TtyChunk {
     }
 }
 
-async fn decode_chunk<S>(mut stream: S) -> Option<(Result<TtyChunk>, S)>
+/// Reads the next frame of a multiplexed stream, each an 8 byte header with the stream the
+/// frame belongs to and its length, followed by its payload
+///
+/// Returns `None` once the stream ended between frames.
+async fn read_frame<S>(stream: &mut S) -> Result<Option<TtyChunk>>
 where
     S: AsyncRead + Unpin,
 {
     let mut header_bytes = [0u8; 8];
 
     match stream.read_exact(&mut header_bytes).await {
-        Err(e) if e.kind() == futures_util::io::ErrorKind::UnexpectedEof => return None,
-        Err(e) => return Some((Err(Error::IO(e)), stream)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::IO(e)),
         _ => (),
     }
 
@@ -65,19 +69,33 @@ where
     let data_length = u32::from_be_bytes(size_bytes.try_into().unwrap());
 
     let mut data = vec![0u8; data_length as usize];
-
-    if stream.read_exact(&mut data).await.is_err() {
-        return None;
+    stream.read_exact(&mut data).await.map_err(Error::IO)?;
+
+    match header_bytes[0] {
+        0 => Ok(Some(TtyChunk::StdIn(data))),
+        1 => Ok(Some(TtyChunk::StdOut(data))),
+        2 => Ok(Some(TtyChunk::StdErr(data))),
+        n => Err(Error::InvalidResponse(format!(
+            "invalid stream number from docker daemon: '{}'",
+            n
+        ))),
     }
+}
 
-    let chunk = match header_bytes[0] {
-        0 => TtyChunk::StdIn(data),
-        1 => TtyChunk::StdOut(data),
-        2 => TtyChunk::StdErr(data),
-        n => panic!("invalid stream number from docker daemon: '{}'", n),
-    };
-
-    Some((Ok(chunk), stream))
+/// Decodes the frames of a multiplexed stream, ending the stream after the first error since
+/// the frames that follow can't be told apart anymore
+fn frames<S>(stream: S) -> impl Stream<Item = Result<TtyChunk>>
+where
+    S: AsyncRead + Unpin,
+{
+    futures_util::stream::unfold(Some(stream), |stream| async move {
+        let mut stream = stream?;
+        match read_frame(&mut stream).await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(stream))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
 }
 
 pub(crate) fn decode<S>(hyper_chunk_stream: S) -> impl Stream<Item = Result<TtyChunk>>
@@ -88,7 +106,16 @@ where
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
         .into_async_read();
 
-    futures_util::stream::unfold(stream, decode_chunk)
+    frames(stream)
+}
+
+/// The output of a container with a TTY, which is not framed since the TTY merges stdout and
+/// stderr. All output is returned as [TtyChunk::StdOut].
+pub(crate) fn raw<S>(hyper_chunk_stream: S) -> impl Stream<Item = Result<TtyChunk>>
+where
+    S: Stream<Item = Result<hyper::body::Bytes>>,
+{
+    hyper_chunk_stream.map_ok(|bytes| TtyChunk::StdOut(bytes.to_vec()))
 }
 
 type TtyReader<'a> = Pin<Box<dyn Stream<Item = Result<TtyChunk>> + Send + 'a>>;
@@ -113,9 +140,25 @@ impl<'a> Multiplexer<'a> {
         let (reader, writer) = tcp_connection.split();
 
         Self {
-            reader: Box::pin(futures_util::stream::unfold(reader, |reader| {
-                decode_chunk(reader)
-            })),
+            reader: Box::pin(frames(reader)),
+            writer: Box::pin(writer),
+        }
+    }
+
+    /// A multiplexer for the connection of a container with a TTY, whose output is not framed
+    /// since the TTY merges stdout and stderr. All output is returned as [TtyChunk::StdOut].
+    pub(crate) fn raw<T>(tcp_connection: T) -> Self
+    where
+        T: AsyncRead + AsyncWrite + Send + 'a,
+    {
+        let (reader, writer) = tcp_connection.split();
+
+        Self {
+            reader: Box::pin(
+                futures_codec::FramedRead::new(reader, futures_codec::BytesCodec {})
+                    .map_ok(|bytes| TtyChunk::StdOut(bytes.to_vec()))
+                    .map_err(Error::IO),
+            ),
             writer: Box::pin(writer),
         }
     }
@@ -169,3 +212,23 @@ impl<'a> Multiplexer<'a> {
         (self.reader, self.writer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{io::Cursor, stream::StreamExt};
+
+    #[test]
+    fn multiplexer_framing() {
+        let mut framed = vec![2, 0, 0, 0, 0, 0, 0, 4];
+        framed.extend_from_slice(b"oops");
+        let chunks: Vec<_> =
+            futures::executor::block_on(Multiplexer::new(Cursor::new(framed)).collect());
+        assert!(matches!(&chunks[..], [Ok(TtyChunk::StdErr(err))] if err == b"oops"));
+
+        let chunks: Vec<_> = futures::executor::block_on(
+            Multiplexer::raw(Cursor::new(b"$ ls\r\n".to_vec())).collect(),
+        );
+        assert!(matches!(&chunks[..], [Ok(TtyChunk::StdOut(out))] if out == b"$ ls\r\n"));
+    }
+}
@@ -13,11 +13,14 @@ use serde_json::{json, Value};
 
 use crate::{
     errors::{Error, Result},
+    query::{self, Filters},
     Docker,
 };
 
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
 
 /// Interface for docker volumes
 ///
@@ -40,7 +43,7 @@ impl<'docker> Volumes<'docker> {
         opts: &VolumeCreateOptions,
     ) -> Result<VolumeCreateInfo> {
         let body: Body = opts.serialize()?.into();
-        let path = vec!["/volumes/create".to_owned()];
+        let path = ["/volumes/create".to_owned()];
 
         self.docker
             .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
@@ -51,13 +54,23 @@ impl<'docker> Volumes<'docker> {
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeList>
     pub async fn list(&self) -> Result<Vec<VolumeInfo>> {
-        let path = vec!["/volumes".to_owned()];
+        self.list_with(&VolumeListOptions::default()).await
+    }
+
+    /// Lists the docker volumes on the current docker host that match the filters of `opts`
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeList>
+    pub async fn list_with(
+        &self,
+        opts: &VolumeListOptions,
+    ) -> Result<Vec<VolumeInfo>> {
+        let mut path = vec!["/volumes".to_owned()];
+        if let Some(query) = opts.serialize()? {
+            path.push(query);
+        }
 
         let volumes_rep = self.docker.get_json::<VolumesInfo>(&path.join("?")).await?;
-        Ok(match volumes_rep.volumes {
-            Some(volumes) => volumes,
-            None => vec![],
-        })
+        Ok(volumes_rep.volumes.unwrap_or_default())
     }
 
     /// Returns a reference to a set of operations available for a named volume
@@ -103,6 +116,68 @@ impl<'docker> Volume<'docker> {
     }
 }
 
+/// Options for filtering volume list results
+#[derive(Default, Debug, Serialize)]
+pub struct VolumeListOptions {
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    filters: Filters,
+}
+
+impl VolumeListOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> VolumeListOptionsBuilder {
+        VolumeListOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Result<Option<String>> {
+        query::encode(self)
+    }
+}
+
+/// Filter options for volume listings
+pub enum VolumeFilter {
+    /// Volumes not referenced by any container
+    Dangling(bool),
+    Driver(String),
+    LabelName(String),
+    Label(String, String),
+    Name(String),
+}
+
+impl query::Filter for VolumeFilter {
+    fn query(self) -> (&'static str, String) {
+        match self {
+            VolumeFilter::Dangling(d) => ("dangling", d.to_string()),
+            VolumeFilter::Driver(d) => ("driver", d),
+            VolumeFilter::LabelName(n) => ("label", n),
+            VolumeFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
+            VolumeFilter::Name(n) => ("name", n),
+        }
+    }
+}
+
+/// Builder interface for `VolumeListOptions`
+#[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
+pub struct VolumeListOptionsBuilder {
+    options: VolumeListOptions,
+}
+
+impl VolumeListOptionsBuilder {
+    pub fn filter(
+        mut self,
+        filters: Vec<VolumeFilter>,
+    ) -> Self {
+        self.options.filters = Filters::typed(filters);
+        self
+    }
+
+    pub fn build(self) -> VolumeListOptions {
+        self.options
+    }
+}
+
 /// Interface for creating volumes
 #[derive(Serialize, Debug)]
 pub struct VolumeCreateOptions {
@@ -119,17 +194,19 @@ impl VolumeCreateOptions {
         &self,
         params: &'a HashMap<K, V>,
         body: &mut BTreeMap<String, Value>,
-    ) where
+    ) -> Result<()>
+    where
         &'a HashMap<K, V>: IntoIterator,
         K: ToString + Eq + Hash,
         V: Serialize,
     {
         for (k, v) in params.iter() {
             let key = k.to_string();
-            let value = serde_json::to_value(v).unwrap();
+            let value = serde_json::to_value(v)?;
 
             body.insert(key, value);
         }
+        Ok(())
     }
 
     /// return a new instance of a builder for options
@@ -139,6 +216,7 @@ impl VolumeCreateOptions {
 }
 
 #[derive(Default)]
+#[must_use = "builder methods take the builder by value and return the updated builder"]
 pub struct VolumeCreateOptionsBuilder {
     params: HashMap<&'static str, Value>,
 }
@@ -150,10 +228,10 @@ impl VolumeCreateOptionsBuilder {
     }
 
     pub fn driver(
-        &mut self,
+        mut self,
         driver_name: &str,
         driver_opts: Option<&HashMap<&str, &str>>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Driver", json!(driver_name));
         if let Some(opts) = driver_opts {
             self.params.insert("DriverOpts", json!(opts));
@@ -162,36 +240,38 @@ impl VolumeCreateOptionsBuilder {
     }
 
     pub fn name(
-        &mut self,
+        mut self,
         name: &str,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Name", json!(name));
         self
     }
 
     pub fn labels(
-        &mut self,
+        mut self,
         labels: &HashMap<&str, &str>,
-    ) -> &mut Self {
+    ) -> Self {
         self.params.insert("Labels", json!(labels));
         self
     }
 
-    pub fn build(&self) -> VolumeCreateOptions {
+    pub fn build(self) -> VolumeCreateOptions {
         VolumeCreateOptions {
-            params: self.params.clone(),
+            params: self.params,
         }
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct VolumeCreateInfo {
     pub name: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct VolumesInfo {
     pub volumes: Option<Vec<VolumeInfo>>,
     pub warnings: Option<Vec<String>>,
@@ -199,10 +279,14 @@ pub struct VolumesInfo {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[non_exhaustive]
 pub struct VolumeInfo {
     #[cfg(feature = "chrono")]
     pub created_at: DateTime<Utc>,
-    #[cfg(not(feature = "chrono"))]
+    #[cfg(feature = "time")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
     pub created_at: String,
     pub driver: String,
     pub labels: Option<HashMap<String, String>>,
@@ -210,6 +294,10 @@ pub struct VolumeInfo {
     pub mountpoint: String,
     pub options: Option<HashMap<String, String>>,
     pub scope: String,
+    /// Fields of the volume that aren't modelled, like `Status` and `UsageData`
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[cfg(test)]
@@ -254,4 +342,52 @@ mod test {
         assert_eq!(volume_info.name, None);
         assert_eq!(volume_info.driver_opts, Some(driver_options))
     }
+
+    #[test]
+    fn volume_list_options_filters() {
+        assert_eq!(VolumeListOptions::default().serialize().unwrap(), None);
+
+        let opts = VolumeListOptions::builder()
+            .filter(vec![
+                VolumeFilter::Dangling(true),
+                VolumeFilter::Label("env".to_owned(), "ci".to_owned()),
+                VolumeFilter::LabelName("keep".to_owned()),
+            ])
+            .build();
+        assert_eq!(
+            opts.serialize().unwrap().unwrap(),
+            "filters=%7B%22dangling%22%3A%5B%22true%22%5D%2C%22label%22%3A%5B%22env%3Dci%22%2C%22keep%22%5D%7D"
+        );
+    }
+
+    #[test]
+    fn list_volumes() {
+        use crate::{
+            mock::{MockDaemon, MockResponse},
+            Docker,
+        };
+
+        let daemon = MockDaemon::new();
+        daemon.respond(
+            "GET",
+            "/volumes",
+            MockResponse::json(&serde_json::json!({ "Volumes": null, "Warnings": null })),
+        );
+        let docker = Docker::mock(daemon.clone());
+        let volumes = docker.volumes();
+
+        futures::executor::block_on(async {
+            assert!(volumes.list().await.unwrap().is_empty());
+            let opts = VolumeListOptions::builder()
+                .filter(vec![VolumeFilter::Dangling(true)])
+                .build();
+            assert!(volumes.list_with(&opts).await.unwrap().is_empty());
+        });
+        let requests = daemon.requests();
+        assert_eq!(requests[0].path, "/volumes");
+        assert_eq!(
+            requests[1].path,
+            "/volumes?filters=%7B%22dangling%22%3A%5B%22true%22%5D%7D"
+        );
+    }
 }